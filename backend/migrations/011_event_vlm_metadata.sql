@@ -0,0 +1,10 @@
+-- Everything needed to debug or re-run an analysis: the exact prompt, the rules
+-- that were in force, which model answered, how long it took and what it cost.
+ALTER TABLE analysis_events
+  ADD COLUMN IF NOT EXISTS system_prompt     TEXT,
+  ADD COLUMN IF NOT EXISTS rules_snapshot    JSONB,
+  ADD COLUMN IF NOT EXISTS model             VARCHAR(255),
+  ADD COLUMN IF NOT EXISTS vlm_backend       VARCHAR(50),
+  ADD COLUMN IF NOT EXISTS latency_ms        INTEGER,
+  ADD COLUMN IF NOT EXISTS prompt_tokens     INTEGER,
+  ADD COLUMN IF NOT EXISTS completion_tokens INTEGER;
@@ -0,0 +1,41 @@
+-- Background jobs that re-run stored frames through the VLM without alerting.
+CREATE TABLE IF NOT EXISTS reanalysis_jobs (
+    id          UUID         PRIMARY KEY DEFAULT gen_random_uuid(),
+    -- "running" | "completed" | "failed"
+    status      VARCHAR(20)  NOT NULL DEFAULT 'running',
+    -- The event filter the job was started with.
+    filter      JSONB        NOT NULL DEFAULT '{}',
+    -- Model override, or NULL to use the configured model.
+    model       VARCHAR(255),
+    total       INTEGER      NOT NULL DEFAULT 0,
+    processed   INTEGER      NOT NULL DEFAULT 0,
+    failed      INTEGER      NOT NULL DEFAULT 0,
+    error       TEXT,
+    created_at  TIMESTAMPTZ  NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMPTZ
+);
+
+-- One row per re-analyzed event, stored next to (never over) the original.
+CREATE TABLE IF NOT EXISTS event_reanalyses (
+    id                  UUID         PRIMARY KEY DEFAULT gen_random_uuid(),
+    job_id              UUID         NOT NULL REFERENCES reanalysis_jobs(id) ON DELETE CASCADE,
+    event_id            UUID         NOT NULL REFERENCES analysis_events(id) ON DELETE CASCADE,
+    original_risk_level VARCHAR(20)  NOT NULL,
+    risk_level          VARCHAR(20)  NOT NULL,
+    triggered_rule      TEXT,
+    title               TEXT,
+    description         TEXT         NOT NULL,
+    events              JSONB        NOT NULL DEFAULT '[]',
+    raw_response        TEXT,
+    system_prompt       TEXT,
+    rules_snapshot      JSONB,
+    model               VARCHAR(255),
+    vlm_backend         VARCHAR(50),
+    latency_ms          INTEGER,
+    prompt_tokens       INTEGER,
+    completion_tokens   INTEGER,
+    created_at          TIMESTAMPTZ  NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_reanalyses_job_id   ON event_reanalyses (job_id);
+CREATE INDEX IF NOT EXISTS idx_reanalyses_event_id ON event_reanalyses (event_id);
@@ -0,0 +1,42 @@
+-- Labeled frame sets for measuring how well a model + prompt applies the rules.
+CREATE TABLE IF NOT EXISTS eval_datasets (
+    id          UUID         PRIMARY KEY DEFAULT gen_random_uuid(),
+    name        VARCHAR(255) NOT NULL,
+    description TEXT,
+    -- Optional fixed rule set [{description, threat_level}]; when NULL each
+    -- sample is evaluated against its stream's current rules.
+    rules       JSONB,
+    created_at  TIMESTAMPTZ  NOT NULL DEFAULT NOW(),
+    updated_at  TIMESTAMPTZ  NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS eval_samples (
+    id                      UUID        PRIMARY KEY DEFAULT gen_random_uuid(),
+    dataset_id              UUID        NOT NULL REFERENCES eval_datasets(id) ON DELETE CASCADE,
+    -- Stream whose rules apply (when the dataset has none of its own).
+    stream_id               UUID        REFERENCES streams(id) ON DELETE SET NULL,
+    -- Stored event the frame was copied from, if any.
+    event_id                UUID        REFERENCES analysis_events(id) ON DELETE SET NULL,
+    image                   BYTEA       NOT NULL,
+    -- "none" | "low" | "medium" | "high"
+    expected_risk_level     VARCHAR(20) NOT NULL,
+    -- Exact rule description expected in triggered_rule; NULL means no rule should match.
+    expected_triggered_rule TEXT,
+    created_at              TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_eval_samples_dataset_id ON eval_samples (dataset_id);
+
+CREATE TABLE IF NOT EXISTS eval_runs (
+    id          UUID         PRIMARY KEY DEFAULT gen_random_uuid(),
+    dataset_id  UUID         NOT NULL REFERENCES eval_datasets(id) ON DELETE CASCADE,
+    -- "running" | "completed" | "failed"
+    status      VARCHAR(20)  NOT NULL DEFAULT 'running',
+    model       VARCHAR(255),
+    report      JSONB,
+    error       TEXT,
+    created_at  TIMESTAMPTZ  NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_eval_runs_dataset_id ON eval_runs (dataset_id);
@@ -0,0 +1,21 @@
+-- Results from a candidate model run in shadow mode next to the primary one.
+-- Shadow results never alert; they exist only to be compared.
+CREATE TABLE IF NOT EXISTS shadow_results (
+    id                UUID         PRIMARY KEY DEFAULT gen_random_uuid(),
+    event_id          UUID         NOT NULL REFERENCES analysis_events(id) ON DELETE CASCADE,
+    risk_level        VARCHAR(20)  NOT NULL,
+    triggered_rule    TEXT,
+    title             TEXT,
+    description       TEXT         NOT NULL,
+    events            JSONB        NOT NULL DEFAULT '[]',
+    raw_response      TEXT,
+    model             VARCHAR(255) NOT NULL,
+    vlm_backend       VARCHAR(50)  NOT NULL,
+    latency_ms        INTEGER,
+    prompt_tokens     INTEGER,
+    completion_tokens INTEGER,
+    created_at        TIMESTAMPTZ  NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_shadow_results_event_id   ON shadow_results (event_id);
+CREATE INDEX IF NOT EXISTS idx_shadow_results_created_at ON shadow_results (created_at DESC);
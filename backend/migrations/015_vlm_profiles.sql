@@ -0,0 +1,19 @@
+-- Named VLM configurations that streams can opt into instead of the global default.
+CREATE TABLE IF NOT EXISTS vlm_profiles (
+    id                  UUID         PRIMARY KEY DEFAULT gen_random_uuid(),
+    name                VARCHAR(255) NOT NULL UNIQUE,
+    -- "ollama" | "openai_compat" | "llama_cpp" | "vllm"
+    backend             VARCHAR(50)  NOT NULL,
+    model               VARCHAR(255) NOT NULL,
+    base_url            TEXT         NOT NULL,
+    api_key             TEXT,
+    connect_timeout_sec INTEGER,
+    request_timeout_sec INTEGER,
+    max_tokens          INTEGER,
+    created_at          TIMESTAMPTZ  NOT NULL DEFAULT NOW(),
+    updated_at          TIMESTAMPTZ  NOT NULL DEFAULT NOW()
+);
+
+-- NULL = use the globally configured VLM.
+ALTER TABLE streams
+  ADD COLUMN IF NOT EXISTS vlm_profile_id UUID REFERENCES vlm_profiles(id) ON DELETE SET NULL;
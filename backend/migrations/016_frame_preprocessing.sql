@@ -0,0 +1,8 @@
+-- Per-stream image preprocessing applied before frames go to the VLM
+-- (rotate/flip, fisheye correction, ROI crop, resize, JPEG quality).
+ALTER TABLE streams
+  ADD COLUMN IF NOT EXISTS preprocessing JSONB;
+
+-- Size of the image actually sent to the VLM and how long preprocessing took.
+ALTER TABLE analysis_events
+  ADD COLUMN IF NOT EXISTS preprocess_stats JSONB;
@@ -0,0 +1,8 @@
+-- Events analyzed together in one tiled VLM call share a batch_id; batch_tile
+-- is the frame's 1-based tile number in the grid image (and in raw_response).
+ALTER TABLE analysis_events
+  ADD COLUMN IF NOT EXISTS batch_id UUID,
+  ADD COLUMN IF NOT EXISTS batch_tile INTEGER;
+
+CREATE INDEX IF NOT EXISTS idx_analysis_events_batch_id
+  ON analysis_events (batch_id) WHERE batch_id IS NOT NULL;
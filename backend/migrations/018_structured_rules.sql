@@ -0,0 +1,17 @@
+-- Structured rule conditions the worker checks itself, on top of the free-text
+-- description the VLM matches against.
+ALTER TABLE stream_rules
+  -- RuleSchedule: windows of the week when the rule is in force; NULL = always
+  ADD COLUMN IF NOT EXISTS schedule      JSONB,
+  -- Polygon of {x, y} points (fractions of the frame) the detection must be in
+  ADD COLUMN IF NOT EXISTS zone          JSONB,
+  -- Minimum number of matching people / vehicles / objects
+  ADD COLUMN IF NOT EXISTS min_count     INTEGER,
+  -- How long the rule must keep matching before it counts
+  ADD COLUMN IF NOT EXISTS min_dwell_sec INTEGER,
+  -- Event types that must all be detected, e.g. {person_detected}
+  ADD COLUMN IF NOT EXISTS event_types   TEXT[] NOT NULL DEFAULT '{}';
+
+-- Outcome of the worker's checks on the rule the VLM triggered.
+ALTER TABLE analysis_events
+  ADD COLUMN IF NOT EXISTS rule_check JSONB;
@@ -0,0 +1,30 @@
+-- Rule scopes: a rule applies to every stream (global), to the streams on one
+-- blueprint, or to a single stream. Streams inherit global and blueprint rules.
+ALTER TABLE stream_rules
+  ADD COLUMN IF NOT EXISTS scope        VARCHAR(20) NOT NULL DEFAULT 'stream',
+  ADD COLUMN IF NOT EXISTS blueprint_id UUID REFERENCES blueprints(id) ON DELETE CASCADE,
+  ALTER COLUMN stream_id DROP NOT NULL;
+
+DO $$
+BEGIN
+  IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'stream_rules_scope_target') THEN
+    ALTER TABLE stream_rules ADD CONSTRAINT stream_rules_scope_target CHECK (
+      (scope = 'global'    AND stream_id IS NULL     AND blueprint_id IS NULL) OR
+      (scope = 'blueprint' AND stream_id IS NULL     AND blueprint_id IS NOT NULL) OR
+      (scope = 'stream'    AND stream_id IS NOT NULL AND blueprint_id IS NULL)
+    );
+  END IF;
+END $$;
+
+CREATE INDEX IF NOT EXISTS idx_rules_blueprint_id ON stream_rules (blueprint_id) WHERE blueprint_id IS NOT NULL;
+
+-- Per-stream changes to an inherited rule: switch it off, or use another threat level.
+CREATE TABLE IF NOT EXISTS stream_rule_overrides (
+    stream_id    UUID        NOT NULL REFERENCES streams(id) ON DELETE CASCADE,
+    rule_id      UUID        NOT NULL REFERENCES stream_rules(id) ON DELETE CASCADE,
+    disabled     BOOLEAN     NOT NULL DEFAULT FALSE,
+    -- "none" | "low" | "medium" | "high"; NULL = keep the rule's own level
+    threat_level VARCHAR(20),
+    updated_at   TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (stream_id, rule_id)
+);
@@ -0,0 +1,101 @@
+-- Reusable sets of rules with {{parameter}} placeholders, applied to streams
+-- or blueprints in one call.
+CREATE TABLE IF NOT EXISTS rule_templates (
+    id          UUID         PRIMARY KEY DEFAULT gen_random_uuid(),
+    name        VARCHAR(255) NOT NULL UNIQUE,
+    description TEXT         NOT NULL DEFAULT '',
+    -- [{ "name", "description", "default" }]
+    parameters  JSONB        NOT NULL DEFAULT '[]',
+    -- rule definitions as accepted by the create-rule endpoints, with placeholders
+    rules       JSONB        NOT NULL DEFAULT '[]',
+    created_at  TIMESTAMPTZ  NOT NULL DEFAULT NOW(),
+    updated_at  TIMESTAMPTZ  NOT NULL DEFAULT NOW()
+);
+
+INSERT INTO rule_templates (name, description, parameters, rules) VALUES
+(
+    'Retail after hours',
+    'Activity inside a shop while it is closed.',
+    '[
+        {"name": "open",  "description": "Opening time, HH:MM", "default": "08:00"},
+        {"name": "close", "description": "Closing time, HH:MM", "default": "21:00"},
+        {"name": "utc_offset_minutes", "description": "Local time offset from UTC in minutes", "default": "0"}
+    ]',
+    '[
+        {
+            "description": "Person inside the store after closing time",
+            "threat_level": "high",
+            "event_types": ["person_detected"],
+            "schedule": {"windows": [{"days": [], "start": "{{close}}", "end": "{{open}}"}],
+                         "utc_offset_minutes": "{{utc_offset_minutes}}"}
+        },
+        {
+            "description": "Person behind the counter or at the till while the store is closed",
+            "threat_level": "high",
+            "schedule": {"windows": [{"days": [], "start": "{{close}}", "end": "{{open}}"}],
+                         "utc_offset_minutes": "{{utc_offset_minutes}}"}
+        },
+        {
+            "description": "Fire or smoke visible",
+            "threat_level": "high"
+        }
+    ]'
+),
+(
+    'Warehouse forklift safety',
+    'Pedestrians and forklifts sharing aisles.',
+    '[
+        {"name": "area", "description": "Name of the area the camera covers", "default": "the aisle"},
+        {"name": "crowd_size", "description": "People in the area that count as a crowd", "default": "5"}
+    ]',
+    '[
+        {
+            "description": "Pedestrian walking next to a moving forklift in {{area}}",
+            "threat_level": "high",
+            "event_types": ["person_detected", "vehicle_detected"]
+        },
+        {
+            "description": "Person riding on the forks or a pallet of a forklift",
+            "threat_level": "high"
+        },
+        {
+            "description": "Pallets or goods blocking {{area}}",
+            "threat_level": "medium",
+            "event_types": ["package_left"],
+            "min_dwell_sec": 300
+        },
+        {
+            "description": "Group of people gathered in {{area}}",
+            "threat_level": "low",
+            "event_types": ["person_detected"],
+            "min_count": "{{crowd_size}}"
+        }
+    ]'
+),
+(
+    'Parking lot',
+    'Loitering and vehicle trouble in a car park.',
+    '[
+        {"name": "loiter_sec", "description": "Seconds a person may linger before it counts as loitering", "default": "120"}
+    ]',
+    '[
+        {
+            "description": "Person trying car door handles or looking into parked cars",
+            "threat_level": "high",
+            "event_types": ["person_detected"]
+        },
+        {
+            "description": "Person loitering between parked vehicles",
+            "threat_level": "medium",
+            "event_types": ["person_detected"],
+            "min_dwell_sec": "{{loiter_sec}}"
+        },
+        {
+            "description": "Vehicle parked across a driveway or exit lane",
+            "threat_level": "low",
+            "event_types": ["vehicle_detected"],
+            "min_dwell_sec": 600
+        }
+    ]'
+)
+ON CONFLICT (name) DO NOTHING;
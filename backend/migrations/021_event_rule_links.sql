@@ -0,0 +1,19 @@
+-- Link events to the rule they triggered, so rules can be analyzed by id
+-- rather than by the free text the VLM returned.
+ALTER TABLE analysis_events
+  ADD COLUMN IF NOT EXISTS rule_id UUID REFERENCES stream_rules(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_events_rule_id ON analysis_events (rule_id, captured_at) WHERE rule_id IS NOT NULL;
+
+-- Existing events: link exact (case-insensitive) matches against the rules
+-- that apply to the event's stream.
+UPDATE analysis_events e
+SET rule_id = r.id
+FROM streams s, stream_rules r
+WHERE e.rule_id IS NULL
+  AND e.triggered_rule IS NOT NULL
+  AND s.id = e.stream_id
+  AND (r.stream_id = s.id
+       OR r.scope = 'global'
+       OR (r.scope = 'blueprint' AND r.blueprint_id = s.blueprint_id))
+  AND LOWER(TRIM(r.description)) = LOWER(TRIM(e.triggered_rule));
@@ -0,0 +1,28 @@
+-- Operator verdicts on events, and the "do not flag" clauses derived from
+-- confirmed false positives. Clauses reach the prompt only once approved.
+ALTER TABLE analysis_events
+  ADD COLUMN IF NOT EXISTS disposition        VARCHAR(20),   -- "false_positive" | "true_positive"
+  ADD COLUMN IF NOT EXISTS disposition_reason TEXT,
+  ADD COLUMN IF NOT EXISTS disposition_at     TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_events_false_positives
+  ON analysis_events (stream_id, disposition_at) WHERE disposition = 'false_positive';
+
+-- Events marked through the status field before dispositions existed.
+UPDATE analysis_events
+SET disposition = 'false_positive', disposition_at = NOW(), status = 'resolved'
+WHERE status = 'false_positive';
+
+CREATE TABLE IF NOT EXISTS feedback_clauses (
+    id               UUID        PRIMARY KEY DEFAULT gen_random_uuid(),
+    stream_id        UUID        NOT NULL REFERENCES streams(id) ON DELETE CASCADE,
+    -- The rule the false positives were attributed to; NULL = the model's own judgment.
+    rule_id          UUID        REFERENCES stream_rules(id) ON DELETE CASCADE,
+    clause           TEXT        NOT NULL,
+    source_event_ids UUID[]      NOT NULL DEFAULT '{}',
+    status           VARCHAR(20) NOT NULL DEFAULT 'pending',  -- "pending" | "approved" | "rejected"
+    created_at       TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    reviewed_at      TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_feedback_clauses_stream ON feedback_clauses (stream_id, status);
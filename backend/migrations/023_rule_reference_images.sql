@@ -0,0 +1,17 @@
+-- Example images attached to a rule and sent to the VLM alongside the frame,
+-- so small models can see what the rule means rather than only read it.
+CREATE TABLE IF NOT EXISTS rule_reference_images (
+    id         UUID        PRIMARY KEY DEFAULT gen_random_uuid(),
+    rule_id    UUID        NOT NULL REFERENCES stream_rules(id) ON DELETE CASCADE,
+    kind       VARCHAR(10) NOT NULL,  -- "positive" (the rule applies) | "negative" (it does not)
+    caption    TEXT,
+    image      BYTEA       NOT NULL,
+    -- Event the image was copied from, if any.
+    event_id   UUID        REFERENCES analysis_events(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_rule_reference_images_rule ON rule_reference_images (rule_id, created_at);
+
+-- Models that take only one image per request get text-only rules.
+ALTER TABLE vlm_profiles ADD COLUMN IF NOT EXISTS multi_image BOOLEAN NOT NULL DEFAULT TRUE;
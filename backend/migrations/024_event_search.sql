@@ -0,0 +1,9 @@
+-- Full-text search over event titles and descriptions. Queries must use the
+-- same expression for the index to apply.
+CREATE INDEX IF NOT EXISTS idx_events_fts ON analysis_events
+  USING GIN (to_tsvector('english', coalesce(title, '') || ' ' || description));
+
+-- Keyset pagination walks (captured_at, id) newest first.
+CREATE INDEX IF NOT EXISTS idx_events_captured_at_id ON analysis_events (captured_at DESC, id DESC);
+
+CREATE INDEX IF NOT EXISTS idx_events_status ON analysis_events (status);
@@ -0,0 +1,34 @@
+-- When an event was resolved, for time-to-resolve statistics. Kept by a
+-- trigger so every way of changing the status records it.
+ALTER TABLE analysis_events ADD COLUMN IF NOT EXISTS resolved_at TIMESTAMPTZ;
+
+CREATE OR REPLACE FUNCTION set_event_resolved_at() RETURNS trigger AS $$
+BEGIN
+  IF NEW.status = 'resolved' AND OLD.status IS DISTINCT FROM 'resolved' THEN
+    NEW.resolved_at := NOW();
+  ELSIF NEW.status <> 'resolved' THEN
+    NEW.resolved_at := NULL;
+  END IF;
+  RETURN NEW;
+END $$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS analysis_events_resolved_at ON analysis_events;
+CREATE TRIGGER analysis_events_resolved_at
+  BEFORE UPDATE OF status ON analysis_events
+  FOR EACH ROW EXECUTE FUNCTION set_event_resolved_at();
+
+-- Events resolved before this column existed have no known resolve time,
+-- except those resolved by giving a disposition. Migration 022 stamped the
+-- events it converted with the time it ran, which sqlx records as that
+-- migration's `installed_on` in the same transaction; those keep none.
+UPDATE analysis_events
+SET resolved_at = disposition_at
+WHERE status = 'resolved' AND resolved_at IS NULL AND disposition_at IS NOT NULL
+  AND disposition_at IS DISTINCT FROM (SELECT installed_on FROM _sqlx_migrations WHERE version = 22);
+
+-- Dashboard aggregations: per-stream time ranges and resolved events.
+CREATE INDEX IF NOT EXISTS idx_events_stream_captured_at ON analysis_events (stream_id, captured_at);
+CREATE INDEX IF NOT EXISTS idx_events_resolved ON analysis_events (captured_at)
+  INCLUDE (risk_level, resolved_at) WHERE resolved_at IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_events_triggered_rule ON analysis_events (triggered_rule, captured_at)
+  WHERE triggered_rule IS NOT NULL;
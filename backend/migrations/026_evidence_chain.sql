@@ -0,0 +1,100 @@
+-- Tamper-evident evidence chain: each event records a SHA-256 of its frame
+-- and immutable metadata, chained to the previous event of the same stream.
+-- Operator workflow columns (status, disposition, resolved_at, rule_id) can
+-- change after the fact and are not part of the hash.
+ALTER TABLE analysis_events
+    ADD COLUMN IF NOT EXISTS chain_seq    BIGINT,
+    ADD COLUMN IF NOT EXISTS prev_hash    TEXT,
+    ADD COLUMN IF NOT EXISTS content_hash TEXT,
+    ADD COLUMN IF NOT EXISTS frame_sha256 TEXT;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_events_chain ON analysis_events (stream_id, chain_seq);
+
+-- Last link of each stream's chain. Locked while an event is inserted, so
+-- concurrent workers can't fork the chain.
+CREATE TABLE IF NOT EXISTS evidence_chain_heads (
+    stream_id UUID   PRIMARY KEY REFERENCES streams(id) ON DELETE CASCADE,
+    seq       BIGINT NOT NULL DEFAULT 0,
+    head_hash TEXT
+);
+
+-- Hex SHA-256 of the event's hashed fields as canonical JSONB text.
+CREATE OR REPLACE FUNCTION event_content_hash(e analysis_events) RETURNS TEXT
+LANGUAGE SQL STABLE AS $$
+    SELECT encode(sha256(convert_to(jsonb_build_object(
+        'prev_hash',      e.prev_hash,
+        'id',             e.id,
+        'stream_id',      e.stream_id,
+        'captured_at_us', (EXTRACT(EPOCH FROM e.captured_at) * 1000000)::BIGINT,
+        'created_at_us',  (EXTRACT(EPOCH FROM e.created_at) * 1000000)::BIGINT,
+        'title',          e.title,
+        'description',    e.description,
+        'events',         e.events,
+        'risk_level',     e.risk_level,
+        'triggered_rule', e.triggered_rule,
+        'raw_response',   e.raw_response,
+        'system_prompt',  e.system_prompt,
+        'rules_snapshot', e.rules_snapshot,
+        'model',          e.model,
+        'vlm_backend',    e.vlm_backend,
+        'frame_sha256',   e.frame_sha256
+    )::TEXT, 'UTF8')), 'hex')
+$$;
+
+CREATE OR REPLACE FUNCTION analysis_events_chain() RETURNS TRIGGER AS $$
+DECLARE
+    head evidence_chain_heads%ROWTYPE;
+BEGIN
+    INSERT INTO evidence_chain_heads (stream_id) VALUES (NEW.stream_id) ON CONFLICT (stream_id) DO NOTHING;
+    SELECT * INTO head FROM evidence_chain_heads WHERE stream_id = NEW.stream_id FOR UPDATE;
+
+    NEW.chain_seq    := head.seq + 1;
+    NEW.prev_hash    := head.head_hash;
+    NEW.frame_sha256 := encode(sha256(NEW.frame), 'hex');
+    NEW.content_hash := event_content_hash(NEW);
+
+    UPDATE evidence_chain_heads SET seq = NEW.chain_seq, head_hash = NEW.content_hash
+    WHERE stream_id = NEW.stream_id;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS analysis_events_chain ON analysis_events;
+CREATE TRIGGER analysis_events_chain
+    BEFORE INSERT ON analysis_events
+    FOR EACH ROW EXECUTE FUNCTION analysis_events_chain();
+
+-- Chain the events stored before this migration, in the order they were stored.
+DO $$
+DECLARE
+    ev   RECORD;
+    head evidence_chain_heads%ROWTYPE;
+    hash TEXT;
+BEGIN
+    FOR ev IN SELECT id, stream_id FROM analysis_events WHERE chain_seq IS NULL ORDER BY stream_id, created_at, id LOOP
+        INSERT INTO evidence_chain_heads (stream_id) VALUES (ev.stream_id) ON CONFLICT (stream_id) DO NOTHING;
+        SELECT * INTO head FROM evidence_chain_heads WHERE stream_id = ev.stream_id;
+
+        UPDATE analysis_events
+        SET chain_seq = head.seq + 1, prev_hash = head.head_hash, frame_sha256 = encode(sha256(frame), 'hex')
+        WHERE id = ev.id;
+        UPDATE analysis_events e SET content_hash = event_content_hash(e) WHERE e.id = ev.id
+        RETURNING content_hash INTO hash;
+
+        UPDATE evidence_chain_heads SET seq = head.seq + 1, head_hash = hash WHERE stream_id = ev.stream_id;
+    END LOOP;
+END $$;
+
+-- Chain heads signed with the evidence key (kept outside the database), so a
+-- chain rewritten end to end still fails verification.
+CREATE TABLE IF NOT EXISTS evidence_checkpoints (
+    id           UUID        PRIMARY KEY DEFAULT gen_random_uuid(),
+    stream_id    UUID        NOT NULL REFERENCES streams(id) ON DELETE CASCADE,
+    chain_seq    BIGINT      NOT NULL,
+    content_hash TEXT        NOT NULL,
+    key_id       TEXT        NOT NULL,
+    -- Base64 Ed25519 signature of "<stream_id>:<chain_seq>:<content_hash>:<created_at µs>".
+    signature    TEXT        NOT NULL,
+    created_at   TIMESTAMPTZ NOT NULL,
+    UNIQUE (stream_id, chain_seq)
+);
@@ -0,0 +1,47 @@
+-- Scheduled digest reports: a schedule aggregates the events of the last
+-- `period_hours` for a stream, a blueprint or everything, on a cron schedule
+-- evaluated at `utc_offset_minutes` from UTC.
+CREATE TABLE IF NOT EXISTS report_schedules (
+    id                 UUID        PRIMARY KEY DEFAULT gen_random_uuid(),
+    name               TEXT        NOT NULL,
+    -- Five-field cron pattern, or a nickname such as "@daily".
+    cron               TEXT        NOT NULL,
+    utc_offset_minutes INT         NOT NULL DEFAULT 0,
+    period_hours       INT         NOT NULL DEFAULT 24,
+    stream_id          UUID        REFERENCES streams(id) ON DELETE CASCADE,
+    blueprint_id       UUID        REFERENCES blueprints(id) ON DELETE CASCADE,
+    llm_summary        BOOLEAN     NOT NULL DEFAULT FALSE,
+    notify_sms         BOOLEAN     NOT NULL DEFAULT FALSE,
+    -- SMS recipient; the global alert number when NULL.
+    notify_phone       TEXT,
+    enabled            BOOLEAN     NOT NULL DEFAULT TRUE,
+    next_run_at        TIMESTAMPTZ NOT NULL,
+    last_run_at        TIMESTAMPTZ,
+    created_at         TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at         TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_report_schedules_due ON report_schedules (next_run_at) WHERE enabled;
+
+-- Generated reports, kept rendered for download.
+CREATE TABLE IF NOT EXISTS reports (
+    id           UUID        PRIMARY KEY DEFAULT gen_random_uuid(),
+    -- NULL for reports generated on request, or once their schedule is deleted.
+    schedule_id  UUID        REFERENCES report_schedules(id) ON DELETE SET NULL,
+    title        TEXT        NOT NULL,
+    stream_id    UUID        REFERENCES streams(id) ON DELETE SET NULL,
+    blueprint_id UUID        REFERENCES blueprints(id) ON DELETE SET NULL,
+    period_start TIMESTAMPTZ NOT NULL,
+    period_end   TIMESTAMPTZ NOT NULL,
+    event_count  BIGINT      NOT NULL,
+    -- `ReportSummary`: totals and top incidents.
+    summary      JSONB       NOT NULL,
+    llm_summary  TEXT,
+    notified     BOOLEAN     NOT NULL DEFAULT FALSE,
+    html         TEXT        NOT NULL,
+    pdf          BYTEA       NOT NULL,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_reports_created ON reports (created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_reports_schedule ON reports (schedule_id, created_at DESC);
@@ -0,0 +1,27 @@
+-- Assistant conversations, so follow-up questions see earlier turns.
+CREATE TABLE IF NOT EXISTS assistant_conversations (
+    id         UUID        PRIMARY KEY DEFAULT gen_random_uuid(),
+    title      TEXT        NOT NULL DEFAULT '',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Every message of a conversation in the order it was sent to the model:
+-- user questions, assistant replies (with the tools they called) and tool results.
+CREATE TABLE IF NOT EXISTS assistant_messages (
+    id              UUID        PRIMARY KEY DEFAULT gen_random_uuid(),
+    conversation_id UUID        NOT NULL REFERENCES assistant_conversations(id) ON DELETE CASCADE,
+    seq             BIGINT      GENERATED ALWAYS AS IDENTITY,
+    -- "user" | "assistant" | "tool"
+    role            TEXT        NOT NULL,
+    content         TEXT        NOT NULL,
+    -- Assistant messages: the calls `[{function: {name, arguments}}]` the model made.
+    tool_calls      JSONB,
+    -- Tool messages: which tool produced the content.
+    tool_name       TEXT,
+    -- The reply was cut short by a cancel; `content` is what had streamed so far.
+    cancelled       BOOLEAN     NOT NULL DEFAULT FALSE,
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_assistant_messages_conversation ON assistant_messages (conversation_id, seq);
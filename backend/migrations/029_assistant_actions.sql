@@ -0,0 +1,22 @@
+-- Destructive assistant tool calls (resolving events, editing or deleting
+-- rules, disabling streams). The model only proposes them; they run once the
+-- user confirms, and not after `expires_at`.
+CREATE TABLE IF NOT EXISTS assistant_actions (
+    -- Also the confirmation token.
+    id              UUID        PRIMARY KEY DEFAULT gen_random_uuid(),
+    conversation_id UUID        REFERENCES assistant_conversations(id) ON DELETE CASCADE,
+    tool            TEXT        NOT NULL,
+    -- Exactly what confirming does, fixed when proposed (e.g. the event ids to resolve).
+    change          JSONB       NOT NULL,
+    summary         TEXT        NOT NULL,
+    -- Rows the change touches, as previewed.
+    affected        BIGINT      NOT NULL,
+    -- "pending" | "executed" | "failed" | "rejected"
+    status          TEXT        NOT NULL DEFAULT 'pending',
+    result          TEXT,
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at      TIMESTAMPTZ NOT NULL,
+    decided_at      TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_assistant_actions_conversation ON assistant_actions (conversation_id, created_at);
@@ -0,0 +1,212 @@
+//! Rule evaluation harness: runs a labeled dataset through a VLM and reports
+//! how well the model + prompt combination applies the rules.
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    sync::Arc,
+};
+
+use sqlx::PgPool;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    analysis::{
+        preprocess::FrameMapping,
+        references, rules,
+        vlm::{registry::VlmRegistry, RiskLevel, VlmRule},
+    },
+    config::VlmBackend,
+    error::{AppError, Result},
+    storage::{
+        db,
+        models::{EvalReport, EvalRun, LatencyStats, LevelMetrics},
+    },
+};
+
+const LEVELS: [RiskLevel; 4] = [RiskLevel::None, RiskLevel::Low, RiskLevel::Medium, RiskLevel::High];
+
+/// One successfully analyzed sample.
+struct Outcome {
+    expected: RiskLevel,
+    predicted: RiskLevel,
+    expected_rule: Option<String>,
+    predicted_rule: Option<String>,
+    latency_ms: i32,
+}
+
+/// The backend a run uses: VLM profile `profile_id`'s, or the default one,
+/// with `model` in place of its own if given.
+pub async fn run_backend(
+    db: &PgPool,
+    vlm: &VlmRegistry,
+    profile_id: Option<Uuid>,
+    model: Option<&str>,
+) -> Result<VlmBackend> {
+    let profile = match profile_id {
+        Some(id) => Some(db::get_vlm_profile(db, id).await?),
+        None => None,
+    };
+    let backend = vlm.backend_for(profile.as_ref())?;
+    Ok(match model.map(str::trim).filter(|m| !m.is_empty()) {
+        Some(model) => backend.with_model(model),
+        None => backend,
+    })
+}
+
+/// Run every sample of a dataset through the backend described by `cfg`, with
+/// the registry's retries, fallback and in-flight limits.
+pub async fn run_dataset(db: &PgPool, vlm: &VlmRegistry, cfg: &VlmBackend, dataset_id: Uuid) -> Result<EvalReport> {
+    let dataset = db::get_eval_dataset(db, dataset_id).await?;
+    let samples = db::list_eval_samples(db, dataset_id).await?;
+    let vlm = vlm.client_for(cfg);
+
+    let dataset_rules: Option<Vec<VlmRule>> = match dataset.rules {
+        Some(v) if !v.is_null() => Some(
+            serde_json::from_value(v)
+                .map_err(|e| AppError::BadRequest(format!("Invalid dataset rules: {e}")))?,
+        ),
+        _ => None,
+    };
+    // Stream id → (name, current rules, feedback clauses), loaded once per stream.
+    let mut streams: HashMap<Uuid, (String, Vec<VlmRule>, Vec<String>)> = HashMap::new();
+
+    info!(dataset = %dataset.name, samples = samples.len(), model = %cfg.model(), "Running evaluation");
+
+    let mut outcomes = Vec::with_capacity(samples.len());
+    let mut errors = 0;
+    for sample in &samples {
+        let image = db::get_eval_sample_image(db, sample.id).await?;
+
+        let (stream_name, stream_rules, stream_exclusions) = match sample.stream_id {
+            Some(sid) => match streams.entry(sid) {
+                Entry::Occupied(e) => {
+                    let (name, rules, exclusions) = e.into_mut();
+                    (name.as_str(), rules.as_slice(), exclusions.as_slice())
+                }
+                Entry::Vacant(e) => {
+                    let stream = db::get_stream(db, sid).await?;
+                    // Samples are sent as stored, without the stream's preprocessing.
+                    let stream_rules = rules::effective_rules(db, sid).await?;
+                    let rules = references::vlm_rules(db, &stream_rules, &FrameMapping::default()).await?;
+                    let exclusions = db::approved_feedback_clauses(db, sid).await?;
+                    let (name, rules, exclusions) = e.insert((stream.name, rules, exclusions));
+                    (name.as_str(), rules.as_slice(), exclusions.as_slice())
+                }
+            },
+            None => (dataset.name.as_str(), &[][..], &[][..]),
+        };
+        // A dataset's fixed rule set replaces the stream's prompt additions entirely.
+        let (rules, exclusions) = match dataset_rules.as_deref() {
+            Some(rules) => (rules, &[][..]),
+            None => (stream_rules, stream_exclusions),
+        };
+
+        match vlm.analyze(&image, stream_name, rules, exclusions).await {
+            Ok(output) => outcomes.push(Outcome {
+                expected: sample.expected_risk_level.parse().unwrap_or_default(),
+                predicted: output.result.risk_level,
+                expected_rule: sample.expected_triggered_rule.clone(),
+                predicted_rule: output.result.triggered_rule,
+                latency_ms: output.latency_ms,
+            }),
+            Err(e) => {
+                warn!(sample = %sample.id, "Evaluation call failed: {e}");
+                errors += 1;
+            }
+        }
+    }
+
+    Ok(build_report(cfg, &outcomes, errors))
+}
+
+/// Create a run record and evaluate the dataset in the background.
+pub async fn start_run(db: &PgPool, vlm: Arc<VlmRegistry>, cfg: VlmBackend, dataset_id: Uuid) -> Result<EvalRun> {
+    db::get_eval_dataset(db, dataset_id).await?;
+    let run = db::create_eval_run(db, dataset_id, Some(cfg.model())).await?;
+
+    let db = db.clone();
+    let run_id = run.id;
+    tokio::spawn(async move {
+        let finished = match run_dataset(&db, &vlm, &cfg, dataset_id).await {
+            Ok(report) => {
+                let report = serde_json::to_value(&report).unwrap_or_default();
+                db::finish_eval_run(&db, run_id, "completed", Some(&report), None).await
+            }
+            Err(e) => {
+                error!(run = %run_id, "Evaluation run failed: {e}");
+                db::finish_eval_run(&db, run_id, "failed", None, Some(&e.to_string())).await
+            }
+        };
+        if let Err(e) = finished {
+            error!(run = %run_id, "Failed to finish evaluation run: {e}");
+        }
+    });
+
+    Ok(run)
+}
+
+fn build_report(cfg: &VlmBackend, outcomes: &[Outcome], errors: i64) -> EvalReport {
+    let total = outcomes.len() as i64;
+    let ratio = |num: i64, den: i64| (den > 0).then(|| num as f64 / den as f64);
+
+    let mut matrix = vec![vec![0i64; LEVELS.len()]; LEVELS.len()];
+    for o in outcomes {
+        matrix[o.expected as usize][o.predicted as usize] += 1;
+    }
+
+    let per_level = LEVELS
+        .iter()
+        .map(|&level| {
+            let i = level as usize;
+            let true_pos = matrix[i][i];
+            let predicted: i64 = matrix.iter().map(|row| row[i]).sum();
+            let support: i64 = matrix[i].iter().sum();
+            LevelMetrics {
+                risk_level: level.as_str().to_string(),
+                support,
+                precision: ratio(true_pos, predicted),
+                recall: ratio(true_pos, support),
+            }
+        })
+        .collect();
+
+    let correct = (0..LEVELS.len()).map(|i| matrix[i][i]).sum();
+
+    let normalize = |r: &Option<String>| {
+        r.as_deref()
+            .map(|s| s.trim().to_lowercase())
+            .filter(|s| !s.is_empty())
+    };
+    let rule_hits = outcomes
+        .iter()
+        .filter(|o| normalize(&o.expected_rule) == normalize(&o.predicted_rule))
+        .count() as i64;
+
+    let latency = if outcomes.is_empty() {
+        None
+    } else {
+        let mut ms: Vec<i32> = outcomes.iter().map(|o| o.latency_ms).collect();
+        ms.sort_unstable();
+        let pct = |p: f64| ms[((ms.len() - 1) as f64 * p).round() as usize];
+        Some(LatencyStats {
+            mean_ms: ms.iter().map(|&v| v as f64).sum::<f64>() / ms.len() as f64,
+            p50_ms: pct(0.5),
+            p95_ms: pct(0.95),
+            max_ms: ms[ms.len() - 1],
+        })
+    };
+
+    EvalReport {
+        model: cfg.model().to_string(),
+        backend: cfg.name().to_string(),
+        samples: total + errors,
+        errors,
+        risk_accuracy: ratio(correct, total),
+        per_level,
+        labels: LEVELS.iter().map(|l| l.as_str().to_string()).collect(),
+        confusion_matrix: matrix,
+        triggered_rule_accuracy: ratio(rule_hits, total),
+        latency,
+    }
+}
@@ -0,0 +1,160 @@
+//! Dashboard statistics over analysis events, aggregated in SQL: counts per
+//! time bucket and grouping, time to resolve, and an hour-of-week heatmap.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    analysis::vlm::RiskLevel,
+    error::{AppError, Result},
+    storage::{
+        db,
+        models::{
+            EventCountTotal, EventCounts, EventCountsQuery, HeatmapCell, ResolutionStats, StatsFilter, StatsQuery,
+        },
+    },
+};
+
+pub const BUCKETS: [&str; 3] = ["hour", "day", "week"];
+pub const GROUP_BYS: [&str; 5] = ["risk_level", "stream", "blueprint", "event_type", "triggered_rule"];
+
+/// Most buckets one request may span, so an hourly series can't cover years.
+const MAX_BUCKETS: i64 = 2000;
+/// Period used by the resolution and heatmap reports when no `from` is given.
+const DEFAULT_PERIOD_DAYS: i64 = 28;
+
+pub async fn counts(db: &PgPool, query: &EventCountsQuery) -> Result<EventCounts> {
+    let bucket = query.bucket.as_deref().unwrap_or("day");
+    let group_by = query.group_by.as_deref().unwrap_or("risk_level");
+    let bucket_len = match bucket {
+        "hour" => Duration::hours(1),
+        "day" => Duration::days(1),
+        "week" => Duration::weeks(1),
+        other => {
+            return Err(AppError::BadRequest(format!(
+                "unknown bucket '{other}', expected one of: {}",
+                BUCKETS.join(", ")
+            )))
+        }
+    };
+    if !GROUP_BYS.contains(&group_by) {
+        return Err(AppError::BadRequest(format!(
+            "unknown group_by '{group_by}', expected one of: {}",
+            GROUP_BYS.join(", ")
+        )));
+    }
+    let default_period = match bucket {
+        "hour" => Duration::days(2),
+        "day" => Duration::days(30),
+        _ => Duration::weeks(26),
+    };
+
+    let filter = filter(
+        query.from,
+        query.to,
+        default_period,
+        query.stream_id,
+        query.blueprint_id,
+        query.risk_level.as_deref(),
+        query.utc_offset_minutes,
+    )?;
+    check_targets(db, &filter).await?;
+    if (filter.to - filter.from).num_seconds() / bucket_len.num_seconds() > MAX_BUCKETS {
+        return Err(AppError::BadRequest(format!(
+            "period too long for {bucket} buckets (at most {MAX_BUCKETS}); use a larger bucket or a shorter period"
+        )));
+    }
+
+    let buckets = db::event_counts(db, &filter, bucket, group_by).await?;
+
+    let mut totals: HashMap<Option<&str>, EventCountTotal> = HashMap::new();
+    for b in &buckets {
+        totals
+            .entry(b.key.as_deref())
+            .or_insert_with(|| EventCountTotal { key: b.key.clone(), label: b.label.clone(), count: 0 })
+            .count += b.count;
+    }
+    let mut totals: Vec<EventCountTotal> = totals.into_values().collect();
+    totals.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.key.cmp(&b.key)));
+
+    Ok(EventCounts {
+        bucket: bucket.to_string(),
+        group_by: group_by.to_string(),
+        from: filter.from,
+        to: filter.to,
+        buckets,
+        totals,
+    })
+}
+
+pub async fn resolution(db: &PgPool, query: &StatsQuery) -> Result<Vec<ResolutionStats>> {
+    let filter = stats_filter(query)?;
+    check_targets(db, &filter).await?;
+    db::resolution_stats(db, &filter).await
+}
+
+pub async fn heatmap(db: &PgPool, query: &StatsQuery) -> Result<Vec<HeatmapCell>> {
+    let filter = stats_filter(query)?;
+    check_targets(db, &filter).await?;
+    db::event_heatmap(db, &filter).await
+}
+
+fn stats_filter(query: &StatsQuery) -> Result<StatsFilter> {
+    filter(
+        query.from,
+        query.to,
+        Duration::days(DEFAULT_PERIOD_DAYS),
+        query.stream_id,
+        query.blueprint_id,
+        query.risk_level.as_deref(),
+        query.utc_offset_minutes,
+    )
+}
+
+/// Validates the shared filters and fills in the default period.
+fn filter(
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    default_period: Duration,
+    stream_id: Option<Uuid>,
+    blueprint_id: Option<Uuid>,
+    risk_level: Option<&str>,
+    utc_offset_minutes: i32,
+) -> Result<StatsFilter> {
+    let to = to.unwrap_or_else(Utc::now);
+    let from = from.unwrap_or(to - default_period);
+    if from >= to {
+        return Err(AppError::BadRequest("from must be before to".into()));
+    }
+    if !(-14 * 60..=14 * 60).contains(&utc_offset_minutes) {
+        return Err(AppError::BadRequest("utc_offset_minutes must be between -840 and 840".into()));
+    }
+    let risk_levels: Vec<String> =
+        risk_level.unwrap_or_default().split(',').map(str::trim).filter(|l| !l.is_empty()).map(String::from).collect();
+    for level in &risk_levels {
+        level.parse::<RiskLevel>().map_err(AppError::BadRequest)?;
+    }
+    Ok(StatsFilter {
+        from,
+        to,
+        stream_id,
+        blueprint_id,
+        risk_levels: (!risk_levels.is_empty()).then_some(risk_levels),
+        utc_offset_minutes,
+    })
+}
+
+/// A filter on a stream or blueprint that doesn't exist is a 404, not an
+/// empty report.
+async fn check_targets(db: &PgPool, filter: &StatsFilter) -> Result<()> {
+    if let Some(id) = filter.stream_id {
+        db::get_stream(db, id).await?;
+    }
+    if let Some(id) = filter.blueprint_id {
+        db::get_blueprint(db, id).await?;
+    }
+    Ok(())
+}
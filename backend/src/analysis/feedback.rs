@@ -0,0 +1,92 @@
+//! Turns operator-confirmed false positives into "do not flag" clauses for a
+//! stream's prompt. Recent false positives are grouped by the rule they were
+//! attributed to and summarized into one pending clause per group; an admin
+//! approves (optionally rewording) or rejects each one, and only approved
+//! clauses are appended by `build_rules_prompt`.
+
+use std::collections::HashSet;
+
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    error::Result,
+    storage::{
+        db,
+        models::{FalsePositiveRow, FeedbackClause, NewFeedbackClause},
+    },
+};
+
+/// Operator verdicts an event can be given.
+pub const DISPOSITIONS: [&str; 2] = ["false_positive", "true_positive"];
+
+/// How far back false positives are considered.
+const WINDOW_DAYS: i64 = 30;
+/// False positives needed before a group gets a clause; one may be a fluke.
+const MIN_FALSE_POSITIVES: usize = 2;
+/// Examples quoted in one clause.
+const MAX_EXAMPLES: usize = 5;
+/// Longest example taken from an event description.
+const MAX_EXAMPLE_CHARS: usize = 120;
+
+/// Rewrites the stream's pending clauses from its unreviewed false positives.
+/// Approved and rejected clauses are kept, and the events they were written
+/// from are not used again.
+pub async fn refresh(db: &PgPool, stream_id: Uuid) -> Result<Vec<FeedbackClause>> {
+    let since = Utc::now() - Duration::days(WINDOW_DAYS);
+    let false_positives = db::unreviewed_false_positives(db, stream_id, since).await?;
+
+    // Group by attributed rule, keeping first-seen order (newest first).
+    let mut groups: Vec<(Option<Uuid>, Vec<&FalsePositiveRow>)> = Vec::new();
+    for fp in &false_positives {
+        match groups.iter_mut().find(|(rule_id, _)| *rule_id == fp.rule_id) {
+            Some((_, group)) => group.push(fp),
+            None => groups.push((fp.rule_id, vec![fp])),
+        }
+    }
+
+    let clauses: Vec<NewFeedbackClause> = groups
+        .into_iter()
+        .filter(|(_, group)| group.len() >= MIN_FALSE_POSITIVES)
+        .map(|(rule_id, group)| NewFeedbackClause {
+            rule_id,
+            clause: summarize(group[0].rule_description.as_deref(), &group),
+            source_event_ids: group.iter().map(|fp| fp.id).collect(),
+        })
+        .collect();
+
+    db::replace_pending_feedback_clauses(db, stream_id, &clauses).await
+}
+
+/// One clause for a group of false positives: the operators' reasons, or the
+/// event titles where no reason was given.
+fn summarize(rule: Option<&str>, group: &[&FalsePositiveRow]) -> String {
+    let mut seen = HashSet::new();
+    let examples: Vec<String> = group
+        .iter()
+        .filter_map(|fp| {
+            let text = [fp.disposition_reason.as_deref(), fp.title.as_deref(), Some(fp.description.as_str())]
+                .into_iter()
+                .flatten()
+                .map(str::trim)
+                .find(|s| !s.is_empty())?;
+            Some(truncate(text, MAX_EXAMPLE_CHARS))
+        })
+        .filter(|text| seen.insert(text.to_lowercase()))
+        .take(MAX_EXAMPLES)
+        .collect();
+
+    let examples = examples.join("; ");
+    match rule {
+        Some(rule) => format!("Do not apply the rule \"{rule}\" to: {examples}."),
+        None => format!("Do not treat these as threats: {examples}."),
+    }
+}
+
+fn truncate(s: &str, max: usize) -> String {
+    match s.char_indices().nth(max) {
+        Some((i, _)) => format!("{}…", s[..i].trim_end()),
+        None => s.to_string(),
+    }
+}
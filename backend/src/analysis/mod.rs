@@ -1,2 +1,13 @@
+pub mod eval;
+pub mod event_stats;
+pub mod feedback;
+pub mod preprocess;
+pub mod reanalysis;
+pub mod references;
+pub mod rule_stats;
+pub mod rules;
+pub mod shadow;
+pub mod templates;
+pub mod tiling;
 pub mod vlm;
 pub mod worker;
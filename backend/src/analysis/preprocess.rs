@@ -0,0 +1,428 @@
+//! Per-stream image preprocessing before a frame goes to the VLM: rotate/flip
+//! for cameras mounted sideways or upside down, fisheye correction, ROI crop,
+//! resize and JPEG re-encoding. Smaller images mean faster, cheaper VLM calls.
+//! Zones are drawn on the frame as captured, so locations are moved between
+//! the two with a `FrameMapping`.
+
+use std::{borrow::Cow, io::Cursor, time::Instant};
+
+use image::{codecs::jpeg::JpegEncoder, imageops::FilterType, DynamicImage, Rgb, RgbImage};
+use sqlx::PgPool;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{
+    analysis::vlm::DetectedEvent,
+    error::{AppError, Result},
+    storage::{
+        db,
+        models::{FisheyeCorrection, PreprocessConfig, PreprocessStats, Roi},
+    },
+};
+
+const DEFAULT_JPEG_QUALITY: u8 = 85;
+
+/// Rejects settings that cannot be applied.
+pub fn validate(cfg: &PreprocessConfig) -> Result<()> {
+    let bad = |msg: &str| Err(AppError::BadRequest(format!("preprocessing: {msg}")));
+
+    if ![0, 90, 180, 270].contains(&cfg.rotate) {
+        return bad("rotate must be 0, 90, 180 or 270");
+    }
+    match cfg.fisheye {
+        Some(FisheyeCorrection::CenterCrop { ratio }) if !(0.1..=1.0).contains(&ratio) => {
+            return bad("fisheye center_crop ratio must be between 0.1 and 1.0");
+        }
+        Some(FisheyeCorrection::Dewarp { strength }) if !(0.0..=0.9).contains(&strength) => {
+            return bad("fisheye dewarp strength must be between 0.0 and 0.9");
+        }
+        _ => {}
+    }
+    if let Some(Roi { x, y, width, height }) = cfg.roi {
+        let in_unit = |v: f64| (0.0..=1.0).contains(&v);
+        if !(in_unit(x) && in_unit(y) && width > 0.0 && height > 0.0 && x + width <= 1.0 && y + height <= 1.0) {
+            return bad("roi must lie within the frame (fractions 0.0 – 1.0, non-empty)");
+        }
+    }
+    if cfg.max_dimension == Some(0) {
+        return bad("max_dimension must be positive");
+    }
+    if cfg.jpeg_quality.is_some_and(|q| !(1..=100).contains(&q)) {
+        return bad("jpeg_quality must be between 1 and 100");
+    }
+    Ok(())
+}
+
+/// The image to send to the VLM for `stream_id`: the frame run through the
+/// stream's preprocessing, or the frame as is if it has none (or it fails).
+pub async fn prepare_frame<'a>(
+    db: &PgPool,
+    stream_id: Uuid,
+    jpeg: &'a [u8],
+) -> Result<(Cow<'a, [u8]>, Option<PreprocessStats>, FrameMapping)> {
+    let Some(cfg) = db::get_stream_preprocessing(db, stream_id).await? else {
+        return Ok((Cow::Borrowed(jpeg), None, FrameMapping::default()));
+    };
+    let cfg: PreprocessConfig = match serde_json::from_value(cfg) {
+        Ok(c) => c,
+        Err(e) => {
+            warn!(stream = %stream_id, "Invalid preprocessing config, sending frame as captured: {e}");
+            return Ok((Cow::Borrowed(jpeg), None, FrameMapping::default()));
+        }
+    };
+
+    let input = jpeg.to_vec();
+    let processed = tokio::task::spawn_blocking(move || apply(&input, &cfg))
+        .await
+        .map_err(|e| AppError::Other(e.into()))?;
+
+    Ok(match processed {
+        Ok((out, stats, mapping)) => (Cow::Owned(out), Some(stats), mapping),
+        Err(e) => {
+            warn!(stream = %stream_id, "Preprocessing failed, sending frame as captured: {e}");
+            (Cow::Borrowed(jpeg), None, FrameMapping::default())
+        }
+    })
+}
+
+/// Runs the pipeline on one JPEG. CPU-bound; call from a blocking task.
+pub fn apply(jpeg: &[u8], cfg: &PreprocessConfig) -> anyhow::Result<(Vec<u8>, PreprocessStats, FrameMapping)> {
+    let started = Instant::now();
+    let mut img = image::load_from_memory(jpeg)?;
+    let mut mapping = FrameMapping::default();
+
+    img = match cfg.rotate {
+        90 => img.rotate90(),
+        180 => img.rotate180(),
+        270 => img.rotate270(),
+        _ => img,
+    };
+    if cfg.rotate != 0 {
+        mapping.steps.push(Step::Rotate(cfg.rotate));
+    }
+    if cfg.flip_horizontal {
+        img = img.fliph();
+        mapping.steps.push(Step::FlipHorizontal);
+    }
+    if cfg.flip_vertical {
+        img = img.flipv();
+        mapping.steps.push(Step::FlipVertical);
+    }
+
+    match cfg.fisheye {
+        Some(FisheyeCorrection::CenterCrop { ratio }) => {
+            let (w, h) = (img.width(), img.height());
+            let (cw, ch) = (scaled(w, ratio), scaled(h, ratio));
+            img = img.crop_imm((w - cw) / 2, (h - ch) / 2, cw, ch);
+            mapping.steps.push(Step::crop(w, h, (w - cw) / 2, (h - ch) / 2, cw, ch));
+        }
+        Some(FisheyeCorrection::Dewarp { strength }) if strength > 0.0 => {
+            img = DynamicImage::ImageRgb8(dewarp(&img.to_rgb8(), strength));
+            mapping.steps.push(Step::Dewarp { strength, width: img.width() as f64, height: img.height() as f64 });
+        }
+        _ => {}
+    }
+
+    if let Some(roi) = cfg.roi {
+        let (w, h) = (img.width(), img.height());
+        let x = ((roi.x * w as f64) as u32).min(w - 1);
+        let y = ((roi.y * h as f64) as u32).min(h - 1);
+        let (cw, ch) = (scaled(w, roi.width).min(w - x), scaled(h, roi.height).min(h - y));
+        img = img.crop_imm(x, y, cw, ch);
+        mapping.steps.push(Step::crop(w, h, x, y, cw, ch));
+    }
+
+    if let Some(max) = cfg.max_dimension {
+        if img.width() > max || img.height() > max {
+            img = img.resize(max, max, FilterType::Triangle);
+        }
+    }
+
+    let mut out = Vec::new();
+    let quality = cfg.jpeg_quality.unwrap_or(DEFAULT_JPEG_QUALITY);
+    JpegEncoder::new_with_quality(&mut Cursor::new(&mut out), quality).encode_image(&img.to_rgb8())?;
+
+    let stats = PreprocessStats {
+        input_bytes: jpeg.len(),
+        output_bytes: out.len(),
+        width: img.width(),
+        height: img.height(),
+        elapsed_ms: started.elapsed().as_millis().min(u32::MAX as u128) as u32,
+    };
+    Ok((out, stats, mapping))
+}
+
+/// How locations move between the frame as captured and the image the VLM
+/// sees, both in fractions (0.0 – 1.0) of their image. The default maps every
+/// point to itself, as when a stream has no preprocessing; resizing and
+/// re-encoding move nothing.
+#[derive(Debug, Clone, Default)]
+pub struct FrameMapping {
+    /// The geometric steps, in the order they were applied to the frame.
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone)]
+enum Step {
+    /// Clockwise, in degrees.
+    Rotate(u16),
+    FlipHorizontal,
+    FlipVertical,
+    /// The part kept, in fractions of the image before cropping.
+    Crop { x: f64, y: f64, width: f64, height: f64 },
+    /// Image size in pixels, which sets the radius `dewarp` works with.
+    Dewarp { strength: f64, width: f64, height: f64 },
+}
+
+impl Step {
+    /// A crop of `(x, y, cw, ch)` pixels out of a `w` × `h` image.
+    fn crop(w: u32, h: u32, x: u32, y: u32, cw: u32, ch: u32) -> Self {
+        let (w, h) = (w as f64, h as f64);
+        Step::Crop { x: x as f64 / w, y: y as f64 / h, width: cw as f64 / w, height: ch as f64 / h }
+    }
+
+    /// Where a point of the image before this step ends up after it.
+    fn forward(&self, (u, v): (f64, f64)) -> (f64, f64) {
+        match *self {
+            Step::Rotate(90) => (1.0 - v, u),
+            Step::Rotate(180) => (1.0 - u, 1.0 - v),
+            Step::Rotate(270) => (v, 1.0 - u),
+            Step::Rotate(_) => (u, v),
+            Step::FlipHorizontal => (1.0 - u, v),
+            Step::FlipVertical => (u, 1.0 - v),
+            Step::Crop { x, y, width, height } => ((u - x) / width, (v - y) / height),
+            Step::Dewarp { strength, width, height } => {
+                // `dewarp` shows the source point at radius ρ at the radius r
+                // with r · (1 − strength · r²) = ρ; that is increasing up to
+                // 1/√(3 · strength), so bisect below it.
+                let source = radius(u, v, width, height);
+                if source == 0.0 {
+                    return (u, v);
+                }
+                let shown = |r: f64| r * (1.0 - strength * r * r);
+                let (mut lo, mut hi) = (0.0, (1.0 / (3.0 * strength)).sqrt());
+                if shown(hi) > source {
+                    for _ in 0..40 {
+                        let mid = (lo + hi) / 2.0;
+                        if shown(mid) < source {
+                            lo = mid;
+                        } else {
+                            hi = mid;
+                        }
+                    }
+                }
+                let k = hi / source;
+                (0.5 + (u - 0.5) * k, 0.5 + (v - 0.5) * k)
+            }
+        }
+    }
+
+    /// Where a point of the image after this step came from.
+    fn back(&self, (u, v): (f64, f64)) -> (f64, f64) {
+        match *self {
+            Step::Rotate(90) => (v, 1.0 - u),
+            Step::Rotate(270) => (1.0 - v, u),
+            Step::Rotate(_) | Step::FlipHorizontal | Step::FlipVertical => self.forward((u, v)),
+            Step::Crop { x, y, width, height } => (x + u * width, y + v * height),
+            Step::Dewarp { strength, width, height } => {
+                let r = radius(u, v, width, height);
+                let k = 1.0 - strength * r * r;
+                (0.5 + (u - 0.5) * k, 0.5 + (v - 0.5) * k)
+            }
+        }
+    }
+}
+
+/// Distance of (u, v) from the center of a `width` × `height` image, with
+/// the corners at 1, as `dewarp` measures it.
+fn radius(u: f64, v: f64, width: f64, height: f64) -> f64 {
+    let (dx, dy) = ((u - 0.5) * width, (v - 0.5) * height);
+    (dx * dx + dy * dy).sqrt() / (width * width + height * height).sqrt() * 2.0
+}
+
+impl FrameMapping {
+    /// Where a point of the captured frame is in the VLM's image. Points the
+    /// image cropped away fall outside 0.0 – 1.0.
+    pub fn to_image(&self, point: (f64, f64)) -> (f64, f64) {
+        self.steps.iter().fold(point, |p, step| step.forward(p))
+    }
+
+    /// Where a point of the VLM's image is in the captured frame.
+    pub fn to_frame(&self, point: (f64, f64)) -> (f64, f64) {
+        self.steps.iter().rev().fold(point, |p, step| step.back(p))
+    }
+
+    /// Moves the detections' bounding boxes from the VLM's image to the
+    /// captured frame: each becomes the box around its mapped corners.
+    pub fn boxes_to_frame(&self, events: &mut [DetectedEvent]) {
+        if self.steps.is_empty() {
+            return;
+        }
+        for bbox in events.iter_mut().filter_map(|e| e.bbox.as_mut()) {
+            let [x0, y0, x1, y1] = *bbox;
+            let corners = [(x0, y0), (x1, y0), (x0, y1), (x1, y1)].map(|c| self.to_frame(c));
+            let (xs, ys) = (corners.map(|c| c.0), corners.map(|c| c.1));
+            let min = |a: [f64; 4]| a.into_iter().fold(f64::INFINITY, f64::min).clamp(0.0, 1.0);
+            let max = |a: [f64; 4]| a.into_iter().fold(f64::NEG_INFINITY, f64::max).clamp(0.0, 1.0);
+            *bbox = [min(xs), min(ys), max(xs), max(ys)];
+        }
+    }
+}
+
+/// `len * fraction`, at least one pixel.
+fn scaled(len: u32, fraction: f64) -> u32 {
+    ((len as f64 * fraction).round() as u32).clamp(1, len)
+}
+
+/// Undoes barrel distortion with a one-coefficient radial model: each output
+/// pixel at normalized radius r samples the source at r · (1 − strength · r²),
+/// which stretches the compressed edges back out. Bilinear sampling.
+fn dewarp(src: &RgbImage, strength: f64) -> RgbImage {
+    let strength = strength as f32;
+    let (w, h) = src.dimensions();
+    let (cx, cy) = ((w as f32 - 1.0) / 2.0, (h as f32 - 1.0) / 2.0);
+    let norm = (cx * cx + cy * cy).sqrt().max(1.0);
+
+    RgbImage::from_fn(w, h, |x, y| {
+        let (dx, dy) = ((x as f32 - cx) / norm, (y as f32 - cy) / norm);
+        let scale = 1.0 - strength * (dx * dx + dy * dy);
+        sample_bilinear(src, cx + dx * scale * norm, cy + dy * scale * norm)
+    })
+}
+
+fn sample_bilinear(src: &RgbImage, x: f32, y: f32) -> Rgb<u8> {
+    let (w, h) = src.dimensions();
+    let x = x.clamp(0.0, (w - 1) as f32);
+    let y = y.clamp(0.0, (h - 1) as f32);
+    let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+    let (x1, y1) = ((x0 + 1).min(w - 1), (y0 + 1).min(h - 1));
+    let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+
+    let (p00, p10) = (src.get_pixel(x0, y0), src.get_pixel(x1, y0));
+    let (p01, p11) = (src.get_pixel(x0, y1), src.get_pixel(x1, y1));
+    Rgb(std::array::from_fn(|c| {
+        let top = p00[c] as f32 * (1.0 - fx) + p10[c] as f32 * fx;
+        let bottom = p01[c] as f32 * (1.0 - fx) + p11[c] as f32 * fx;
+        (top * (1.0 - fy) + bottom * fy).round() as u8
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{
+        analysis::{
+            rules,
+            vlm::{AnalysisResult, RiskLevel, VlmRule},
+        },
+        storage::models::StreamRule,
+    };
+
+    /// A 200 × 100 frame run through `cfg`.
+    fn mapping(cfg: PreprocessConfig) -> FrameMapping {
+        let mut frame = Vec::new();
+        JpegEncoder::new(&mut Cursor::new(&mut frame)).encode_image(&RgbImage::new(200, 100)).unwrap();
+        apply(&frame, &cfg).unwrap().2
+    }
+
+    fn config(roi: Option<Roi>) -> PreprocessConfig {
+        PreprocessConfig {
+            rotate: 0,
+            flip_horizontal: false,
+            flip_vertical: false,
+            fisheye: None,
+            roi,
+            max_dimension: Some(64),
+            jpeg_quality: None,
+        }
+    }
+
+    fn close(a: (f64, f64), b: (f64, f64)) -> bool {
+        (a.0 - b.0).abs() < 1e-3 && (a.1 - b.1).abs() < 1e-3
+    }
+
+    #[test]
+    fn roi_maps_image_corners_to_the_frame() {
+        let m = mapping(config(Some(Roi { x: 0.5, y: 0.0, width: 0.5, height: 0.5 })));
+        assert!(close(m.to_frame((0.0, 0.0)), (0.5, 0.0)));
+        assert!(close(m.to_frame((1.0, 1.0)), (1.0, 0.5)));
+        assert!(close(m.to_image((0.75, 0.25)), (0.5, 0.5)));
+    }
+
+    #[test]
+    fn every_step_maps_back_to_where_it_came_from() {
+        let cfg = PreprocessConfig {
+            rotate: 90,
+            flip_horizontal: true,
+            fisheye: Some(FisheyeCorrection::Dewarp { strength: 0.4 }),
+            ..config(Some(Roi { x: 0.1, y: 0.2, width: 0.8, height: 0.6 }))
+        };
+        let m = mapping(cfg);
+        for point in [(0.3, 0.4), (0.5, 0.5), (0.6, 0.45), (0.4, 0.65)] {
+            let back = m.to_frame(m.to_image(point));
+            assert!(close(back, point), "{point:?} came back as {back:?}");
+        }
+        // Rotated a quarter turn clockwise, then mirrored: the frame's top
+        // edge becomes the image's left edge, read top to bottom.
+        let m = mapping(PreprocessConfig { rotate: 90, flip_horizontal: true, ..config(None) });
+        assert!(close(m.to_image((0.25, 0.0)), (0.0, 0.25)));
+    }
+
+    fn dock_rule(zone: serde_json::Value) -> StreamRule {
+        StreamRule {
+            id: Uuid::new_v4(),
+            scope: "stream".into(),
+            stream_id: None,
+            blueprint_id: None,
+            description: "Person at the loading dock".into(),
+            threat_level: "high".into(),
+            position: 0,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            schedule: None,
+            zone: Some(zone),
+            min_count: None,
+            min_dwell_sec: None,
+            event_types: vec!["person_detected".into()],
+        }
+    }
+
+    #[test]
+    fn zones_are_checked_where_the_detection_is_on_the_frame() {
+        // The VLM sees the right half of the frame; the zone covers the
+        // middle of that half, and the person stands in the image's middle.
+        let m = mapping(config(Some(Roi { x: 0.5, y: 0.0, width: 0.5, height: 1.0 })));
+        let rules = [dock_rule(json!([{"x":0.65,"y":0.0},{"x":0.85,"y":0.0},{"x":0.85,"y":1.0},{"x":0.65,"y":1.0}]))];
+        let mut result: AnalysisResult = serde_json::from_value(json!({
+            "description": "A person by the dock door",
+            "events": [{"event_type":"person_detected","confidence":0.9,"bbox":[0.4,0.3,0.6,0.9]}],
+            "risk_level": "high",
+            "triggered_rule": "Person at the loading dock"
+        }))
+        .unwrap();
+
+        // Taken as frame locations, the image's middle is left of the zone.
+        let check = rules::check_triggered(&rules, &mut result.clone(), Uuid::nil(), Utc::now(), None);
+        assert!(!check.unwrap().passed);
+
+        m.boxes_to_frame(&mut result.events);
+        let [x0, _, x1, _] = result.events[0].bbox.unwrap();
+        assert!((x0 - 0.7).abs() < 1e-3 && (x1 - 0.8).abs() < 1e-3);
+        let check = rules::check_triggered(&rules, &mut result, Uuid::nil(), Utc::now(), None);
+        assert!(check.unwrap().passed);
+        assert_eq!(result.risk_level, RiskLevel::High);
+    }
+
+    #[test]
+    fn prompt_gives_zone_corners_in_the_image() {
+        let m = mapping(config(Some(Roi { x: 0.5, y: 0.0, width: 0.5, height: 1.0 })));
+        let rule = dock_rule(json!([{"x":0.75,"y":0.5},{"x":0.5,"y":0.0},{"x":0.25,"y":1.0}]));
+        let conditions = VlmRule::for_image(rule, &m).conditions.unwrap();
+        // The last corner lies left of the crop and is kept to the image's edge.
+        assert!(conditions.contains("(0.50, 0.50) (0.00, 0.00) (0.00, 1.00)"), "{conditions}");
+    }
+}
@@ -0,0 +1,211 @@
+//! Re-runs stored frames through the VLM against the current rules (optionally
+//! with a different model) and stores the results next to the original events.
+//! Re-analysis never broadcasts or alerts.
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    sync::Arc,
+};
+
+use sqlx::PgPool;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    analysis::{
+        preprocess, references, rules,
+        vlm::{registry::VlmRegistry, DynVlmClient, RiskLevel},
+    },
+    error::Result,
+    storage::{
+        db,
+        models::{
+            EventQuery, NewEventReanalysis, ReanalysisJob, ReanalyzeRequest, RiskDiffSummary,
+            RiskTransition, StreamRule,
+        },
+    },
+};
+
+/// Upper bound on how many events a single job may re-run.
+const MAX_EVENTS_PER_JOB: i64 = 1000;
+
+/// Stream name, current rules and feedback clauses, and VLM client, loaded once per stream.
+type StreamContext = (String, Vec<StreamRule>, Vec<String>, DynVlmClient);
+
+/// Create a job for the events matching `req` and run it in the background.
+/// Each event goes to its stream's current VLM, with `req.model` in place of
+/// its model if given.
+pub async fn start_job(db: &PgPool, vlm: Arc<VlmRegistry>, req: &ReanalyzeRequest) -> Result<ReanalysisJob> {
+    let query = EventQuery {
+        stream_id: req.stream_id,
+        blueprint_id: req.blueprint_id,
+        risk_level: req.risk_level.clone(),
+        status: req.status.clone(),
+        event_type: req.event_type.clone(),
+        triggered_rule: req.triggered_rule.clone(),
+        q: req.q.clone(),
+        from: req.from,
+        to: req.to,
+        limit: req.limit.clamp(1, MAX_EVENTS_PER_JOB),
+        ..Default::default()
+    };
+    let event_ids = db::list_event_ids(db, &query).await?;
+
+    let filter = serde_json::json!({
+        "stream_id": query.stream_id,
+        "blueprint_id": query.blueprint_id,
+        "risk_level": query.risk_level,
+        "status": query.status,
+        "event_type": query.event_type,
+        "triggered_rule": query.triggered_rule,
+        "q": query.q,
+        "from": query.from,
+        "to": query.to,
+        "limit": query.limit,
+    });
+    let model = req.model.as_deref().map(str::trim).filter(|m| !m.is_empty()).map(String::from);
+    let job = db::create_reanalysis_job(db, filter, model.as_deref(), event_ids.len() as i32).await?;
+
+    info!(job = %job.id, events = event_ids.len(), "Starting re-analysis job");
+
+    let db = db.clone();
+    let job_id = job.id;
+    tokio::spawn(async move {
+        let (status, err) = match run_job(&db, &vlm, model.as_deref(), job_id, &event_ids).await {
+            Ok(()) => ("completed", None),
+            Err(e) => {
+                error!(job = %job_id, "Re-analysis job failed: {e}");
+                ("failed", Some(e.to_string()))
+            }
+        };
+        if let Err(e) = db::finish_reanalysis_job(&db, job_id, status, err.as_deref()).await {
+            error!(job = %job_id, "Failed to finish re-analysis job: {e}");
+        }
+    });
+
+    Ok(job)
+}
+
+async fn run_job(
+    db: &PgPool,
+    vlm: &VlmRegistry,
+    model: Option<&str>,
+    job_id: Uuid,
+    event_ids: &[Uuid],
+) -> Result<()> {
+    let mut streams = HashMap::new();
+
+    // One event failing (deleted since the job started, an unreadable frame,
+    // a VLM error) counts it as failed; the job goes on with the rest.
+    for &event_id in event_ids {
+        let analyzed = match reanalyze_event(db, vlm, model, &mut streams, job_id, event_id).await {
+            Ok(analyzed) => analyzed,
+            Err(e) => {
+                warn!(job = %job_id, event = %event_id, "Re-analysis failed: {e}");
+                false
+            }
+        };
+        db::record_reanalysis_progress(db, job_id, !analyzed).await?;
+    }
+
+    info!(job = %job_id, "Re-analysis job complete");
+    Ok(())
+}
+
+/// Re-runs one event and stores the result. Returns false if there was
+/// nothing to analyze.
+async fn reanalyze_event(
+    db: &PgPool,
+    vlm: &VlmRegistry,
+    model: Option<&str>,
+    streams: &mut HashMap<Uuid, StreamContext>,
+    job_id: Uuid,
+    event_id: Uuid,
+) -> Result<bool> {
+    let event = db::get_event(db, event_id).await?;
+    let Some(frame) = event.frame.as_deref() else {
+        warn!(job = %job_id, event = %event_id, "Event has no stored frame, skipping");
+        return Ok(false);
+    };
+
+    let (stream_name, stream_rules, exclusions, client) = match streams.entry(event.stream_id) {
+        Entry::Occupied(e) => e.into_mut(),
+        Entry::Vacant(e) => {
+            let stream = db::get_stream(db, event.stream_id).await?;
+            let rules = rules::effective_rules(db, event.stream_id).await?;
+            let exclusions = db::approved_feedback_clauses(db, event.stream_id).await?;
+            let client = match model {
+                Some(model) => vlm.client_for(&vlm.stream_backend(db, event.stream_id).await?.with_model(model)),
+                None => vlm.for_stream(db, event.stream_id).await?,
+            };
+            e.insert((stream.name, rules, exclusions, client))
+        }
+    };
+    // The rules that would have been in force when the frame was captured.
+    let active_rules = rules::active_at(stream_rules.clone(), event.captured_at);
+    let (image, _, mapping) = preprocess::prepare_frame(db, event.stream_id, frame).await?;
+    let rules = references::vlm_rules(db, &active_rules, &mapping).await?;
+
+    let mut output = client.analyze(&image, stream_name, &rules, exclusions).await?;
+    mapping.boxes_to_frame(&mut output.result.events);
+    rules::check_triggered(&active_rules, &mut output.result, event.stream_id, event.captured_at, None);
+    let result = &output.result;
+    let triggered_rule = if rules.is_empty() {
+        None
+    } else {
+        result.triggered_rule.as_deref().map(str::trim).filter(|s| !s.is_empty())
+    };
+
+    db::insert_event_reanalysis(
+        db,
+        &NewEventReanalysis {
+            job_id,
+            event_id,
+            original_risk_level: &event.risk_level,
+            risk_level: result.risk_level.as_str(),
+            triggered_rule,
+            title: result.title.as_deref().map(str::trim).filter(|s| !s.is_empty()),
+            description: &result.description,
+            events: serde_json::to_value(&result.events).unwrap_or_default(),
+            raw_response: Some(&output.raw_response),
+            system_prompt: Some(&output.system_prompt),
+            rules_snapshot: serde_json::to_value(&rules).ok(),
+            model: Some(&output.model),
+            vlm_backend: Some(output.backend),
+            latency_ms: Some(output.latency_ms),
+            prompt_tokens: output.usage.prompt_tokens,
+            completion_tokens: output.usage.completion_tokens,
+        },
+    )
+    .await?;
+    Ok(true)
+}
+
+/// Diff of a job's results against the original risk levels.
+pub async fn summarize(db: &PgPool, job_id: Uuid) -> Result<RiskDiffSummary> {
+    let transitions = db::reanalysis_transitions(db, job_id).await?;
+    Ok(diff_summary(transitions))
+}
+
+/// Classifies each (original → new) transition as unchanged, escalated or downgraded.
+pub fn diff_summary(transitions: Vec<RiskTransition>) -> RiskDiffSummary {
+    let mut summary = RiskDiffSummary {
+        compared: 0,
+        unchanged: 0,
+        escalated: 0,
+        downgraded: 0,
+        transitions: Vec::new(),
+    };
+    for t in &transitions {
+        summary.compared += t.count;
+        let from = t.from.parse::<RiskLevel>().unwrap_or_default();
+        let to = t.to.parse::<RiskLevel>().unwrap_or_default();
+        match to.cmp(&from) {
+            std::cmp::Ordering::Equal => summary.unchanged += t.count,
+            std::cmp::Ordering::Greater => summary.escalated += t.count,
+            std::cmp::Ordering::Less => summary.downgraded += t.count,
+        }
+    }
+    summary.transitions = transitions;
+    summary
+}
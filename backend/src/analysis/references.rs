@@ -0,0 +1,100 @@
+//! Reference images: positive and negative examples attached to a rule, sent
+//! to multi-image VLMs before the frame so small models can see what a rule
+//! like "anyone not in the blue uniform behind the counter" means. Uploads are
+//! scaled down and re-encoded once, so every prompt carries small JPEGs.
+
+use std::{collections::HashMap, io::Cursor, sync::Arc};
+
+use base64::{engine::general_purpose::STANDARD as B64, Engine};
+use image::{codecs::jpeg::JpegEncoder, imageops::FilterType};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    analysis::{
+        preprocess::FrameMapping,
+        vlm::{ReferenceImage, VlmRule},
+    },
+    error::{AppError, Result},
+    storage::{
+        db,
+        models::{CreateReferenceImageRequest, RuleReferenceImage, StreamRule},
+    },
+};
+
+/// "positive": the rule applies to the scene; "negative": it does not.
+pub const KINDS: [&str; 2] = ["positive", "negative"];
+
+/// Images one rule may have; each one costs prompt tokens on every frame.
+const MAX_PER_RULE: i64 = 4;
+/// Longest side of a stored reference image, in pixels.
+const MAX_DIMENSION: u32 = 768;
+const JPEG_QUALITY: u8 = 85;
+
+/// Validates and stores a reference image for `rule_id`, uploaded or copied
+/// from an event's frame.
+pub async fn add(db: &PgPool, rule_id: Uuid, req: &CreateReferenceImageRequest) -> Result<RuleReferenceImage> {
+    db::get_rule_by_id(db, rule_id).await?;
+    if !KINDS.contains(&req.kind.as_str()) {
+        return Err(AppError::BadRequest(format!("kind must be one of: {}", KINDS.join(", "))));
+    }
+    if db::count_reference_images(db, rule_id).await? >= MAX_PER_RULE {
+        return Err(AppError::BadRequest(format!(
+            "rule {rule_id} already has {MAX_PER_RULE} reference images; delete one first"
+        )));
+    }
+
+    let image = match (&req.image_base64, req.event_id) {
+        (Some(s), _) if !s.is_empty() => {
+            B64.decode(s.as_bytes()).map_err(|_| AppError::BadRequest("invalid image_base64".into()))?
+        }
+        (_, Some(event_id)) => db::get_event(db, event_id)
+            .await?
+            .frame
+            .ok_or_else(|| AppError::BadRequest(format!("Event {event_id} has no stored frame")))?,
+        _ => return Err(AppError::BadRequest("image_base64 or event_id is required".into())),
+    };
+    let image = tokio::task::spawn_blocking(move || normalize(&image))
+        .await
+        .map_err(|e| AppError::Other(e.into()))??;
+
+    let caption = req.caption.as_deref().map(str::trim).filter(|c| !c.is_empty());
+    db::create_reference_image(db, rule_id, &req.kind, caption, &image, req.event_id).await
+}
+
+/// `rules` as prompt rules for an image preprocessed as `mapping` describes,
+/// each with its reference images.
+pub async fn vlm_rules(db: &PgPool, rules: &[StreamRule], mapping: &FrameMapping) -> Result<Vec<VlmRule>> {
+    let ids: Vec<Uuid> = rules.iter().map(|r| r.id).collect();
+    let mut images: HashMap<Uuid, Vec<ReferenceImage>> = HashMap::new();
+    for row in db::reference_images_for_rules(db, &ids).await? {
+        images.entry(row.rule_id).or_default().push(ReferenceImage {
+            positive: row.kind == "positive",
+            caption: row.caption,
+            jpeg: Arc::from(row.image),
+        });
+    }
+
+    Ok(rules
+        .iter()
+        .map(|rule| {
+            let references = images.remove(&rule.id).unwrap_or_default();
+            VlmRule { references, ..VlmRule::for_image(rule.clone(), mapping) }
+        })
+        .collect())
+}
+
+/// Decodes an uploaded image, scales it to at most `MAX_DIMENSION` and
+/// re-encodes it as JPEG. CPU-bound; call from a blocking task.
+fn normalize(image: &[u8]) -> Result<Vec<u8>> {
+    let mut img = image::load_from_memory(image)
+        .map_err(|e| AppError::BadRequest(format!("image could not be decoded: {e}")))?;
+    if img.width() > MAX_DIMENSION || img.height() > MAX_DIMENSION {
+        img = img.resize(MAX_DIMENSION, MAX_DIMENSION, FilterType::Triangle);
+    }
+    let mut out = Vec::new();
+    JpegEncoder::new_with_quality(&mut Cursor::new(&mut out), JPEG_QUALITY)
+        .encode_image(&img.to_rgb8())
+        .map_err(|e| AppError::Other(e.into()))?;
+    Ok(out)
+}
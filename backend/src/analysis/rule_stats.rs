@@ -0,0 +1,83 @@
+//! Which rules actually fire: hits, false positives and confidence per rule,
+//! from the events linked to each rule (see `rules::match_rule`).
+
+use std::collections::HashMap;
+
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+
+use crate::{
+    analysis::rules,
+    error::{AppError, Result},
+    storage::{
+        db,
+        models::{DailyRuleHits, RuleStats, RuleStatsQuery},
+    },
+};
+
+/// Period used when the query gives no `from`.
+const DEFAULT_PERIOD_DAYS: i64 = 30;
+/// Frames a rule must have been applied to before it is flagged.
+const MIN_ANALYZED_FOR_FLAG: i64 = 50;
+/// Hit rate from which a rule is flagged as always firing.
+const ALWAYS_FIRES_RATE: f64 = 0.9;
+
+pub async fn report(db: &PgPool, query: &RuleStatsQuery) -> Result<Vec<RuleStats>> {
+    let to = query.to.unwrap_or_else(Utc::now);
+    let from = query.from.unwrap_or(to - Duration::days(DEFAULT_PERIOD_DAYS));
+    if from >= to {
+        return Err(AppError::BadRequest("from must be before to".into()));
+    }
+
+    let rules = match query.stream_id {
+        Some(id) => {
+            db::get_stream(db, id).await?;
+            rules::effective_rules(db, id).await?
+        }
+        None => db::list_all_rules(db).await?,
+    };
+    let mut hits: HashMap<_, _> =
+        db::rule_hit_stats(db, from, to, query.stream_id).await?.into_iter().map(|h| (h.rule_id, h)).collect();
+    let mut daily: HashMap<_, Vec<DailyRuleHits>> = HashMap::new();
+    for d in db::rule_daily_hits(db, from, to, query.stream_id).await? {
+        daily.entry(d.rule_id).or_default().push(d);
+    }
+    let ratio = |num: i64, den: i64| (den > 0).then(|| num as f64 / den as f64);
+
+    Ok(rules
+        .into_iter()
+        .map(|rule| {
+            let h = hits.remove(&rule.id);
+            let (analyzed, hit_count, false_positives) =
+                h.as_ref().map_or((0, 0, 0), |h| (h.analyzed, h.hits, h.false_positives));
+            let hit_rate = ratio(hit_count, analyzed);
+            let flag = if analyzed < MIN_ANALYZED_FOR_FLAG {
+                None
+            } else if hit_count == 0 {
+                Some("never_fires".to_string())
+            } else if hit_rate.is_some_and(|r| r >= ALWAYS_FIRES_RATE) {
+                Some("always_fires".to_string())
+            } else {
+                None
+            };
+
+            RuleStats {
+                rule_id: rule.id,
+                scope: rule.scope,
+                stream_id: rule.stream_id,
+                blueprint_id: rule.blueprint_id,
+                description: rule.description,
+                threat_level: rule.threat_level,
+                analyzed,
+                hits: hit_count,
+                hit_rate,
+                false_positives,
+                false_positive_rate: ratio(false_positives, hit_count),
+                last_triggered: h.as_ref().and_then(|h| h.last_triggered),
+                mean_confidence: h.and_then(|h| h.mean_confidence),
+                hits_per_day: daily.remove(&rule.id).unwrap_or_default(),
+                flag,
+            }
+        })
+        .collect())
+}
@@ -0,0 +1,422 @@
+//! The deterministic side of rules. A stream's rules are the global rules, its
+//! blueprint's rules and its own, with the stream's overrides applied. Before
+//! a VLM call, rules whose schedule is not in force at capture time are left
+//! out of the prompt. After it, the rule the VLM triggered is checked against
+//! its structured conditions (event types, count, zone, dwell time); a rule
+//! that fails them is dropped from the result.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+};
+
+use chrono::{DateTime, Datelike, FixedOffset, NaiveTime, Timelike, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    analysis::vlm::{AnalysisResult, DetectedEvent, RiskLevel, EVENT_TYPES},
+    error::{AppError, Result},
+    storage::{
+        db,
+        models::{
+            EffectiveRule, RuleCheck, RuleOverride, RuleSchedule, ScheduleWindow, StreamRule, ZonePoint,
+        },
+    },
+};
+
+const DAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+/// Rejects structured rule fields that cannot be evaluated.
+pub fn validate(
+    schedule: Option<&RuleSchedule>,
+    zone: Option<&[ZonePoint]>,
+    min_count: Option<i32>,
+    min_dwell_sec: Option<i32>,
+    event_types: &[String],
+) -> Result<()> {
+    let bad = |msg: String| Err(AppError::BadRequest(msg));
+
+    if let Some(schedule) = schedule {
+        if schedule.windows.is_empty() {
+            return bad("schedule needs at least one window".into());
+        }
+        if !(-14 * 60..=14 * 60).contains(&schedule.utc_offset_minutes) {
+            return bad("schedule utc_offset_minutes must be between -840 and 840".into());
+        }
+        for w in &schedule.windows {
+            if let Some(day) = w.days.iter().find(|d| !DAYS.contains(&d.as_str())) {
+                return bad(format!("unknown schedule day '{day}', expected one of: {}", DAYS.join(", ")));
+            }
+            for t in [&w.start, &w.end] {
+                if parse_time(t).is_none() {
+                    return bad(format!("schedule time '{t}' must be HH:MM"));
+                }
+            }
+            // A window with no length would never be in force (windows end
+            // before `end`); a rule in force all the time has no schedule.
+            if parse_time(&w.start) == parse_time(&w.end) {
+                return bad(format!("schedule window {} – {} is empty: start and end must differ", w.start, w.end));
+            }
+        }
+    }
+    if let Some(zone) = zone {
+        if zone.len() < 3 {
+            return bad("zone must have at least 3 points".into());
+        }
+        if zone.iter().any(|p| !(0.0..=1.0).contains(&p.x) || !(0.0..=1.0).contains(&p.y)) {
+            return bad("zone points must be fractions of the frame (0.0 – 1.0)".into());
+        }
+    }
+    if min_count.is_some_and(|n| n < 1) || min_dwell_sec.is_some_and(|s| s < 1) {
+        return bad("min_count and min_dwell_sec must be positive".into());
+    }
+    if let Some(t) = event_types.iter().find(|t| !EVENT_TYPES.contains(&t.as_str())) {
+        return bad(format!("unknown event type '{t}', expected one of: {}", EVENT_TYPES.join(", ")));
+    }
+    Ok(())
+}
+
+/// The rules that apply to `stream_id`, most general first. See `merge`.
+pub async fn effective(db: &PgPool, stream_id: Uuid) -> Result<Vec<EffectiveRule>> {
+    let rules = db::list_rules_for_stream(db, stream_id).await?;
+    let overrides = db::list_rule_overrides(db, stream_id).await?;
+    Ok(merge(rules, &overrides))
+}
+
+/// `effective` without the inheritance details, as the worker uses them.
+pub async fn effective_rules(db: &PgPool, stream_id: Uuid) -> Result<Vec<StreamRule>> {
+    Ok(effective(db, stream_id).await?.into_iter().map(|e| e.rule).collect())
+}
+
+/// Applies a stream's overrides to its inherited and own rules (ordered global,
+/// blueprint, stream). Disabled rules are dropped and overridden threat levels
+/// replaced. A rule with the same description as a more general one replaces
+/// it, so a stream can redefine an inherited rule.
+pub fn merge(rules: Vec<StreamRule>, overrides: &[RuleOverride]) -> Vec<EffectiveRule> {
+    let mut merged: Vec<EffectiveRule> = Vec::new();
+    for mut rule in rules {
+        let o = overrides.iter().find(|o| o.rule_id == rule.id);
+        if o.is_some_and(|o| o.disabled) {
+            continue;
+        }
+        let overridden_threat_level = o
+            .and_then(|o| o.threat_level.clone())
+            .map(|level| std::mem::replace(&mut rule.threat_level, level));
+
+        let key = rule.description.trim().to_lowercase();
+        merged.retain(|e| e.rule.description.trim().to_lowercase() != key);
+        merged.push(EffectiveRule { inherited: rule.scope != "stream", rule, overridden_threat_level });
+    }
+    merged
+}
+
+/// The rules in force at `at`, in their original order.
+pub fn active_at(rules: Vec<StreamRule>, at: DateTime<Utc>) -> Vec<StreamRule> {
+    rules.into_iter().filter(|r| is_active(r, at)).collect()
+}
+
+fn is_active(rule: &StreamRule, at: DateTime<Utc>) -> bool {
+    let Some(schedule) = rule.schedule.clone().and_then(|v| serde_json::from_value::<RuleSchedule>(v).ok())
+    else {
+        return true;
+    };
+    let Some(offset) = FixedOffset::east_opt(schedule.utc_offset_minutes * 60) else {
+        return true;
+    };
+    let local = at.with_timezone(&offset);
+    schedule.windows.iter().any(|w| in_window(w, local))
+}
+
+fn in_window(w: &ScheduleWindow, local: DateTime<FixedOffset>) -> bool {
+    let (Some(start), Some(end)) = (parse_time(&w.start), parse_time(&w.end)) else {
+        return false;
+    };
+    let now = NaiveTime::from_hms_opt(local.hour(), local.minute(), 0).unwrap_or_default();
+    let today = local.weekday().num_days_from_monday() as usize;
+    let yesterday = (today + 6) % 7;
+    let on_day = |day: usize| w.days.is_empty() || w.days.iter().any(|d| d == DAYS[day]);
+
+    if start <= end {
+        on_day(today) && start <= now && now < end
+    } else {
+        // Runs past midnight: the evening part today, or the morning part of
+        // a window that started yesterday.
+        (on_day(today) && now >= start) || (on_day(yesterday) && now < end)
+    }
+}
+
+fn parse_time(s: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(s.trim(), "%H:%M").ok()
+}
+
+/// Word overlap (Dice coefficient) above which a VLM's rule text is taken to
+/// mean a rule.
+const MATCH_THRESHOLD: f64 = 0.6;
+
+/// Fewest words the VLM's text needs before it may stand for a description it
+/// is only part of or only resembles; shorter fragments ("person", "at night")
+/// fit too many.
+const MIN_PARTIAL_WORDS: usize = 3;
+
+/// The rule the VLM's `triggered_rule` text refers to. Models paraphrase, so
+/// besides an exact match (ignoring case and punctuation) this accepts text
+/// that contains a description's words in order. Text of at least
+/// `MIN_PARTIAL_WORDS` words may also be a run of words found in one
+/// description only, or else share the most words with a description.
+pub fn match_rule<'a>(rules: &'a [StreamRule], text: &str) -> Option<&'a StreamRule> {
+    let text = words(text);
+    if text.is_empty() {
+        return None;
+    }
+    let described: Vec<(&StreamRule, Vec<String>)> = rules.iter().map(|r| (r, words(&r.description))).collect();
+
+    if let Some((rule, _)) = described.iter().find(|(_, d)| *d == text) {
+        return Some(rule);
+    }
+    let in_text = described.iter().filter(|(_, d)| contains_run(&text, d)).max_by_key(|(_, d)| d.len());
+    if let Some((rule, _)) = in_text {
+        return Some(rule);
+    }
+    if text.len() < MIN_PARTIAL_WORDS {
+        return None;
+    }
+    let mut in_descriptions = described.iter().filter(|(_, d)| contains_run(d, &text));
+    if let (Some((rule, _)), None) = (in_descriptions.next(), in_descriptions.next()) {
+        return Some(rule);
+    }
+
+    let ours: HashSet<&String> = text.iter().collect();
+    described
+        .iter()
+        .map(|(rule, d)| {
+            let theirs: HashSet<&String> = d.iter().collect();
+            let common = ours.intersection(&theirs).count();
+            (rule, 2.0 * common as f64 / (ours.len() + theirs.len()) as f64)
+        })
+        .filter(|(_, score)| *score >= MATCH_THRESHOLD)
+        // Earlier rules win ties.
+        .fold(None, |best: Option<(&&StreamRule, f64)>, (rule, score)| match best {
+            Some((_, b)) if b >= score => best,
+            _ => Some((rule, score)),
+        })
+        .map(|(rule, _)| *rule)
+}
+
+/// Lowercase words, punctuation dropped.
+fn words(s: &str) -> Vec<String> {
+    s.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Whether `needle` appears in `hay` as whole words, in order and next to each other.
+fn contains_run(hay: &[String], needle: &[String]) -> bool {
+    !needle.is_empty() && hay.windows(needle.len()).any(|w| w == needle)
+}
+
+/// Whether a rule has anything for `check_triggered` to verify.
+fn has_conditions(rule: &StreamRule) -> bool {
+    rule.zone.is_some() || rule.min_count.is_some() || rule.min_dwell_sec.is_some() || !rule.event_types.is_empty()
+}
+
+/// How long each stream's current rule match has lasted: stream id → (rule id,
+/// capture time of the first frame in the run of matching frames).
+#[derive(Default)]
+pub struct DwellTracker {
+    runs: Mutex<HashMap<Uuid, (Uuid, DateTime<Utc>)>>,
+}
+
+impl DwellTracker {
+    /// Records whether `rule_id` matched on a frame captured at `at` and returns
+    /// when its current run started. Any other rule's run is ended.
+    fn observe(&self, stream_id: Uuid, rule_id: Option<Uuid>, at: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut runs = self.runs.lock().unwrap();
+        let Some(rule_id) = rule_id else {
+            runs.remove(&stream_id);
+            return None;
+        };
+        let run = runs.entry(stream_id).or_insert((rule_id, at));
+        if run.0 != rule_id {
+            *run = (rule_id, at);
+        }
+        run.1 = run.1.min(at);
+        Some(run.1)
+    }
+}
+
+/// Checks the rule the VLM triggered against its structured conditions. If one
+/// fails, the rule is removed from `result` and the risk capped at low: the
+/// model saw something close to the rule, but not what the rule asks for.
+/// `dwell` is None outside live analysis, where there are no consecutive frames.
+pub fn check_triggered(
+    rules: &[StreamRule],
+    result: &mut AnalysisResult,
+    stream_id: Uuid,
+    captured_at: DateTime<Utc>,
+    dwell: Option<&DwellTracker>,
+) -> Option<RuleCheck> {
+    let rule = result.triggered_rule.as_deref().and_then(|t| match_rule(rules, t));
+    let Some(rule) = rule.filter(|r| has_conditions(r)) else {
+        if let Some(dwell) = dwell {
+            dwell.observe(stream_id, None, captured_at);
+        }
+        return None;
+    };
+
+    let (mut failed, mut unverified) = check_detections(rule, &result.events);
+
+    if let Some(min_dwell) = rule.min_dwell_sec {
+        match dwell {
+            Some(dwell) => {
+                let matching = failed.is_empty().then_some(rule.id);
+                if let Some(since) = dwell.observe(stream_id, matching, captured_at) {
+                    let lasted = (captured_at - since).num_seconds();
+                    if lasted < min_dwell as i64 {
+                        failed.push(format!("matching for {lasted}s, needs {min_dwell}s"));
+                    }
+                }
+            }
+            None => unverified.push("dwell time is only checked during live analysis".into()),
+        }
+    } else if let Some(dwell) = dwell {
+        dwell.observe(stream_id, None, captured_at);
+    }
+
+    let passed = failed.is_empty();
+    if !passed {
+        result.triggered_rule = None;
+        result.risk_level = result.risk_level.min(RiskLevel::Low);
+    }
+    failed.extend(unverified);
+    Some(RuleCheck { rule_id: rule.id, passed, notes: failed })
+}
+
+/// (failed, unverified) notes for the rule's event type, zone and count
+/// conditions against the VLM's detections.
+fn check_detections(rule: &StreamRule, events: &[DetectedEvent]) -> (Vec<String>, Vec<String>) {
+    let mut failed = Vec::new();
+    let mut unverified = Vec::new();
+
+    for t in &rule.event_types {
+        if !events.iter().any(|e| &e.event_type == t) {
+            failed.push(format!("no {t} detected"));
+        }
+    }
+
+    let mut relevant: Vec<&DetectedEvent> = events
+        .iter()
+        .filter(|e| e.event_type != "empty_scene")
+        .filter(|e| rule.event_types.is_empty() || rule.event_types.contains(&e.event_type))
+        .collect();
+
+    let zone: Option<Vec<ZonePoint>> = rule.zone.clone().and_then(|v| serde_json::from_value(v).ok());
+    if let Some(zone) = zone {
+        if relevant.iter().all(|e| e.bbox.is_none()) {
+            unverified.push("zone not checked: no detection locations reported".into());
+        } else {
+            // Detections without a location cannot be placed; keep them.
+            relevant.retain(|e| e.bbox.is_none_or(|b| contains(&zone, (b[0] + b[2]) / 2.0, (b[1] + b[3]) / 2.0)));
+            if relevant.is_empty() {
+                failed.push("no matching detection inside the zone".into());
+            }
+        }
+    }
+
+    if let Some(min_count) = rule.min_count {
+        let count: u32 = relevant.iter().map(|e| e.count.unwrap_or(1).max(1)).sum();
+        if count < min_count as u32 {
+            failed.push(format!("counted {count}, needs {min_count}"));
+        }
+    }
+
+    (failed, unverified)
+}
+
+/// Point-in-polygon by ray casting.
+fn contains(polygon: &[ZonePoint], x: f64, y: f64) -> bool {
+    if polygon.len() < 3 {
+        return false;
+    }
+    let mut inside = false;
+    let mut j = polygon.len() - 1;
+    for (i, pi) in polygon.iter().enumerate() {
+        let pj = &polygon[j];
+        if (pi.y > y) != (pj.y > y) && x < (pj.x - pi.x) * (y - pi.y) / (pj.y - pi.y) + pi.x {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(start: &str, end: &str) -> RuleSchedule {
+        RuleSchedule {
+            windows: vec![ScheduleWindow { days: Vec::new(), start: start.into(), end: end.into() }],
+            utc_offset_minutes: 0,
+        }
+    }
+
+    fn rule(description: &str) -> StreamRule {
+        StreamRule {
+            id: Uuid::new_v4(),
+            scope: "global".into(),
+            stream_id: None,
+            blueprint_id: None,
+            description: description.into(),
+            threat_level: "medium".into(),
+            position: 0,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            schedule: None,
+            zone: None,
+            min_count: None,
+            min_dwell_sec: None,
+            event_types: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn matches_whole_words_only() {
+        let rules = [rule("Cat"), rule("Person climbing the fence")];
+        let matched = |text| match_rule(&rules, text).map(|r| r.description.as_str());
+        assert_eq!(matched("person climbing the fence!"), Some("Person climbing the fence"));
+        assert_eq!(matched("A person climbing the fence at the back"), Some("Person climbing the fence"));
+        // "cat" is inside "location" but not one of its words.
+        assert_eq!(matched("Unusual location for a parked car"), None);
+    }
+
+    #[test]
+    fn short_fragments_do_not_stand_for_a_description() {
+        let rules = [rule("Person climbing the fence"), rule("Person loitering near the gate")];
+        let matched = |text| match_rule(&rules, text).map(|r| r.description.as_str());
+        assert_eq!(matched("person"), None);
+        assert_eq!(matched("climbing the fence"), Some("Person climbing the fence"));
+        // Fits both descriptions.
+        let rules = [
+            rule("Person waiting near the gate after closing time"),
+            rule("Van parked near the gate after closing time"),
+        ];
+        assert!(match_rule(&rules, "near the gate").is_none());
+        // Nor does sharing words with a short description.
+        let rules = [rule("Person loitering")];
+        assert!(match_rule(&rules, "person").is_none());
+        assert!(match_rule(&rules, "loitering person").is_none());
+        assert!(match_rule(&rules, "a person is loitering").is_some());
+    }
+
+    #[test]
+    fn schedule_windows_must_have_a_length() {
+        let check = |start, end| validate(Some(&schedule(start, end)), None, None, None, &[]);
+        assert!(matches!(check("08:00", "08:00"), Err(AppError::BadRequest(_))));
+        assert!(matches!(check("8:00", " 08:00"), Err(AppError::BadRequest(_))));
+        assert!(check("08:00", "17:00").is_ok());
+        assert!(check("22:00", "06:00").is_ok());
+    }
+}
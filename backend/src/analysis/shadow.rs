@@ -0,0 +1,141 @@
+//! Shadow mode: a candidate VLM analyzes a random sample of frames alongside the
+//! primary one. Its answers are stored next to the primary events so the two
+//! models can be compared on real traffic. Shadow results never alert.
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::{
+    analysis::{
+        preprocess::FrameMapping,
+        reanalysis::diff_summary,
+        rules,
+        vlm::{registry::VlmRegistry, DynVlmClient, VlmRule},
+    },
+    config::ShadowConfig,
+    error::Result,
+    storage::{
+        db,
+        models::{NewShadowResult, ShadowReport, ShadowReportQuery, StreamRule},
+    },
+};
+
+/// A frame the primary model analyzed, with what the shadow model needs to
+/// analyze it the same way.
+pub struct ShadowFrame {
+    /// The primary event the answer is stored next to.
+    pub event_id: Uuid,
+    pub stream_id: Uuid,
+    pub stream_name: String,
+    pub captured_at: DateTime<Utc>,
+    /// The preprocessed image, and how it maps to the captured frame.
+    pub image: Vec<u8>,
+    pub mapping: FrameMapping,
+    /// The stream's rules in force at capture time, and as the prompt gives them.
+    pub active_rules: Vec<StreamRule>,
+    pub rules: Vec<VlmRule>,
+    pub exclusions: Vec<String>,
+}
+
+pub struct ShadowAnalyzer {
+    vlm: DynVlmClient,
+    /// The shadow endpoint's in-flight limit, shared with any other client of it.
+    in_flight: Arc<Semaphore>,
+    sample_rate: f64,
+}
+
+impl ShadowAnalyzer {
+    pub fn new(cfg: &ShadowConfig, registry: &VlmRegistry) -> Arc<Self> {
+        Arc::new(Self {
+            vlm: registry.shadow_client(&cfg.vlm),
+            in_flight: registry.in_flight(&cfg.vlm),
+            sample_rate: cfg.sample_rate,
+        })
+    }
+
+    /// Whether the current frame should also go to the shadow model: it falls
+    /// in the sample and the endpoint has a call free, which the returned
+    /// permit holds until dropped. A sampled frame finding the endpoint at its
+    /// limit is skipped rather than queued, so shadow calls never hold up
+    /// analysis on a shared endpoint.
+    pub fn sample(&self) -> Option<OwnedSemaphorePermit> {
+        if rand::random::<f64>() >= self.sample_rate {
+            return None;
+        }
+        let permit = Arc::clone(&self.in_flight).try_acquire_owned().ok();
+        if permit.is_none() {
+            debug!("Shadow sample skipped: the shadow endpoint is at its in-flight limit");
+        }
+        permit
+    }
+
+    /// Analyze a frame that already produced an event and store the shadow
+    /// answer. It is checked against the rules' conditions like the primary
+    /// one, so the two are compared on equal terms.
+    pub async fn analyze_and_store(&self, db: &PgPool, frame: &ShadowFrame) {
+        let stream_name = &frame.stream_name;
+        let mut output = match self.vlm.analyze(&frame.image, stream_name, &frame.rules, &frame.exclusions).await {
+            Ok(o) => o,
+            Err(e) => {
+                warn!(stream = %stream_name, "Shadow analysis failed: {e}");
+                return;
+            }
+        };
+        // Dwell time is left unchecked: the tracker follows the primary model.
+        frame.mapping.boxes_to_frame(&mut output.result.events);
+        rules::check_triggered(&frame.active_rules, &mut output.result, frame.stream_id, frame.captured_at, None);
+        let result = &output.result;
+        // Same as the primary path: with no rules there is nothing to trigger.
+        let triggered_rule = if frame.rules.is_empty() {
+            None
+        } else {
+            result.triggered_rule.as_deref().map(str::trim).filter(|s| !s.is_empty())
+        };
+
+        let stored = db::insert_shadow_result(
+            db,
+            &NewShadowResult {
+                event_id: frame.event_id,
+                risk_level: result.risk_level.as_str(),
+                triggered_rule,
+                title: result.title.as_deref().map(str::trim).filter(|s| !s.is_empty()),
+                description: &result.description,
+                events: serde_json::to_value(&result.events).unwrap_or_default(),
+                raw_response: Some(&output.raw_response),
+                model: &output.model,
+                vlm_backend: output.backend,
+                latency_ms: Some(output.latency_ms),
+                prompt_tokens: output.usage.prompt_tokens,
+                completion_tokens: output.usage.completion_tokens,
+            },
+        )
+        .await;
+
+        match stored {
+            Ok(()) => debug!(stream = %stream_name, model = %output.model, risk = %result.risk_level.as_str(), "Shadow result stored"),
+            Err(e) => warn!(stream = %stream_name, "Failed to store shadow result: {e}"),
+        }
+    }
+}
+
+/// Agreement between the primary and shadow models over the filtered events.
+pub async fn report(db: &PgPool, query: &ShadowReportQuery) -> Result<ShadowReport> {
+    let agreement = db::shadow_agreement(db, query).await?;
+    let diff = diff_summary(db::shadow_transitions(db, query).await?);
+    let ratio = |num: i64, den: i64| (den > 0).then(|| num as f64 / den as f64);
+
+    Ok(ShadowReport {
+        shadow_models: db::shadow_models(db, query).await?,
+        compared: agreement.compared,
+        risk_agreement: ratio(diff.unchanged, agreement.compared),
+        triggered_rule_agreement: ratio(agreement.rule_matches, agreement.compared),
+        mean_primary_latency_ms: agreement.mean_primary_latency_ms,
+        mean_shadow_latency_ms: agreement.mean_shadow_latency_ms,
+        diff,
+    })
+}
@@ -0,0 +1,263 @@
+//! Rule templates: named sets of rules with `{{parameter}}` placeholders that
+//! are filled in and added to streams or a blueprint in one go. Templates are
+//! imported and exported as JSON or YAML so they can be kept under version
+//! control.
+
+use std::collections::{HashMap, HashSet};
+
+use serde_json::Value;
+use sqlx::PgPool;
+
+use crate::{
+    analysis::{rules, vlm::RiskLevel},
+    error::{AppError, Result},
+    storage::{
+        db,
+        models::{
+            ApplyTemplateRequest, ApplyTemplateResult, CreateRuleRequest, RuleScope, RuleTemplate,
+            RuleTemplateDoc,
+        },
+    },
+};
+
+/// Rule fields that are numbers; a placeholder making up the whole value is
+/// filled in as a number rather than a string.
+const NUMERIC_FIELDS: [&str; 4] = ["position", "min_count", "min_dwell_sec", "utc_offset_minutes"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Yaml,
+}
+
+impl Format {
+    /// From a `format` query value, falling back to the Content-Type header.
+    pub fn detect(format: Option<&str>, content_type: Option<&str>) -> Result<Self> {
+        match format.map(str::to_ascii_lowercase).as_deref() {
+            Some("json") => Ok(Self::Json),
+            Some("yaml" | "yml") => Ok(Self::Yaml),
+            Some(other) => Err(AppError::BadRequest(format!("unknown format '{other}', expected json or yaml"))),
+            None if content_type.is_some_and(|c| c.contains("yaml")) => Ok(Self::Yaml),
+            None => Ok(Self::Json),
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Yaml => "application/yaml",
+        }
+    }
+}
+
+/// Rejects templates that cannot be applied: bad parameter names, placeholders
+/// without a parameter, and rules that are invalid even with the defaults.
+pub fn validate(doc: &RuleTemplateDoc) -> Result<()> {
+    let bad = |msg: String| Err(AppError::BadRequest(format!("template '{}': {msg}", doc.name)));
+
+    if doc.name.trim().is_empty() {
+        return Err(AppError::BadRequest("template name must not be empty".into()));
+    }
+    if doc.rules.is_empty() {
+        return bad("needs at least one rule".into());
+    }
+    let mut declared = HashSet::new();
+    for p in &doc.parameters {
+        if p.name.is_empty() || !p.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return bad(format!("parameter name '{}' may only use letters, digits and underscores", p.name));
+        }
+        if !declared.insert(p.name.as_str()) {
+            return bad(format!("parameter '{}' is declared twice", p.name));
+        }
+    }
+
+    let defaults: HashMap<&str, &str> =
+        doc.parameters.iter().filter_map(|p| Some((p.name.as_str(), p.default.as_deref()?))).collect();
+    for (i, rule) in doc.rules.iter().enumerate() {
+        if !rule.is_object() {
+            return bad(format!("rule {} is not an object", i + 1));
+        }
+        let used = placeholders(rule);
+        if let Some(p) = used.iter().find(|p| !declared.contains(p.as_str())) {
+            return bad(format!("rule {} uses undeclared parameter '{{{{{p}}}}}'", i + 1));
+        }
+        // Rules that depend on a required parameter are checked when applied.
+        if used.iter().all(|p| defaults.contains_key(p.as_str())) {
+            render_rule(rule, &defaults).map_err(|e| prefix(&doc.name, i, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// The template's rules with placeholders filled in from `params`, falling
+/// back to parameter defaults.
+pub fn render(doc: &RuleTemplateDoc, params: &HashMap<String, String>) -> Result<Vec<CreateRuleRequest>> {
+    if let Some(unknown) = params.keys().find(|k| !doc.parameters.iter().any(|p| &p.name == *k)) {
+        return Err(AppError::BadRequest(format!("template '{}' has no parameter '{unknown}'", doc.name)));
+    }
+    let mut values = HashMap::new();
+    for p in &doc.parameters {
+        match params.get(&p.name).or(p.default.as_ref()) {
+            Some(v) => values.insert(p.name.as_str(), v.as_str()),
+            None => {
+                return Err(AppError::BadRequest(format!(
+                    "template '{}' needs a value for parameter '{}'",
+                    doc.name, p.name
+                )))
+            }
+        };
+    }
+    doc.rules
+        .iter()
+        .enumerate()
+        .map(|(i, rule)| render_rule(rule, &values).map_err(|e| prefix(&doc.name, i, e)))
+        .collect()
+}
+
+/// Renders the template and adds its rules to every target. A target that
+/// already has a rule with the same description keeps it.
+pub async fn apply(db: &PgPool, template: &RuleTemplate, req: &ApplyTemplateRequest) -> Result<ApplyTemplateResult> {
+    let mut targets: Vec<RuleScope> = Vec::new();
+    for &id in &req.stream_ids {
+        db::get_stream(db, id).await?;
+        if !targets.contains(&RuleScope::Stream(id)) {
+            targets.push(RuleScope::Stream(id));
+        }
+    }
+    if let Some(id) = req.blueprint_id {
+        db::get_blueprint(db, id).await?;
+        targets.push(RuleScope::Blueprint(id));
+    }
+    if targets.is_empty() {
+        return Err(AppError::BadRequest("give stream_ids and/or a blueprint_id to apply the template to".into()));
+    }
+
+    // Render everything up front so a bad parameter adds nothing.
+    let rendered = render(&to_doc(template)?, &req.params)?;
+
+    let mut result = ApplyTemplateResult { created: Vec::new(), skipped: 0 };
+    for scope in targets {
+        let mut existing: HashSet<String> =
+            db::list_rules(db, scope).await?.iter().map(|r| r.description.trim().to_lowercase()).collect();
+        for rule in &rendered {
+            if !existing.insert(rule.description.trim().to_lowercase()) {
+                result.skipped += 1;
+                continue;
+            }
+            result.created.push(db::create_rule(db, scope, rule).await?);
+        }
+    }
+    Ok(result)
+}
+
+/// A stored template in import / export form.
+pub fn to_doc(t: &RuleTemplate) -> Result<RuleTemplateDoc> {
+    let invalid = |e: serde_json::Error| AppError::Other(anyhow::anyhow!("rule template {} is corrupt: {e}", t.id));
+    Ok(RuleTemplateDoc {
+        name: t.name.clone(),
+        description: t.description.clone(),
+        parameters: serde_json::from_value(t.parameters.clone()).map_err(invalid)?,
+        rules: serde_json::from_value(t.rules.clone()).map_err(invalid)?,
+    })
+}
+
+/// Parses an import body: one template or a list of them.
+pub fn parse(body: &str, format: Format) -> Result<Vec<RuleTemplateDoc>> {
+    let invalid = |e: String| AppError::BadRequest(format!("invalid template file: {e}"));
+    let value: Value = match format {
+        Format::Json => serde_json::from_str(body).map_err(|e| invalid(e.to_string()))?,
+        Format::Yaml => serde_yaml::from_str(body).map_err(|e| invalid(e.to_string()))?,
+    };
+    if value.is_array() {
+        serde_json::from_value(value).map_err(|e| invalid(e.to_string()))
+    } else {
+        Ok(vec![serde_json::from_value(value).map_err(|e| invalid(e.to_string()))?])
+    }
+}
+
+pub fn serialize(docs: &[RuleTemplateDoc], format: Format) -> Result<String> {
+    match format {
+        Format::Json => serde_json::to_string_pretty(docs).map_err(|e| AppError::Other(e.into())),
+        Format::Yaml => serde_yaml::to_string(docs).map_err(|e| AppError::Other(e.into())),
+    }
+}
+
+fn prefix(name: &str, index: usize, e: AppError) -> AppError {
+    match e {
+        AppError::BadRequest(msg) => AppError::BadRequest(format!("template '{name}', rule {}: {msg}", index + 1)),
+        e => e,
+    }
+}
+
+fn render_rule(rule: &Value, values: &HashMap<&str, &str>) -> Result<CreateRuleRequest> {
+    let mut rule = rule.clone();
+    fill(&mut rule, None, values);
+    let rule: CreateRuleRequest =
+        serde_json::from_value(rule).map_err(|e| AppError::BadRequest(format!("invalid rule: {e}")))?;
+    rule.threat_level.parse::<RiskLevel>().map_err(AppError::BadRequest)?;
+    rules::validate(
+        rule.schedule.as_ref(),
+        rule.zone.as_deref(),
+        rule.min_count,
+        rule.min_dwell_sec,
+        &rule.event_types,
+    )?;
+    Ok(rule)
+}
+
+/// Replaces placeholders in every string inside `v`. `key` is the object key
+/// `v` sits under, for `NUMERIC_FIELDS`.
+fn fill(v: &mut Value, key: Option<&str>, values: &HashMap<&str, &str>) {
+    match v {
+        Value::String(s) => {
+            let filled = substitute(s, values);
+            let t = s.trim();
+            let whole = t.starts_with("{{") && t.ends_with("}}") && placeholder_names(t).len() == 1;
+            *v = match filled.trim().parse::<i64>() {
+                Ok(n) if whole && key.is_some_and(|k| NUMERIC_FIELDS.contains(&k)) => Value::from(n),
+                _ => Value::String(filled),
+            };
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| fill(item, key, values)),
+        Value::Object(map) => map.iter_mut().for_each(|(k, item)| fill(item, Some(k), values)),
+        _ => {}
+    }
+}
+
+fn substitute(s: &str, values: &HashMap<&str, &str>) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start..].find("}}") else { break };
+        let name = rest[start + 2..start + len].trim();
+        out.push_str(&rest[..start]);
+        match values.get(name) {
+            Some(value) => out.push_str(value),
+            None => out.push_str(&rest[start..start + len + 2]),
+        }
+        rest = &rest[start + len + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn placeholder_names(s: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = s;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start..].find("}}") else { break };
+        names.push(rest[start + 2..start + len].trim());
+        rest = &rest[start + len + 2..];
+    }
+    names
+}
+
+/// Every placeholder name used anywhere in `v`.
+fn placeholders(v: &Value) -> HashSet<String> {
+    match v {
+        Value::String(s) => placeholder_names(s).into_iter().map(String::from).collect(),
+        Value::Array(items) => items.iter().flat_map(placeholders).collect(),
+        Value::Object(map) => map.values().flat_map(placeholders).collect(),
+        _ => HashSet::new(),
+    }
+}
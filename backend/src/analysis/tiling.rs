@@ -0,0 +1,91 @@
+//! Composes frames from several cameras into one labeled grid image, so a
+//! single VLM call can analyze them all (see `VlmClient::analyze_tiles`).
+//! Each frame is scaled to fit a 16:9 cell and tagged with its 1-based tile
+//! number in the top-left corner, which the tiled prompt refers to.
+
+use std::io::Cursor;
+
+use image::{codecs::jpeg::JpegEncoder, imageops::FilterType, Rgb, RgbImage};
+
+const JPEG_QUALITY: u8 = 85;
+/// Gap between cells, so tiles are visibly separate.
+const GUTTER: u32 = 4;
+
+/// 3×5 bitmaps of the digits 0–9, one row per entry, high bit = left column.
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b010, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+/// (columns, rows) of the most square grid that holds `count` tiles.
+pub fn grid_shape(count: usize) -> (u32, u32) {
+    let cols = (count as f64).sqrt().ceil().max(1.0) as u32;
+    let rows = (count as u32).div_ceil(cols).max(1);
+    (cols, rows)
+}
+
+/// Lays out `frames` (JPEGs) left to right, top to bottom in cells
+/// `tile_width` wide, and returns the grid as a JPEG. CPU-bound; call from a
+/// blocking task.
+pub fn compose_grid(frames: &[&[u8]], tile_width: u32) -> anyhow::Result<Vec<u8>> {
+    let (cols, rows) = grid_shape(frames.len());
+    let (cell_w, cell_h) = (tile_width.max(64), (tile_width.max(64) * 9 / 16).max(36));
+    let mut grid = RgbImage::new(cols * cell_w + (cols - 1) * GUTTER, rows * cell_h + (rows - 1) * GUTTER);
+
+    for (i, jpeg) in frames.iter().enumerate() {
+        let (col, row) = (i as u32 % cols, i as u32 / cols);
+        let (cell_x, cell_y) = (col * (cell_w + GUTTER), row * (cell_h + GUTTER));
+
+        let tile = image::load_from_memory(jpeg)?.resize(cell_w, cell_h, FilterType::Triangle).to_rgb8();
+        // Center the scaled frame in its cell; the rest stays black.
+        let x = cell_x + (cell_w - tile.width()) / 2;
+        let y = cell_y + (cell_h - tile.height()) / 2;
+        image::imageops::replace(&mut grid, &tile, x as i64, y as i64);
+
+        draw_label(&mut grid, cell_x, cell_y, cell_h, i + 1);
+    }
+
+    let mut out = Vec::new();
+    JpegEncoder::new_with_quality(&mut Cursor::new(&mut out), JPEG_QUALITY).encode_image(&grid)?;
+    Ok(out)
+}
+
+/// White digits of `number` on a black box at the cell's top-left corner,
+/// sized to about a tenth of the cell height.
+fn draw_label(img: &mut RgbImage, cell_x: u32, cell_y: u32, cell_h: u32, number: usize) {
+    let digits: Vec<usize> = number.to_string().bytes().map(|b| (b - b'0') as usize).collect();
+    let scale = (cell_h / 50).max(2);
+    let pad = scale;
+    let box_w = pad * 2 + digits.len() as u32 * 4 * scale - scale;
+    let box_h = pad * 2 + 5 * scale;
+
+    fill(img, cell_x, cell_y, box_w, box_h, Rgb([0, 0, 0]));
+    for (d, &digit) in digits.iter().enumerate() {
+        let origin_x = cell_x + pad + d as u32 * 4 * scale;
+        for (row, bits) in DIGITS[digit].iter().enumerate() {
+            for col in 0..3 {
+                if bits & (0b100 >> col) != 0 {
+                    let x = origin_x + col * scale;
+                    let y = cell_y + pad + row as u32 * scale;
+                    fill(img, x, y, scale, scale, Rgb([255, 255, 255]));
+                }
+            }
+        }
+    }
+}
+
+fn fill(img: &mut RgbImage, x: u32, y: u32, w: u32, h: u32, color: Rgb<u8>) {
+    for py in y..(y + h).min(img.height()) {
+        for px in x..(x + w).min(img.width()) {
+            img.put_pixel(px, py, color);
+        }
+    }
+}
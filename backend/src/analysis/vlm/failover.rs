@@ -0,0 +1,290 @@
+//! Composite client: tries each backend in order, retrying transient errors
+//! with exponential backoff. A backend that keeps failing to answer has its
+//! circuit opened and is skipped until a cooldown passes, after which a single
+//! call tests it again. Calls to one endpoint are
+//! capped at a fixed number in flight, however many workers want it.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tokio::sync::Semaphore;
+use tracing::warn;
+
+use crate::{
+    config::{VlmBackend, VlmResilienceConfig},
+    error::{AppError, Result},
+};
+
+use super::{build_vlm_client, DynVlmClient, VlmCompletion, VlmPrompt};
+
+pub struct FailoverClient {
+    backends: Vec<Member>,
+    policy: VlmResilienceConfig,
+}
+
+struct Member {
+    /// "backend/model", for logs and errors.
+    label: String,
+    client: DynVlmClient,
+    breaker: Mutex<Breaker>,
+    /// Shared with every other client of the same endpoint. None when the
+    /// caller takes the permit itself (see `VlmRegistry::shadow_client`).
+    in_flight: Option<Arc<Semaphore>>,
+}
+
+/// One semaphore per endpoint (base URL), so a server shared by several
+/// profiles is limited as a whole.
+pub struct InFlightLimits {
+    max_per_endpoint: usize,
+    by_endpoint: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl InFlightLimits {
+    pub fn new(max_per_endpoint: usize) -> Self {
+        Self { max_per_endpoint, by_endpoint: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn for_endpoint(&self, base_url: &str) -> Arc<Semaphore> {
+        let key = base_url.trim_end_matches('/').to_string();
+        let mut map = self.by_endpoint.lock().unwrap();
+        Arc::clone(map.entry(key).or_insert_with(|| Arc::new(Semaphore::new(self.max_per_endpoint))))
+    }
+}
+
+#[derive(Default)]
+struct Breaker {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+    /// When the call testing a half-open circuit started. Other calls skip
+    /// the backend meanwhile; a test call that never reports back (e.g. it was
+    /// dropped) is given up on after another cooldown.
+    probe_started: Option<Instant>,
+}
+
+impl FailoverClient {
+    pub fn new(backends: &[VlmBackend], policy: VlmResilienceConfig, limits: Option<&InFlightLimits>) -> Self {
+        let backends = backends
+            .iter()
+            .map(|b| Member {
+                label: format!("{}/{}", b.name(), b.model()),
+                client: build_vlm_client(b),
+                breaker: Mutex::new(Breaker::default()),
+                in_flight: limits.map(|l| l.for_endpoint(b.base_url())),
+            })
+            .collect();
+        Self { backends, policy }
+    }
+}
+
+impl Member {
+    /// Whether a call may go to this backend: always while the circuit is
+    /// closed; once it is past its cooldown (half-open), only the one call
+    /// that tests it.
+    fn try_acquire(&self, policy: &VlmResilienceConfig) -> bool {
+        let mut breaker = self.breaker.lock().unwrap();
+        let now = Instant::now();
+        match breaker.open_until {
+            None => true,
+            Some(until) if now < until => false,
+            Some(_) => {
+                let cooldown = Duration::from_secs(policy.breaker_cooldown_sec);
+                if breaker.probe_started.is_some_and(|started| now < started + cooldown) {
+                    return false;
+                }
+                breaker.probe_started = Some(now);
+                true
+            }
+        }
+    }
+
+    /// The backend answered, even if with an unusable response: it is reachable.
+    fn record_answer(&self) {
+        *self.breaker.lock().unwrap() = Breaker::default();
+    }
+
+    /// Counts a failure to answer (transport error, timeout, 429 or 5xx).
+    /// Returns true if it opened the circuit, or re-opened it after a failed test.
+    fn record_failure(&self, policy: &VlmResilienceConfig) -> bool {
+        let mut breaker = self.breaker.lock().unwrap();
+        breaker.consecutive_failures += 1;
+        let testing = breaker.open_until.is_some();
+        if testing || breaker.consecutive_failures >= policy.breaker_threshold {
+            breaker.open_until = Some(Instant::now() + Duration::from_secs(policy.breaker_cooldown_sec));
+            breaker.probe_started = None;
+            return true;
+        }
+        false
+    }
+}
+
+#[async_trait::async_trait]
+impl super::VlmClient for FailoverClient {
+    /// Returns `VlmUnavailable` when no backend could be reached; a backend
+    /// that answered with an unusable response yields its `Vlm` error instead.
+    async fn complete(&self, prompt: &VlmPrompt<'_>) -> Result<VlmCompletion> {
+        let mut last_error: Option<AppError> = None;
+
+        for member in &self.backends {
+            if !member.try_acquire(&self.policy) {
+                continue;
+            }
+
+            let mut attempt = 0;
+            loop {
+                let result = {
+                    // Held only for the call itself, not during backoff.
+                    let _permit = match &member.in_flight {
+                        Some(in_flight) => Some(in_flight.acquire().await.expect("semaphore never closed")),
+                        None => None,
+                    };
+                    member.client.complete(prompt).await
+                };
+                match result {
+                    Ok(output) => {
+                        member.record_answer();
+                        return Ok(output);
+                    }
+                    Err(e) => {
+                        // Only failures to answer count towards the circuit; a
+                        // 4xx or malformed answer means the backend is up.
+                        let transient = matches!(e, AppError::VlmUnavailable(_));
+                        let opened = if transient {
+                            member.record_failure(&self.policy)
+                        } else {
+                            member.record_answer();
+                            false
+                        };
+                        if opened {
+                            warn!(backend = %member.label, "VLM circuit opened: {e}");
+                        }
+                        if transient && !opened && attempt < self.policy.max_retries {
+                            let delay = self.policy.retry_backoff_ms.saturating_mul(1 << attempt.min(16));
+                            warn!(backend = %member.label, attempt, "VLM call failed, retrying in {delay}ms: {e}");
+                            tokio::time::sleep(Duration::from_millis(delay)).await;
+                            attempt += 1;
+                            continue;
+                        }
+                        warn!(backend = %member.label, "VLM backend failed: {e}");
+                        // Keep a non-transient error over a transient one: it
+                        // means a backend was reachable.
+                        if !transient || !matches!(last_error, Some(AppError::Vlm(_))) {
+                            last_error = Some(e);
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+
+        Err(match last_error {
+            Some(AppError::Vlm(msg)) => AppError::Vlm(msg),
+            Some(e) => AppError::VlmUnavailable(format!("all VLM backends failed, last error: {e}")),
+            None => AppError::VlmUnavailable("all VLM backends have open circuits".into()),
+        })
+    }
+
+    /// Only if every backend does, since any of them may end up answering.
+    fn multi_image(&self) -> bool {
+        self.backends.iter().all(|m| m.client.multi_image())
+    }
+}
+
+/// The primary backend followed by the fallback (if any), with retries,
+/// circuit breaking and per-endpoint in-flight limits.
+pub fn build_failover_client(
+    primary: &VlmBackend,
+    fallback: Option<&VlmBackend>,
+    policy: &VlmResilienceConfig,
+    limits: &InFlightLimits,
+) -> DynVlmClient {
+    let backends: Vec<VlmBackend> = std::iter::once(primary).chain(fallback).cloned().collect();
+    Arc::new(FailoverClient::new(&backends, policy.clone(), Some(limits)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A backend that always fails with the given error.
+    struct Failing(fn() -> AppError);
+
+    #[async_trait::async_trait]
+    impl super::super::VlmClient for Failing {
+        async fn complete(&self, _prompt: &VlmPrompt<'_>) -> Result<VlmCompletion> {
+            Err((self.0)())
+        }
+
+        fn multi_image(&self) -> bool {
+            false
+        }
+    }
+
+    fn policy() -> VlmResilienceConfig {
+        VlmResilienceConfig { max_retries: 0, retry_backoff_ms: 0, breaker_threshold: 2, breaker_cooldown_sec: 60 }
+    }
+
+    fn client(error: fn() -> AppError) -> FailoverClient {
+        let member = Member {
+            label: "test/model".into(),
+            client: Arc::new(Failing(error)),
+            breaker: Mutex::new(Breaker::default()),
+            in_flight: Some(Arc::new(Semaphore::new(4))),
+        };
+        FailoverClient { backends: vec![member], policy: policy() }
+    }
+
+    fn prompt() -> VlmPrompt<'static> {
+        VlmPrompt { system: "", text: "", image_jpeg: &[], references: Vec::new(), schema: serde_json::Value::Null }
+    }
+
+    #[tokio::test]
+    async fn transient_failures_open_the_circuit() {
+        use super::super::VlmClient;
+        let client = client(|| AppError::VlmUnavailable("HTTP 503".into()));
+        for _ in 0..2 {
+            assert!(client.complete(&prompt()).await.unwrap_err().to_string().contains("503"));
+        }
+        let err = client.complete(&prompt()).await.unwrap_err().to_string();
+        assert!(err.contains("open circuits"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn answers_that_are_errors_never_open_the_circuit() {
+        use super::super::VlmClient;
+        let client = client(|| AppError::Vlm("HTTP 400: bad request".into()));
+        for _ in 0..5 {
+            let err = client.complete(&prompt()).await.unwrap_err();
+            assert!(matches!(err, AppError::Vlm(_)), "{err}");
+        }
+        assert!(client.backends[0].breaker.lock().unwrap().open_until.is_none());
+    }
+
+    #[test]
+    fn half_open_circuit_lets_one_call_through() {
+        let client = client(|| AppError::VlmUnavailable("timeout".into()));
+        let member = &client.backends[0];
+        let policy = policy();
+        assert!(!member.record_failure(&policy));
+        assert!(member.record_failure(&policy));
+        assert!(!member.try_acquire(&policy));
+
+        // Cooldown over: one test call, and nobody else until it reports back.
+        member.breaker.lock().unwrap().open_until = Some(Instant::now() - Duration::from_secs(1));
+        assert!(member.try_acquire(&policy));
+        assert!(!member.try_acquire(&policy));
+
+        // The test call failing re-opens the circuit at once.
+        assert!(member.record_failure(&policy));
+        assert!(!member.try_acquire(&policy));
+
+        // One succeeding closes it for everyone.
+        member.breaker.lock().unwrap().open_until = Some(Instant::now() - Duration::from_secs(1));
+        assert!(member.try_acquire(&policy));
+        member.record_answer();
+        assert!(member.try_acquire(&policy));
+        assert!(member.try_acquire(&policy));
+    }
+}
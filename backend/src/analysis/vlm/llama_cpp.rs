@@ -0,0 +1,96 @@
+/// llama.cpp `llama-server` client (multimodal models served with `--mmproj`).
+///
+/// Uses the server's OpenAI-compatible chat endpoint plus its extensions:
+/// • `json_schema` – converted to a GBNF grammar server-side, so the reply is
+///   always valid JSON in our schema.
+/// • `cache_prompt` – reuses the KV cache for the shared prefix (the system
+///   prompt), so only the image and question are evaluated per frame.
+///
+/// The image goes before the text in the user turn: llama.cpp inserts the
+/// image embedding where the part appears, and models answer better when
+/// the question follows the image.
+use serde_json::Value;
+
+use crate::{config::OpenAiCompatConfig, error::Result};
+
+use super::{openai_compat::OpenAiCompatClient, VlmClient, VlmCompletion, VlmPrompt};
+
+pub struct LlamaCppClient(OpenAiCompatClient);
+
+impl LlamaCppClient {
+    pub fn new(cfg: &OpenAiCompatConfig) -> Self {
+        Self(OpenAiCompatClient::new(cfg))
+    }
+}
+
+#[async_trait::async_trait]
+impl VlmClient for LlamaCppClient {
+    async fn complete(&self, prompt: &VlmPrompt<'_>) -> Result<VlmCompletion> {
+        let mut body = self.0.body(prompt);
+        body["cache_prompt"] = Value::Bool(true);
+        body["json_schema"] = prompt.schema.clone();
+        self.0.send(&body, "llama_cpp", "llama.cpp").await
+    }
+
+    fn multi_image(&self) -> bool {
+        self.0.multi_image()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::analysis::vlm::{analysis_json_schema, test_server::CannedServer, RiskLevel, VlmRule};
+
+    fn rules() -> Vec<VlmRule> {
+        vec![VlmRule {
+            description: "Person behind the counter after closing".into(),
+            threat_level: "high".into(),
+            conditions: None,
+            references: Vec::new(),
+        }]
+    }
+
+    #[tokio::test]
+    async fn sends_schema_and_cache_prompt_and_parses_the_reply() {
+        let server = CannedServer::start(include_str!("testdata/llama_cpp_response.json")).await;
+        let client = LlamaCppClient::new(&server.config("qwen2-vl-7b-q4"));
+
+        let output = client.analyze(b"jpeg", "Shop", &rules(), &[]).await.unwrap();
+
+        let body = server.request();
+        assert_eq!(body["json_schema"], analysis_json_schema(&rules()));
+        assert_eq!(body["cache_prompt"], json!(true));
+        assert_eq!(body["stream"], json!(false));
+        assert_eq!(body["model"], json!("qwen2-vl-7b-q4"));
+        // The image comes before the question.
+        let content = &body["messages"][1]["content"];
+        assert_eq!(content[0]["type"], json!("image_url"));
+        assert_eq!(content[1]["type"], json!("text"));
+
+        assert_eq!(output.backend, "llama_cpp");
+        assert_eq!(output.result.risk_level, RiskLevel::High);
+        assert_eq!(output.result.title.as_deref(), Some("Person behind counter"));
+        assert_eq!(output.result.triggered_rule.as_deref(), Some("Person behind the counter after closing"));
+        assert_eq!(output.result.events.len(), 1);
+        assert_eq!(output.result.events[0].event_type, "person_detected");
+        assert_eq!(output.usage.prompt_tokens, Some(1164));
+        assert_eq!(output.usage.completion_tokens, Some(71));
+    }
+
+    #[tokio::test]
+    async fn truncated_reply_falls_back_to_the_raw_text() {
+        let server = CannedServer::start(include_str!("testdata/llama_cpp_truncated.json")).await;
+        let client = LlamaCppClient::new(&server.config("qwen2-vl-7b-q4"));
+
+        let output = client.analyze(b"jpeg", "Gate", &[], &[]).await.unwrap();
+
+        assert!(output.raw_response.starts_with("{\"title\": \"Delivery van at gate\""));
+        assert_eq!(output.result.description, output.raw_response);
+        assert_eq!(output.result.risk_level, RiskLevel::None);
+        assert!(output.result.events.is_empty());
+        assert_eq!(output.result.title, None);
+    }
+}
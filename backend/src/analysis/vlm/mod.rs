@@ -1,14 +1,25 @@
+pub mod failover;
+pub mod llama_cpp;
 pub mod ollama;
 pub mod openai_compat;
+pub mod registry;
+pub mod vllm;
 
-use std::sync::Arc;
+#[cfg(test)]
+mod test_server;
+
+use std::{sync::Arc, time::Duration};
 
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::error;
 
 use crate::{
+    analysis::preprocess::FrameMapping,
     config::VlmBackend,
-    error::Result,
+    error::{AppError, Result},
+    storage::models::{StreamRule, ZonePoint},
 };
 
 // ─── Analysis result types ────────────────────────────────────────────────────
@@ -34,23 +45,89 @@ pub struct DetectedEvent {
     pub details: Option<String>,
     /// 0.0 – 1.0
     pub confidence: f32,
+    /// How many people / vehicles / objects the event covers.
+    #[serde(default)]
+    pub count: Option<u32>,
+    /// [x_min, y_min, x_max, y_max] in fractions (0.0 – 1.0) of the image the
+    /// VLM saw; moved to the captured frame before events are checked and stored.
+    #[serde(default)]
+    pub bbox: Option<[f64; 4]>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+/// Ordered from least to most severe, so levels can be compared directly.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 #[serde(rename_all = "lowercase")]
 pub enum RiskLevel {
+    #[default]
     None,
     Low,
     Medium,
     High,
 }
 
-impl Default for RiskLevel {
-    fn default() -> Self {
-        Self::None
+impl RiskLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RiskLevel::None => "none",
+            RiskLevel::Low => "low",
+            RiskLevel::Medium => "medium",
+            RiskLevel::High => "high",
+        }
     }
 }
 
+impl std::str::FromStr for RiskLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "low" => Ok(Self::Low),
+            "medium" => Ok(Self::Medium),
+            "high" => Ok(Self::High),
+            other => Err(format!("Unknown risk level: '{other}'")),
+        }
+    }
+}
+
+/// Token accounting for a single call, when the backend reports it.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: Option<i32>,
+    pub completion_tokens: Option<i32>,
+}
+
+/// The parsed result of one `analyze` call together with everything needed to
+/// debug it or re-run the frame later.
+#[derive(Debug, Clone)]
+pub struct VlmOutput {
+    pub result: AnalysisResult,
+    /// Unparsed model output, exactly as returned by the backend.
+    pub raw_response: String,
+    /// Full system prompt sent to the model (base prompt + rules addendum).
+    pub system_prompt: String,
+    pub model: String,
+    /// "ollama" | "openai_compat" | "llama_cpp" | "vllm"
+    pub backend: &'static str,
+    /// Wall-clock time of the HTTP round trip.
+    pub latency_ms: i32,
+    pub usage: TokenUsage,
+}
+
+/// The result of one `analyze_tiles` call. Call metadata is for the whole grid.
+#[derive(Debug, Clone)]
+pub struct TiledVlmOutput {
+    /// One entry per tile, in tile order; `None` where the model gave no
+    /// usable answer for that tile.
+    pub results: Vec<Option<AnalysisResult>>,
+    pub raw_response: String,
+    pub system_prompt: String,
+    pub model: String,
+    pub backend: &'static str,
+    pub latency_ms: i32,
+    pub usage: TokenUsage,
+}
+
 // ─── Prompt ───────────────────────────────────────────────────────────────────
 
 /// System prompt sent to the VLM before the image.
@@ -64,7 +141,9 @@ Analyze the provided camera frame and respond ONLY with a valid JSON object usin
     {
       "event_type": "one of: person_detected, vehicle_detected, crowd_detected, fire_detected, smoke_detected, unusual_activity, empty_scene, animal_detected, package_left",
       "details": "optional string with additional details, or null",
-      "confidence": 0.95
+      "confidence": 0.95,
+      "count": "how many people, vehicles or objects this event covers, or null",
+      "bbox": "[x_min, y_min, x_max, y_max] around them as fractions (0-1) of the image width and height, or null"
     }
   ],
   "risk_level": "one of: none, low, medium, high",
@@ -73,50 +152,424 @@ Analyze the provided camera frame and respond ONLY with a valid JSON object usin
 
 Return ONLY the JSON object. Do not include any other text, markdown, or explanation."#;
 
+/// Allowed `event_type` values, as listed in `SYSTEM_PROMPT`.
+pub const EVENT_TYPES: [&str; 9] = [
+    "person_detected",
+    "vehicle_detected",
+    "crowd_detected",
+    "fire_detected",
+    "smoke_detected",
+    "unusual_activity",
+    "empty_scene",
+    "animal_detected",
+    "package_left",
+];
+
+/// JSON schema of the answer described by `SYSTEM_PROMPT`, for backends that
+/// can constrain decoding to it. `triggered_rule` is limited to the exact rule
+/// descriptions (or null), so the model cannot paraphrase a rule.
+pub fn analysis_json_schema(rules: &[VlmRule]) -> Value {
+    let mut rule_choices: Vec<Value> = vec![Value::Null];
+    rule_choices.extend(rules.iter().map(|r| Value::String(r.description.clone())));
+
+    json!({
+        "type": "object",
+        "properties": {
+            "title": { "type": "string" },
+            "description": { "type": "string" },
+            "events": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "event_type": { "type": "string", "enum": EVENT_TYPES },
+                        "details": { "type": ["string", "null"] },
+                        "confidence": { "type": "number", "minimum": 0, "maximum": 1 },
+                        "count": { "type": ["integer", "null"], "minimum": 1 },
+                        "bbox": {
+                            "type": ["array", "null"],
+                            "items": { "type": "number", "minimum": 0, "maximum": 1 },
+                            "minItems": 4,
+                            "maxItems": 4
+                        }
+                    },
+                    "required": ["event_type", "details", "confidence"]
+                }
+            },
+            "risk_level": { "type": "string", "enum": ["none", "low", "medium", "high"] },
+            "triggered_rule": { "enum": rule_choices }
+        },
+        "required": ["title", "description", "events", "risk_level", "triggered_rule"]
+    })
+}
+
+// ─── Tiled prompt ─────────────────────────────────────────────────────────────
+
+/// One camera's tile in a batched grid image. Tiles are numbered from 1 in
+/// the order given.
+pub struct Tile<'a> {
+    pub stream_name: &'a str,
+    pub rules: &'a [VlmRule],
+    /// Approved "do not flag" clauses of the tile's stream.
+    pub exclusions: &'a [String],
+}
+
+/// System prompt for a grid of camera frames analyzed in one call.
+pub const TILED_SYSTEM_PROMPT: &str = r#"You are a security camera analysis AI.
+The image is a grid of frames from different security cameras. Each tile is labeled with its number in its top-left corner. Analyze every tile on its own: never let what you see in one tile influence the answer for another.
+Respond ONLY with a valid JSON object using this exact schema, with one entry per tile in tile order:
+
+{
+  "tiles": [
+    {
+      "tile": 1,
+      "title": "Short title (max 4 words) that describes the security concern you actually see in THIS tile. Focus on the threatening action, object, or behavior. Do not use generic scene names like 'kitchen' or 'office'.",
+      "description": "Brief natural language description of the tile's scene",
+      "events": [
+        {
+          "event_type": "one of: person_detected, vehicle_detected, crowd_detected, fire_detected, smoke_detected, unusual_activity, empty_scene, animal_detected, package_left",
+          "details": "optional string with additional details, or null",
+          "confidence": 0.95,
+          "count": "how many people, vehicles or objects this event covers, or null",
+          "bbox": "[x_min, y_min, x_max, y_max] around them as fractions (0-1) of the TILE's width and height, or null"
+        }
+      ],
+      "risk_level": "one of: none, low, medium, high",
+      "triggered_rule": "Copy verbatim from THIS tile's custom rule list, or null if none of its rules matched. Never invent a rule or use another tile's rule."
+    }
+  ]
+}
+
+Return ONLY the JSON object. Do not include any other text, markdown, or explanation."#;
+
+/// The tiled system prompt followed by each tile's camera and custom rules.
+pub fn build_tiled_system_prompt(tiles: &[Tile<'_>]) -> String {
+    let mut out = format!("{TILED_SYSTEM_PROMPT}\n\nThe grid has {} tiles.\n", tiles.len());
+    for (i, tile) in tiles.iter().enumerate() {
+        out.push_str(&format!("\nTile {} — camera '{}'", i + 1, tile.stream_name));
+        if tile.rules.is_empty() {
+            out.push_str(": no custom rules, set triggered_rule to null.\n");
+        } else {
+            out.push_str(
+                ". Custom threat assessment rules (apply strictly when setting this tile's risk_level; \
+                 they override your default judgment):\n",
+            );
+            for rule in tile.rules {
+                out.push_str(&rule_line(rule));
+            }
+        }
+        for clause in tile.exclusions {
+            out.push_str(&format!("  Confirmed false alarm on this camera, do not flag: {clause}\n"));
+        }
+    }
+    out.push_str(
+        "\nFor each tile, if one of its rules matches, use the rule's threat level and copy its description \
+         verbatim into triggered_rule (the highest level if several match); otherwise use your own judgment \
+         and set triggered_rule to null.",
+    );
+    out
+}
+
+/// JSON schema of the tiled answer: exactly one object per tile, in order,
+/// each limited to its own tile number and rule descriptions.
+pub fn tiled_json_schema(tiles: &[Tile<'_>]) -> Value {
+    let items: Vec<Value> = tiles
+        .iter()
+        .enumerate()
+        .map(|(i, tile)| {
+            let mut item = analysis_json_schema(tile.rules);
+            item["properties"]["tile"] = json!({ "const": i + 1 });
+            if let Some(required) = item["required"].as_array_mut() {
+                required.insert(0, json!("tile"));
+            }
+            item
+        })
+        .collect();
+
+    json!({
+        "type": "object",
+        "properties": {
+            "tiles": {
+                "type": "array",
+                "prefixItems": items,
+                "items": false,
+                "minItems": tiles.len(),
+                "maxItems": tiles.len()
+            }
+        },
+        "required": ["tiles"]
+    })
+}
+
 // ─── Per-stream rules ─────────────────────────────────────────────────────────
 
 /// A lightweight rule passed to the VLM to customise its threat-level decision.
 /// Constructed from `storage::models::StreamRule` by the analysis worker.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VlmRule {
     pub description: String,
     /// "none" | "low" | "medium" | "high"
     pub threat_level: String,
+    /// The rule's structured conditions the model can see in one frame, in
+    /// words (event types, count, zone). Schedules and dwell time are checked
+    /// by the worker instead.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conditions: Option<String>,
+    /// Example images of the rule, attached by `analysis::references`. Not
+    /// part of the rules snapshot.
+    #[serde(skip)]
+    pub references: Vec<ReferenceImage>,
 }
 
-/// Builds the rules addendum that is appended to the base system prompt.
-/// Returns an empty string when there are no rules.
-pub fn build_rules_prompt(rules: &[VlmRule]) -> String {
-    if rules.is_empty() {
-        return "\n\nThere are no custom rules for this camera. Set triggered_rule to null.".to_string();
+/// Most reference images sent with one frame; images of later rules beyond
+/// this are left out.
+pub const MAX_REFERENCE_IMAGES: usize = 6;
+
+/// An example image of a rule, sent before the frame to multi-image backends.
+#[derive(Debug, Clone)]
+pub struct ReferenceImage {
+    /// True if the rule applies to the scene shown, false if it does not.
+    pub positive: bool,
+    pub caption: Option<String>,
+    pub jpeg: Arc<[u8]>,
+}
+
+/// The reference images sent with a frame, with their rule, in the order the
+/// prompt numbers them.
+pub fn reference_images(rules: &[VlmRule]) -> Vec<(&VlmRule, &ReferenceImage)> {
+    rules
+        .iter()
+        .flat_map(|rule| rule.references.iter().map(move |image| (rule, image)))
+        .take(MAX_REFERENCE_IMAGES)
+        .collect()
+}
+
+impl VlmRule {
+    /// The rule as the VLM should read it for an image preprocessed as
+    /// `mapping` describes: zones are drawn on the captured frame, so their
+    /// corners are given where they fall in the image, kept to its edges.
+    pub fn for_image(r: StreamRule, mapping: &FrameMapping) -> Self {
+        let mut conditions = Vec::new();
+        if !r.event_types.is_empty() {
+            conditions.push(format!("requires {}", r.event_types.join(" and ")));
+        }
+        if let Some(n) = r.min_count {
+            conditions.push(format!("at least {n} of them"));
+        }
+        let zone: Option<Vec<ZonePoint>> = r.zone.and_then(|v| serde_json::from_value(v).ok());
+        if let Some(zone) = zone {
+            let corners: Vec<String> = zone
+                .iter()
+                .map(|p| {
+                    let (x, y) = mapping.to_image((p.x, p.y));
+                    format!("({:.2}, {:.2})", x.clamp(0.0, 1.0), y.clamp(0.0, 1.0))
+                })
+                .collect();
+            conditions.push(format!(
+                "only inside the area with corners {} (fractions of the image; report bbox for these events)",
+                corners.join(" ")
+            ));
+        }
+        VlmRule {
+            description: r.description,
+            threat_level: r.threat_level,
+            conditions: (!conditions.is_empty()).then(|| conditions.join("; ")),
+            references: Vec::new(),
+        }
     }
+}
 
-    let mut out = String::from(
-        "\n\nCustom threat assessment rules for this camera \
-         (apply these strictly when setting risk_level — they override your default judgment):\n",
+/// One line of the rules list in a prompt, plus its conditions if any.
+fn rule_line(rule: &VlmRule) -> String {
+    let mut line = format!("- {}: {}\n", rule.threat_level.to_uppercase(), rule.description);
+    if let Some(conditions) = &rule.conditions {
+        line.push_str(&format!("  (applies only if: {conditions})\n"));
+    }
+    line
+}
+
+/// Builds the rules addendum that is appended to the base system prompt:
+/// the stream's rules, what their reference images show, then its approved
+/// "do not flag" clauses (see `analysis::feedback`).
+pub fn build_rules_prompt(rules: &[VlmRule], exclusions: &[String]) -> String {
+    let mut out = if rules.is_empty() {
+        "\n\nThere are no custom rules for this camera. Set triggered_rule to null.".to_string()
+    } else {
+        let mut out = String::from(
+            "\n\nCustom threat assessment rules for this camera \
+             (apply these strictly when setting risk_level — they override your default judgment):\n",
+        );
+        for rule in rules {
+            out.push_str(&rule_line(rule));
+        }
+        out.push_str(
+            "\nIf a rule matches what you see, use its threat level and set triggered_rule to that rule's description (copy verbatim from the list above, without its conditions). \
+             If multiple rules match, use the highest level and set triggered_rule to that rule's description. \
+             If no rule matches, use your own judgment for risk_level and set triggered_rule to null. \
+             Do not invent or paraphrase a rule; triggered_rule must be either null or an exact copy from the list above.",
+        );
+        out
+    };
+    out.push_str(&references_section(rules));
+    out.push_str(&exclusions_section(exclusions));
+    out
+}
+
+/// What each reference image shows, as a prompt section; empty if none.
+fn references_section(rules: &[VlmRule]) -> String {
+    let images = reference_images(rules);
+    if images.is_empty() {
+        return String::new();
+    }
+    let mut out = format!(
+        "\n\nThe first {} images are reference examples for the rules above, not camera frames; \
+         the last image is the camera frame to analyze. Use the examples only to understand what each rule \
+         means, and never report anything seen in them:\n",
+        images.len()
     );
-    for rule in rules {
-        out.push_str(&format!("- {}: {}\n", rule.threat_level.to_uppercase(), rule.description));
+    for (i, (rule, image)) in images.iter().enumerate() {
+        let verdict = if image.positive { "the rule applies" } else { "the rule does NOT apply" };
+        out.push_str(&format!("- Image {}: {verdict} — \"{}\"", i + 1, rule.description));
+        if let Some(caption) = image.caption.as_deref().filter(|c| !c.trim().is_empty()) {
+            out.push_str(&format!(" ({})", caption.trim()));
+        }
+        out.push('\n');
     }
-    out.push_str(
-        "\nIf a rule matches what you see, use its threat level and set triggered_rule to that rule's description (copy verbatim from the list above). \
-         If multiple rules match, use the highest level and set triggered_rule to that rule's description. \
-         If no rule matches, use your own judgment for risk_level and set triggered_rule to null. \
-         Do not invent or paraphrase a rule; triggered_rule must be either null or an exact copy from the list above.",
+    out
+}
+
+/// Operator-confirmed false alarms, as a prompt section; empty if none.
+fn exclusions_section(exclusions: &[String]) -> String {
+    if exclusions.is_empty() {
+        return String::new();
+    }
+    let mut out = String::from(
+        "\n\nOperators reviewed earlier alerts from this camera and confirmed these as false alarms. \
+         Do not flag them (use risk_level none or low, and no triggered_rule):\n",
     );
+    for clause in exclusions {
+        out.push_str(&format!("- {clause}\n"));
+    }
     out
 }
 
+/// The complete system prompt for a given rule set.
+pub fn build_system_prompt(rules: &[VlmRule], exclusions: &[String]) -> String {
+    format!("{SYSTEM_PROMPT}{}", build_rules_prompt(rules, exclusions))
+}
+
 // ─── Trait ────────────────────────────────────────────────────────────────────
 
+/// Everything sent to the model for one call.
+pub struct VlmPrompt<'a> {
+    pub system: &'a str,
+    /// User text, sent after the image.
+    pub text: &'a str,
+    pub image_jpeg: &'a [u8],
+    /// Rule reference images, sent before `image_jpeg` in the order the
+    /// system prompt numbers them. Empty for single-image backends.
+    pub references: Vec<&'a ReferenceImage>,
+    /// JSON schema of the answer, for backends that can constrain decoding.
+    pub schema: Value,
+}
+
+/// A backend's unparsed answer to a `VlmPrompt`.
+#[derive(Debug, Clone)]
+pub struct VlmCompletion {
+    pub raw_response: String,
+    pub model: String,
+    /// "ollama" | "openai_compat" | "llama_cpp" | "vllm"
+    pub backend: &'static str,
+    pub latency_ms: i32,
+    pub usage: TokenUsage,
+}
+
 #[async_trait]
 pub trait VlmClient: Send + Sync {
-    /// Analyze a JPEG image and return structured results.
+    /// Send one prompt with its image and return the model's raw answer.
+    async fn complete(&self, prompt: &VlmPrompt<'_>) -> Result<VlmCompletion>;
+
+    /// Whether the backend takes more than one image per call. Rule reference
+    /// images are only sent to backends that do.
+    fn multi_image(&self) -> bool;
+
+    /// Analyze a JPEG image and return structured results along with the
+    /// prompt, raw output and call metadata.
     async fn analyze(
         &self,
         image_jpeg: &[u8],
         stream_name: &str,
         rules: &[VlmRule],
-    ) -> Result<AnalysisResult>;
+        exclusions: &[String],
+    ) -> Result<VlmOutput> {
+        // Single-image backends get the rules as text only.
+        let text_only: Vec<VlmRule>;
+        let rules = if self.multi_image() || rules.iter().all(|r| r.references.is_empty()) {
+            rules
+        } else {
+            text_only = rules.iter().map(|r| VlmRule { references: Vec::new(), ..r.clone() }).collect();
+            &text_only
+        };
+        let references: Vec<&ReferenceImage> = reference_images(rules).into_iter().map(|(_, image)| image).collect();
+
+        let system = build_system_prompt(rules, exclusions);
+        let text = if references.is_empty() {
+            format!("Analyze this security camera frame from '{stream_name}'. Respond with the required JSON.")
+        } else {
+            format!(
+                "Analyze the last image, the security camera frame from '{stream_name}'. \
+                 Respond with the required JSON."
+            )
+        };
+        let completion = self
+            .complete(&VlmPrompt {
+                system: &system,
+                text: &text,
+                image_jpeg,
+                references,
+                schema: analysis_json_schema(rules),
+            })
+            .await?;
+
+        Ok(VlmOutput {
+            result: parse_or_fallback(&completion.raw_response),
+            raw_response: completion.raw_response,
+            system_prompt: system,
+            model: completion.model,
+            backend: completion.backend,
+            latency_ms: completion.latency_ms,
+            usage: completion.usage,
+        })
+    }
+
+    /// Analyze a grid of frames from several cameras (see `analysis::tiling`)
+    /// in one call, returning one result per tile.
+    async fn analyze_tiles(&self, grid_jpeg: &[u8], tiles: &[Tile<'_>]) -> Result<TiledVlmOutput> {
+        let system = build_tiled_system_prompt(tiles);
+        let text = format!(
+            "Analyze each of the {} numbered camera tiles in this image. Respond with the required JSON.",
+            tiles.len()
+        );
+        let completion = self
+            .complete(&VlmPrompt {
+                system: &system,
+                text: &text,
+                image_jpeg: grid_jpeg,
+                references: Vec::new(),
+                schema: tiled_json_schema(tiles),
+            })
+            .await?;
+
+        Ok(TiledVlmOutput {
+            results: parse_tiles(&completion.raw_response, tiles.len()),
+            raw_response: completion.raw_response,
+            system_prompt: system,
+            model: completion.model,
+            backend: completion.backend,
+            latency_ms: completion.latency_ms,
+            usage: completion.usage,
+        })
+    }
 }
 
 pub type DynVlmClient = Arc<dyn VlmClient>;
@@ -127,11 +580,47 @@ pub fn build_vlm_client(cfg: &VlmBackend) -> DynVlmClient {
     match cfg {
         VlmBackend::Ollama(c) => Arc::new(ollama::OllamaClient::new(c)),
         VlmBackend::OpenAiCompat(c) => Arc::new(openai_compat::OpenAiCompatClient::new(c)),
+        VlmBackend::LlamaCpp(c) => Arc::new(llama_cpp::LlamaCppClient::new(c)),
+        VlmBackend::Vllm(c) => Arc::new(vllm::VllmClient::new(c)),
     }
 }
 
 // ─── Helpers ─────────────────────────────────────────────────────────────────
 
+/// Maps a failed HTTP status to an error; 429 and 5xx mean "try again later".
+pub(crate) fn http_error(status: reqwest::StatusCode, msg: String) -> AppError {
+    if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        AppError::VlmUnavailable(msg)
+    } else {
+        AppError::Vlm(msg)
+    }
+}
+
+/// A response body that could not be read: a timeout is worth retrying, a
+/// malformed body is not.
+pub(crate) fn body_error(e: reqwest::Error, context: &str) -> AppError {
+    if e.is_timeout() {
+        AppError::VlmUnavailable(format!("{context}: {e}"))
+    } else {
+        AppError::Vlm(format!("{context}: {e}"))
+    }
+}
+
+/// HTTP client with the backend's optional connect / whole-request timeouts.
+fn http_client(connect_timeout_sec: Option<u64>, request_timeout_sec: Option<u64>) -> reqwest::Client {
+    let mut builder = reqwest::Client::builder();
+    if let Some(secs) = connect_timeout_sec {
+        builder = builder.connect_timeout(Duration::from_secs(secs));
+    }
+    if let Some(secs) = request_timeout_sec {
+        builder = builder.timeout(Duration::from_secs(secs));
+    }
+    builder.build().unwrap_or_else(|e| {
+        error!("Could not build the VLM HTTP client, its timeouts won't apply: {e}");
+        reqwest::Client::new()
+    })
+}
+
 /// Try to parse the VLM's raw text output as `AnalysisResult`.
 ///
 /// Strategy:
@@ -175,3 +664,50 @@ pub fn parse_or_fallback(raw: &str) -> AnalysisResult {
         triggered_rule: None,
     }
 }
+
+
+#[derive(Deserialize)]
+struct TiledAnswer {
+    tiles: Vec<TileAnswer>,
+}
+
+#[derive(Deserialize)]
+struct TileAnswer {
+    #[serde(default)]
+    tile: Option<usize>,
+    #[serde(flatten)]
+    result: AnalysisResult,
+}
+
+/// Split a tiled answer into per-tile results, matched by tile number (or by
+/// position when the model left numbers out). Tiles the model skipped, and
+/// every tile if the answer cannot be parsed, are `None`.
+pub fn parse_tiles(raw: &str, tile_count: usize) -> Vec<Option<AnalysisResult>> {
+    let cleaned = raw
+        .trim()
+        .trim_start_matches("```json")
+        .trim_start_matches("```")
+        .trim_end_matches("```")
+        .trim();
+
+    // Same scan as `parse_or_fallback`: direct parse, then from the rightmost '{' back.
+    let answer = serde_json::from_str::<TiledAnswer>(cleaned).ok().or_else(|| {
+        let mut search_end = cleaned.len();
+        while let Some(start) = cleaned[..search_end].rfind('{') {
+            if let Ok(answer) = serde_json::from_str::<TiledAnswer>(&cleaned[start..]) {
+                return Some(answer);
+            }
+            search_end = start;
+        }
+        None
+    });
+
+    let mut results = vec![None; tile_count];
+    for (position, answer) in answer.map(|a| a.tiles).unwrap_or_default().into_iter().enumerate() {
+        let index = answer.tile.map_or(position, |n| n.wrapping_sub(1));
+        if let Some(slot) = results.get_mut(index).filter(|slot| slot.is_none()) {
+            *slot = Some(answer.result);
+        }
+    }
+    results
+}
@@ -1,3 +1,5 @@
+use std::time::Instant;
+
 use base64::{engine::general_purpose::STANDARD as B64, Engine};
 use serde::{Deserialize, Serialize};
 use tracing::debug;
@@ -7,20 +9,24 @@ use crate::{
     error::{AppError, Result},
 };
 
-use super::{build_rules_prompt, parse_or_fallback, AnalysisResult, VlmRule, SYSTEM_PROMPT};
+use super::{body_error, http_client, http_error, TokenUsage, VlmCompletion, VlmPrompt};
 
 pub struct OllamaClient {
     client: reqwest::Client,
     base_url: String,
     model: String,
+    max_tokens: Option<u32>,
+    multi_image: bool,
 }
 
 impl OllamaClient {
     pub fn new(cfg: &OllamaConfig) -> Self {
         Self {
-            client: reqwest::Client::new(),
+            client: http_client(cfg.connect_timeout_sec, cfg.request_timeout_sec),
             base_url: cfg.base_url.trim_end_matches('/').to_string(),
             model: cfg.model.clone(),
+            max_tokens: cfg.max_tokens,
+            multi_image: cfg.multi_image,
         }
     }
 }
@@ -32,64 +38,91 @@ struct GenerateRequest<'a> {
     model: &'a str,
     prompt: &'a str,
     system: &'a str,
-    /// Base64-encoded images (no data URI prefix).
+    /// Base64-encoded images (no data URI prefix): reference images in prompt
+    /// order, then the frame.
     images: Vec<String>,
     stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    options: Option<GenerateOptions>,
+}
+
+#[derive(Serialize)]
+struct GenerateOptions {
+    num_predict: u32,
 }
 
 #[derive(Deserialize)]
 struct GenerateResponse {
     response: String,
+    /// Tokens in the prompt (including image tokens).
+    #[serde(default)]
+    prompt_eval_count: Option<i32>,
+    /// Tokens generated.
+    #[serde(default)]
+    eval_count: Option<i32>,
 }
 
 // ─── VlmClient impl ───────────────────────────────────────────────────────────
 
 #[async_trait::async_trait]
 impl super::VlmClient for OllamaClient {
-    async fn analyze(
-        &self,
-        image_jpeg: &[u8],
-        stream_name: &str,
-        rules: &[VlmRule],
-    ) -> Result<AnalysisResult> {
-        let b64 = B64.encode(image_jpeg);
-        let prompt = format!(
-            "Analyze this security camera frame from '{stream_name}'. Respond with the required JSON."
-        );
-        let system = format!("{SYSTEM_PROMPT}{}", build_rules_prompt(rules));
+    async fn complete(&self, prompt: &VlmPrompt<'_>) -> Result<VlmCompletion> {
+        let images = prompt
+            .references
+            .iter()
+            .map(|r| B64.encode(&r.jpeg))
+            .chain(std::iter::once(B64.encode(prompt.image_jpeg)))
+            .collect();
 
         let body = GenerateRequest {
             model: &self.model,
-            prompt: &prompt,
-            system: &system,
-            images: vec![b64],
+            prompt: prompt.text,
+            system: prompt.system,
+            images,
             stream: false,
+            options: self.max_tokens.map(|num_predict| GenerateOptions { num_predict }),
         };
 
         let url = format!("{}/api/generate", self.base_url);
         debug!(model = %self.model, url = %url, "Calling Ollama");
 
+        let started = Instant::now();
         let resp = self
             .client
             .post(&url)
             .json(&body)
             .send()
             .await
-            .map_err(|e| AppError::Vlm(format!("Ollama request failed: {e}")))?;
+            .map_err(|e| AppError::VlmUnavailable(format!("Ollama request failed: {e}")))?;
 
         if !resp.status().is_success() {
             let status = resp.status();
             let text = resp.text().await.unwrap_or_default();
-            return Err(AppError::Vlm(format!("Ollama HTTP {status}: {text}")));
+            return Err(http_error(status, format!("Ollama HTTP {status}: {text}")));
         }
 
         let gen: GenerateResponse = resp
             .json()
             .await
-            .map_err(|e| AppError::Vlm(format!("Failed to deserialize Ollama response: {e}")))?;
+            .map_err(|e| body_error(e, "Failed to deserialize Ollama response"))?;
+
+        let latency_ms = started.elapsed().as_millis().min(i32::MAX as u128) as i32;
 
         debug!(raw = %gen.response, "Ollama raw response");
 
-        Ok(parse_or_fallback(&gen.response))
+        Ok(VlmCompletion {
+            raw_response: gen.response,
+            model: self.model.clone(),
+            backend: "ollama",
+            latency_ms,
+            usage: TokenUsage {
+                prompt_tokens: gen.prompt_eval_count,
+                completion_tokens: gen.eval_count,
+            },
+        })
+    }
+
+    fn multi_image(&self) -> bool {
+        self.multi_image
     }
 }
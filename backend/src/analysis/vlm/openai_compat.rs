@@ -7,9 +7,11 @@
 /// • OpenAI – set base_url to `https://api.openai.com/v1` and model to
 ///   `gpt-4o` or `gpt-4-turbo`.
 /// • Any other /v1/chat/completions provider.
+use std::time::Instant;
+
 use base64::{engine::general_purpose::STANDARD as B64, Engine};
 use serde::Deserialize;
-use serde_json::json;
+use serde_json::{json, Value};
 use tracing::debug;
 
 use crate::{
@@ -17,24 +19,49 @@ use crate::{
     error::{AppError, Result},
 };
 
-use super::{build_rules_prompt, parse_or_fallback, AnalysisResult, VlmRule, SYSTEM_PROMPT};
+use super::{body_error, http_client, http_error, TokenUsage, VlmCompletion, VlmPrompt};
 
 pub struct OpenAiCompatClient {
     client: reqwest::Client,
     base_url: String,
     api_key: String,
     model: String,
+    max_tokens: u32,
+    multi_image: bool,
 }
 
 impl OpenAiCompatClient {
     pub fn new(cfg: &OpenAiCompatConfig) -> Self {
         Self {
-            client: reqwest::Client::new(),
+            client: http_client(cfg.connect_timeout_sec, cfg.request_timeout_sec),
             base_url: cfg.base_url.trim_end_matches('/').to_string(),
             api_key: cfg.api_key.clone(),
             model: cfg.model.clone(),
+            max_tokens: cfg.max_tokens.unwrap_or(512),
+            multi_image: cfg.multi_image,
         }
     }
+
+    /// The chat request for `prompt`. The llama.cpp and vLLM clients wrap this
+    /// client and add their server's fields to it before sending.
+    pub(super) fn body(&self, prompt: &VlmPrompt<'_>) -> Value {
+        chat_body(&self.model, prompt, self.max_tokens)
+    }
+
+    /// Sends `body` and reports the reply as `backend`'s; `label` names the
+    /// server in logs and errors.
+    pub(super) async fn send(&self, body: &Value, backend: &'static str, label: &str) -> Result<VlmCompletion> {
+        debug!(model = %self.model, "Calling {label} server");
+        let reply = post_chat(&self.client, &self.base_url, &self.api_key, body, label).await?;
+
+        Ok(VlmCompletion {
+            raw_response: reply.content,
+            model: self.model.clone(),
+            backend,
+            latency_ms: reply.latency_ms,
+            usage: reply.usage,
+        })
+    }
 }
 
 // ─── Request / Response types ─────────────────────────────────────────────────
@@ -42,6 +69,16 @@ impl OpenAiCompatClient {
 #[derive(Deserialize)]
 struct ChatResponse {
     choices: Vec<Choice>,
+    #[serde(default)]
+    usage: Option<Usage>,
+}
+
+#[derive(Deserialize)]
+struct Usage {
+    #[serde(default)]
+    prompt_tokens: Option<i32>,
+    #[serde(default)]
+    completion_tokens: Option<i32>,
 }
 
 #[derive(Deserialize)]
@@ -54,79 +91,115 @@ struct AssistantMessage {
     content: String,
 }
 
-// ─── VlmClient impl ───────────────────────────────────────────────────────────
+// ─── Chat-completions call ────────────────────────────────────────────────────
+
+/// A vision chat request: system prompt, then the image before the text.
+/// Reference images come first, each after a label with its number.
+fn chat_body(model: &str, prompt: &VlmPrompt<'_>, max_tokens: u32) -> Value {
+    let image_part = |jpeg: &[u8]| {
+        json!({
+            "type": "image_url",
+            "image_url": { "url": format!("data:image/jpeg;base64,{}", B64.encode(jpeg)) }
+        })
+    };
+
+    let mut content = Vec::new();
+    for (i, reference) in prompt.references.iter().enumerate() {
+        content.push(json!({ "type": "text", "text": format!("Reference image {}:", i + 1) }));
+        content.push(image_part(&reference.jpeg));
+    }
+    if !prompt.references.is_empty() {
+        content.push(json!({ "type": "text", "text": "Camera frame to analyze:" }));
+    }
+    content.push(image_part(prompt.image_jpeg));
+    content.push(json!({ "type": "text", "text": prompt.text }));
+
+    json!({
+        "model": model,
+        "messages": [
+            {
+                "role": "system",
+                "content": prompt.system
+            },
+            {
+                "role": "user",
+                "content": content
+            }
+        ],
+        "max_tokens": max_tokens,
+        "stream": false
+    })
+}
 
-#[async_trait::async_trait]
-impl super::VlmClient for OpenAiCompatClient {
-    async fn analyze(
-        &self,
-        image_jpeg: &[u8],
-        stream_name: &str,
-        rules: &[VlmRule],
-    ) -> Result<AnalysisResult> {
-        let b64 = B64.encode(image_jpeg);
-        let data_uri = format!("data:image/jpeg;base64,{b64}");
-        let system = format!("{SYSTEM_PROMPT}{}", build_rules_prompt(rules));
-
-        let body = json!({
-            "model": self.model,
-            "messages": [
-                {
-                    "role": "system",
-                    "content": system
-                },
-                {
-                    "role": "user",
-                    "content": [
-                        {
-                            "type": "image_url",
-                            "image_url": { "url": data_uri }
-                        },
-                        {
-                            "type": "text",
-                            "text": format!(
-                                "Analyze this security camera frame from '{stream_name}'. Respond with the required JSON."
-                            )
-                        }
-                    ]
-                }
-            ],
-            "max_tokens": 512,
-            "stream": false
-        });
-
-        let url = format!("{}/chat/completions", self.base_url);
-        debug!(model = %self.model, url = %url, "Calling OpenAI-compat API");
-
-        let resp = self
-            .client
-            .post(&url)
-            .bearer_auth(&self.api_key)
-            .json(&body)
-            .send()
-            .await
-            .map_err(|e| AppError::Vlm(format!("OpenAI-compat request failed: {e}")))?;
-
-        if !resp.status().is_success() {
-            let status = resp.status();
-            let text = resp.text().await.unwrap_or_default();
-            return Err(AppError::Vlm(format!("OpenAI-compat HTTP {status}: {text}")));
-        }
+struct ChatReply {
+    content: String,
+    usage: TokenUsage,
+    latency_ms: i32,
+}
+
+/// POSTs `body` to `{base_url}/chat/completions`. `label` names the server in errors.
+async fn post_chat(
+    client: &reqwest::Client,
+    base_url: &str,
+    api_key: &str,
+    body: &Value,
+    label: &str,
+) -> Result<ChatReply> {
+    let url = format!("{base_url}/chat/completions");
+    debug!(url = %url, "Calling {label} chat API");
+
+    let started = Instant::now();
+    let mut req = client.post(&url).json(body);
+    // Self-hosted servers often run without auth.
+    if !api_key.is_empty() {
+        req = req.bearer_auth(api_key);
+    }
+    let resp = req
+        .send()
+        .await
+        .map_err(|e| AppError::VlmUnavailable(format!("{label} request failed: {e}")))?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_default();
+        return Err(http_error(status, format!("{label} HTTP {status}: {text}")));
+    }
 
-        let chat: ChatResponse = resp
-            .json()
-            .await
-            .map_err(|e| AppError::Vlm(format!("Failed to deserialize chat response: {e}")))?;
+    let chat: ChatResponse = resp
+        .json()
+        .await
+        .map_err(|e| body_error(e, "Failed to deserialize chat response"))?;
+
+    let latency_ms = started.elapsed().as_millis().min(i32::MAX as u128) as i32;
+    let usage = chat
+        .usage
+        .map(|u| TokenUsage {
+            prompt_tokens: u.prompt_tokens,
+            completion_tokens: u.completion_tokens,
+        })
+        .unwrap_or_default();
+
+    let content = chat
+        .choices
+        .into_iter()
+        .next()
+        .map(|c| c.message.content)
+        .unwrap_or_default();
+
+    debug!(raw = %content, "{label} raw response");
+
+    Ok(ChatReply { content, usage, latency_ms })
+}
 
-        let content = chat
-            .choices
-            .into_iter()
-            .next()
-            .map(|c| c.message.content)
-            .unwrap_or_default();
+// ─── VlmClient impl ───────────────────────────────────────────────────────────
 
-        debug!(raw = %content, "OpenAI-compat raw response");
+#[async_trait::async_trait]
+impl super::VlmClient for OpenAiCompatClient {
+    async fn complete(&self, prompt: &VlmPrompt<'_>) -> Result<VlmCompletion> {
+        self.send(&self.body(prompt), "openai_compat", "OpenAI-compat").await
+    }
 
-        Ok(parse_or_fallback(&content))
+    fn multi_image(&self) -> bool {
+        self.multi_image
     }
 }
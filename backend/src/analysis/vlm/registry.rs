@@ -0,0 +1,173 @@
+//! Resolves which VLM client analyzes a stream: the client for the stream's
+//! profile if it has one, otherwise the global default. Profile clients are
+//! built once and rebuilt when the profile is edited. Every client retries,
+//! circuit-breaks and falls back to the configured fallback backend.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use tokio::sync::Semaphore;
+use uuid::Uuid;
+
+use crate::{
+    config::{AppConfig, OllamaConfig, OpenAiCompatConfig, VlmBackend, VlmResilienceConfig},
+    error::{AppError, Result},
+    storage::{db, models::VlmProfile},
+};
+
+use super::{
+    failover::{build_failover_client, FailoverClient, InFlightLimits},
+    DynVlmClient,
+};
+
+/// Backend names a profile may use.
+pub const PROFILE_BACKENDS: [&str; 4] = ["ollama", "openai_compat", "llama_cpp", "vllm"];
+
+pub struct VlmRegistry {
+    default: DynVlmClient,
+    /// The settings `default` was built from.
+    default_backend: VlmBackend,
+    fallback: Option<VlmBackend>,
+    policy: VlmResilienceConfig,
+    limits: InFlightLimits,
+    /// (connect, request) timeouts for profiles that don't set their own.
+    default_timeouts: (Option<u64>, Option<u64>),
+    /// Profile id → (profile `updated_at` the client was built from, client).
+    profiles: RwLock<HashMap<Uuid, (DateTime<Utc>, DynVlmClient)>>,
+}
+
+impl VlmRegistry {
+    pub fn new(cfg: &AppConfig) -> Arc<Self> {
+        let limits = InFlightLimits::new(cfg.vlm_max_in_flight);
+        Arc::new(Self {
+            default: build_failover_client(&cfg.vlm, cfg.vlm_fallback.as_ref(), &cfg.vlm_resilience, &limits),
+            default_backend: cfg.vlm.clone(),
+            fallback: cfg.vlm_fallback.clone(),
+            policy: cfg.vlm_resilience.clone(),
+            limits,
+            default_timeouts: cfg.vlm.timeouts(),
+            profiles: RwLock::new(HashMap::new()),
+        })
+    }
+
+    /// The globally configured client.
+    pub fn default_client(&self) -> DynVlmClient {
+        Arc::clone(&self.default)
+    }
+
+    /// The client for `stream_id`'s current profile, or the default.
+    pub async fn for_stream(&self, db: &PgPool, stream_id: Uuid) -> Result<DynVlmClient> {
+        match db::get_stream_vlm_profile(db, stream_id).await? {
+            Some(profile) => self.for_profile(&profile),
+            None => Ok(self.default_client()),
+        }
+    }
+
+    pub fn for_profile(&self, profile: &VlmProfile) -> Result<DynVlmClient> {
+        if let Some((built_at, client)) = self.profiles.read().unwrap().get(&profile.id) {
+            if *built_at == profile.updated_at {
+                return Ok(Arc::clone(client));
+            }
+        }
+        let backend = profile_backend(profile, self.default_timeouts)?;
+        let client = build_failover_client(&backend, self.fallback.as_ref(), &self.policy, &self.limits);
+        self.profiles
+            .write()
+            .unwrap()
+            .insert(profile.id, (profile.updated_at, Arc::clone(&client)));
+        Ok(client)
+    }
+
+    /// The backend settings `for_stream` would use for `stream_id`.
+    pub async fn stream_backend(&self, db: &PgPool, stream_id: Uuid) -> Result<VlmBackend> {
+        self.backend_for(db::get_stream_vlm_profile(db, stream_id).await?.as_ref())
+    }
+
+    /// The backend settings of `profile`, or the default's without one.
+    pub fn backend_for(&self, profile: Option<&VlmProfile>) -> Result<VlmBackend> {
+        match profile {
+            Some(profile) => profile_backend(profile, self.default_timeouts),
+            None => Ok(self.default_backend.clone()),
+        }
+    }
+
+    /// A client for `backend` (e.g. a stream's with another model), with the
+    /// same retries, circuit breaking, fallback and in-flight limits as the
+    /// others. Not cached: build it once per job.
+    pub fn client_for(&self, backend: &VlmBackend) -> DynVlmClient {
+        build_failover_client(backend, self.fallback.as_ref(), &self.policy, &self.limits)
+    }
+
+    /// A client for a shadow model: retried and circuit-broken like the others,
+    /// with no fallback. It takes no in-flight permit itself; callers take one
+    /// from `in_flight` first, so shadow calls count towards the endpoint's
+    /// limit and can give up when it is reached instead of waiting.
+    pub fn shadow_client(&self, backend: &VlmBackend) -> DynVlmClient {
+        Arc::new(FailoverClient::new(std::slice::from_ref(backend), self.policy.clone(), None))
+    }
+
+    /// The in-flight limit shared by every client of `backend`'s endpoint.
+    pub fn in_flight(&self, backend: &VlmBackend) -> Arc<Semaphore> {
+        self.limits.for_endpoint(backend.base_url())
+    }
+
+    /// Drop the cached client of a deleted profile.
+    pub fn evict(&self, profile_id: Uuid) {
+        self.profiles.write().unwrap().remove(&profile_id);
+    }
+}
+
+/// Rejects unknown backends and non-positive timeouts / token limits.
+pub fn validate_profile_fields(backend: Option<&str>, limits: [Option<i32>; 3]) -> Result<()> {
+    if let Some(b) = backend.filter(|b| !PROFILE_BACKENDS.contains(b)) {
+        return Err(AppError::BadRequest(format!(
+            "unknown backend '{b}', expected one of: {}",
+            PROFILE_BACKENDS.join(", ")
+        )));
+    }
+    if limits.iter().flatten().any(|&v| v <= 0) {
+        return Err(AppError::BadRequest("timeouts and max_tokens must be positive".into()));
+    }
+    Ok(())
+}
+
+/// Backend settings described by a stored profile; unset timeouts take
+/// `default_timeouts` (connect, request).
+pub fn profile_backend(p: &VlmProfile, default_timeouts: (Option<u64>, Option<u64>)) -> Result<VlmBackend> {
+    let secs = |v: Option<i32>, default: Option<u64>| v.map(|s| s.max(1) as u64).or(default);
+    let (default_connect, default_request) = default_timeouts;
+    let max_tokens = p.max_tokens.map(|t| t.max(1) as u32);
+    Ok(match p.backend.as_str() {
+        "ollama" => VlmBackend::Ollama(OllamaConfig {
+            base_url: p.base_url.clone(),
+            model: p.model.clone(),
+            connect_timeout_sec: secs(p.connect_timeout_sec, default_connect),
+            request_timeout_sec: secs(p.request_timeout_sec, default_request),
+            max_tokens,
+            multi_image: p.multi_image,
+        }),
+        kind @ ("openai_compat" | "llama_cpp" | "vllm") => {
+            let cfg = OpenAiCompatConfig {
+                base_url: p.base_url.clone(),
+                api_key: p.api_key.clone().unwrap_or_default(),
+                model: p.model.clone(),
+                connect_timeout_sec: secs(p.connect_timeout_sec, default_connect),
+                request_timeout_sec: secs(p.request_timeout_sec, default_request),
+                max_tokens,
+                multi_image: p.multi_image,
+            };
+            match kind {
+                "llama_cpp" => VlmBackend::LlamaCpp(cfg),
+                "vllm" => VlmBackend::Vllm(cfg),
+                _ => VlmBackend::OpenAiCompat(cfg),
+            }
+        }
+        other => {
+            return Err(AppError::Vlm(format!("VLM profile '{}' has unknown backend '{other}'", p.name)))
+        }
+    })
+}
@@ -0,0 +1,54 @@
+//! A stand-in for an OpenAI-compatible server in tests: answers every chat
+//! request with a canned response and keeps the request bodies.
+
+use std::sync::{Arc, Mutex};
+
+use axum::{extract::State, routing::post, Json, Router};
+use serde_json::Value;
+
+use crate::config::OpenAiCompatConfig;
+
+pub struct CannedServer {
+    pub base_url: String,
+    requests: Arc<Mutex<Vec<Value>>>,
+}
+
+impl CannedServer {
+    /// Serves `response` (a `/chat/completions` body in the server's format) on
+    /// a free local port.
+    pub async fn start(response: &'static str) -> Self {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let app = Router::new()
+            .route(
+                "/v1/chat/completions",
+                post(move |State(requests): State<Arc<Mutex<Vec<Value>>>>, Json(body): Json<Value>| async move {
+                    requests.lock().unwrap().push(body);
+                    Json(serde_json::from_str::<Value>(response).unwrap())
+                }),
+            )
+            .with_state(Arc::clone(&requests));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        Self { base_url: format!("http://{addr}/v1"), requests }
+    }
+
+    /// The one request the server got.
+    pub fn request(&self) -> Value {
+        let requests = self.requests.lock().unwrap();
+        assert_eq!(requests.len(), 1, "expected one request");
+        requests[0].clone()
+    }
+
+    pub fn config(&self, model: &str) -> OpenAiCompatConfig {
+        OpenAiCompatConfig {
+            base_url: self.base_url.clone(),
+            api_key: String::new(),
+            model: model.to_string(),
+            connect_timeout_sec: Some(5),
+            request_timeout_sec: Some(5),
+            max_tokens: None,
+            multi_image: false,
+        }
+    }
+}
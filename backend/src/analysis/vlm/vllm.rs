@@ -0,0 +1,85 @@
+/// vLLM OpenAI-compatible server client.
+///
+/// Sends the answer schema as `guided_json`, so vLLM's guided decoding only
+/// produces valid JSON in our schema. `triggered_rule` is constrained to the
+/// camera's rule descriptions (see `analysis_json_schema`). vLLM only accepts
+/// as many images per prompt as `--limit-mm-per-prompt` allows; rule
+/// reference images count towards it, so turn `multi_image` off if the limit
+/// is one.
+use crate::{config::OpenAiCompatConfig, error::Result};
+
+use super::{openai_compat::OpenAiCompatClient, VlmClient, VlmCompletion, VlmPrompt};
+
+pub struct VllmClient(OpenAiCompatClient);
+
+impl VllmClient {
+    pub fn new(cfg: &OpenAiCompatConfig) -> Self {
+        Self(OpenAiCompatClient::new(cfg))
+    }
+}
+
+#[async_trait::async_trait]
+impl VlmClient for VllmClient {
+    async fn complete(&self, prompt: &VlmPrompt<'_>) -> Result<VlmCompletion> {
+        let mut body = self.0.body(prompt);
+        body["guided_json"] = prompt.schema.clone();
+        self.0.send(&body, "vllm", "vLLM").await
+    }
+
+    fn multi_image(&self) -> bool {
+        self.0.multi_image()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::analysis::vlm::{analysis_json_schema, test_server::CannedServer, RiskLevel, VlmRule};
+
+    fn rules() -> Vec<VlmRule> {
+        vec![VlmRule {
+            description: "Vehicle in the loading bay".into(),
+            threat_level: "medium".into(),
+            conditions: Some("event types: vehicle_detected".into()),
+            references: Vec::new(),
+        }]
+    }
+
+    #[tokio::test]
+    async fn sends_guided_json_and_parses_the_reply() {
+        let server = CannedServer::start(include_str!("testdata/vllm_response.json")).await;
+        let client = VllmClient::new(&server.config("Qwen/Qwen2-VL-7B-Instruct"));
+
+        let output = client.analyze(b"jpeg", "Dock", &rules(), &[]).await.unwrap();
+
+        let body = server.request();
+        assert_eq!(body["guided_json"], analysis_json_schema(&rules()));
+        assert_eq!(body["guided_json"]["properties"]["triggered_rule"]["enum"], json!([null, "Vehicle in the loading bay"]));
+        assert!(body.get("json_schema").is_none());
+        assert!(body.get("cache_prompt").is_none());
+        assert_eq!(body["model"], json!("Qwen/Qwen2-VL-7B-Instruct"));
+
+        assert_eq!(output.backend, "vllm");
+        assert_eq!(output.model, "Qwen/Qwen2-VL-7B-Instruct");
+        assert_eq!(output.result.risk_level, RiskLevel::None);
+        assert_eq!(output.result.title.as_deref(), Some("Empty loading dock"));
+        assert_eq!(output.result.triggered_rule, None);
+        assert!(output.result.events.is_empty());
+        assert_eq!(output.usage.prompt_tokens, Some(1342));
+        assert_eq!(output.usage.completion_tokens, Some(39));
+    }
+
+    #[tokio::test]
+    async fn prose_reply_falls_back_to_the_raw_text() {
+        let server = CannedServer::start(include_str!("testdata/vllm_unparseable.json")).await;
+        let client = VllmClient::new(&server.config("Qwen/Qwen2-VL-7B-Instruct"));
+
+        let output = client.analyze(b"jpeg", "Dock", &rules(), &[]).await.unwrap();
+
+        assert_eq!(output.result.description, "I'm sorry, but the image is too dark for me to make out anything.");
+        assert_eq!(output.result.risk_level, RiskLevel::None);
+        assert!(output.result.events.is_empty());
+    }
+}
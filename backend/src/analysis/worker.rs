@@ -1,58 +1,164 @@
+use std::{
+    borrow::Cow,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
 use sqlx::PgPool;
 use tokio::sync::{broadcast, mpsc};
-use tracing::{error, info};
+use tracing::{error, info, warn};
 use uuid::Uuid;
 
 use crate::{
-    analysis::vlm::{DynVlmClient, RiskLevel, VlmRule},
-    storage::{db, models::AnalysisEvent},
+    analysis::{
+        preprocess::{self, FrameMapping},
+        references,
+        rules::{self, DwellTracker},
+        shadow::{ShadowAnalyzer, ShadowFrame},
+        tiling,
+        vlm::{registry::VlmRegistry, DynVlmClient, RiskLevel, Tile, TokenUsage, VlmOutput, VlmRule},
+    },
+    config::BatchConfig,
+    error::AppError,
+    storage::{
+        db,
+        models::{AnalysisEvent, NewAnalysisEvent, PreprocessStats, StreamRule},
+    },
     streams::source::CapturedFrame,
 };
 
 /// A pool of async workers that consume frames, call the VLM, persist results,
-/// and broadcast the resulting event to WebSocket subscribers.
+/// and broadcast the resulting event to WebSocket subscribers. With batching
+/// on, a worker takes several frames at once and analyzes them as one grid.
 pub struct AnalysisWorkerPool {
     worker_count: usize,
-    vlm: DynVlmClient,
+    /// Frames older than this when dequeued are dropped (0 = never).
+    max_frame_age_sec: u64,
+    batch: BatchConfig,
+    ctx: Arc<WorkerContext>,
+}
+
+/// Everything a worker needs to process a frame, shared by all workers.
+struct WorkerContext {
+    /// Resolves each stream's VLM (its profile, or the global default).
+    vlm: Arc<VlmRegistry>,
     db: PgPool,
     event_tx: broadcast::Sender<AnalysisEvent>,
+    /// Candidate model run on a sample of frames, if shadow mode is configured.
+    shadow: Option<Arc<ShadowAnalyzer>>,
+    /// When the last "all VLM backends down" system alert went out.
+    last_outage_alert: Mutex<Option<Instant>>,
+    /// Width of each tile in a batched grid image.
+    tile_width: u32,
+    /// How long each stream's triggered rule has kept matching.
+    dwell: DwellTracker,
 }
 
+/// A frame with what its analysis needs: the stream's rules and VLM, and the
+/// (preprocessed) image the VLM sees.
+struct PreparedFrame<'a> {
+    frame: &'a CapturedFrame,
+    /// The stream's rules in force at capture time.
+    active_rules: Vec<StreamRule>,
+    rules: Vec<VlmRule>,
+    /// The stream's approved "do not flag" clauses.
+    exclusions: Vec<String>,
+    image: Cow<'a, [u8]>,
+    preprocess_stats: Option<PreprocessStats>,
+    /// Between the frame and `image`, whose locations the VLM reports.
+    mapping: FrameMapping,
+    vlm: DynVlmClient,
+}
+
+/// Minimum time between two "all VLM backends down" system alerts.
+const OUTAGE_ALERT_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
 impl AnalysisWorkerPool {
     pub fn new(
         worker_count: usize,
-        vlm: DynVlmClient,
+        max_frame_age_sec: u64,
+        batch: BatchConfig,
+        vlm: Arc<VlmRegistry>,
         db: PgPool,
         event_tx: broadcast::Sender<AnalysisEvent>,
+        shadow: Option<Arc<ShadowAnalyzer>>,
     ) -> Self {
-        Self { worker_count, vlm, db, event_tx }
+        Self {
+            worker_count,
+            max_frame_age_sec,
+            ctx: Arc::new(WorkerContext {
+                vlm,
+                db,
+                event_tx,
+                shadow,
+                last_outage_alert: Mutex::new(None),
+                tile_width: batch.tile_width,
+                dwell: DwellTracker::default(),
+            }),
+            batch,
+        }
     }
 
     /// Consumes from `frame_rx` using `worker_count` concurrent tasks.
     pub async fn run(self, frame_rx: mpsc::Receiver<CapturedFrame>) {
         // Wrap receiver in an Arc<Mutex> so workers can share it.
-        let rx = std::sync::Arc::new(tokio::sync::Mutex::new(frame_rx));
+        let rx = Arc::new(tokio::sync::Mutex::new(frame_rx));
+
+        let max_age = (self.max_frame_age_sec > 0)
+            .then(|| chrono::Duration::seconds(self.max_frame_age_sec.min(i64::MAX as u64) as i64));
+
+        let batch_size = self.batch.size;
+        let batch_window = Duration::from_millis(self.batch.window_ms);
 
         let mut handles = Vec::new();
         for i in 0..self.worker_count {
-            let rx = std::sync::Arc::clone(&rx);
-            let vlm = std::sync::Arc::clone(&self.vlm);
-            let db = self.db.clone();
-            let event_tx = self.event_tx.clone();
+            let rx = Arc::clone(&rx);
+            let ctx = Arc::clone(&self.ctx);
 
             let handle = tokio::spawn(async move {
                 info!(worker = i, "Analysis worker started");
                 loop {
-                    let frame = {
+                    let frames = {
                         let mut guard = rx.lock().await;
-                        guard.recv().await
+                        let Some(first) = guard.recv().await else {
+                            // Channel closed
+                            info!(worker = i, "Frame channel closed, worker exiting");
+                            break;
+                        };
+                        // Batching: keep taking frames until the batch is full
+                        // or the window since the first one closes.
+                        let mut frames = vec![first];
+                        let deadline = tokio::time::Instant::now() + batch_window;
+                        while frames.len() < batch_size {
+                            match tokio::time::timeout_at(deadline, guard.recv()).await {
+                                Ok(Some(frame)) => frames.push(frame),
+                                _ => break,
+                            }
+                        }
+                        frames
                     };
 
-                    match frame {
-                        Some(frame) => {
-                            if let Err(e) =
-                                process_frame(&frame, &vlm, &db, &event_tx).await
-                            {
+                    let frames: Vec<CapturedFrame> = frames
+                        .into_iter()
+                        .filter(|frame| {
+                            let stale = max_age.is_some_and(|max| Utc::now() - frame.captured_at > max);
+                            if stale {
+                                warn!(
+                                    worker = i,
+                                    stream = %frame.stream_name,
+                                    age_sec = (Utc::now() - frame.captured_at).num_seconds(),
+                                    "Dropping stale frame"
+                                );
+                            }
+                            !stale
+                        })
+                        .collect();
+
+                    match frames.as_slice() {
+                        [] => {}
+                        [frame] => {
+                            if let Err(e) = process_frame(frame, &ctx).await {
                                 error!(
                                     worker = i,
                                     stream = %frame.stream_name,
@@ -60,11 +166,7 @@ impl AnalysisWorkerPool {
                                 );
                             }
                         }
-                        None => {
-                            // Channel closed
-                            info!(worker = i, "Frame channel closed, worker exiting");
-                            break;
-                        }
+                        _ => process_batch(&frames, &ctx).await,
                     }
                 }
             });
@@ -78,30 +180,180 @@ impl AnalysisWorkerPool {
     }
 }
 
-async fn process_frame(
-    frame: &CapturedFrame,
-    vlm: &DynVlmClient,
-    db: &PgPool,
-    event_tx: &broadcast::Sender<AnalysisEvent>,
-) -> anyhow::Result<()> {
+async fn process_frame(frame: &CapturedFrame, ctx: &WorkerContext) -> anyhow::Result<()> {
     info!(stream = %frame.stream_name, "Analyzing frame");
+    let prepared = prepare_frame(frame, ctx).await?;
+    analyze_single(&prepared, ctx).await
+}
+
+async fn prepare_frame<'a>(frame: &'a CapturedFrame, ctx: &WorkerContext) -> anyhow::Result<PreparedFrame<'a>> {
+    let db = &ctx.db;
+
+    // The VLM sees the preprocessed image; the event keeps the frame as captured.
+    let (image, preprocess_stats, mapping) = preprocess::prepare_frame(db, frame.stream_id, &frame.data).await?;
+
+    // Fetch the per-stream rules in force when the frame was captured and
+    // convert them to VlmRule for prompt injection.
+    let stream_rules = rules::effective_rules(db, frame.stream_id).await.unwrap_or_default();
+    let active_rules = rules::active_at(stream_rules, frame.captured_at);
+    let rules = references::vlm_rules(db, &active_rules, &mapping).await.unwrap_or_else(|e| {
+        warn!(stream = %frame.stream_name, "Could not load rule reference images: {e}");
+        active_rules.iter().map(|r| VlmRule::for_image(r.clone(), &mapping)).collect()
+    });
+    let exclusions = db::approved_feedback_clauses(db, frame.stream_id).await.unwrap_or_default();
+
+    let vlm = ctx.vlm.for_stream(db, frame.stream_id).await?;
+    Ok(PreparedFrame { frame, active_rules, rules, exclusions, image, preprocess_stats, mapping, vlm })
+}
+
+/// One VLM call for one frame.
+async fn analyze_single(p: &PreparedFrame<'_>, ctx: &WorkerContext) -> anyhow::Result<()> {
+    match p.vlm.analyze(&p.image, &p.frame.stream_name, &p.rules, &p.exclusions).await {
+        Ok(output) => store_result(p, &output, None, ctx).await,
+        Err(e) => record_failed_frame(p.frame, &p.rules, &e, ctx).await,
+    }
+}
+
+/// Analyzes frames from several streams, composing the ones that share a VLM
+/// into one grid image per VLM. Errors are logged per frame.
+async fn process_batch(frames: &[CapturedFrame], ctx: &WorkerContext) {
+    info!(frames = frames.len(), "Analyzing batch");
+
+    let mut groups: Vec<Vec<PreparedFrame>> = Vec::new();
+    for frame in frames {
+        let prepared = match prepare_frame(frame, ctx).await {
+            Ok(p) => p,
+            Err(e) => {
+                error!(stream = %frame.stream_name, "Frame processing error: {e}");
+                continue;
+            }
+        };
+        // Reference images need a call of their own: a grid is one image.
+        if prepared.vlm.multi_image() && prepared.rules.iter().any(|r| !r.references.is_empty()) {
+            groups.push(vec![prepared]);
+            continue;
+        }
+        // Only frames going to the same VLM client can share a call.
+        match groups.iter_mut().find(|g| Arc::ptr_eq(&g[0].vlm, &prepared.vlm)) {
+            Some(group) => group.push(prepared),
+            None => groups.push(vec![prepared]),
+        }
+    }
+
+    futures::future::join_all(groups.iter().map(|group| async move {
+        let result = match group.as_slice() {
+            [single] => analyze_single(single, ctx).await,
+            _ => analyze_tiled(group, ctx).await,
+        };
+        if let Err(e) = result {
+            error!(streams = group.len(), "Batch processing error: {e}");
+        }
+    }))
+    .await;
+}
+
+/// One VLM call for a grid of frames; each tile's answer becomes its own
+/// event. Tiles the model left out are analyzed on their own.
+async fn analyze_tiled(group: &[PreparedFrame<'_>], ctx: &WorkerContext) -> anyhow::Result<()> {
+    let images: Vec<Vec<u8>> = group.iter().map(|p| p.image.to_vec()).collect();
+    let tile_width = ctx.tile_width;
+    let grid = tokio::task::spawn_blocking(move || {
+        let frames: Vec<&[u8]> = images.iter().map(Vec::as_slice).collect();
+        tiling::compose_grid(&frames, tile_width)
+    })
+    .await?;
+    let grid = match grid {
+        Ok(g) => g,
+        Err(e) => {
+            warn!(frames = group.len(), "Could not compose batch grid, analyzing frames one by one: {e}");
+            for p in group {
+                analyze_single(p, ctx).await?;
+            }
+            return Ok(());
+        }
+    };
 
-    // Fetch per-stream rules and convert to VlmRule for prompt injection.
-    let stream_rules = db::list_rules(db, frame.stream_id).await.unwrap_or_default();
-    let vlm_rules: Vec<VlmRule> = stream_rules
-        .into_iter()
-        .map(|r| VlmRule { description: r.description, threat_level: r.threat_level })
+    let tiles: Vec<Tile> = group
+        .iter()
+        .map(|p| Tile { stream_name: &p.frame.stream_name, rules: &p.rules, exclusions: &p.exclusions })
         .collect();
+    let output = match group[0].vlm.analyze_tiles(&grid, &tiles).await {
+        Ok(o) => o,
+        Err(e) => {
+            for p in group {
+                record_failed_frame(p.frame, &p.rules, &e, ctx).await?;
+            }
+            return Ok(());
+        }
+    };
+
+    info!(
+        tiles = group.len(),
+        model = %output.model,
+        latency_ms = output.latency_ms,
+        "Batch analysis complete"
+    );
 
-    let result = vlm.analyze(&frame.data, &frame.stream_name, &vlm_rules).await?;
+    // Token counts are for the whole grid; each event gets an even share.
+    let tile_count = group.len() as i32;
+    let share = |tokens: Option<i32>| tokens.map(|t| t / tile_count);
+    let batch_id = Uuid::new_v4();
+
+    for (i, (p, result)) in group.iter().zip(output.results).enumerate() {
+        let stored = match result {
+            Some(result) => {
+                let tile_output = VlmOutput {
+                    result,
+                    raw_response: output.raw_response.clone(),
+                    system_prompt: output.system_prompt.clone(),
+                    model: output.model.clone(),
+                    backend: output.backend,
+                    latency_ms: output.latency_ms,
+                    usage: TokenUsage {
+                        prompt_tokens: share(output.usage.prompt_tokens),
+                        completion_tokens: share(output.usage.completion_tokens),
+                    },
+                };
+                store_result(p, &tile_output, Some((batch_id, i as i32 + 1)), ctx).await
+            }
+            None => {
+                warn!(stream = %p.frame.stream_name, tile = i + 1, "No answer for tile, analyzing the frame on its own");
+                analyze_single(p, ctx).await
+            }
+        };
+        if let Err(e) = stored {
+            error!(stream = %p.frame.stream_name, "Frame processing error: {e}");
+        }
+    }
+    Ok(())
+}
+
+/// Persists a successful analysis, broadcasts it, and kicks off shadow
+/// analysis and alerts. `batch` is (batch id, tile number) for tiled calls.
+async fn store_result(
+    p: &PreparedFrame<'_>,
+    output: &VlmOutput,
+    batch: Option<(Uuid, i32)>,
+    ctx: &WorkerContext,
+) -> anyhow::Result<()> {
+    let db = &ctx.db;
+    let frame = p.frame;
+    let vlm_rules = &p.rules;
+
+    // Locations are stored and checked against zones on the captured frame.
+    // The VLM's rule match only stands if the rule's structured conditions hold.
+    let mut result = output.result.clone();
+    p.mapping.boxes_to_frame(&mut result.events);
+    let rule_check = rules::check_triggered(
+        &p.active_rules,
+        &mut result,
+        frame.stream_id,
+        frame.captured_at,
+        Some(&ctx.dwell),
+    );
 
     let event_id = Uuid::new_v4();
-    let risk_str = match result.risk_level {
-        RiskLevel::None => "none",
-        RiskLevel::Low => "low",
-        RiskLevel::Medium => "medium",
-        RiskLevel::High => "high",
-    };
+    let risk_str = result.risk_level.as_str();
 
     let events_json = serde_json::to_value(&result.events)?;
     let title: Option<&str> = result.title.as_deref().and_then(|s| {
@@ -116,32 +368,75 @@ async fn process_frame(
             if t.is_empty() { None } else { Some(t) }
         })
     };
+    let rule_id = triggered_rule.and_then(|t| rules::match_rule(&p.active_rules, t)).map(|r| r.id);
 
-    // Persist to DB
+    // Persist to DB, including the prompt and raw output so the event can be
+    // debugged or re-run later.
     let event = db::insert_event(
         db,
-        event_id,
-        frame.stream_id,
-        frame.captured_at,
-        &result.description,
-        events_json,
-        risk_str,
-        triggered_rule,
-        title,
-        Some(&frame.data),
-        "unresolved",
+        &NewAnalysisEvent {
+            id: event_id,
+            stream_id: frame.stream_id,
+            captured_at: frame.captured_at,
+            description: &result.description,
+            events: events_json,
+            risk_level: risk_str,
+            triggered_rule,
+            title,
+            frame: Some(&frame.data),
+            status: "unresolved",
+            raw_response: Some(&output.raw_response),
+            system_prompt: Some(&output.system_prompt),
+            rules_snapshot: Some(serde_json::to_value(vlm_rules)?),
+            model: Some(&output.model),
+            vlm_backend: Some(output.backend),
+            latency_ms: Some(output.latency_ms),
+            prompt_tokens: output.usage.prompt_tokens,
+            completion_tokens: output.usage.completion_tokens,
+            preprocess_stats: p.preprocess_stats.as_ref().and_then(|s| serde_json::to_value(s).ok()),
+            batch_id: batch.map(|(id, _)| id),
+            batch_tile: batch.map(|(_, tile)| tile),
+            rule_check: rule_check.and_then(|c| serde_json::to_value(c).ok()),
+            rule_id,
+        },
     )
     .await?;
 
     info!(
         stream = %frame.stream_name,
         risk = %risk_str,
+        model = %output.model,
+        latency_ms = output.latency_ms,
         description = %result.description,
         "Analysis complete"
     );
 
     // Broadcast to WebSocket subscribers (ignore if no subscribers)
-    let _ = event_tx.send(event);
+    let _ = ctx.event_tx.send(event);
+
+    // Shadow mode: run the candidate model on a sample of frames in the
+    // background so it never slows down (or alerts on) the primary path.
+    // Frames finding the shadow endpoint busy are skipped.
+    let shadow = ctx.shadow.as_ref().and_then(|s| Some((s, s.sample()?)));
+    if let Some((shadow, permit)) = shadow {
+        let shadow = Arc::clone(shadow);
+        let db = db.clone();
+        let shadow_frame = ShadowFrame {
+            event_id,
+            stream_id: frame.stream_id,
+            stream_name: frame.stream_name.clone(),
+            captured_at: frame.captured_at,
+            image: p.image.to_vec(),
+            mapping: p.mapping.clone(),
+            active_rules: p.active_rules.clone(),
+            rules: vlm_rules.clone(),
+            exclusions: p.exclusions.clone(),
+        };
+        tokio::spawn(async move {
+            shadow.analyze_and_store(&db, &shadow_frame).await;
+            drop(permit);
+        });
+    }
 
     // High risk only: send Twilio SMS to global alert number (DB then env)
     if result.risk_level == RiskLevel::High {
@@ -156,3 +451,69 @@ async fn process_frame(
 
     Ok(())
 }
+
+/// Stores a frame the VLM could not analyze as an `analysis_failed` event so it
+/// is not lost, and raises a system alert if no VLM backend is reachable.
+async fn record_failed_frame(
+    frame: &CapturedFrame,
+    vlm_rules: &[VlmRule],
+    err: &AppError,
+    ctx: &WorkerContext,
+) -> anyhow::Result<()> {
+    let db = &ctx.db;
+    warn!(stream = %frame.stream_name, "Frame analysis failed: {err}");
+
+    let description = format!("Analysis failed: {err}");
+    let event = db::insert_event(
+        db,
+        &NewAnalysisEvent {
+            id: Uuid::new_v4(),
+            stream_id: frame.stream_id,
+            captured_at: frame.captured_at,
+            description: &description,
+            events: serde_json::json!([]),
+            risk_level: RiskLevel::None.as_str(),
+            triggered_rule: None,
+            title: None,
+            frame: Some(&frame.data),
+            status: "analysis_failed",
+            raw_response: None,
+            system_prompt: None,
+            rules_snapshot: Some(serde_json::to_value(vlm_rules)?),
+            model: None,
+            vlm_backend: None,
+            latency_ms: None,
+            prompt_tokens: None,
+            completion_tokens: None,
+            preprocess_stats: None,
+            batch_id: None,
+            batch_tile: None,
+            rule_check: None,
+            rule_id: None,
+        },
+    )
+    .await?;
+    let _ = ctx.event_tx.send(event);
+
+    if matches!(err, AppError::VlmUnavailable(_)) {
+        let due = {
+            let mut last = ctx.last_outage_alert.lock().unwrap();
+            let due = last.is_none_or(|t| t.elapsed() >= OUTAGE_ALERT_INTERVAL);
+            if due {
+                *last = Some(Instant::now());
+            }
+            due
+        };
+        if due {
+            error!("All VLM backends are down: {err}");
+            let db = db.clone();
+            let message = format!("all VLM backends are down, frames are not being analyzed. {err}");
+            tokio::spawn(async move {
+                let to_number = db::get_alert_phone_number(&db).await.ok().flatten();
+                crate::notifications::twilio::send_system_alert(to_number.as_deref(), &message).await;
+            });
+        }
+    }
+
+    Ok(())
+}
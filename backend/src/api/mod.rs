@@ -5,7 +5,7 @@ pub mod ws;
 use std::sync::Arc;
 
 use axum::{
-    routing::{get, post, put},
+    routing::{delete, get, post, put},
     Router,
 };
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
@@ -42,13 +42,147 @@ pub fn router(state: Arc<AppState>, stream_manager: Arc<StreamManager>) -> Route
             "/api/streams/:id/rules/:rule_id",
             put(routes::update_rule).delete(routes::delete_rule),
         )
+        .route(
+            "/api/streams/:id/rules/:rule_id/override",
+            put(routes::set_rule_override).delete(routes::delete_rule_override),
+        )
+        .route("/api/streams/:id/effective-rules", get(routes::effective_rules))
+        // False-positive feedback
+        .route("/api/streams/:id/feedback-clauses", get(routes::list_feedback_clauses))
+        .route("/api/streams/:id/feedback-clauses/refresh", post(routes::refresh_feedback_clauses))
+        .route(
+            "/api/feedback-clauses/:id",
+            put(routes::review_feedback_clause).delete(routes::delete_feedback_clause),
+        )
+        // Global and blueprint rules, inherited by streams
+        .route("/api/rules", get(routes::list_global_rules).post(routes::create_global_rule))
+        .route("/api/rules/stats", get(routes::rule_stats))
+        .route(
+            "/api/rules/:rule_id/reference-images",
+            get(routes::list_reference_images).post(routes::create_reference_image),
+        )
+        .route(
+            "/api/rules/:rule_id/reference-images/:image_id",
+            delete(routes::delete_reference_image),
+        )
+        .route(
+            "/api/rules/:rule_id/reference-images/:image_id/image",
+            get(routes::get_reference_image),
+        )
+        .route(
+            "/api/rules/:rule_id",
+            put(routes::update_global_rule).delete(routes::delete_global_rule),
+        )
+        // Rule templates
+        .route(
+            "/api/rule-templates",
+            get(routes::list_rule_templates).post(routes::create_rule_template),
+        )
+        .route("/api/rule-templates/export", get(routes::export_rule_templates))
+        .route("/api/rule-templates/import", post(routes::import_rule_templates))
+        .route(
+            "/api/rule-templates/:id",
+            get(routes::get_rule_template)
+                .put(routes::update_rule_template)
+                .delete(routes::delete_rule_template),
+        )
+        .route("/api/rule-templates/:id/apply", post(routes::apply_rule_template))
+        .route("/api/rule-templates/:id/export", get(routes::export_rule_template))
+        .route(
+            "/api/blueprints/:id/rules",
+            get(routes::list_blueprint_rules).post(routes::create_blueprint_rule),
+        )
+        .route(
+            "/api/blueprints/:id/rules/:rule_id",
+            put(routes::update_blueprint_rule).delete(routes::delete_blueprint_rule),
+        )
         // Assistant
         .route("/api/assistant/chat", post(routes::assistant_chat))
+        .route(
+            "/api/assistant/conversations",
+            get(routes::list_conversations).post(routes::create_conversation),
+        )
+        .route(
+            "/api/assistant/conversations/:id",
+            get(routes::get_conversation).delete(routes::delete_conversation),
+        )
+        .route("/api/assistant/conversations/:id/messages", post(routes::send_conversation_message))
+        .route("/api/assistant/conversations/:id/cancel", post(routes::cancel_conversation_reply))
+        .route("/api/assistant/actions", get(routes::list_assistant_actions))
+        .route("/api/assistant/actions/:id", get(routes::get_assistant_action))
+        .route("/api/assistant/actions/:id/confirm", post(routes::confirm_assistant_action))
+        .route("/api/assistant/actions/:id/reject", post(routes::reject_assistant_action))
         // Events
         .route("/api/events", get(routes::list_events))
+        .route("/api/events/export", get(routes::export_events))
+        .route("/api/events/reanalyze", post(routes::reanalyze_events))
+        .route("/api/events/reanalyze/:job_id", get(routes::get_reanalysis_job))
+        .route("/api/events/reanalyze/:job_id/results", get(routes::list_reanalysis_results))
         .route("/api/events/:id", get(routes::get_event).put(routes::update_event))
+        .route("/api/events/:id/shadow", get(routes::list_shadow_results))
+        // Shadow mode
+        .route("/api/shadow/report", get(routes::shadow_report))
+        // Evidence
+        .route("/api/evidence/public-key", get(routes::evidence_public_key))
+        .route("/api/evidence/verify", get(routes::verify_evidence_chain))
+        .route(
+            "/api/evidence/checkpoints",
+            get(routes::list_evidence_checkpoints).post(routes::create_evidence_checkpoints),
+        )
+        // Dashboard statistics
+        .route("/api/stats/events", get(routes::event_counts))
+        .route("/api/stats/resolution", get(routes::resolution_stats))
+        .route("/api/stats/heatmap", get(routes::event_heatmap))
+        // Digest reports
+        .route(
+            "/api/report-schedules",
+            get(routes::list_report_schedules).post(routes::create_report_schedule),
+        )
+        .route(
+            "/api/report-schedules/:id",
+            get(routes::get_report_schedule)
+                .put(routes::update_report_schedule)
+                .delete(routes::delete_report_schedule),
+        )
+        .route("/api/report-schedules/:id/run", post(routes::run_report_schedule))
+        .route("/api/reports", get(routes::list_reports).post(routes::create_report))
+        .route("/api/reports/:id", get(routes::get_report).delete(routes::delete_report))
+        .route("/api/reports/:id/html", get(routes::get_report_html))
+        .route("/api/reports/:id/pdf", get(routes::get_report_pdf))
+        // SMS alerts
         .route("/api/alert-phone-number", get(routes::get_alert_phone_number).put(routes::update_alert_phone_number))
         .route("/api/test-twilio", post(routes::test_twilio_alert))
+        // Evaluation datasets
+        .route(
+            "/api/eval/datasets",
+            get(routes::list_eval_datasets).post(routes::create_eval_dataset),
+        )
+        .route(
+            "/api/eval/datasets/:id",
+            get(routes::get_eval_dataset).delete(routes::delete_eval_dataset),
+        )
+        .route(
+            "/api/eval/datasets/:id/samples",
+            get(routes::list_eval_samples).post(routes::create_eval_sample),
+        )
+        .route("/api/eval/datasets/:id/samples/:sample_id", delete(routes::delete_eval_sample))
+        .route(
+            "/api/eval/datasets/:id/runs",
+            get(routes::list_eval_runs).post(routes::run_eval),
+        )
+        .route("/api/eval/runs/:run_id", get(routes::get_eval_run))
+        // VLM profiles
+        .route(
+            "/api/vlm-profiles",
+            get(routes::list_vlm_profiles).post(routes::create_vlm_profile),
+        )
+        .route(
+            "/api/vlm-profiles/:id",
+            get(routes::get_vlm_profile)
+                .put(routes::update_vlm_profile)
+                .delete(routes::delete_vlm_profile),
+        )
+        .route("/api/vlm-profiles/:id/test", post(routes::test_vlm_profile))
         // Blueprints and cameras
         .route(
             "/api/blueprints",
@@ -1,8 +1,21 @@
 use utoipa::OpenApi;
 
 use crate::storage::models::{
-    AlertSettings, AnalysisEvent, AssistantChatRequest, BlueprintResponse, BlueprintSummary,
-    CreateBlueprintRequest, CreateRuleRequest, CreateStreamRequest, Stream, StreamRule,
+    AlertSettings, AnalysisEvent, AssistantAction, AssistantChatRequest, AssistantConversation, AssistantConversationDetail,
+    AssistantMessage, AssistantEvent, CreateConversationRequest, SendMessageRequest, BlueprintResponse, BlueprintSummary,
+    CreateBlueprintRequest, CreateEvalDatasetRequest, CreateEvalSampleRequest, CreateRuleRequest,
+    CreateStreamRequest, EvalDataset, EvalReport, EvalRun, EvalSample, EventReanalysis,
+    LatencyStats, LevelMetrics, RunEvalRequest, ShadowReport, ShadowResult,
+    ReanalysisJob, ReanalysisJobStatus, RiskDiffSummary, ReanalyzeRequest, RiskTransition,
+    Stream, StreamRule, CreateVlmProfileRequest, TestVlmProfileRequest, UpdateVlmProfileRequest,
+    VlmProfile, VlmProfileTestResult, PreprocessConfig, FisheyeCorrection, Roi, PreprocessStats,
+    RuleSchedule, ScheduleWindow, ZonePoint, RuleCheck, RuleOverride, SetRuleOverrideRequest, EffectiveRule,
+    RuleTemplate, TemplateParameter, RuleTemplateDoc, UpdateRuleTemplateRequest, ApplyTemplateRequest,
+    ApplyTemplateResult, RuleStats, DailyRuleHits, FeedbackClause, ReviewFeedbackClauseRequest,
+    RuleReferenceImage, CreateReferenceImageRequest,
+    EvidencePublicKey, EvidenceCheckpoint, ChainVerification, StreamChainStatus, ChainBreak, EventCounts, EventCountBucket, EventCountTotal, ResolutionStats, HeatmapCell,
+    ReportSchedule, CreateReportScheduleRequest, UpdateReportScheduleRequest, CreateReportRequest, Report,
+    ReportSummary, ReportIncident,
     UpdateAlertSettings, UpdateBlueprintRequest, UpdateEventRequest, UpdateRuleRequest, UpdateStreamRequest,
 };
 use super::routes;
@@ -29,13 +42,93 @@ use super::routes;
         routes::snapshot,
         routes::stream_live,
         routes::assistant_chat,
+        routes::list_conversations,
+        routes::create_conversation,
+        routes::get_conversation,
+        routes::delete_conversation,
+        routes::send_conversation_message,
+        routes::cancel_conversation_reply,
+        routes::list_assistant_actions,
+        routes::get_assistant_action,
+        routes::confirm_assistant_action,
+        routes::reject_assistant_action,
         routes::list_events,
         routes::get_event,
         routes::update_event,
+        routes::list_feedback_clauses,
+        routes::refresh_feedback_clauses,
+        routes::review_feedback_clause,
+        routes::delete_feedback_clause,
+        routes::reanalyze_events,
+        routes::get_reanalysis_job,
+        routes::list_reanalysis_results,
+        routes::list_shadow_results,
+        routes::shadow_report,
+        routes::export_events,
+        routes::evidence_public_key,
+        routes::verify_evidence_chain,
+        routes::list_evidence_checkpoints,
+        routes::create_evidence_checkpoints,
+        routes::event_counts,
+        routes::resolution_stats,
+        routes::event_heatmap,
+        routes::list_report_schedules,
+        routes::get_report_schedule,
+        routes::create_report_schedule,
+        routes::update_report_schedule,
+        routes::delete_report_schedule,
+        routes::run_report_schedule,
+        routes::list_reports,
+        routes::create_report,
+        routes::get_report,
+        routes::get_report_html,
+        routes::get_report_pdf,
+        routes::delete_report,
+        routes::list_eval_datasets,
+        routes::get_eval_dataset,
+        routes::create_eval_dataset,
+        routes::delete_eval_dataset,
+        routes::list_eval_samples,
+        routes::create_eval_sample,
+        routes::delete_eval_sample,
+        routes::run_eval,
+        routes::list_eval_runs,
+        routes::get_eval_run,
+        routes::list_vlm_profiles,
+        routes::get_vlm_profile,
+        routes::create_vlm_profile,
+        routes::update_vlm_profile,
+        routes::delete_vlm_profile,
+        routes::test_vlm_profile,
         routes::list_rules,
         routes::create_rule,
         routes::update_rule,
         routes::delete_rule,
+        routes::effective_rules,
+        routes::set_rule_override,
+        routes::delete_rule_override,
+        routes::list_global_rules,
+        routes::create_global_rule,
+        routes::update_global_rule,
+        routes::delete_global_rule,
+        routes::rule_stats,
+        routes::list_reference_images,
+        routes::create_reference_image,
+        routes::get_reference_image,
+        routes::delete_reference_image,
+        routes::list_blueprint_rules,
+        routes::create_blueprint_rule,
+        routes::update_blueprint_rule,
+        routes::delete_blueprint_rule,
+        routes::list_rule_templates,
+        routes::get_rule_template,
+        routes::create_rule_template,
+        routes::update_rule_template,
+        routes::delete_rule_template,
+        routes::apply_rule_template,
+        routes::export_rule_templates,
+        routes::export_rule_template,
+        routes::import_rule_templates,
         routes::list_blueprints,
         routes::get_blueprint,
         routes::create_blueprint,
@@ -50,11 +143,73 @@ use super::routes;
             Stream,
             CreateStreamRequest,
             UpdateStreamRequest,
+            PreprocessConfig,
+            FisheyeCorrection,
+            Roi,
+            PreprocessStats,
             AnalysisEvent,
             UpdateEventRequest,
+            ReanalyzeRequest,
+            ReanalysisJob,
+            ReanalysisJobStatus,
+            RiskDiffSummary,
+            RiskTransition,
+            EventReanalysis,
+            ShadowResult,
+            ShadowReport,
+            EvidencePublicKey,
+            EvidenceCheckpoint,
+            ChainVerification,
+            StreamChainStatus,
+            ChainBreak,
+            EventCounts,
+            EventCountBucket,
+            EventCountTotal,
+            ResolutionStats,
+            HeatmapCell,
+            ReportSchedule,
+            CreateReportScheduleRequest,
+            UpdateReportScheduleRequest,
+            CreateReportRequest,
+            Report,
+            ReportSummary,
+            ReportIncident,
+            EvalDataset,
+            CreateEvalDatasetRequest,
+            EvalSample,
+            CreateEvalSampleRequest,
+            EvalRun,
+            RunEvalRequest,
+            EvalReport,
+            LevelMetrics,
+            LatencyStats,
+            VlmProfile,
+            CreateVlmProfileRequest,
+            UpdateVlmProfileRequest,
+            TestVlmProfileRequest,
+            VlmProfileTestResult,
             StreamRule,
+            RuleOverride,
+            SetRuleOverrideRequest,
+            EffectiveRule,
+            RuleReferenceImage,
+            CreateReferenceImageRequest,
+            RuleTemplate,
+            TemplateParameter,
+            RuleTemplateDoc,
+            UpdateRuleTemplateRequest,
+            ApplyTemplateRequest,
+            ApplyTemplateResult,
+            RuleStats,
+            DailyRuleHits,
+            FeedbackClause,
+            ReviewFeedbackClauseRequest,
             CreateRuleRequest,
             UpdateRuleRequest,
+            RuleSchedule,
+            ScheduleWindow,
+            ZonePoint,
+            RuleCheck,
             BlueprintSummary,
             BlueprintResponse,
             CreateBlueprintRequest,
@@ -62,14 +217,29 @@ use super::routes;
             AlertSettings,
             UpdateAlertSettings,
             AssistantChatRequest,
+            AssistantConversation,
+            AssistantConversationDetail,
+            AssistantMessage,
+            CreateConversationRequest,
+            SendMessageRequest,
+            AssistantEvent,
+            AssistantAction,
         )
     ),
     tags(
-        (name = "assistant", description = "AI assistant for app questions"),
+        (name = "assistant", description = "AI assistant for app questions, with stored conversations and streamed replies"),
         (name = "health",  description = "Service health check"),
         (name = "streams", description = "Video stream management"),
         (name = "events",  description = "Analysis event retrieval"),
-        (name = "rules",   description = "Per-stream VLM threat assessment rules"),
+        (name = "shadow",  description = "Shadow-mode comparison of a candidate VLM"),
+        (name = "evidence", description = "Tamper-evident event chain, signed checkpoints and evidence exports"),
+        (name = "stats",   description = "Event counts, time to resolve and heatmaps for dashboards"),
+        (name = "reports", description = "Scheduled digest reports rendered to HTML and PDF"),
+        (name = "eval",    description = "Labeled datasets for evaluating models and rules"),
+        (name = "vlm-profiles", description = "Named VLM configurations assignable per stream"),
+        (name = "rules",   description = "VLM threat assessment rules: global, per blueprint and per stream"),
+        (name = "feedback", description = "Do-not-flag prompt clauses learned from false positives, with review"),
+        (name = "rule-templates", description = "Reusable rule sets with parameters; apply, import and export"),
         (name = "blueprints", description = "Blueprints (floor plan images)"),
         (name = "alert-phone", description = "Alert phone number (SMS when high risk)"),
         (name = "notifications", description = "Alert / notification testing"),
@@ -2,9 +2,12 @@ use std::sync::Arc;
 
 use axum::{
     body::Body,
-    extract::{Path, Query, State},
-    http::{header, StatusCode},
-    response::{IntoResponse, Response},
+    extract::{Path, Query, RawQuery, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{
+        sse::{Event as SseEvent, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
     Json,
 };
 use bytes::Bytes;
@@ -16,14 +19,28 @@ use base64::{engine::general_purpose::STANDARD as B64, Engine};
 
 
 use crate::{
+    analysis::{
+        eval, event_stats, feedback, preprocess, reanalysis, references, rule_stats, rules, shadow, templates,
+        vlm::{registry, RiskLevel, VlmRule},
+    },
+    assistant,
     error::{AppError, Result},
+    evidence::{chain, export},
+    reports::{self, schedule as report_schedule},
     state::AppState,
     storage::{
         db,
         models::{
-            AlertSettings, AssistantChatRequest, BlueprintResponse, CreateBlueprintRequest,
-            CreateRuleRequest, CreateStreamRequest, EventQuery, StreamQuery,
+            AlertSettings, ApplyTemplateRequest, AssistantActionQuery, AssistantChatRequest, AssistantConversationDetail,
+            CreateConversationRequest, SendMessageRequest, BlueprintResponse, ChainQuery, CheckpointQuery,
+            CreateBlueprintRequest, CreateReportRequest, CreateReportScheduleRequest, ReportQuery,
+            UpdateReportScheduleRequest,
+            CreateEvalDatasetRequest, CreateEvalSampleRequest, CreateReferenceImageRequest, CreateRuleRequest,
+            CreateStreamRequest, CreateVlmProfileRequest, EventCountsQuery, EventQuery, ExportQuery, FeedbackClauseQuery, ReviewFeedbackClauseRequest, ReanalysisJobStatus, ReanalyzeRequest,
+            RuleScope, RuleStatsQuery, RuleTemplateDoc, RunEvalRequest, SetRuleOverrideRequest, ShadowReportQuery, StatsQuery, StreamQuery, StreamRule,
+            TemplateFormatQuery, TestVlmProfileRequest, UpdateRuleTemplateRequest,
             UpdateAlertSettings, UpdateBlueprintRequest, UpdateEventRequest, UpdateRuleRequest, UpdateStreamRequest,
+            UpdateVlmProfileRequest, VlmProfileTestResult,
         },
     },
     streams::manager::{StreamManager, StreamRecord},
@@ -99,6 +116,12 @@ pub async fn create_stream(
     if let Some(bid) = req.blueprint_id {
         let _ = db::get_blueprint(&state.db, bid).await?;
     }
+    if let Some(pid) = req.vlm_profile_id {
+        let _ = db::get_vlm_profile(&state.db, pid).await?;
+    }
+    if let Some(cfg) = &req.preprocessing {
+        preprocess::validate(cfg)?;
+    }
     let stream = db::create_stream(&state.db, &req).await?;
 
     // Start capture task if enabled
@@ -137,6 +160,12 @@ pub async fn update_stream(
     if let Some(Some(bid)) = req.blueprint_id {
         let _ = db::get_blueprint(&state.db, bid).await?;
     }
+    if let Some(Some(pid)) = req.vlm_profile_id {
+        let _ = db::get_vlm_profile(&state.db, pid).await?;
+    }
+    if let Some(Some(cfg)) = &req.preprocessing {
+        preprocess::validate(cfg)?;
+    }
     let stream = db::update_stream(&state.db, id, &req).await?;
 
     // Restart capture task to apply new settings
@@ -292,15 +321,24 @@ pub async fn stream_live(
     tag = "events",
     params(EventQuery),
     responses(
-        (status = 200, description = "List of analysis events", body = Vec<AnalysisEvent>)
+        (status = 200, description = "List of analysis events, newest first", body = Vec<AnalysisEvent>,
+            headers(("x-next-cursor" = String, description = "Pass as `cursor` for the next page; absent on the last page"))),
+        (status = 400, description = "Invalid cursor, or both cursor and offset given")
     )
 )]
 pub async fn list_events(
     State(state): State<Arc<AppState>>,
     Query(query): Query<EventQuery>,
 ) -> Result<impl IntoResponse> {
-    let events = db::list_events(&state.db, &query).await?;
-    Ok(Json(events))
+    if query.cursor.is_some() && query.offset != 0 {
+        return Err(AppError::BadRequest("use either cursor or offset, not both".into()));
+    }
+    let (events, next_cursor) = db::list_events_page(&state.db, &query).await?;
+    let mut headers = HeaderMap::new();
+    if let Some(cursor) = next_cursor.and_then(|c| HeaderValue::from_str(&c).ok()) {
+        headers.insert("x-next-cursor", cursor);
+    }
+    Ok((headers, Json(events)))
 }
 
 #[utoipa::path(
@@ -329,324 +367,2039 @@ pub async fn get_event(
     request_body = UpdateEventRequest,
     responses(
         (status = 200, description = "Event updated", body = AnalysisEvent),
+        (status = 400, description = "Nothing to update, or unknown disposition"),
         (status = 404, description = "Event not found")
     )
 )]
+/// Marking an event a false positive resolves it and refreshes the stream's
+/// pending feedback clauses.
 pub async fn update_event(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
     Json(req): Json<UpdateEventRequest>,
 ) -> Result<impl IntoResponse> {
-    let event = db::update_event_status(&state.db, id, &req.status).await?;
+    let mut event = None;
+    if let Some(disposition) = &req.disposition {
+        if let Some(d) = disposition.as_deref().filter(|d| !feedback::DISPOSITIONS.contains(d)) {
+            return Err(AppError::BadRequest(format!(
+                "unknown disposition '{d}', expected one of: {}",
+                feedback::DISPOSITIONS.join(", ")
+            )));
+        }
+        let reason = req.disposition_reason.as_deref().map(str::trim).filter(|r| !r.is_empty());
+        event = Some(db::set_event_disposition(&state.db, id, disposition.as_deref(), reason).await?);
+    }
+    let status = req.status.as_deref().or(matches!(req.disposition, Some(Some(_))).then_some("resolved"));
+    if let Some(status) = status {
+        event = Some(db::update_event_status(&state.db, id, status).await?);
+    }
+    let Some(event) = event else {
+        return Err(AppError::BadRequest("give a status and/or a disposition".into()));
+    };
+
+    if req.disposition.is_some() {
+        feedback::refresh(&state.db, event.stream_id).await?;
+    }
     Ok(Json(event))
 }
 
-// ─── Assistant ───────────────────────────────────────────────────────────────
+// ─── Evidence export ──────────────────────────────────────────────────────────
 
 #[utoipa::path(
-    post,
-    path = "/api/assistant/chat",
-    tag = "assistant",
-    request_body = AssistantChatRequest,
+    get,
+    path = "/api/events/export",
+    tag = "evidence",
+    params(EventQuery, ExportQuery),
     responses(
-        (status = 200, description = "AI response", body = serde_json::Value,
-         example = json!({"response": "Today there were 3 high-risk events..."}))
+        (status = 200, description = "Matching events, newest first, as a streamed CSV, JSON Lines or ZIP download; \
+            limit, offset and cursor are ignored", content_type = "application/octet-stream"),
+        (status = 400, description = "Unknown format or invalid filters")
     )
 )]
-pub async fn assistant_chat(
+pub async fn export_events(
     State(state): State<Arc<AppState>>,
-    Json(req): Json<AssistantChatRequest>,
-) -> Result<impl IntoResponse> {
-    let response = crate::assistant::chat(&state.db, &req.message).await?;
-    Ok(Json(serde_json::json!({ "response": response })))
+    Query(query): Query<EventQuery>,
+    Query(export): Query<ExportQuery>,
+    RawQuery(raw_query): RawQuery,
+) -> Result<Response> {
+    let format = export::Format::parse(export.format.as_deref())?;
+    let body =
+        export::export(state.db.clone(), Arc::clone(&state.signer), query, format, raw_query.unwrap_or_default())
+            .await?;
+    let filename = format!("events-{}.{}", chrono::Utc::now().format("%Y%m%dT%H%M%SZ"), format.extension());
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{filename}\"")),
+        ],
+        body,
+    )
+        .into_response())
 }
 
-// ─── Alert phone number (SMS when high risk) ───────────────────────────────────
-
 #[utoipa::path(
     get,
-    path = "/api/alert-phone-number",
-    tag = "alert-phone",
+    path = "/api/evidence/public-key",
+    tag = "evidence",
     responses(
-        (status = 200, description = "Current alert phone number", body = AlertSettings)
+        (status = 200, description = "Key that export signatures verify against", body = EvidencePublicKey)
     )
 )]
-/// Get the global alert phone number (used for high-risk SMS). Null if not set.
-pub async fn get_alert_phone_number(State(state): State<Arc<AppState>>) -> Result<Json<AlertSettings>> {
-    let alert_phone_number = db::get_alert_phone_number(&state.db).await?;
-    Ok(Json(AlertSettings { alert_phone_number }))
+pub async fn evidence_public_key(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(state.signer.public_key())
 }
 
 #[utoipa::path(
-    put,
-    path = "/api/alert-phone-number",
-    tag = "alert-phone",
-    request_body = UpdateAlertSettings,
+    get,
+    path = "/api/evidence/verify",
+    tag = "evidence",
+    params(ChainQuery),
     responses(
-        (status = 204, description = "Alert phone number updated")
+        (status = 200, description = "Every break found in the evidence chains; `ok` is true when there are none",
+         body = ChainVerification),
+        (status = 404, description = "Stream not found")
     )
 )]
-/// Set the global alert phone number (used for high-risk SMS). Pass null to clear.
-pub async fn update_alert_phone_number(
+/// Recomputes the hash of every stored frame and event, so it can take a
+/// while on a large database.
+pub async fn verify_evidence_chain(
     State(state): State<Arc<AppState>>,
-    Json(req): Json<UpdateAlertSettings>,
-) -> Result<StatusCode> {
-    db::set_alert_phone_number(&state.db, req.alert_phone_number.as_deref()).await?;
-    Ok(StatusCode::NO_CONTENT)
+    Query(q): Query<ChainQuery>,
+) -> Result<impl IntoResponse> {
+    Ok(Json(chain::verify(&state.db, &state.signer, q.stream_id).await?))
 }
 
-// ─── Test Twilio alert (for development) ─────────────────────────────────────
-
 #[utoipa::path(
-    post,
-    path = "/api/test-twilio",
-    tag = "notifications",
+    get,
+    path = "/api/evidence/checkpoints",
+    tag = "evidence",
+    params(CheckpointQuery),
     responses(
-        (status = 200, description = "Test SMS triggered",
-         body = serde_json::Value,
-         example = json!({"message": "Test alert triggered. Check ALERT_PHONE_NUMBER for SMS (and backend logs if none)."}))
+        (status = 200, description = "Signed chain checkpoints, newest first", body = Vec<EvidenceCheckpoint>)
     )
 )]
-/// Sends one test SMS via Twilio. Use to verify Twilio env vars and ALERT_PHONE_NUMBER.
-pub async fn test_twilio_alert() -> impl IntoResponse {
-    tokio::spawn(crate::notifications::twilio::send_alert(
-        None,
-        "test-stream",
-        "high",
-        "This is a test alert from Cipher-Shield.",
-    ));
-    Json(serde_json::json!({
-        "message": "Test alert triggered. Check ALERT_PHONE_NUMBER for SMS (and backend logs if none)."
-    }))
+pub async fn list_evidence_checkpoints(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<CheckpointQuery>,
+) -> Result<impl IntoResponse> {
+    Ok(Json(db::list_checkpoints(&state.db, q.stream_id, q.limit).await?))
 }
 
-// ─── Blueprints ───────────────────────────────────────────────────────────────
-
 #[utoipa::path(
-    get,
-    path = "/api/blueprints",
-    tag = "blueprints",
+    post,
+    path = "/api/evidence/checkpoints",
+    tag = "evidence",
     responses(
-        (status = 200, description = "List of blueprints (no image data)", body = Vec<BlueprintSummary>)
+        (status = 201, description = "Checkpoints signed now, one per stream whose chain moved since its last one",
+         body = Vec<EvidenceCheckpoint>)
     )
 )]
-pub async fn list_blueprints(
-    State(state): State<Arc<AppState>>,
-) -> Result<impl IntoResponse> {
-    let list = db::list_blueprints(&state.db).await?;
-    Ok(Json(list))
+pub async fn create_evidence_checkpoints(State(state): State<Arc<AppState>>) -> Result<impl IntoResponse> {
+    let created = chain::checkpoint(&state.db, &state.signer).await?;
+    Ok((StatusCode::CREATED, Json(created)))
 }
 
+// ─── False-positive feedback ──────────────────────────────────────────────────
+
 #[utoipa::path(
     get,
-    path = "/api/blueprints/{id}",
-    tag = "blueprints",
-    params(("id" = Uuid, Path, description = "Blueprint ID")),
+    path = "/api/streams/{id}/feedback-clauses",
+    tag = "feedback",
+    params(("id" = Uuid, Path, description = "Stream ID"), FeedbackClauseQuery),
     responses(
-        (status = 200, description = "Blueprint with image as base64", body = BlueprintResponse),
-        (status = 404, description = "Blueprint not found")
+        (status = 200, description = "The stream's feedback clauses; approved ones are in its prompt", body = Vec<FeedbackClause>)
     )
 )]
-pub async fn get_blueprint(
+pub async fn list_feedback_clauses(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
+    Query(q): Query<FeedbackClauseQuery>,
 ) -> Result<impl IntoResponse> {
-    let bp = db::get_blueprint(&state.db, id).await?;
-    let image_base64 = bp
-        .image_data
-        .as_ref()
-        .map(|b| B64.encode(b));
-    let resp = BlueprintResponse {
-        id: bp.id,
-        name: bp.name,
-        image_base64,
-        created_at: bp.created_at,
-        updated_at: bp.updated_at,
-    };
-    Ok(Json(resp))
+    Ok(Json(db::list_feedback_clauses(&state.db, id, q.status.as_deref()).await?))
 }
 
 #[utoipa::path(
     post,
-    path = "/api/blueprints",
-    tag = "blueprints",
-    request_body = CreateBlueprintRequest,
+    path = "/api/streams/{id}/feedback-clauses/refresh",
+    tag = "feedback",
+    params(("id" = Uuid, Path, description = "Stream ID")),
     responses(
-        (status = 201, description = "Blueprint created", body = BlueprintResponse),
-        (status = 400, description = "Invalid request (e.g. invalid base64)")
+        (status = 200, description = "Pending clauses, rewritten from recent unreviewed false positives", body = Vec<FeedbackClause>),
+        (status = 404, description = "Stream not found")
     )
 )]
-pub async fn create_blueprint(
+pub async fn refresh_feedback_clauses(
     State(state): State<Arc<AppState>>,
-    Json(req): Json<CreateBlueprintRequest>,
+    Path(id): Path<Uuid>,
 ) -> Result<impl IntoResponse> {
-    let name = req.name.unwrap_or_else(|| "Blueprint".into());
-    let image_data = match &req.image_base64 {
-        Some(s) if !s.is_empty() => {
-            let bytes = B64.decode(s.as_bytes())
-                .map_err(|_| AppError::BadRequest("invalid image_base64".into()))?;
-            Some(bytes)
-        }
-        _ => None,
-    };
-    let bp = db::create_blueprint(
-        &state.db,
-        &name,
-        image_data.as_deref(),
-    ).await?;
-    let image_base64 = bp.image_data.as_ref().map(|b| B64.encode(b));
-    let resp = BlueprintResponse {
-        id: bp.id,
-        name: bp.name,
-        image_base64,
-        created_at: bp.created_at,
-        updated_at: bp.updated_at,
-    };
-    Ok((StatusCode::CREATED, Json(resp)))
+    db::get_stream(&state.db, id).await?;
+    Ok(Json(feedback::refresh(&state.db, id).await?))
 }
 
 #[utoipa::path(
     put,
-    path = "/api/blueprints/{id}",
-    tag = "blueprints",
-    params(("id" = Uuid, Path, description = "Blueprint ID")),
-    request_body = UpdateBlueprintRequest,
+    path = "/api/feedback-clauses/{id}",
+    tag = "feedback",
+    params(("id" = Uuid, Path, description = "Clause ID")),
+    request_body = ReviewFeedbackClauseRequest,
     responses(
-        (status = 200, description = "Blueprint updated", body = BlueprintResponse),
-        (status = 400, description = "Invalid request"),
-        (status = 404, description = "Blueprint not found")
+        (status = 200, description = "Clause reviewed", body = FeedbackClause),
+        (status = 400, description = "Invalid status or empty clause"),
+        (status = 404, description = "Clause not found")
     )
 )]
-pub async fn update_blueprint(
+/// Approved clauses are added to the stream's prompt from its next frame on.
+pub async fn review_feedback_clause(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-    Json(req): Json<UpdateBlueprintRequest>,
+    Json(req): Json<ReviewFeedbackClauseRequest>,
 ) -> Result<impl IntoResponse> {
-    let image_data = match &req.image_base64 {
-        Some(s) if !s.is_empty() => {
-            let bytes = B64.decode(s.as_bytes())
-                .map_err(|_| AppError::BadRequest("invalid image_base64".into()))?;
-            Some(bytes)
-        }
-        Some(_) => None,
-        None => None,
-    };
-    let bp = db::update_blueprint(
-        &state.db,
-        id,
-        req.name.as_deref(),
-        image_data.as_ref().map(|v| v.as_slice()),
-    ).await?;
-    let image_base64 = bp.image_data.as_ref().map(|b| B64.encode(b));
-    let resp = BlueprintResponse {
-        id: bp.id,
-        name: bp.name,
-        image_base64,
-        created_at: bp.created_at,
-        updated_at: bp.updated_at,
-    };
-    Ok(Json(resp))
+    if !["approved", "rejected"].contains(&req.status.as_str()) {
+        return Err(AppError::BadRequest("status must be \"approved\" or \"rejected\"".into()));
+    }
+    let clause = req.clause.as_deref().map(str::trim);
+    if clause == Some("") {
+        return Err(AppError::BadRequest("clause must not be empty".into()));
+    }
+    Ok(Json(db::review_feedback_clause(&state.db, id, &req.status, clause).await?))
 }
 
 #[utoipa::path(
     delete,
-    path = "/api/blueprints/{id}",
-    tag = "blueprints",
-    params(("id" = Uuid, Path, description = "Blueprint ID")),
+    path = "/api/feedback-clauses/{id}",
+    tag = "feedback",
+    params(("id" = Uuid, Path, description = "Clause ID")),
     responses(
-        (status = 204, description = "Blueprint deleted"),
-        (status = 404, description = "Blueprint not found")
+        (status = 204, description = "Clause deleted; its events may be summarized again"),
+        (status = 404, description = "Clause not found")
     )
 )]
-pub async fn delete_blueprint(
+pub async fn delete_feedback_clause(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
 ) -> Result<impl IntoResponse> {
-    db::delete_blueprint(&state.db, id).await?;
+    db::delete_feedback_clause(&state.db, id).await?;
     Ok(StatusCode::NO_CONTENT)
 }
 
-// ─── Stream Rules ─────────────────────────────────────────────────────────────
+// ─── Re-analysis ──────────────────────────────────────────────────────────────
+
+#[utoipa::path(
+    post,
+    path = "/api/events/reanalyze",
+    tag = "events",
+    request_body = ReanalyzeRequest,
+    responses(
+        (status = 202, description = "Re-analysis job started", body = ReanalysisJob)
+    )
+)]
+/// Re-run stored frames matching the filter against the current rules (or another
+/// model) in the background. Results are stored next to the originals; nothing is alerted.
+pub async fn reanalyze_events(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ReanalyzeRequest>,
+) -> Result<impl IntoResponse> {
+    let job = reanalysis::start_job(&state.db, Arc::clone(&state.vlm), &req).await?;
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
 
 #[utoipa::path(
     get,
-    path = "/api/streams/{id}/rules",
-    tag = "rules",
-    params(("id" = Uuid, Path, description = "Stream ID")),
+    path = "/api/events/reanalyze/{job_id}",
+    tag = "events",
+    params(("job_id" = Uuid, Path, description = "Re-analysis job ID")),
     responses(
-        (status = 200, description = "Rules for the stream", body = Vec<StreamRule>)
+        (status = 200, description = "Job progress and risk-level diff", body = ReanalysisJobStatus),
+        (status = 404, description = "Job not found")
     )
 )]
-pub async fn list_rules(
+pub async fn get_reanalysis_job(
     State(state): State<Arc<AppState>>,
-    Path(id): Path<Uuid>,
+    Path(job_id): Path<Uuid>,
 ) -> Result<impl IntoResponse> {
-    let rules = db::list_rules(&state.db, id).await?;
-    Ok(Json(rules))
+    let job = db::get_reanalysis_job(&state.db, job_id).await?;
+    let summary = reanalysis::summarize(&state.db, job_id).await?;
+    Ok(Json(ReanalysisJobStatus { job, summary }))
 }
 
 #[utoipa::path(
-    post,
-    path = "/api/streams/{id}/rules",
-    tag = "rules",
-    params(("id" = Uuid, Path, description = "Stream ID")),
-    request_body = CreateRuleRequest,
+    get,
+    path = "/api/events/reanalyze/{job_id}/results",
+    tag = "events",
+    params(("job_id" = Uuid, Path, description = "Re-analysis job ID")),
     responses(
-        (status = 201, description = "Rule created", body = StreamRule),
-        (status = 404, description = "Stream not found")
+        (status = 200, description = "New results, each linked to its original event", body = Vec<EventReanalysis>),
+        (status = 404, description = "Job not found")
     )
 )]
-pub async fn create_rule(
+pub async fn list_reanalysis_results(
+    State(state): State<Arc<AppState>>,
+    Path(job_id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    db::get_reanalysis_job(&state.db, job_id).await?;
+    let results = db::list_event_reanalyses(&state.db, job_id).await?;
+    Ok(Json(results))
+}
+
+// ─── Shadow mode ──────────────────────────────────────────────────────────────
+
+#[utoipa::path(
+    get,
+    path = "/api/events/{id}/shadow",
+    tag = "shadow",
+    params(("id" = Uuid, Path, description = "Event ID")),
+    responses(
+        (status = 200, description = "Shadow model results for the event", body = Vec<ShadowResult>),
+        (status = 404, description = "Event not found")
+    )
+)]
+pub async fn list_shadow_results(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-    Json(req): Json<CreateRuleRequest>,
 ) -> Result<impl IntoResponse> {
-    // Ensure the stream exists first.
-    db::get_stream(&state.db, id).await?;
-    let rule = db::create_rule(&state.db, id, &req).await?;
-    Ok((StatusCode::CREATED, Json(rule)))
+    db::get_event(&state.db, id).await?;
+    let results = db::list_shadow_results(&state.db, id).await?;
+    Ok(Json(results))
 }
 
 #[utoipa::path(
-    put,
-    path = "/api/streams/{id}/rules/{rule_id}",
-    tag = "rules",
-    params(
-        ("id" = Uuid, Path, description = "Stream ID"),
-        ("rule_id" = Uuid, Path, description = "Rule ID"),
-    ),
-    request_body = UpdateRuleRequest,
+    get,
+    path = "/api/shadow/report",
+    tag = "shadow",
+    params(ShadowReportQuery),
     responses(
-        (status = 200, description = "Rule updated", body = StreamRule),
-        (status = 404, description = "Rule not found")
+        (status = 200, description = "Agreement between the primary and shadow models", body = ShadowReport)
     )
 )]
-pub async fn update_rule(
+pub async fn shadow_report(
     State(state): State<Arc<AppState>>,
-    Path((id, rule_id)): Path<(Uuid, Uuid)>,
-    Json(req): Json<UpdateRuleRequest>,
+    Query(query): Query<ShadowReportQuery>,
 ) -> Result<impl IntoResponse> {
-    let rule = db::update_rule(&state.db, rule_id, id, &req).await?;
-    Ok(Json(rule))
+    let report = shadow::report(&state.db, &query).await?;
+    Ok(Json(report))
 }
 
+// ─── Statistics ───────────────────────────────────────────────────────────────
+
 #[utoipa::path(
-    delete,
-    path = "/api/streams/{id}/rules/{rule_id}",
-    tag = "rules",
-    params(
-        ("id" = Uuid, Path, description = "Stream ID"),
-        ("rule_id" = Uuid, Path, description = "Rule ID"),
-    ),
+    get,
+    path = "/api/stats/events",
+    tag = "stats",
+    params(EventCountsQuery),
     responses(
-        (status = 204, description = "Rule deleted"),
-        (status = 404, description = "Rule not found")
+        (status = 200, description = "Event counts per time bucket and group, with totals per group", body = EventCounts),
+        (status = 400, description = "Unknown bucket or group_by, or period too long for the bucket"),
+        (status = 404, description = "Stream or blueprint not found")
     )
 )]
-pub async fn delete_rule(
+pub async fn event_counts(
     State(state): State<Arc<AppState>>,
-    Path((id, rule_id)): Path<(Uuid, Uuid)>,
+    Query(q): Query<EventCountsQuery>,
+) -> Result<impl IntoResponse> {
+    Ok(Json(event_stats::counts(&state.db, &q).await?))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/stats/resolution",
+    tag = "stats",
+    params(StatsQuery),
+    responses(
+        (status = 200, description = "Time to resolve per risk level; the row without a risk level covers all events",
+         body = Vec<ResolutionStats>),
+        (status = 404, description = "Stream or blueprint not found")
+    )
+)]
+pub async fn resolution_stats(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<StatsQuery>,
+) -> Result<impl IntoResponse> {
+    Ok(Json(event_stats::resolution(&state.db, &q).await?))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/stats/heatmap",
+    tag = "stats",
+    params(StatsQuery),
+    responses(
+        (status = 200, description = "Event counts for each of the 168 hours of the week", body = Vec<HeatmapCell>),
+        (status = 404, description = "Stream or blueprint not found")
+    )
+)]
+pub async fn event_heatmap(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<StatsQuery>,
+) -> Result<impl IntoResponse> {
+    Ok(Json(event_stats::heatmap(&state.db, &q).await?))
+}
+
+// ─── Reports ──────────────────────────────────────────────────────────────────
+
+#[utoipa::path(
+    get,
+    path = "/api/report-schedules",
+    tag = "reports",
+    responses(
+        (status = 200, description = "List of report schedules", body = Vec<ReportSchedule>)
+    )
+)]
+pub async fn list_report_schedules(State(state): State<Arc<AppState>>) -> Result<impl IntoResponse> {
+    Ok(Json(db::list_report_schedules(&state.db).await?))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/report-schedules/{id}",
+    tag = "reports",
+    params(("id" = Uuid, Path, description = "Schedule ID")),
+    responses(
+        (status = 200, description = "Schedule found", body = ReportSchedule),
+        (status = 404, description = "Schedule not found")
+    )
+)]
+pub async fn get_report_schedule(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    Ok(Json(db::get_report_schedule(&state.db, id).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/report-schedules",
+    tag = "reports",
+    request_body = CreateReportScheduleRequest,
+    responses(
+        (status = 201, description = "Schedule created", body = ReportSchedule),
+        (status = 400, description = "Invalid cron pattern, offset or period"),
+        (status = 404, description = "Stream or blueprint not found")
+    )
+)]
+pub async fn create_report_schedule(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<CreateReportScheduleRequest>,
+) -> Result<impl IntoResponse> {
+    let schedule = report_schedule::create(&state.db, &req).await?;
+    Ok((StatusCode::CREATED, Json(schedule)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/report-schedules/{id}",
+    tag = "reports",
+    params(("id" = Uuid, Path, description = "Schedule ID")),
+    request_body = UpdateReportScheduleRequest,
+    responses(
+        (status = 200, description = "Schedule updated; its next run is recomputed", body = ReportSchedule),
+        (status = 400, description = "Invalid cron pattern, offset or period"),
+        (status = 404, description = "Schedule, stream or blueprint not found")
+    )
+)]
+pub async fn update_report_schedule(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<UpdateReportScheduleRequest>,
+) -> Result<impl IntoResponse> {
+    Ok(Json(report_schedule::update(&state.db, id, &req).await?))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/report-schedules/{id}",
+    tag = "reports",
+    params(("id" = Uuid, Path, description = "Schedule ID")),
+    responses(
+        (status = 204, description = "Schedule deleted; its reports are kept"),
+        (status = 404, description = "Schedule not found")
+    )
+)]
+pub async fn delete_report_schedule(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    db::delete_report_schedule(&state.db, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/report-schedules/{id}/run",
+    tag = "reports",
+    params(("id" = Uuid, Path, description = "Schedule ID")),
+    responses(
+        (status = 201, description = "Report generated for the period ending now, and texted if the schedule \
+            asks for it", body = Report),
+        (status = 404, description = "Schedule not found")
+    )
+)]
+/// Runs a schedule now, e.g. to preview it. Its next scheduled run is unchanged.
+pub async fn run_report_schedule(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    let schedule = db::get_report_schedule(&state.db, id).await?;
+    let report = report_schedule::run(&state.db, state.chat.as_ref(), &schedule, &state.reports).await?;
+    Ok((StatusCode::CREATED, Json(report)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/reports",
+    tag = "reports",
+    params(ReportQuery),
+    responses(
+        (status = 200, description = "Generated reports, newest first", body = Vec<Report>)
+    )
+)]
+pub async fn list_reports(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<ReportQuery>,
+) -> Result<impl IntoResponse> {
+    Ok(Json(db::list_reports(&state.db, &q).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/reports",
+    tag = "reports",
+    request_body = CreateReportRequest,
+    responses(
+        (status = 201, description = "Report generated and stored", body = Report),
+        (status = 400, description = "Invalid period or offset"),
+        (status = 404, description = "Stream or blueprint not found")
+    )
+)]
+pub async fn create_report(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<CreateReportRequest>,
+) -> Result<impl IntoResponse> {
+    let report = reports::generate_now(&state.db, state.chat.as_ref(), &req).await?;
+    Ok((StatusCode::CREATED, Json(report)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/reports/{id}",
+    tag = "reports",
+    params(("id" = Uuid, Path, description = "Report ID")),
+    responses(
+        (status = 200, description = "Report found", body = Report),
+        (status = 404, description = "Report not found")
+    )
+)]
+pub async fn get_report(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    Ok(Json(db::get_report(&state.db, id).await?))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/reports/{id}/html",
+    tag = "reports",
+    params(("id" = Uuid, Path, description = "Report ID")),
+    responses(
+        (status = 200, description = "Self-contained HTML page", content_type = "text/html"),
+        (status = 404, description = "Report not found")
+    )
+)]
+pub async fn get_report_html(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    let html = db::get_report_html(&state.db, id).await?;
+    Ok(axum::response::Html(html))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/reports/{id}/pdf",
+    tag = "reports",
+    params(("id" = Uuid, Path, description = "Report ID")),
+    responses(
+        (status = 200, description = "PDF download", content_type = "application/pdf"),
+        (status = 404, description = "Report not found")
+    )
+)]
+pub async fn get_report_pdf(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    let pdf = db::get_report_pdf(&state.db, id).await?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/pdf".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"report-{id}.pdf\"")),
+        ],
+        pdf,
+    ))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/reports/{id}",
+    tag = "reports",
+    params(("id" = Uuid, Path, description = "Report ID")),
+    responses(
+        (status = 204, description = "Report deleted"),
+        (status = 404, description = "Report not found")
+    )
+)]
+pub async fn delete_report(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    db::delete_report(&state.db, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// ─── Evaluation datasets ──────────────────────────────────────────────────────
+
+#[utoipa::path(
+    get,
+    path = "/api/eval/datasets",
+    tag = "eval",
+    responses(
+        (status = 200, description = "List of evaluation datasets", body = Vec<EvalDataset>)
+    )
+)]
+pub async fn list_eval_datasets(State(state): State<Arc<AppState>>) -> Result<impl IntoResponse> {
+    let list = db::list_eval_datasets(&state.db).await?;
+    Ok(Json(list))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/eval/datasets/{id}",
+    tag = "eval",
+    params(("id" = Uuid, Path, description = "Dataset ID")),
+    responses(
+        (status = 200, description = "Dataset found", body = EvalDataset),
+        (status = 404, description = "Dataset not found")
+    )
+)]
+pub async fn get_eval_dataset(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    let dataset = db::get_eval_dataset(&state.db, id).await?;
+    Ok(Json(dataset))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/eval/datasets",
+    tag = "eval",
+    request_body = CreateEvalDatasetRequest,
+    responses(
+        (status = 201, description = "Dataset created", body = EvalDataset),
+        (status = 400, description = "Invalid rules")
+    )
+)]
+pub async fn create_eval_dataset(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<CreateEvalDatasetRequest>,
+) -> Result<impl IntoResponse> {
+    if let Some(rules) = req.rules.as_ref().filter(|v| !v.is_null()) {
+        serde_json::from_value::<Vec<VlmRule>>(rules.clone())
+            .map_err(|e| AppError::BadRequest(format!("invalid rules: {e}")))?;
+    }
+    let dataset = db::create_eval_dataset(
+        &state.db,
+        &req.name,
+        req.description.as_deref(),
+        req.rules.as_ref(),
+    )
+    .await?;
+    Ok((StatusCode::CREATED, Json(dataset)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/eval/datasets/{id}",
+    tag = "eval",
+    params(("id" = Uuid, Path, description = "Dataset ID")),
+    responses(
+        (status = 204, description = "Dataset deleted"),
+        (status = 404, description = "Dataset not found")
+    )
+)]
+pub async fn delete_eval_dataset(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    db::delete_eval_dataset(&state.db, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/eval/datasets/{id}/samples",
+    tag = "eval",
+    params(("id" = Uuid, Path, description = "Dataset ID")),
+    responses(
+        (status = 200, description = "Labeled samples (without image data)", body = Vec<EvalSample>)
+    )
+)]
+pub async fn list_eval_samples(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    let samples = db::list_eval_samples(&state.db, id).await?;
+    Ok(Json(samples))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/eval/datasets/{id}/samples",
+    tag = "eval",
+    params(("id" = Uuid, Path, description = "Dataset ID")),
+    request_body = CreateEvalSampleRequest,
+    responses(
+        (status = 201, description = "Sample added", body = EvalSample),
+        (status = 400, description = "Missing image or invalid label"),
+        (status = 404, description = "Dataset or event not found")
+    )
+)]
+/// Add a labeled frame, either uploaded as base64 or copied from a stored event.
+pub async fn create_eval_sample(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<CreateEvalSampleRequest>,
+) -> Result<impl IntoResponse> {
+    db::get_eval_dataset(&state.db, id).await?;
+    req.expected_risk_level
+        .parse::<RiskLevel>()
+        .map_err(AppError::BadRequest)?;
+
+    let (image, stream_id) = match (&req.image_base64, req.event_id) {
+        (Some(s), _) if !s.is_empty() => {
+            let bytes = B64.decode(s.as_bytes())
+                .map_err(|_| AppError::BadRequest("invalid image_base64".into()))?;
+            (bytes, req.stream_id)
+        }
+        (_, Some(event_id)) => {
+            let event = db::get_event(&state.db, event_id).await?;
+            let frame = event
+                .frame
+                .ok_or_else(|| AppError::BadRequest(format!("Event {event_id} has no stored frame")))?;
+            (frame, req.stream_id.or(Some(event.stream_id)))
+        }
+        _ => return Err(AppError::BadRequest("image_base64 or event_id is required".into())),
+    };
+    if let Some(sid) = stream_id {
+        db::get_stream(&state.db, sid).await?;
+    }
+
+    let sample = db::create_eval_sample(
+        &state.db,
+        id,
+        stream_id,
+        req.event_id,
+        &image,
+        &req.expected_risk_level,
+        req.expected_triggered_rule.as_deref().map(str::trim).filter(|s| !s.is_empty()),
+    )
+    .await?;
+    Ok((StatusCode::CREATED, Json(sample)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/eval/datasets/{id}/samples/{sample_id}",
+    tag = "eval",
+    params(
+        ("id" = Uuid, Path, description = "Dataset ID"),
+        ("sample_id" = Uuid, Path, description = "Sample ID"),
+    ),
+    responses(
+        (status = 204, description = "Sample deleted"),
+        (status = 404, description = "Sample not found")
+    )
+)]
+pub async fn delete_eval_sample(
+    State(state): State<Arc<AppState>>,
+    Path((id, sample_id)): Path<(Uuid, Uuid)>,
+) -> Result<impl IntoResponse> {
+    db::delete_eval_sample(&state.db, sample_id, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/eval/datasets/{id}/runs",
+    tag = "eval",
+    params(("id" = Uuid, Path, description = "Dataset ID")),
+    request_body = RunEvalRequest,
+    responses(
+        (status = 202, description = "Evaluation started", body = EvalRun),
+        (status = 404, description = "Dataset or VLM profile not found")
+    )
+)]
+/// Run the dataset through the configured VLM, a VLM profile or another model in the background.
+pub async fn run_eval(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<RunEvalRequest>,
+) -> Result<impl IntoResponse> {
+    let cfg = eval::run_backend(&state.db, &state.vlm, req.profile_id, req.model.as_deref()).await?;
+    let run = eval::start_run(&state.db, Arc::clone(&state.vlm), cfg, id).await?;
+    Ok((StatusCode::ACCEPTED, Json(run)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/eval/datasets/{id}/runs",
+    tag = "eval",
+    params(("id" = Uuid, Path, description = "Dataset ID")),
+    responses(
+        (status = 200, description = "Runs for the dataset, newest first", body = Vec<EvalRun>)
+    )
+)]
+pub async fn list_eval_runs(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    let runs = db::list_eval_runs(&state.db, id).await?;
+    Ok(Json(runs))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/eval/runs/{run_id}",
+    tag = "eval",
+    params(("run_id" = Uuid, Path, description = "Evaluation run ID")),
+    responses(
+        (status = 200, description = "Run status; `report` is an EvalReport once completed", body = EvalRun),
+        (status = 404, description = "Run not found")
+    )
+)]
+pub async fn get_eval_run(
+    State(state): State<Arc<AppState>>,
+    Path(run_id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    let run = db::get_eval_run(&state.db, run_id).await?;
+    Ok(Json(run))
+}
+
+// ─── Assistant ───────────────────────────────────────────────────────────────
+
+#[utoipa::path(
+    post,
+    path = "/api/assistant/chat",
+    tag = "assistant",
+    request_body = AssistantChatRequest,
+    responses(
+        (status = 200, description = "AI response", body = serde_json::Value,
+         example = json!({"response": "Today there were 3 high-risk events..."})),
+        (status = 404, description = "Conversation not found"),
+        (status = 409, description = "The conversation is already answering a question")
+    )
+)]
+pub async fn assistant_chat(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Extension(manager): axum::extract::Extension<Arc<StreamManager>>,
+    Json(req): Json<AssistantChatRequest>,
+) -> Result<impl IntoResponse> {
+    let response = assistant::chat(&state, &manager, req.conversation_id, &req.message).await?;
+    Ok(Json(serde_json::json!({ "response": response })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/assistant/conversations",
+    tag = "assistant",
+    responses(
+        (status = 200, description = "Conversations, most recently active first", body = Vec<AssistantConversation>)
+    )
+)]
+pub async fn list_conversations(State(state): State<Arc<AppState>>) -> Result<impl IntoResponse> {
+    Ok(Json(db::list_conversations(&state.db).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/assistant/conversations",
+    tag = "assistant",
+    request_body = CreateConversationRequest,
+    responses(
+        (status = 201, description = "Conversation created", body = AssistantConversation)
+    )
+)]
+pub async fn create_conversation(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<CreateConversationRequest>,
+) -> Result<impl IntoResponse> {
+    let title = req.title.as_deref().map(str::trim).unwrap_or_default();
+    let conversation = db::create_conversation(&state.db, title).await?;
+    Ok((StatusCode::CREATED, Json(conversation)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/assistant/conversations/{id}",
+    tag = "assistant",
+    params(("id" = Uuid, Path, description = "Conversation ID")),
+    responses(
+        (status = 200, description = "Conversation with its messages", body = AssistantConversationDetail),
+        (status = 404, description = "Conversation not found")
+    )
+)]
+pub async fn get_conversation(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    let conversation = db::get_conversation(&state.db, id).await?;
+    let messages = db::conversation_messages(&state.db, id, i64::MAX).await?;
+    Ok(Json(AssistantConversationDetail {
+        conversation,
+        running: state.assistant_runs.is_running(id),
+        messages,
+    }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/assistant/conversations/{id}",
+    tag = "assistant",
+    params(("id" = Uuid, Path, description = "Conversation ID")),
+    responses(
+        (status = 204, description = "Conversation and its messages deleted; a running reply is cancelled"),
+        (status = 404, description = "Conversation not found")
+    )
+)]
+pub async fn delete_conversation(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    state.assistant_runs.cancel(id);
+    db::delete_conversation(&state.db, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/assistant/conversations/{id}/messages",
+    tag = "assistant",
+    params(("id" = Uuid, Path, description = "Conversation ID")),
+    request_body = SendMessageRequest,
+    responses(
+        (status = 200, description = "Server-sent events, each an `AssistantEvent` as JSON: tokens, tool calls and \
+            results, then `done`, `cancelled` or `error`", content_type = "text/event-stream", body = AssistantEvent),
+        (status = 404, description = "Conversation not found"),
+        (status = 409, description = "The conversation is already answering a question")
+    )
+)]
+/// Ask a question in a conversation and stream the reply. Closing the
+/// connection cancels the reply.
+pub async fn send_conversation_message(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Extension(manager): axum::extract::Extension<Arc<StreamManager>>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<SendMessageRequest>,
+) -> Result<impl IntoResponse> {
+    let events = assistant::stream(state, manager, id, req.message).await?;
+    let events = events.map(|event| SseEvent::default().json_data(&event));
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/assistant/conversations/{id}/cancel",
+    tag = "assistant",
+    params(("id" = Uuid, Path, description = "Conversation ID")),
+    responses(
+        (status = 204, description = "Running reply cancelled; the stream ends with a `cancelled` event"),
+        (status = 404, description = "No reply is running in the conversation")
+    )
+)]
+pub async fn cancel_conversation_reply(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    if !state.assistant_runs.cancel(id) {
+        return Err(AppError::NotFound(format!("No reply running in conversation {id}")));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/assistant/actions",
+    tag = "assistant",
+    params(AssistantActionQuery),
+    responses(
+        (status = 200, description = "Actions the assistant proposed, newest first (at most 200)", body = Vec<AssistantAction>)
+    )
+)]
+pub async fn list_assistant_actions(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<AssistantActionQuery>,
+) -> Result<impl IntoResponse> {
+    Ok(Json(db::list_assistant_actions(&state.db, &q).await?))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/assistant/actions/{id}",
+    tag = "assistant",
+    params(("id" = Uuid, Path, description = "Action ID (confirmation token)")),
+    responses(
+        (status = 200, description = "Proposed action", body = AssistantAction),
+        (status = 404, description = "Action not found")
+    )
+)]
+pub async fn get_assistant_action(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    Ok(Json(db::get_assistant_action(&state.db, id).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/assistant/actions/{id}/confirm",
+    tag = "assistant",
+    params(("id" = Uuid, Path, description = "Action ID (confirmation token)")),
+    responses(
+        (status = 200, description = "Action executed; `status` is \"executed\" or \"failed\" with the outcome in `result`", body = AssistantAction),
+        (status = 404, description = "Action not found"),
+        (status = 409, description = "Action already confirmed or rejected, or expired")
+    )
+)]
+/// Carry out an action the assistant proposed.
+pub async fn confirm_assistant_action(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Extension(manager): axum::extract::Extension<Arc<StreamManager>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    Ok(Json(assistant::actions::confirm(&state, &manager, id).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/assistant/actions/{id}/reject",
+    tag = "assistant",
+    params(("id" = Uuid, Path, description = "Action ID (confirmation token)")),
+    responses(
+        (status = 200, description = "Action rejected; nothing was changed", body = AssistantAction),
+        (status = 404, description = "Action not found"),
+        (status = 409, description = "Action already confirmed or rejected, or expired")
+    )
+)]
+pub async fn reject_assistant_action(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    Ok(Json(assistant::actions::reject(&state, id).await?))
+}
+
+// ─── VLM profiles ─────────────────────────────────────────────────────────────
+
+#[utoipa::path(
+    get,
+    path = "/api/vlm-profiles",
+    tag = "vlm-profiles",
+    responses(
+        (status = 200, description = "List of VLM profiles", body = Vec<VlmProfile>)
+    )
+)]
+pub async fn list_vlm_profiles(State(state): State<Arc<AppState>>) -> Result<impl IntoResponse> {
+    let list = db::list_vlm_profiles(&state.db).await?;
+    Ok(Json(list))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/vlm-profiles/{id}",
+    tag = "vlm-profiles",
+    params(("id" = Uuid, Path, description = "Profile ID")),
+    responses(
+        (status = 200, description = "Profile found", body = VlmProfile),
+        (status = 404, description = "Profile not found")
+    )
+)]
+pub async fn get_vlm_profile(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    let profile = db::get_vlm_profile(&state.db, id).await?;
+    Ok(Json(profile))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/vlm-profiles",
+    tag = "vlm-profiles",
+    request_body = CreateVlmProfileRequest,
+    responses(
+        (status = 201, description = "Profile created", body = VlmProfile),
+        (status = 400, description = "Unknown backend, invalid limits or name taken")
+    )
+)]
+pub async fn create_vlm_profile(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<CreateVlmProfileRequest>,
+) -> Result<impl IntoResponse> {
+    registry::validate_profile_fields(
+        Some(&req.backend),
+        [req.connect_timeout_sec, req.request_timeout_sec, req.max_tokens],
+    )?;
+    let profile = db::create_vlm_profile(&state.db, &req).await?;
+    Ok((StatusCode::CREATED, Json(profile)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/vlm-profiles/{id}",
+    tag = "vlm-profiles",
+    params(("id" = Uuid, Path, description = "Profile ID")),
+    request_body = UpdateVlmProfileRequest,
+    responses(
+        (status = 200, description = "Profile updated", body = VlmProfile),
+        (status = 400, description = "Unknown backend, invalid limits or name taken"),
+        (status = 404, description = "Profile not found")
+    )
+)]
+/// Streams using the profile pick up the change on their next frame.
+pub async fn update_vlm_profile(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<UpdateVlmProfileRequest>,
+) -> Result<impl IntoResponse> {
+    registry::validate_profile_fields(
+        req.backend.as_deref(),
+        [req.connect_timeout_sec, req.request_timeout_sec, req.max_tokens],
+    )?;
+    let profile = db::update_vlm_profile(&state.db, id, &req).await?;
+    Ok(Json(profile))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/vlm-profiles/{id}",
+    tag = "vlm-profiles",
+    params(("id" = Uuid, Path, description = "Profile ID")),
+    responses(
+        (status = 204, description = "Profile deleted; its streams fall back to the global VLM"),
+        (status = 404, description = "Profile not found")
+    )
+)]
+pub async fn delete_vlm_profile(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    db::delete_vlm_profile(&state.db, id).await?;
+    state.vlm.evict(id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/vlm-profiles/{id}/test",
+    tag = "vlm-profiles",
+    params(("id" = Uuid, Path, description = "Profile ID")),
+    request_body = TestVlmProfileRequest,
+    responses(
+        (status = 200, description = "The profile's answer for the stream's latest frame", body = VlmProfileTestResult),
+        (status = 404, description = "Profile or stream not found, or no frame captured yet"),
+        (status = 502, description = "VLM call failed")
+    )
+)]
+/// Run a profile against a stream's latest snapshot and rules. Nothing is stored or alerted.
+pub async fn test_vlm_profile(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<TestVlmProfileRequest>,
+) -> Result<impl IntoResponse> {
+    let profile = db::get_vlm_profile(&state.db, id).await?;
+    let stream = db::get_stream(&state.db, req.stream_id).await?;
+    let frame = state
+        .frame_store
+        .get_latest(stream.id)
+        .await
+        .ok_or_else(|| AppError::NotFound("No frame captured yet".into()))?;
+    let active_rules = rules::active_at(rules::effective_rules(&state.db, stream.id).await?, chrono::Utc::now());
+    let exclusions = db::approved_feedback_clauses(&state.db, stream.id).await?;
+
+    let (image, _, mapping) = preprocess::prepare_frame(&state.db, stream.id, &frame).await?;
+    let rules = references::vlm_rules(&state.db, &active_rules, &mapping).await?;
+    let vlm = state.vlm.for_profile(&profile)?;
+    let output = vlm.analyze(&image, &stream.name, &rules, &exclusions).await?;
+    let mut result = output.result;
+    mapping.boxes_to_frame(&mut result.events);
+
+    Ok(Json(VlmProfileTestResult {
+        profile_id: profile.id,
+        stream_id: stream.id,
+        model: output.model,
+        backend: output.backend.to_string(),
+        latency_ms: output.latency_ms,
+        prompt_tokens: output.usage.prompt_tokens,
+        completion_tokens: output.usage.completion_tokens,
+        risk_level: result.risk_level.as_str().to_string(),
+        triggered_rule: if rules.is_empty() { None } else { result.triggered_rule },
+        title: result.title,
+        description: result.description,
+        events: serde_json::to_value(&result.events).unwrap_or_default(),
+        raw_response: output.raw_response,
+    }))
+}
+
+// ─── Alert phone number (SMS when high risk) ───────────────────────────────────
+
+#[utoipa::path(
+    get,
+    path = "/api/alert-phone-number",
+    tag = "alert-phone",
+    responses(
+        (status = 200, description = "Current alert phone number", body = AlertSettings)
+    )
+)]
+/// Get the global alert phone number (used for high-risk SMS). Null if not set.
+pub async fn get_alert_phone_number(State(state): State<Arc<AppState>>) -> Result<Json<AlertSettings>> {
+    let alert_phone_number = db::get_alert_phone_number(&state.db).await?;
+    Ok(Json(AlertSettings { alert_phone_number }))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/alert-phone-number",
+    tag = "alert-phone",
+    request_body = UpdateAlertSettings,
+    responses(
+        (status = 204, description = "Alert phone number updated")
+    )
+)]
+/// Set the global alert phone number (used for high-risk SMS). Pass null to clear.
+pub async fn update_alert_phone_number(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<UpdateAlertSettings>,
+) -> Result<StatusCode> {
+    db::set_alert_phone_number(&state.db, req.alert_phone_number.as_deref()).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// ─── Test Twilio alert (for development) ─────────────────────────────────────
+
+#[utoipa::path(
+    post,
+    path = "/api/test-twilio",
+    tag = "notifications",
+    responses(
+        (status = 200, description = "Test SMS triggered",
+         body = serde_json::Value,
+         example = json!({"message": "Test alert triggered. Check ALERT_PHONE_NUMBER for SMS (and backend logs if none)."}))
+    )
+)]
+/// Sends one test SMS via Twilio. Use to verify Twilio env vars and ALERT_PHONE_NUMBER.
+pub async fn test_twilio_alert() -> impl IntoResponse {
+    tokio::spawn(crate::notifications::twilio::send_alert(
+        None,
+        "test-stream",
+        "high",
+        "This is a test alert from Cipher-Shield.",
+    ));
+    Json(serde_json::json!({
+        "message": "Test alert triggered. Check ALERT_PHONE_NUMBER for SMS (and backend logs if none)."
+    }))
+}
+
+// ─── Rule Templates ───────────────────────────────────────────────────────────
+
+#[utoipa::path(
+    get,
+    path = "/api/rule-templates",
+    tag = "rule-templates",
+    responses(
+        (status = 200, description = "All rule templates", body = Vec<RuleTemplate>)
+    )
+)]
+pub async fn list_rule_templates(State(state): State<Arc<AppState>>) -> Result<impl IntoResponse> {
+    Ok(Json(db::list_rule_templates(&state.db).await?))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/rule-templates/{id}",
+    tag = "rule-templates",
+    params(("id" = Uuid, Path, description = "Template ID")),
+    responses(
+        (status = 200, description = "Rule template", body = RuleTemplate),
+        (status = 404, description = "Template not found")
+    )
+)]
+pub async fn get_rule_template(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    Ok(Json(db::get_rule_template(&state.db, id).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/rule-templates",
+    tag = "rule-templates",
+    request_body = RuleTemplateDoc,
+    responses(
+        (status = 201, description = "Template created", body = RuleTemplate),
+        (status = 400, description = "Invalid template or name taken")
+    )
+)]
+pub async fn create_rule_template(
+    State(state): State<Arc<AppState>>,
+    Json(doc): Json<RuleTemplateDoc>,
+) -> Result<impl IntoResponse> {
+    templates::validate(&doc)?;
+    let template = db::create_rule_template(&state.db, &doc).await?;
+    Ok((StatusCode::CREATED, Json(template)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/rule-templates/{id}",
+    tag = "rule-templates",
+    params(("id" = Uuid, Path, description = "Template ID")),
+    request_body = UpdateRuleTemplateRequest,
+    responses(
+        (status = 200, description = "Template updated", body = RuleTemplate),
+        (status = 400, description = "Invalid template or name taken"),
+        (status = 404, description = "Template not found")
+    )
+)]
+/// Rules already created from the template are not changed.
+pub async fn update_rule_template(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<UpdateRuleTemplateRequest>,
+) -> Result<impl IntoResponse> {
+    let current = templates::to_doc(&db::get_rule_template(&state.db, id).await?)?;
+    let doc = RuleTemplateDoc {
+        name: req.name.unwrap_or(current.name),
+        description: req.description.unwrap_or(current.description),
+        parameters: req.parameters.unwrap_or(current.parameters),
+        rules: req.rules.unwrap_or(current.rules),
+    };
+    templates::validate(&doc)?;
+    Ok(Json(db::update_rule_template(&state.db, id, &doc).await?))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/rule-templates/{id}",
+    tag = "rule-templates",
+    params(("id" = Uuid, Path, description = "Template ID")),
+    responses(
+        (status = 204, description = "Template deleted"),
+        (status = 404, description = "Template not found")
+    )
+)]
+pub async fn delete_rule_template(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    db::delete_rule_template(&state.db, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/rule-templates/{id}/apply",
+    tag = "rule-templates",
+    params(("id" = Uuid, Path, description = "Template ID")),
+    request_body = ApplyTemplateRequest,
+    responses(
+        (status = 200, description = "Rules added to the streams and/or blueprint", body = ApplyTemplateResult),
+        (status = 400, description = "No target, unknown or missing parameter, or a rule is invalid once filled in"),
+        (status = 404, description = "Template, stream or blueprint not found")
+    )
+)]
+/// Nothing is added if any parameter is missing or any rule is invalid.
+pub async fn apply_rule_template(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<ApplyTemplateRequest>,
+) -> Result<impl IntoResponse> {
+    let template = db::get_rule_template(&state.db, id).await?;
+    Ok(Json(templates::apply(&state.db, &template, &req).await?))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/rule-templates/export",
+    tag = "rule-templates",
+    params(TemplateFormatQuery),
+    responses(
+        (status = 200, description = "All templates as a JSON or YAML list, importable as is", body = Vec<RuleTemplateDoc>)
+    )
+)]
+pub async fn export_rule_templates(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<TemplateFormatQuery>,
+) -> Result<Response> {
+    let docs = db::list_rule_templates(&state.db)
+        .await?
+        .iter()
+        .map(templates::to_doc)
+        .collect::<Result<Vec<_>>>()?;
+    template_file(&docs, q.format.as_deref(), "rule-templates")
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/rule-templates/{id}/export",
+    tag = "rule-templates",
+    params(("id" = Uuid, Path, description = "Template ID"), TemplateFormatQuery),
+    responses(
+        (status = 200, description = "The template as a one-item JSON or YAML list", body = Vec<RuleTemplateDoc>),
+        (status = 404, description = "Template not found")
+    )
+)]
+pub async fn export_rule_template(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(q): Query<TemplateFormatQuery>,
+) -> Result<Response> {
+    let doc = templates::to_doc(&db::get_rule_template(&state.db, id).await?)?;
+    template_file(&[doc], q.format.as_deref(), "rule-template")
+}
+
+fn template_file(docs: &[RuleTemplateDoc], format: Option<&str>, file_stem: &str) -> Result<Response> {
+    let format = templates::Format::detect(format, None)?;
+    let ext = if format == templates::Format::Yaml { "yaml" } else { "json" };
+    let body = templates::serialize(docs, format)?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{file_stem}.{ext}\"")),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/rule-templates/import",
+    tag = "rule-templates",
+    params(TemplateFormatQuery),
+    request_body(
+        content = Vec<RuleTemplateDoc>,
+        description = "One template or a list, as JSON or YAML (Content-Type application/yaml or ?format=yaml)"
+    ),
+    responses(
+        (status = 200, description = "Imported templates", body = Vec<RuleTemplate>),
+        (status = 400, description = "Unparseable file or invalid template; nothing is imported")
+    )
+)]
+/// Templates are matched by name: existing ones are replaced, others created.
+pub async fn import_rule_templates(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<TemplateFormatQuery>,
+    headers: HeaderMap,
+    body: String,
+) -> Result<impl IntoResponse> {
+    let content_type = headers.get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok());
+    let docs = templates::parse(&body, templates::Format::detect(q.format.as_deref(), content_type)?)?;
+    for doc in &docs {
+        templates::validate(doc)?;
+    }
+    let mut imported = Vec::with_capacity(docs.len());
+    for doc in &docs {
+        imported.push(db::upsert_rule_template(&state.db, doc).await?);
+    }
+    Ok(Json(imported))
+}
+
+// ─── Blueprints ───────────────────────────────────────────────────────────────
+
+#[utoipa::path(
+    get,
+    path = "/api/blueprints",
+    tag = "blueprints",
+    responses(
+        (status = 200, description = "List of blueprints (no image data)", body = Vec<BlueprintSummary>)
+    )
+)]
+pub async fn list_blueprints(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse> {
+    let list = db::list_blueprints(&state.db).await?;
+    Ok(Json(list))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/blueprints/{id}",
+    tag = "blueprints",
+    params(("id" = Uuid, Path, description = "Blueprint ID")),
+    responses(
+        (status = 200, description = "Blueprint with image as base64", body = BlueprintResponse),
+        (status = 404, description = "Blueprint not found")
+    )
+)]
+pub async fn get_blueprint(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    let bp = db::get_blueprint(&state.db, id).await?;
+    let image_base64 = bp
+        .image_data
+        .as_ref()
+        .map(|b| B64.encode(b));
+    let resp = BlueprintResponse {
+        id: bp.id,
+        name: bp.name,
+        image_base64,
+        created_at: bp.created_at,
+        updated_at: bp.updated_at,
+    };
+    Ok(Json(resp))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/blueprints",
+    tag = "blueprints",
+    request_body = CreateBlueprintRequest,
+    responses(
+        (status = 201, description = "Blueprint created", body = BlueprintResponse),
+        (status = 400, description = "Invalid request (e.g. invalid base64)")
+    )
+)]
+pub async fn create_blueprint(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<CreateBlueprintRequest>,
+) -> Result<impl IntoResponse> {
+    let name = req.name.unwrap_or_else(|| "Blueprint".into());
+    let image_data = match &req.image_base64 {
+        Some(s) if !s.is_empty() => {
+            let bytes = B64.decode(s.as_bytes())
+                .map_err(|_| AppError::BadRequest("invalid image_base64".into()))?;
+            Some(bytes)
+        }
+        _ => None,
+    };
+    let bp = db::create_blueprint(
+        &state.db,
+        &name,
+        image_data.as_deref(),
+    ).await?;
+    let image_base64 = bp.image_data.as_ref().map(|b| B64.encode(b));
+    let resp = BlueprintResponse {
+        id: bp.id,
+        name: bp.name,
+        image_base64,
+        created_at: bp.created_at,
+        updated_at: bp.updated_at,
+    };
+    Ok((StatusCode::CREATED, Json(resp)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/blueprints/{id}",
+    tag = "blueprints",
+    params(("id" = Uuid, Path, description = "Blueprint ID")),
+    request_body = UpdateBlueprintRequest,
+    responses(
+        (status = 200, description = "Blueprint updated", body = BlueprintResponse),
+        (status = 400, description = "Invalid request"),
+        (status = 404, description = "Blueprint not found")
+    )
+)]
+pub async fn update_blueprint(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<UpdateBlueprintRequest>,
+) -> Result<impl IntoResponse> {
+    let image_data = match &req.image_base64 {
+        Some(s) if !s.is_empty() => {
+            let bytes = B64.decode(s.as_bytes())
+                .map_err(|_| AppError::BadRequest("invalid image_base64".into()))?;
+            Some(bytes)
+        }
+        Some(_) => None,
+        None => None,
+    };
+    let bp = db::update_blueprint(
+        &state.db,
+        id,
+        req.name.as_deref(),
+        image_data.as_deref(),
+    ).await?;
+    let image_base64 = bp.image_data.as_ref().map(|b| B64.encode(b));
+    let resp = BlueprintResponse {
+        id: bp.id,
+        name: bp.name,
+        image_base64,
+        created_at: bp.created_at,
+        updated_at: bp.updated_at,
+    };
+    Ok(Json(resp))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/blueprints/{id}",
+    tag = "blueprints",
+    params(("id" = Uuid, Path, description = "Blueprint ID")),
+    responses(
+        (status = 204, description = "Blueprint deleted"),
+        (status = 404, description = "Blueprint not found")
+    )
+)]
+pub async fn delete_blueprint(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    db::delete_blueprint(&state.db, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// ─── Stream Rules ─────────────────────────────────────────────────────────────
+
+#[utoipa::path(
+    get,
+    path = "/api/streams/{id}/rules",
+    tag = "rules",
+    params(("id" = Uuid, Path, description = "Stream ID")),
+    responses(
+        (status = 200, description = "The stream's own rules (see effective-rules for inherited ones)", body = Vec<StreamRule>)
+    )
+)]
+pub async fn list_rules(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    let rules = db::list_rules(&state.db, RuleScope::Stream(id)).await?;
+    Ok(Json(rules))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/streams/{id}/rules",
+    tag = "rules",
+    params(("id" = Uuid, Path, description = "Stream ID")),
+    request_body = CreateRuleRequest,
+    responses(
+        (status = 201, description = "Rule created", body = StreamRule),
+        (status = 404, description = "Stream not found")
+    )
+)]
+pub async fn create_rule(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<CreateRuleRequest>,
+) -> Result<impl IntoResponse> {
+    // Ensure the stream exists first.
+    db::get_stream(&state.db, id).await?;
+    create_scoped_rule(&state, RuleScope::Stream(id), req).await
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/streams/{id}/rules/{rule_id}",
+    tag = "rules",
+    params(
+        ("id" = Uuid, Path, description = "Stream ID"),
+        ("rule_id" = Uuid, Path, description = "Rule ID"),
+    ),
+    request_body = UpdateRuleRequest,
+    responses(
+        (status = 200, description = "Rule updated", body = StreamRule),
+        (status = 404, description = "Rule not found")
+    )
+)]
+pub async fn update_rule(
+    State(state): State<Arc<AppState>>,
+    Path((id, rule_id)): Path<(Uuid, Uuid)>,
+    Json(req): Json<UpdateRuleRequest>,
+) -> Result<impl IntoResponse> {
+    update_scoped_rule(&state, rule_id, RuleScope::Stream(id), req).await
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/streams/{id}/rules/{rule_id}",
+    tag = "rules",
+    params(
+        ("id" = Uuid, Path, description = "Stream ID"),
+        ("rule_id" = Uuid, Path, description = "Rule ID"),
+    ),
+    responses(
+        (status = 204, description = "Rule deleted"),
+        (status = 404, description = "Rule not found")
+    )
+)]
+pub async fn delete_rule(
+    State(state): State<Arc<AppState>>,
+    Path((id, rule_id)): Path<(Uuid, Uuid)>,
+) -> Result<impl IntoResponse> {
+    db::delete_rule(&state.db, rule_id, RuleScope::Stream(id)).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/streams/{id}/effective-rules",
+    tag = "rules",
+    params(("id" = Uuid, Path, description = "Stream ID")),
+    responses(
+        (status = 200, description = "Global, blueprint and stream rules as they apply to the stream", body = Vec<EffectiveRule>),
+        (status = 404, description = "Stream not found")
+    )
+)]
+pub async fn effective_rules(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    db::get_stream(&state.db, id).await?;
+    Ok(Json(rules::effective(&state.db, id).await?))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/streams/{id}/rules/{rule_id}/override",
+    tag = "rules",
+    params(
+        ("id" = Uuid, Path, description = "Stream ID"),
+        ("rule_id" = Uuid, Path, description = "ID of an inherited (global or blueprint) rule"),
+    ),
+    request_body = SetRuleOverrideRequest,
+    responses(
+        (status = 200, description = "Override saved", body = RuleOverride),
+        (status = 400, description = "Invalid threat level, or the rule is not inherited by the stream"),
+        (status = 404, description = "Stream or rule not found")
+    )
+)]
+pub async fn set_rule_override(
+    State(state): State<Arc<AppState>>,
+    Path((id, rule_id)): Path<(Uuid, Uuid)>,
+    Json(req): Json<SetRuleOverrideRequest>,
+) -> Result<impl IntoResponse> {
+    db::get_stream(&state.db, id).await?;
+    if let Some(level) = &req.threat_level {
+        level.parse::<RiskLevel>().map_err(AppError::BadRequest)?;
+    }
+    let inherited = db::list_rules_for_stream(&state.db, id).await?;
+    match inherited.iter().find(|r| r.id == rule_id) {
+        Some(r) if r.scope == "stream" => {
+            return Err(AppError::BadRequest("the stream's own rules are edited directly, not overridden".into()));
+        }
+        Some(_) => {}
+        None => return Err(AppError::NotFound(format!("Rule {rule_id} does not apply to stream {id}"))),
+    }
+    let o = db::set_rule_override(&state.db, id, rule_id, &req).await?;
+    Ok(Json(o))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/streams/{id}/rules/{rule_id}/override",
+    tag = "rules",
+    params(
+        ("id" = Uuid, Path, description = "Stream ID"),
+        ("rule_id" = Uuid, Path, description = "Rule ID"),
+    ),
+    responses(
+        (status = 204, description = "Override removed; the inherited rule applies as defined"),
+        (status = 404, description = "No such override")
+    )
+)]
+pub async fn delete_rule_override(
+    State(state): State<Arc<AppState>>,
+    Path((id, rule_id)): Path<(Uuid, Uuid)>,
+) -> Result<impl IntoResponse> {
+    db::delete_rule_override(&state.db, id, rule_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// ─── Global and Blueprint Rules ───────────────────────────────────────────────
+
+#[utoipa::path(
+    get,
+    path = "/api/rules",
+    tag = "rules",
+    responses(
+        (status = 200, description = "Rules inherited by every stream", body = Vec<StreamRule>)
+    )
+)]
+pub async fn list_global_rules(State(state): State<Arc<AppState>>) -> Result<impl IntoResponse> {
+    Ok(Json(db::list_rules(&state.db, RuleScope::Global).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/rules",
+    tag = "rules",
+    request_body = CreateRuleRequest,
+    responses(
+        (status = 201, description = "Rule created", body = StreamRule),
+        (status = 400, description = "Invalid rule")
+    )
+)]
+pub async fn create_global_rule(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<CreateRuleRequest>,
+) -> Result<impl IntoResponse> {
+    create_scoped_rule(&state, RuleScope::Global, req).await
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/rules/{rule_id}",
+    tag = "rules",
+    params(("rule_id" = Uuid, Path, description = "Rule ID")),
+    request_body = UpdateRuleRequest,
+    responses(
+        (status = 200, description = "Rule updated", body = StreamRule),
+        (status = 404, description = "Rule not found")
+    )
+)]
+pub async fn update_global_rule(
+    State(state): State<Arc<AppState>>,
+    Path(rule_id): Path<Uuid>,
+    Json(req): Json<UpdateRuleRequest>,
+) -> Result<impl IntoResponse> {
+    update_scoped_rule(&state, rule_id, RuleScope::Global, req).await
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/rules/{rule_id}",
+    tag = "rules",
+    params(("rule_id" = Uuid, Path, description = "Rule ID")),
+    responses(
+        (status = 204, description = "Rule deleted"),
+        (status = 404, description = "Rule not found")
+    )
+)]
+pub async fn delete_global_rule(
+    State(state): State<Arc<AppState>>,
+    Path(rule_id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    db::delete_rule(&state.db, rule_id, RuleScope::Global).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/blueprints/{id}/rules",
+    tag = "rules",
+    params(("id" = Uuid, Path, description = "Blueprint ID")),
+    responses(
+        (status = 200, description = "Rules inherited by the streams on the blueprint", body = Vec<StreamRule>)
+    )
+)]
+pub async fn list_blueprint_rules(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    Ok(Json(db::list_rules(&state.db, RuleScope::Blueprint(id)).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/blueprints/{id}/rules",
+    tag = "rules",
+    params(("id" = Uuid, Path, description = "Blueprint ID")),
+    request_body = CreateRuleRequest,
+    responses(
+        (status = 201, description = "Rule created", body = StreamRule),
+        (status = 404, description = "Blueprint not found")
+    )
+)]
+pub async fn create_blueprint_rule(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<CreateRuleRequest>,
+) -> Result<impl IntoResponse> {
+    db::get_blueprint(&state.db, id).await?;
+    create_scoped_rule(&state, RuleScope::Blueprint(id), req).await
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/blueprints/{id}/rules/{rule_id}",
+    tag = "rules",
+    params(
+        ("id" = Uuid, Path, description = "Blueprint ID"),
+        ("rule_id" = Uuid, Path, description = "Rule ID"),
+    ),
+    request_body = UpdateRuleRequest,
+    responses(
+        (status = 200, description = "Rule updated", body = StreamRule),
+        (status = 404, description = "Rule not found")
+    )
+)]
+pub async fn update_blueprint_rule(
+    State(state): State<Arc<AppState>>,
+    Path((id, rule_id)): Path<(Uuid, Uuid)>,
+    Json(req): Json<UpdateRuleRequest>,
+) -> Result<impl IntoResponse> {
+    update_scoped_rule(&state, rule_id, RuleScope::Blueprint(id), req).await
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/blueprints/{id}/rules/{rule_id}",
+    tag = "rules",
+    params(
+        ("id" = Uuid, Path, description = "Blueprint ID"),
+        ("rule_id" = Uuid, Path, description = "Rule ID"),
+    ),
+    responses(
+        (status = 204, description = "Rule deleted"),
+        (status = 404, description = "Rule not found")
+    )
+)]
+pub async fn delete_blueprint_rule(
+    State(state): State<Arc<AppState>>,
+    Path((id, rule_id)): Path<(Uuid, Uuid)>,
+) -> Result<impl IntoResponse> {
+    db::delete_rule(&state.db, rule_id, RuleScope::Blueprint(id)).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn create_scoped_rule(
+    state: &AppState,
+    scope: RuleScope,
+    req: CreateRuleRequest,
+) -> Result<(StatusCode, Json<StreamRule>)> {
+    rules::validate(
+        req.schedule.as_ref(),
+        req.zone.as_deref(),
+        req.min_count,
+        req.min_dwell_sec,
+        &req.event_types,
+    )?;
+    let rule = db::create_rule(&state.db, scope, &req).await?;
+    Ok((StatusCode::CREATED, Json(rule)))
+}
+
+async fn update_scoped_rule(
+    state: &AppState,
+    rule_id: Uuid,
+    scope: RuleScope,
+    req: UpdateRuleRequest,
+) -> Result<Json<StreamRule>> {
+    rules::validate(
+        req.schedule.as_ref().and_then(Option::as_ref),
+        req.zone.as_ref().and_then(Option::as_deref),
+        req.min_count.flatten(),
+        req.min_dwell_sec.flatten(),
+        req.event_types.as_deref().unwrap_or_default(),
+    )?;
+    let rule = db::update_rule(&state.db, rule_id, scope, &req).await?;
+    Ok(Json(rule))
+}
+
+// ─── Rule statistics ──────────────────────────────────────────────────────────
+
+#[utoipa::path(
+    get,
+    path = "/api/rules/stats",
+    tag = "rules",
+    params(RuleStatsQuery),
+    responses(
+        (status = 200, description = "Hits, false positives and confidence per rule over the period", body = Vec<RuleStats>),
+        (status = 404, description = "Stream not found")
+    )
+)]
+/// Rules that never fire, or fire on nearly every frame, are flagged once
+/// enough frames have been analyzed.
+pub async fn rule_stats(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<RuleStatsQuery>,
+) -> Result<impl IntoResponse> {
+    Ok(Json(rule_stats::report(&state.db, &q).await?))
+}
+
+// ─── Rule reference images ────────────────────────────────────────────────────
+
+#[utoipa::path(
+    get,
+    path = "/api/rules/{rule_id}/reference-images",
+    tag = "rules",
+    params(("rule_id" = Uuid, Path, description = "Rule ID (any scope)")),
+    responses(
+        (status = 200, description = "The rule's reference images (without image data)", body = Vec<RuleReferenceImage>),
+        (status = 404, description = "Rule not found")
+    )
+)]
+pub async fn list_reference_images(
+    State(state): State<Arc<AppState>>,
+    Path(rule_id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    db::get_rule_by_id(&state.db, rule_id).await?;
+    Ok(Json(db::list_reference_images(&state.db, rule_id).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/rules/{rule_id}/reference-images",
+    tag = "rules",
+    params(("rule_id" = Uuid, Path, description = "Rule ID (any scope)")),
+    request_body = CreateReferenceImageRequest,
+    responses(
+        (status = 201, description = "Reference image added", body = RuleReferenceImage),
+        (status = 400, description = "Missing or undecodable image, invalid kind, or too many images"),
+        (status = 404, description = "Rule or event not found")
+    )
+)]
+/// Attach a positive or negative example, uploaded as base64 or copied from a
+/// stored event. The image is scaled down and sent with every frame the rule
+/// applies to, on VLMs that accept several images.
+pub async fn create_reference_image(
+    State(state): State<Arc<AppState>>,
+    Path(rule_id): Path<Uuid>,
+    Json(req): Json<CreateReferenceImageRequest>,
+) -> Result<impl IntoResponse> {
+    let image = references::add(&state.db, rule_id, &req).await?;
+    Ok((StatusCode::CREATED, Json(image)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/rules/{rule_id}/reference-images/{image_id}/image",
+    tag = "rules",
+    params(
+        ("rule_id" = Uuid, Path, description = "Rule ID"),
+        ("image_id" = Uuid, Path, description = "Reference image ID"),
+    ),
+    responses(
+        (status = 200, description = "The reference image as sent to the VLM", content_type = "image/jpeg"),
+        (status = 404, description = "Reference image not found")
+    )
+)]
+pub async fn get_reference_image(
+    State(state): State<Arc<AppState>>,
+    Path((rule_id, image_id)): Path<(Uuid, Uuid)>,
+) -> Result<impl IntoResponse> {
+    let image = db::get_reference_image_data(&state.db, image_id, rule_id).await?;
+    Ok(([(header::CONTENT_TYPE, "image/jpeg")], image))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/rules/{rule_id}/reference-images/{image_id}",
+    tag = "rules",
+    params(
+        ("rule_id" = Uuid, Path, description = "Rule ID"),
+        ("image_id" = Uuid, Path, description = "Reference image ID"),
+    ),
+    responses(
+        (status = 204, description = "Reference image deleted"),
+        (status = 404, description = "Reference image not found")
+    )
+)]
+pub async fn delete_reference_image(
+    State(state): State<Arc<AppState>>,
+    Path((rule_id, image_id)): Path<(Uuid, Uuid)>,
 ) -> Result<impl IntoResponse> {
-    db::delete_rule(&state.db, rule_id, id).await?;
+    db::delete_reference_image(&state.db, image_id, rule_id).await?;
     Ok(StatusCode::NO_CONTENT)
 }
@@ -32,7 +32,7 @@ async fn handle_socket(mut socket: WebSocket, state: Arc<AppState>) {
                     Ok(event) => {
                         match serde_json::to_string(&event) {
                             Ok(json) => {
-                                if socket.send(Message::Text(json.into())).await.is_err() {
+                                if socket.send(Message::Text(json)).await.is_err() {
                                     // Client disconnected
                                     break;
                                 }
@@ -46,7 +46,7 @@ async fn handle_socket(mut socket: WebSocket, state: Arc<AppState>) {
                             "type": "lag_warning",
                             "missed": n
                         });
-                        let _ = socket.send(Message::Text(msg.to_string().into())).await;
+                        let _ = socket.send(Message::Text(msg.to_string())).await;
                     }
                     Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
                 }
@@ -0,0 +1,148 @@
+//! Destructive assistant actions. The model only proposes them: what they
+//! touch is fixed and counted when proposed, and nothing changes until the
+//! user confirms the action through the API before it expires.
+
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    assistant::tools::ToolContext,
+    error::{AppError, Result},
+    state::AppState,
+    storage::{
+        db,
+        models::{AssistantAction, UpdateRuleRequest},
+    },
+    streams::manager::StreamManager,
+};
+
+/// What confirming an action does.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "tool", rename_all = "snake_case")]
+pub enum Change {
+    /// The events that were unresolved when the action was proposed.
+    ResolveEvents { event_ids: Vec<Uuid> },
+    UpdateRule { rule_id: Uuid, description: Option<String>, threat_level: Option<String> },
+    DeleteRule { rule_id: Uuid },
+    DisableStream { stream_id: Uuid },
+}
+
+impl Change {
+    fn tool(&self) -> &'static str {
+        match self {
+            Change::ResolveEvents { .. } => "resolve_events",
+            Change::UpdateRule { .. } => "update_rule",
+            Change::DeleteRule { .. } => "delete_rule",
+            Change::DisableStream { .. } => "disable_stream",
+        }
+    }
+
+    fn affected(&self) -> i64 {
+        match self {
+            Change::ResolveEvents { event_ids } => event_ids.len() as i64,
+            _ => 1,
+        }
+    }
+}
+
+/// Stores `change` as a pending action of the conversation the tool runs in.
+pub async fn propose(ctx: &ToolContext, change: Change, summary: String) -> Result<AssistantAction> {
+    let limits = &ctx.state.assistant;
+    if change.affected() > limits.max_action_rows {
+        return Err(AppError::BadRequest(format!(
+            "this would change {} rows, more than the {} one assistant action may change",
+            change.affected(),
+            limits.max_action_rows
+        )));
+    }
+    let expires_at = Utc::now() + Duration::seconds(limits.action_ttl_sec as i64);
+    let value = serde_json::to_value(&change).map_err(|e| AppError::Other(e.into()))?;
+    db::insert_assistant_action(
+        &ctx.state.db,
+        ctx.conversation_id,
+        change.tool(),
+        &value,
+        &summary,
+        change.affected(),
+        expires_at,
+    )
+    .await
+}
+
+/// Executes a pending action. The outcome is stored on the action and, if it
+/// was proposed in a conversation, added to it so the model knows.
+pub async fn confirm(state: &AppState, manager: &StreamManager, id: Uuid) -> Result<AssistantAction> {
+    let action = decide(state, id, "executed").await?;
+    let change: Change = serde_json::from_value(action.change.clone()).map_err(|e| AppError::Other(e.into()))?;
+    let (status, result) = match execute(state, manager, change).await {
+        Ok(result) => ("executed", result),
+        Err(e) => ("failed", e.to_string()),
+    };
+    let action = db::set_assistant_action_result(&state.db, id, status, &result).await?;
+    if let Some(conversation_id) = action.conversation_id {
+        let note = format!("The user confirmed \"{}\". {}", action.summary, result);
+        db::insert_conversation_message(&state.db, conversation_id, "assistant", &note, None, None, false).await?;
+    }
+    Ok(action)
+}
+
+pub async fn reject(state: &AppState, id: Uuid) -> Result<AssistantAction> {
+    let action = decide(state, id, "rejected").await?;
+    if let Some(conversation_id) = action.conversation_id {
+        let note = format!("The user rejected \"{}\"; nothing was changed.", action.summary);
+        db::insert_conversation_message(&state.db, conversation_id, "assistant", &note, None, None, false).await?;
+    }
+    Ok(action)
+}
+
+async fn decide(state: &AppState, id: Uuid, status: &str) -> Result<AssistantAction> {
+    if let Some(action) = db::decide_assistant_action(&state.db, id, status).await? {
+        return Ok(action);
+    }
+    let action = db::get_assistant_action(&state.db, id).await?;
+    Err(AppError::Conflict(if action.status == "pending" {
+        format!("action {id} expired at {}", action.expires_at.format("%Y-%m-%d %H:%M UTC"))
+    } else {
+        format!("action {id} is already {}", action.status)
+    }))
+}
+
+async fn execute(state: &AppState, manager: &StreamManager, change: Change) -> Result<String> {
+    let db = &state.db;
+    match change {
+        Change::ResolveEvents { event_ids } => {
+            let n = db::resolve_events_by_ids(db, &event_ids).await?;
+            Ok(if n as usize == event_ids.len() {
+                format!("Resolved {n} events.")
+            } else {
+                format!("Resolved {n} of {} events; the others were resolved in the meantime.", event_ids.len())
+            })
+        }
+        Change::UpdateRule { rule_id, description, threat_level } => {
+            let rule = db::get_rule_by_id(db, rule_id).await?;
+            let req = UpdateRuleRequest {
+                description,
+                threat_level,
+                position: None,
+                schedule: None,
+                zone: None,
+                min_count: None,
+                min_dwell_sec: None,
+                event_types: None,
+            };
+            let rule = db::update_rule(db, rule.id, rule.rule_scope(), &req).await?;
+            Ok(format!("Updated rule {}: {} ({}).", rule.id, rule.description, rule.threat_level))
+        }
+        Change::DeleteRule { rule_id } => {
+            let rule = db::get_rule_by_id(db, rule_id).await?;
+            db::delete_rule(db, rule.id, rule.rule_scope()).await?;
+            Ok(format!("Deleted rule \"{}\".", rule.description))
+        }
+        Change::DisableStream { stream_id } => {
+            let stream = db::set_stream_enabled(db, stream_id, false).await?;
+            manager.stop_stream(stream_id).await;
+            Ok(format!("Disabled stream {}.", stream.name))
+        }
+    }
+}
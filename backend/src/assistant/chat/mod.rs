@@ -0,0 +1,150 @@
+pub mod ollama;
+pub mod openai_compat;
+
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde_json::Value;
+use tokio_util::sync::CancellationToken;
+use tracing::error;
+
+use crate::{
+    analysis::vlm::{body_error, http_error},
+    config::VlmBackend,
+    error::{AppError, Result},
+};
+
+// ─── Messages ─────────────────────────────────────────────────────────────────
+
+/// One message of a chat, independent of how a backend encodes it.
+#[derive(Debug, Clone)]
+pub enum ChatMessage {
+    System(String),
+    User(String),
+    Assistant { content: String, tool_calls: Vec<ToolCall> },
+    /// The result of a tool call, following the assistant message that made it.
+    Tool { name: String, content: String },
+}
+
+#[derive(Debug, Clone)]
+pub struct ToolCall {
+    /// The backend's id for the call, which its result must refer to. Ollama
+    /// gives none.
+    pub id: Option<String>,
+    pub name: String,
+    pub arguments: Value,
+}
+
+/// What the model answered in one round.
+#[derive(Debug, Default)]
+pub struct ChatRound {
+    pub content: String,
+    pub tool_calls: Vec<ToolCall>,
+    /// The round was cut short by a cancel; `content` is what arrived before.
+    pub cancelled: bool,
+}
+
+// ─── Trait ────────────────────────────────────────────────────────────────────
+
+#[async_trait]
+pub trait ChatClient: Send + Sync {
+    /// One streamed chat request offering `tools` (function definitions in
+    /// OpenAI's format). Text is passed to `on_text` as it arrives; tool calls
+    /// are collected for the caller to run.
+    async fn chat(
+        &self,
+        messages: &[ChatMessage],
+        tools: &[Value],
+        cancel: &CancellationToken,
+        on_text: &mut (dyn for<'t> FnMut(&'t str) + Send),
+    ) -> Result<ChatRound>;
+
+    /// Answers `prompt` in one round without tools (e.g. the written summary of a report).
+    async fn complete(&self, system: &str, prompt: &str) -> Result<String> {
+        let messages = [ChatMessage::System(system.to_string()), ChatMessage::User(prompt.to_string())];
+        let round = self.chat(&messages, &[], &CancellationToken::new(), &mut |_| {}).await?;
+        Ok(round.content.trim().to_string())
+    }
+}
+
+pub type DynChatClient = Arc<dyn ChatClient>;
+
+// ─── Factory ──────────────────────────────────────────────────────────────────
+
+pub fn build_chat_client(cfg: &VlmBackend) -> DynChatClient {
+    match cfg {
+        VlmBackend::Ollama(c) => Arc::new(ollama::OllamaChatClient::new(c)),
+        VlmBackend::OpenAiCompat(c) => Arc::new(openai_compat::OpenAiChatClient::new(c, "OpenAI-compat")),
+        // llama-server needs `--jinja` for tool calling; vLLM needs
+        // `--enable-auto-tool-choice` and a `--tool-call-parser`.
+        VlmBackend::LlamaCpp(c) => Arc::new(openai_compat::OpenAiChatClient::new(c, "llama.cpp")),
+        VlmBackend::Vllm(c) => Arc::new(openai_compat::OpenAiChatClient::new(c, "vLLM")),
+    }
+}
+
+// ─── Helpers ─────────────────────────────────────────────────────────────────
+
+/// HTTP client for streamed replies. The request timeout bounds the wait for
+/// each chunk rather than the whole reply, which lasts as long as the model
+/// writes.
+fn http_client(connect_timeout_sec: Option<u64>, request_timeout_sec: Option<u64>) -> reqwest::Client {
+    let mut builder = reqwest::Client::builder();
+    if let Some(secs) = connect_timeout_sec {
+        builder = builder.connect_timeout(Duration::from_secs(secs));
+    }
+    if let Some(secs) = request_timeout_sec {
+        builder = builder.read_timeout(Duration::from_secs(secs));
+    }
+    builder.build().unwrap_or_else(|e| {
+        error!("Could not build the chat HTTP client, its timeouts won't apply: {e}");
+        reqwest::Client::new()
+    })
+}
+
+/// Sends `req` and reads the streamed reply line by line, passing each
+/// non-blank line to `on_line` until it returns true or the reply ends.
+/// Returns false if `cancel` fired first. `label` names the server in errors.
+async fn stream_lines(
+    req: reqwest::RequestBuilder,
+    label: &str,
+    cancel: &CancellationToken,
+    mut on_line: impl FnMut(&[u8]) -> Result<bool> + Send,
+) -> Result<bool> {
+    let resp = tokio::select! {
+        resp = req.send() => resp.map_err(|e| AppError::VlmUnavailable(format!("{label} request failed: {e}")))?,
+        _ = cancel.cancelled() => return Ok(false),
+    };
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_default();
+        return Err(http_error(status, format!("{label} HTTP {status}: {text}")));
+    }
+
+    let mut bytes = resp.bytes_stream();
+    let mut buf = Vec::new();
+    loop {
+        let chunk = tokio::select! {
+            chunk = bytes.next() => chunk,
+            _ = cancel.cancelled() => return Ok(false),
+        };
+        let Some(chunk) = chunk else {
+            break;
+        };
+        buf.extend_from_slice(&chunk.map_err(|e| body_error(e, label))?);
+        while let Some(end) = buf.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = buf.drain(..=end).collect();
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            if on_line(&line)? {
+                return Ok(true);
+            }
+        }
+    }
+    // A last line without a newline.
+    if !buf.iter().all(u8::is_ascii_whitespace) {
+        on_line(&buf)?;
+    }
+    Ok(true)
+}
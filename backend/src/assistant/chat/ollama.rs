@@ -0,0 +1,96 @@
+/// Ollama `/api/chat` client with tool calling. Replies stream as
+/// newline-delimited JSON, one chunk per line.
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+
+use crate::{
+    config::OllamaConfig,
+    error::{AppError, Result},
+};
+
+use super::{http_client, stream_lines, ChatClient, ChatMessage, ChatRound, ToolCall};
+
+pub struct OllamaChatClient {
+    client: reqwest::Client,
+    base_url: String,
+    model: String,
+}
+
+impl OllamaChatClient {
+    pub fn new(cfg: &OllamaConfig) -> Self {
+        Self {
+            client: http_client(cfg.connect_timeout_sec, cfg.request_timeout_sec),
+            base_url: cfg.base_url.trim_end_matches('/').to_string(),
+            model: cfg.model.clone(),
+        }
+    }
+}
+
+/// Ollama names the tool a result belongs to; calls have no ids.
+fn message_json(message: &ChatMessage) -> Value {
+    match message {
+        ChatMessage::System(content) => json!({"role":"system","content":content}),
+        ChatMessage::User(content) => json!({"role":"user","content":content}),
+        ChatMessage::Assistant { content, tool_calls } => {
+            let mut m = json!({"role":"assistant","content":content});
+            if !tool_calls.is_empty() {
+                m["tool_calls"] = tool_calls
+                    .iter()
+                    .map(|tc| json!({"function":{"name":tc.name,"arguments":tc.arguments}}))
+                    .collect();
+            }
+            m
+        }
+        ChatMessage::Tool { name, content } => json!({"role":"tool","tool_name":name,"content":content}),
+    }
+}
+
+#[async_trait]
+impl ChatClient for OllamaChatClient {
+    async fn chat(
+        &self,
+        messages: &[ChatMessage],
+        tools: &[Value],
+        cancel: &CancellationToken,
+        on_text: &mut (dyn for<'t> FnMut(&'t str) + Send),
+    ) -> Result<ChatRound> {
+        let mut body = json!({
+            "model": self.model,
+            "messages": messages.iter().map(message_json).collect::<Vec<_>>(),
+            "stream": true
+        });
+        if !tools.is_empty() {
+            body["tools"] = Value::from(tools);
+        }
+
+        let url = format!("{}/api/chat", self.base_url);
+        debug!(model = %self.model, url = %url, "Calling Ollama chat");
+
+        let mut round = ChatRound::default();
+        let finished = stream_lines(self.client.post(&url).json(&body), "Ollama", cancel, |line| {
+            let j: Value = serde_json::from_slice(line).map_err(|e| AppError::Vlm(format!("Ollama JSON: {e}")))?;
+            if let Some(err) = j.get("error").and_then(|e| e.as_str()) {
+                return Err(AppError::Vlm(format!("Ollama: {err}")));
+            }
+            let msg = j.get("message");
+            if let Some(text) = msg.and_then(|m| m.get("content")).and_then(|c| c.as_str()).filter(|t| !t.is_empty()) {
+                round.content.push_str(text);
+                on_text(text);
+            }
+            for call in msg.and_then(|m| m.get("tool_calls")).and_then(|t| t.as_array()).into_iter().flatten() {
+                let function = call.get("function");
+                round.tool_calls.push(ToolCall {
+                    id: None,
+                    name: function.and_then(|f| f.get("name")).and_then(|n| n.as_str()).unwrap_or("").to_string(),
+                    arguments: function.and_then(|f| f.get("arguments")).cloned().unwrap_or(json!({})),
+                });
+            }
+            Ok(j.get("done").and_then(|d| d.as_bool()) == Some(true))
+        })
+        .await?;
+        round.cancelled = !finished;
+        Ok(round)
+    }
+}
@@ -0,0 +1,316 @@
+/// OpenAI-compatible `/chat/completions` client with tool calling, for
+/// OpenAI, llama.cpp's `llama-server`, vLLM and the like. Replies stream as
+/// server-sent events whose deltas carry text and pieces of tool calls.
+use std::collections::VecDeque;
+
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+
+use crate::{
+    config::OpenAiCompatConfig,
+    error::{AppError, Result},
+};
+
+use super::{http_client, stream_lines, ChatClient, ChatMessage, ChatRound, ToolCall};
+
+pub struct OpenAiChatClient {
+    client: reqwest::Client,
+    base_url: String,
+    api_key: String,
+    model: String,
+    /// Names the server in errors, e.g. "vLLM".
+    label: &'static str,
+}
+
+impl OpenAiChatClient {
+    pub fn new(cfg: &OpenAiCompatConfig, label: &'static str) -> Self {
+        Self {
+            client: http_client(cfg.connect_timeout_sec, cfg.request_timeout_sec),
+            base_url: cfg.base_url.trim_end_matches('/').to_string(),
+            api_key: cfg.api_key.clone(),
+            model: cfg.model.clone(),
+            label,
+        }
+    }
+}
+
+/// The result sent for a call that has none, as when a reply was cancelled.
+const NO_RESULT: &str = "No result: the call did not finish.";
+
+/// Tool results refer to their call by id. Results are stored right after the
+/// assistant message, in the order of its calls, so each takes the id of the
+/// next of those calls still waiting for one. Calls without a backend id (e.g.
+/// made through Ollama) are numbered here. The server rejects a call left
+/// without a result and a result without a call, so the first is given one and
+/// the second is left out.
+fn messages_json(messages: &[ChatMessage]) -> Vec<Value> {
+    let mut out = Vec::new();
+    let mut waiting = VecDeque::new();
+    let mut numbered = 0;
+    for message in messages {
+        if !matches!(message, ChatMessage::Tool { .. }) {
+            out.extend(
+                waiting.drain(..).map(|id| json!({"role":"tool","tool_call_id":id,"content":NO_RESULT})),
+            );
+        }
+        match message {
+            ChatMessage::System(content) => out.push(json!({"role":"system","content":content})),
+            ChatMessage::User(content) => out.push(json!({"role":"user","content":content})),
+            ChatMessage::Assistant { content, tool_calls } if tool_calls.is_empty() => {
+                out.push(json!({"role":"assistant","content":content}))
+            }
+            ChatMessage::Assistant { content, tool_calls } => {
+                let calls: Vec<Value> = tool_calls
+                    .iter()
+                    .map(|tc| {
+                        let id = tc.id.clone().unwrap_or_else(|| {
+                            numbered += 1;
+                            format!("call_{numbered}")
+                        });
+                        waiting.push_back(id.clone());
+                        json!({
+                            "id": id,
+                            "type": "function",
+                            "function": {"name": tc.name, "arguments": tc.arguments.to_string()}
+                        })
+                    })
+                    .collect();
+                let content = (!content.is_empty()).then_some(content);
+                out.push(json!({"role":"assistant","content":content,"tool_calls":calls}));
+            }
+            ChatMessage::Tool { content, .. } => {
+                if let Some(id) = waiting.pop_front() {
+                    out.push(json!({"role":"tool","tool_call_id":id,"content":content}));
+                }
+            }
+        }
+    }
+    out.extend(waiting.drain(..).map(|id| json!({"role":"tool","tool_call_id":id,"content":NO_RESULT})));
+    out
+}
+
+/// A tool call as its pieces arrive: the id and name first, the arguments as
+/// JSON text in any number of parts.
+#[derive(Default)]
+struct PartialCall {
+    id: Option<String>,
+    name: String,
+    arguments: String,
+}
+
+/// Adds a streamed piece of a tool call to `calls`. Pieces name their call by
+/// `index`; some servers leave it out, in which case a piece with an id or a
+/// name starts a new call and any other continues the last one.
+fn add_piece(calls: &mut Vec<PartialCall>, piece: &Value) {
+    let function = piece.get("function");
+    let id = piece.get("id").and_then(|i| i.as_str()).filter(|i| !i.is_empty());
+    let name = function.and_then(|f| f.get("name")).and_then(|n| n.as_str());
+    let index = match piece.get("index").and_then(|i| i.as_u64()) {
+        Some(i) => i as usize,
+        None if id.is_some() || name.is_some() || calls.is_empty() => calls.len(),
+        None => calls.len() - 1,
+    };
+    if calls.len() <= index {
+        calls.resize_with(index + 1, PartialCall::default);
+    }
+    let call = &mut calls[index];
+    if let Some(id) = id {
+        call.id = Some(id.to_string());
+    }
+    if let Some(name) = name {
+        call.name.push_str(name);
+    }
+    if let Some(arguments) = function.and_then(|f| f.get("arguments")).and_then(|a| a.as_str()) {
+        call.arguments.push_str(arguments);
+    }
+}
+
+#[async_trait]
+impl ChatClient for OpenAiChatClient {
+    async fn chat(
+        &self,
+        messages: &[ChatMessage],
+        tools: &[Value],
+        cancel: &CancellationToken,
+        on_text: &mut (dyn for<'t> FnMut(&'t str) + Send),
+    ) -> Result<ChatRound> {
+        let mut body = json!({
+            "model": self.model,
+            "messages": messages_json(messages),
+            "stream": true
+        });
+        if !tools.is_empty() {
+            body["tools"] = Value::from(tools);
+        }
+
+        let url = format!("{}/chat/completions", self.base_url);
+        debug!(model = %self.model, url = %url, "Calling {} chat API", self.label);
+
+        let mut req = self.client.post(&url).json(&body);
+        // Self-hosted servers often run without auth.
+        if !self.api_key.is_empty() {
+            req = req.bearer_auth(&self.api_key);
+        }
+
+        let label = self.label;
+        let mut round = ChatRound::default();
+        let mut calls: Vec<PartialCall> = Vec::new();
+        let finished = stream_lines(req, label, cancel, |line| {
+            let line = String::from_utf8_lossy(line);
+            // Only `data:` lines carry chunks; others are comments or event names.
+            let Some(data) = line.trim().strip_prefix("data:").map(str::trim) else {
+                return Ok(false);
+            };
+            if data == "[DONE]" {
+                return Ok(true);
+            }
+            let j: Value = serde_json::from_str(data).map_err(|e| AppError::Vlm(format!("{label} JSON: {e}")))?;
+            if let Some(err) = j.get("error") {
+                let message = err.get("message").and_then(|m| m.as_str()).map_or_else(|| err.to_string(), String::from);
+                return Err(AppError::Vlm(format!("{label}: {message}")));
+            }
+            let delta = j.get("choices").and_then(|c| c.get(0)).and_then(|c| c.get("delta"));
+            if let Some(text) = delta.and_then(|d| d.get("content")).and_then(|c| c.as_str()).filter(|t| !t.is_empty()) {
+                round.content.push_str(text);
+                on_text(text);
+            }
+            for piece in delta.and_then(|d| d.get("tool_calls")).and_then(|t| t.as_array()).into_iter().flatten() {
+                add_piece(&mut calls, piece);
+            }
+            Ok(false)
+        })
+        .await?;
+
+        round.cancelled = !finished;
+        round.tool_calls = calls
+            .into_iter()
+            .filter(|c| !c.name.is_empty())
+            .map(|c| ToolCall {
+                id: c.id,
+                name: c.name,
+                // Unparseable arguments count as none; the tool reports what's missing.
+                arguments: serde_json::from_str(&c.arguments).unwrap_or(json!({})),
+            })
+            .collect();
+        Ok(round)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(id: Option<&str>, name: &str) -> ToolCall {
+        ToolCall { id: id.map(String::from), name: name.into(), arguments: json!({}) }
+    }
+
+    fn tool(name: &str) -> ChatMessage {
+        ChatMessage::Tool { name: name.into(), content: format!("{name} result") }
+    }
+
+    /// (role, tool_call_id) of each message.
+    fn roles(messages: &[Value]) -> Vec<(&str, Option<&str>)> {
+        messages.iter().map(|m| (m["role"].as_str().unwrap(), m["tool_call_id"].as_str())).collect()
+    }
+
+    #[test]
+    fn results_take_the_ids_of_their_calls() {
+        let messages = [
+            ChatMessage::User("how many events?".into()),
+            ChatMessage::Assistant {
+                content: String::new(),
+                tool_calls: vec![call(Some("chatcmpl-tool-a"), "count_events"), call(None, "list_streams")],
+            },
+            tool("count_events"),
+            tool("list_streams"),
+            ChatMessage::Assistant {
+                content: String::new(),
+                tool_calls: vec![call(Some("chatcmpl-tool-b"), "count_events")],
+            },
+            tool("count_events"),
+        ];
+        let json = messages_json(&messages);
+        assert_eq!(json[1]["tool_calls"][0]["id"], "chatcmpl-tool-a");
+        assert_eq!(json[1]["tool_calls"][1]["id"], "call_1");
+        assert_eq!(
+            roles(&json),
+            [
+                ("user", None),
+                ("assistant", None),
+                ("tool", Some("chatcmpl-tool-a")),
+                ("tool", Some("call_1")),
+                ("assistant", None),
+                ("tool", Some("chatcmpl-tool-b")),
+            ]
+        );
+    }
+
+    #[test]
+    fn unanswered_calls_are_closed_and_stray_results_dropped() {
+        let messages = [
+            ChatMessage::User("delete the old events".into()),
+            ChatMessage::Assistant {
+                content: String::new(),
+                tool_calls: vec![call(Some("a"), "count_events"), call(Some("b"), "delete_events")],
+            },
+            tool("count_events"),
+            // `delete_events` was left without a result.
+            ChatMessage::User("never mind".into()),
+            // A result no call is waiting for.
+            tool("delete_events"),
+            ChatMessage::Assistant { content: String::new(), tool_calls: vec![call(Some("c"), "count_events")] },
+        ];
+        let json = messages_json(&messages);
+        assert_eq!(
+            roles(&json),
+            [
+                ("user", None),
+                ("assistant", None),
+                ("tool", Some("a")),
+                ("tool", Some("b")),
+                ("user", None),
+                ("assistant", None),
+                ("tool", Some("c")),
+            ]
+        );
+        assert_eq!(json[3]["content"], NO_RESULT);
+        assert_eq!(json[6]["content"], NO_RESULT);
+    }
+
+    #[test]
+    fn pieces_without_an_index_continue_the_last_call() {
+        let mut calls = Vec::new();
+        for piece in [
+            json!({"id":"a","function":{"name":"count_events","arguments":""}}),
+            json!({"function":{"arguments":"{\"risk_level\":"}}),
+            json!({"function":{"arguments":"\"high\"}"}}),
+            json!({"id":"b","function":{"name":"list_streams"}}),
+            json!({"function":{"arguments":"{}"}}),
+        ] {
+            add_piece(&mut calls, &piece);
+        }
+        let calls: Vec<_> = calls.iter().map(|c| (c.id.as_deref(), c.name.as_str(), c.arguments.as_str())).collect();
+        assert_eq!(
+            calls,
+            [(Some("a"), "count_events", r#"{"risk_level":"high"}"#), (Some("b"), "list_streams", "{}")]
+        );
+    }
+
+    #[test]
+    fn indexed_pieces_go_to_their_call() {
+        let mut calls = Vec::new();
+        for piece in [
+            json!({"index":0,"id":"a","function":{"name":"count_events","arguments":""}}),
+            json!({"index":1,"id":"b","function":{"name":"list_streams","arguments":""}}),
+            json!({"index":0,"function":{"arguments":"{}"}}),
+            json!({"index":1,"function":{"arguments":"{}"}}),
+        ] {
+            add_piece(&mut calls, &piece);
+        }
+        assert_eq!(calls.len(), 2);
+        assert!(calls.iter().all(|c| c.arguments == "{}"));
+        assert_eq!(calls[1].id.as_deref(), Some("b"));
+    }
+}
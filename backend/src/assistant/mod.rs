@@ -0,0 +1,281 @@
+//! AI assistant with tool calling, on any chat backend (see `chat`). Handles arbitrary time ranges and questions.
+//! Conversations are stored so follow-up questions see earlier turns; replies
+//! stream token by token, with each tool call and its result reported as it
+//! happens, and can be cancelled while running. Tools that change or remove
+//! data only propose an action for the user to confirm.
+
+pub mod actions;
+pub mod chat;
+mod tools;
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use futures::{Stream, StreamExt};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, Result},
+    state::AppState,
+    storage::{db, models::AssistantEvent},
+    streams::manager::StreamManager,
+};
+use chat::{ChatMessage, ToolCall};
+use tools::ToolContext;
+
+const SYSTEM_PROMPT: &str = "You are an assistant for Cipher-Shield, a security camera app. Use the tools to answer \
+and to make changes. Look up stream, blueprint and rule ids with the tools instead of guessing them. Tools that \
+change or remove data only propose the change: tell the user what it will do and that they have to confirm it \
+in the app, and never say it is done before they have. Be concise.";
+const MAX_TOOL_ROUNDS: usize = 5;
+/// Most earlier messages of a conversation sent along with a new question.
+const HISTORY_MESSAGES: i64 = 40;
+/// Stored as the result of tool calls a cancel stopped before they finished.
+const CANCELLED_CALL: &str = "Cancelled before it finished.";
+
+/// Replies being generated, by conversation, so they can be cancelled and
+/// a conversation only answers one question at a time.
+#[derive(Default)]
+pub struct AssistantRuns {
+    running: Mutex<HashMap<Uuid, CancellationToken>>,
+}
+
+impl AssistantRuns {
+    fn start(self: &Arc<Self>, conversation_id: Uuid) -> Result<RunGuard> {
+        let mut running = self.running.lock().unwrap();
+        if running.contains_key(&conversation_id) {
+            return Err(AppError::Conflict(format!("conversation {conversation_id} is already answering a question")));
+        }
+        let token = CancellationToken::new();
+        running.insert(conversation_id, token.clone());
+        Ok(RunGuard { runs: Arc::clone(self), conversation_id, token })
+    }
+
+    /// Cancels the conversation's running reply; false if there is none.
+    pub fn cancel(&self, conversation_id: Uuid) -> bool {
+        match self.running.lock().unwrap().get(&conversation_id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_running(&self, conversation_id: Uuid) -> bool {
+        self.running.lock().unwrap().contains_key(&conversation_id)
+    }
+}
+
+/// A registered reply; unregistered when dropped.
+struct RunGuard {
+    runs: Arc<AssistantRuns>,
+    conversation_id: Uuid,
+    token: CancellationToken,
+}
+
+impl Drop for RunGuard {
+    fn drop(&mut self) {
+        self.runs.running.lock().unwrap().remove(&self.conversation_id);
+    }
+}
+
+/// Answers `message` in one response. With a conversation, earlier turns are
+/// sent along and this turn is stored.
+pub async fn chat(
+    state: &Arc<AppState>,
+    manager: &Arc<StreamManager>,
+    conversation_id: Option<Uuid>,
+    message: &str,
+) -> Result<String> {
+    validate_message(message)?;
+    let ctx = ToolContext { state: Arc::clone(state), manager: Arc::clone(manager), conversation_id };
+    let Some(id) = conversation_id else {
+        return run_turn(&ctx, None, message, &CancellationToken::new(), |_| {}).await;
+    };
+    db::get_conversation(&state.db, id).await?;
+    let run = state.assistant_runs.start(id)?;
+    run_turn(&ctx, Some(id), message, &run.token, |_| {}).await
+}
+
+/// Answers `message` in a conversation as a stream of events, ending with
+/// `Done`, `Cancelled` or `Error`. Dropping the stream (e.g. the client
+/// disconnecting) cancels the reply.
+pub async fn stream(
+    state: Arc<AppState>,
+    manager: Arc<StreamManager>,
+    conversation_id: Uuid,
+    message: String,
+) -> Result<impl Stream<Item = AssistantEvent>> {
+    validate_message(&message)?;
+    db::get_conversation(&state.db, conversation_id).await?;
+    let run = state.assistant_runs.start(conversation_id)?;
+    let cancel_on_drop = run.token.clone().drop_guard();
+
+    let (tx, rx) = mpsc::unbounded_channel();
+    let ctx = ToolContext { state, manager, conversation_id: Some(conversation_id) };
+    tokio::spawn(async move {
+        let emit = |event| {
+            let _ = tx.send(event);
+        };
+        if let Err(e) = run_turn(&ctx, Some(conversation_id), &message, &run.token, emit).await {
+            let _ = tx.send(AssistantEvent::Error { message: e.to_string() });
+        }
+        drop(run);
+    });
+
+    Ok(UnboundedReceiverStream::new(rx).map(move |event| {
+        let _ = &cancel_on_drop;
+        event
+    }))
+}
+
+fn validate_message(message: &str) -> Result<()> {
+    if message.trim().is_empty() {
+        return Err(AppError::BadRequest("message must not be empty".into()));
+    }
+    Ok(())
+}
+
+/// Runs the tool loop for one question, reporting progress through `emit`,
+/// and returns the reply (so far, if cancelled).
+async fn run_turn(
+    ctx: &ToolContext,
+    conversation_id: Option<Uuid>,
+    message: &str,
+    cancel: &CancellationToken,
+    mut emit: impl FnMut(AssistantEvent) + Send,
+) -> Result<String> {
+    let db = &ctx.state.db;
+    let tools: Vec<Value> = serde_json::from_str(tools::TOOLS).unwrap();
+
+    let mut messages = vec![ChatMessage::System(SYSTEM_PROMPT.to_string())];
+    if let Some(id) = conversation_id {
+        messages.extend(history(db, id).await?);
+        db::insert_conversation_message(db, id, "user", message, None, None, false).await?;
+    }
+    messages.push(ChatMessage::User(message.to_string()));
+
+    for _ in 0..MAX_TOOL_ROUNDS {
+        let mut on_text = |text: &str| emit(AssistantEvent::Token { text: text.to_string() });
+        let round = ctx.state.chat.chat(&messages, &tools, cancel, &mut on_text).await?;
+        if round.cancelled {
+            return cancelled(db, conversation_id, round.content, &[], &mut emit).await;
+        }
+
+        let stored_calls = (!round.tool_calls.is_empty()).then(|| stored_tool_calls(&round.tool_calls));
+        if let Some(id) = conversation_id {
+            db::insert_conversation_message(db, id, "assistant", &round.content, stored_calls.as_ref(), None, false)
+                .await?;
+        }
+        let Some(calls) = stored_calls else {
+            let content = if round.content.is_empty() { "No response.".to_string() } else { round.content };
+            emit(AssistantEvent::Done { content: content.clone() });
+            return Ok(content);
+        };
+        debug!(%calls, "Assistant tool calls");
+        messages.push(ChatMessage::Assistant { content: round.content, tool_calls: round.tool_calls.clone() });
+
+        for (i, tc) in round.tool_calls.iter().enumerate() {
+            emit(AssistantEvent::ToolCall { name: tc.name.clone(), arguments: tc.arguments.clone() });
+
+            let output = tokio::select! {
+                output = tools::execute(ctx, &tc.name, &tc.arguments) => output,
+                _ = cancel.cancelled() => {
+                    let unanswered = &round.tool_calls[i..];
+                    return cancelled(db, conversation_id, String::new(), unanswered, &mut emit).await;
+                }
+            };
+            emit(AssistantEvent::ToolResult { name: tc.name.clone(), result: output.text.clone() });
+            if let Some(action) = output.proposed {
+                emit(AssistantEvent::ActionProposed { action });
+            }
+            if let Some(id) = conversation_id {
+                db::insert_conversation_message(db, id, "tool", &output.text, None, Some(&tc.name), false).await?;
+            }
+            messages.push(ChatMessage::Tool { name: tc.name.clone(), content: output.text });
+        }
+    }
+
+    let content = "Max tool rounds reached.".to_string();
+    emit(AssistantEvent::Done { content: content.clone() });
+    Ok(content)
+}
+
+/// Stores the reply cut short by a cancel and reports it. `unanswered` are
+/// the tool calls that were still to run; each is stored with a result saying
+/// so, as chat APIs expect every call to have one.
+async fn cancelled(
+    db: &PgPool,
+    conversation_id: Option<Uuid>,
+    content: String,
+    unanswered: &[ToolCall],
+    emit: &mut impl FnMut(AssistantEvent),
+) -> Result<String> {
+    if let Some(id) = conversation_id {
+        for tc in unanswered {
+            db::insert_conversation_message(db, id, "tool", CANCELLED_CALL, None, Some(&tc.name), false).await?;
+        }
+        db::insert_conversation_message(db, id, "assistant", &content, None, None, true).await?;
+    }
+    emit(AssistantEvent::Cancelled { content: content.clone() });
+    Ok(content)
+}
+
+/// Earlier messages of the conversation as chat messages. The window starts
+/// at a question, so it never opens with a tool result cut off from its call.
+async fn history(db: &PgPool, conversation_id: Uuid) -> Result<Vec<ChatMessage>> {
+    let rows = db::conversation_messages(db, conversation_id, HISTORY_MESSAGES).await?;
+    let start = rows.iter().position(|m| m.role == "user").unwrap_or(rows.len());
+    Ok(rows[start..]
+        .iter()
+        .map(|m| match m.role.as_str() {
+            "assistant" => ChatMessage::Assistant {
+                content: m.content.clone(),
+                tool_calls: m.tool_calls.as_ref().map(parse_tool_calls).unwrap_or_default(),
+            },
+            "tool" => ChatMessage::Tool { name: m.tool_name.clone().unwrap_or_default(), content: m.content.clone() },
+            _ => ChatMessage::User(m.content.clone()),
+        })
+        .collect())
+}
+
+/// Tool calls as stored on a conversation message:
+/// `[{"id": …, "function": {"name": …, "arguments": {…}}}]`, the id only when
+/// the backend gave one.
+fn stored_tool_calls(calls: &[ToolCall]) -> Value {
+    calls
+        .iter()
+        .map(|tc| {
+            let mut call = json!({"function":{"name":tc.name,"arguments":tc.arguments}});
+            if let Some(id) = &tc.id {
+                call["id"] = json!(id);
+            }
+            call
+        })
+        .collect()
+}
+
+fn parse_tool_calls(stored: &Value) -> Vec<ToolCall> {
+    stored
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|tc| {
+            let function = tc.get("function")?;
+            Some(ToolCall {
+                id: tc.get("id").and_then(|i| i.as_str()).map(String::from),
+                name: function.get("name")?.as_str()?.to_string(),
+                arguments: function.get("arguments").cloned().unwrap_or(json!({})),
+            })
+        })
+        .collect()
+}
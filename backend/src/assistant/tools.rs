@@ -0,0 +1,440 @@
+//! Tools the assistant model can call, and what each returns to it.
+//!
+//! Tools that change or remove existing data (resolving events, editing or
+//! deleting rules, disabling streams) don't act: they propose an action the
+//! user confirms or rejects (see `actions`).
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+use serde_json::{Map, Value};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    analysis::{event_stats, preprocess, references, rules, vlm::RiskLevel},
+    assistant::actions::{self, Change},
+    error::{AppError, Result},
+    reports::render,
+    state::AppState,
+    storage::{
+        db,
+        models::{AssistantAction, CreateRuleRequest, EventCountsQuery, EventQuery, RuleScope, StatsQuery},
+    },
+    streams::manager::{StreamManager, StreamRecord},
+};
+
+/// Period `event_stats` covers when the model gives no `from`.
+const DEFAULT_STATS_DAYS: i64 = 30;
+
+pub const TOOLS: &str = r#"[
+  {"type":"function","function":{"name":"list_events","description":"List security events. Use for any question about what happened, threats, summaries, counts.","parameters":{"type":"object","properties":{"from":{"type":"string","description":"Start date ISO8601 e.g. 2024-01-01 or 2024-01-01T00:00:00Z"}, "to":{"type":"string","description":"End date ISO8601"}, "stream_id":{"type":"string","description":"Stream UUID filter"}, "risk_level":{"type":"string","description":"none|low|medium|high"}, "limit":{"type":"integer","description":"Max events to return (default 50, max 200). Use a smaller limit to avoid context overflow; call again with different from/to if needed."}}}}},
+  {"type":"function","function":{"name":"list_streams","description":"List camera streams with their ids, whether they are enabled and the blueprint they are placed on.","parameters":{"type":"object","properties":{"name":{"type":"string","description":"Only streams whose name contains this (case-insensitive), e.g. dock"}}}}},
+  {"type":"function","function":{"name":"find_blueprints","description":"Find blueprints (floor plans) by name, with the streams placed on each.","parameters":{"type":"object","properties":{"name":{"type":"string","description":"Part of the blueprint name (case-insensitive); empty lists all"}}}}},
+  {"type":"function","function":{"name":"list_rules","description":"List detection rules with their ids and threat levels. With stream_id, lists every rule in force on that stream, including global and blueprint rules it inherits.","parameters":{"type":"object","properties":{"stream_id":{"type":"string","description":"Stream UUID"},"blueprint_id":{"type":"string","description":"Blueprint UUID; rules of every stream on the blueprint"}}}}},
+  {"type":"function","function":{"name":"create_rule","description":"Add a detection rule. scope stream with stream_id applies it to one camera, blueprint with blueprint_id to every camera on a blueprint, global to all cameras; only use global when the user asks for every camera.","parameters":{"type":"object","properties":{"description":{"type":"string","description":"What the rule detects, e.g. Person without a hi-vis vest"},"threat_level":{"type":"string","description":"none|low|medium|high"},"scope":{"type":"string","description":"stream|blueprint|global"},"stream_id":{"type":"string","description":"Stream UUID, for scope stream"},"blueprint_id":{"type":"string","description":"Blueprint UUID, for scope blueprint"}},"required":["description","threat_level","scope"]}}},
+  {"type":"function","function":{"name":"update_rule","description":"Propose changing a rule's description or threat level; the user confirms it in the app.","parameters":{"type":"object","properties":{"rule_id":{"type":"string","description":"Rule UUID from list_rules"},"description":{"type":"string"},"threat_level":{"type":"string","description":"none|low|medium|high"}},"required":["rule_id"]}}},
+  {"type":"function","function":{"name":"delete_rule","description":"Propose deleting a rule; the user confirms it in the app.","parameters":{"type":"object","properties":{"rule_id":{"type":"string","description":"Rule UUID from list_rules"}},"required":["rule_id"]}}},
+  {"type":"function","function":{"name":"enable_stream","description":"Enable a stream and start capturing and analyzing its frames.","parameters":{"type":"object","properties":{"stream_id":{"type":"string","description":"Stream UUID"}},"required":["stream_id"]}}},
+  {"type":"function","function":{"name":"disable_stream","description":"Propose disabling a stream, which stops capturing its frames; the user confirms it in the app.","parameters":{"type":"object","properties":{"stream_id":{"type":"string","description":"Stream UUID"}},"required":["stream_id"]}}},
+  {"type":"function","function":{"name":"describe_snapshot","description":"Look at a camera right now: describes the stream's latest captured frame with the vision model and the risk level its rules give it. Nothing is stored.","parameters":{"type":"object","properties":{"stream_id":{"type":"string","description":"Stream UUID"}},"required":["stream_id"]}}},
+  {"type":"function","function":{"name":"event_stats","description":"Count events over a period grouped by risk level, stream, blueprint, event type or triggered rule, with how many were resolved and how fast. Prefer this over list_events for totals and trends.","parameters":{"type":"object","properties":{"group_by":{"type":"string","description":"risk_level|stream|blueprint|event_type|triggered_rule (default risk_level)"},"from":{"type":"string","description":"Start date ISO8601 (default 30 days before to)"},"to":{"type":"string","description":"End date ISO8601 (default now)"},"stream_id":{"type":"string"},"blueprint_id":{"type":"string"},"risk_level":{"type":"string","description":"One level or several separated by commas"}}}}},
+  {"type":"function","function":{"name":"resolve_events","description":"Propose marking unresolved events as resolved; the reply says how many events match and the user confirms it in the app. You can resolve by time/stream/risk OR by specific event IDs. When the user asks to resolve only certain kinds of events (e.g. people at desks), first call list_events to get events with their ids, pick the ids whose title/description match, then call resolve_events with event_ids set to that list.","parameters":{"type":"object","properties":{"from":{"type":"string","description":"Start date ISO8601 (optional)"},"to":{"type":"string","description":"End date ISO8601 (optional)"},"stream_id":{"type":"string"},"risk_level":{"type":"string"},"event_ids":{"type":"array","items":{"type":"string"},"description":"Resolve only these event UUIDs from list_events. If set, from/to/stream_id/risk_level are ignored."}}}}}
+]"#;
+
+/// What the tools act on.
+pub struct ToolContext {
+    pub state: Arc<AppState>,
+    pub manager: Arc<StreamManager>,
+    /// Conversation proposed actions belong to.
+    pub conversation_id: Option<Uuid>,
+}
+
+/// A tool's result as text for the model, and the action it proposed, if any.
+pub struct ToolOutput {
+    pub text: String,
+    pub proposed: Option<AssistantAction>,
+}
+
+impl From<String> for ToolOutput {
+    fn from(text: String) -> Self {
+        Self { text, proposed: None }
+    }
+}
+
+/// Runs tool `name`. Failures are reported in the text, so the model can
+/// explain or retry.
+pub async fn execute(ctx: &ToolContext, name: &str, args: &serde_json::Value) -> ToolOutput {
+    let db = &ctx.state.db;
+    let args_obj = args.as_object().cloned().unwrap_or_default();
+    let get = |k: &str| args_obj.get(k).and_then(|v| v.as_str()).unwrap_or("").to_string();
+
+    let text = match name {
+        "list_events" => {
+            let from = parse_opt_datetime(&get("from"));
+            let to = parse_opt_datetime(&get("to"));
+            let sid_s = get("stream_id");
+            let sid = if sid_s.trim().is_empty() { None } else { Uuid::parse_str(sid_s.trim()).ok() };
+            let rl_s = get("risk_level");
+            let risk_level = if rl_s.trim().is_empty() { None } else { Some(rl_s.trim().to_string()) };
+            let limit = args_obj.get("limit").and_then(|v| v.as_i64()).unwrap_or(50).min(200);
+            let query = EventQuery {
+                stream_id: sid,
+                risk_level,
+                from,
+                to,
+                limit,
+                exclude_frame: true,
+                ..Default::default()
+            };
+            match db::list_events(db, &query).await {
+                Ok(events) => {
+                    let txt: String = events.iter()
+                        .map(|e| format!("id:{} | {} | {} | {} | {} | {} | {}", e.id, e.captured_at.format("%Y-%m-%d %H:%M"), e.risk_level, e.title.as_deref().unwrap_or("-"), e.description, e.status, e.stream_id))
+                        .collect::<Vec<_>>().join("\n");
+                    if txt.is_empty() { "No events.".to_string() } else { format!("{} events:\n{}", events.len(), txt) }
+                }
+                Err(e) => format!("Database error: {}", e),
+            }
+        }
+        "list_streams" => text(list_streams(db, &get("name")).await),
+        "find_blueprints" => text(find_blueprints(db, &get("name")).await),
+        "list_rules" => text(list_rules(db, &args_obj).await),
+        "create_rule" => text(create_rule(db, &args_obj).await),
+        "enable_stream" => text(enable_stream(ctx, &args_obj).await),
+        "describe_snapshot" => text(describe_snapshot(ctx, &args_obj).await),
+        "event_stats" => text(stats(db, &args_obj).await),
+        "update_rule" => return proposal(update_rule(ctx, &args_obj).await),
+        "delete_rule" => return proposal(delete_rule(ctx, &args_obj).await),
+        "disable_stream" => return proposal(disable_stream(ctx, &args_obj).await),
+        "resolve_events" => return proposal(resolve_events(ctx, &args_obj).await),
+        _ => format!("Unknown tool: {}", name),
+    };
+    text.into()
+}
+
+async fn list_streams(db: &PgPool, name: &str) -> Result<String> {
+    let name = name.trim().to_lowercase();
+    let lines: Vec<String> = db::list_streams(db, None)
+        .await?
+        .iter()
+        .filter(|s| s.name.to_lowercase().contains(&name))
+        .map(|s| {
+            format!(
+                "{} | {} | {} | {} | blueprint: {}",
+                s.id,
+                s.name,
+                s.source_type,
+                if s.enabled { "enabled" } else { "disabled" },
+                s.blueprint_id.map(|id| id.to_string()).unwrap_or_else(|| "-".into())
+            )
+        })
+        .collect();
+    Ok(if lines.is_empty() { "No streams match.".into() } else { lines.join("\n") })
+}
+
+async fn find_blueprints(db: &PgPool, name: &str) -> Result<String> {
+    let name = name.trim().to_lowercase();
+    let mut lines = Vec::new();
+    for b in db::list_blueprints(db).await?.into_iter().filter(|b| b.name.to_lowercase().contains(&name)) {
+        let streams: Vec<String> = db::list_streams(db, Some(b.id)).await?.into_iter().map(|s| s.name).collect();
+        let streams = if streams.is_empty() { "none".to_string() } else { streams.join(", ") };
+        lines.push(format!("{} | {} | streams: {}", b.id, b.name, streams));
+    }
+    Ok(if lines.is_empty() { "No blueprints match.".into() } else { lines.join("\n") })
+}
+
+async fn list_rules(db: &PgPool, args: &Map<String, Value>) -> Result<String> {
+    let lines: Vec<String> = match (uuid_arg(args, "stream_id")?, uuid_arg(args, "blueprint_id")?) {
+        (Some(id), _) => {
+            db::get_stream(db, id).await?;
+            rules::effective(db, id)
+                .await?
+                .iter()
+                .map(|e| {
+                    let inherited = if e.inherited { " (inherited)" } else { "" };
+                    format!("{} | {} | {} | {}{}", e.rule.id, e.rule.threat_level, e.rule.description, e.rule.scope, inherited)
+                })
+                .collect()
+        }
+        (None, blueprint_id) => {
+            let scope = blueprint_id.map(RuleScope::Blueprint).unwrap_or(RuleScope::Global);
+            db::list_rules(db, scope)
+                .await?
+                .iter()
+                .map(|r| format!("{} | {} | {} | {}", r.id, r.threat_level, r.description, r.scope))
+                .collect()
+        }
+    };
+    Ok(if lines.is_empty() { "No rules.".into() } else { lines.join("\n") })
+}
+
+async fn create_rule(db: &PgPool, args: &Map<String, Value>) -> Result<String> {
+    let description = str_arg(args, "description").ok_or_else(|| AppError::BadRequest("description is required".into()))?;
+    let threat_level = threat_level_arg(args)?.ok_or_else(|| AppError::BadRequest("threat_level is required".into()))?;
+    // The scope is named explicitly, so a forgotten id can't turn a rule for
+    // one camera into one for all of them.
+    let scope = match str_arg(args, "scope").map(str::to_lowercase).as_deref() {
+        Some("stream") => {
+            let id = required_uuid(args, "stream_id")?;
+            db::get_stream(db, id).await?;
+            RuleScope::Stream(id)
+        }
+        Some("blueprint") => {
+            let id = required_uuid(args, "blueprint_id")?;
+            db::get_blueprint(db, id).await?;
+            RuleScope::Blueprint(id)
+        }
+        Some("global") if str_arg(args, "stream_id").is_some() || str_arg(args, "blueprint_id").is_some() => {
+            return Err(AppError::BadRequest("a global rule takes no stream_id or blueprint_id".into()));
+        }
+        Some("global") => RuleScope::Global,
+        _ => return Err(AppError::BadRequest("scope must be stream, blueprint or global".into())),
+    };
+    let req = CreateRuleRequest {
+        description: description.to_string(),
+        threat_level: threat_level.as_str().to_string(),
+        position: 0,
+        schedule: None,
+        zone: None,
+        min_count: None,
+        min_dwell_sec: None,
+        event_types: Vec::new(),
+    };
+    let rule = db::create_rule(db, scope, &req).await?;
+    Ok(format!("Created {} rule {}: {} ({}).", rule.scope, rule.id, rule.description, rule.threat_level))
+}
+
+async fn update_rule(ctx: &ToolContext, args: &Map<String, Value>) -> Result<ToolOutput> {
+    let rule = db::get_rule_by_id(&ctx.state.db, required_uuid(args, "rule_id")?).await?;
+    let description = str_arg(args, "description");
+    let threat_level = threat_level_arg(args)?;
+    if description.is_none() && threat_level.is_none() {
+        return Err(AppError::BadRequest("give a new description or threat_level".into()));
+    }
+    let summary = format!(
+        "Change {} rule \"{}\" ({}) to \"{}\" ({})",
+        rule.scope,
+        rule.description,
+        rule.threat_level,
+        description.unwrap_or(&rule.description),
+        threat_level.map(|l| l.as_str()).unwrap_or(&rule.threat_level)
+    );
+    let change = Change::UpdateRule {
+        rule_id: rule.id,
+        description: description.map(str::to_string),
+        threat_level: threat_level.map(|l| l.as_str().to_string()),
+    };
+    propose(ctx, change, summary).await
+}
+
+async fn delete_rule(ctx: &ToolContext, args: &Map<String, Value>) -> Result<ToolOutput> {
+    let rule = db::get_rule_by_id(&ctx.state.db, required_uuid(args, "rule_id")?).await?;
+    let summary = format!("Delete {} rule \"{}\" ({})", rule.scope, rule.description, rule.threat_level);
+    propose(ctx, Change::DeleteRule { rule_id: rule.id }, summary).await
+}
+
+async fn enable_stream(ctx: &ToolContext, args: &Map<String, Value>) -> Result<String> {
+    let id = required_uuid(args, "stream_id")?;
+    let stream = db::get_stream(&ctx.state.db, id).await?;
+    if stream.enabled {
+        return Ok(format!("Stream {} is already enabled.", stream.name));
+    }
+    let stream = db::set_stream_enabled(&ctx.state.db, id, true).await?;
+    let record = StreamRecord {
+        id: stream.id,
+        name: stream.name.clone(),
+        source_type: stream.source_type.clone(),
+        source_url: stream.source_url.clone(),
+        capture_interval_sec: stream.capture_interval_sec,
+        enabled: true,
+    };
+    ctx.manager.start_stream(record).await;
+    Ok(format!("Enabled stream {}.", stream.name))
+}
+
+async fn disable_stream(ctx: &ToolContext, args: &Map<String, Value>) -> Result<ToolOutput> {
+    let stream = db::get_stream(&ctx.state.db, required_uuid(args, "stream_id")?).await?;
+    if !stream.enabled {
+        return Ok(format!("Stream {} is already disabled.", stream.name).into());
+    }
+    let summary = format!("Disable stream {} and stop analyzing its frames", stream.name);
+    propose(ctx, Change::DisableStream { stream_id: stream.id }, summary).await
+}
+
+/// The preview fixes which events confirming resolves: those unresolved now.
+async fn resolve_events(ctx: &ToolContext, args: &Map<String, Value>) -> Result<ToolOutput> {
+    let db = &ctx.state.db;
+    let max = ctx.state.assistant.max_action_rows;
+    let listed: Vec<Uuid> = args
+        .get("event_ids")
+        .and_then(|v| v.as_array())
+        .map(|arr| arr.iter().filter_map(|v| v.as_str().and_then(|s| Uuid::parse_str(s.trim()).ok())).collect())
+        .unwrap_or_default();
+
+    // e.g. "high risk events on stream Dock from 2026-10-18 00:00"
+    let (event_ids, which) = if !listed.is_empty() {
+        let ids = db::unresolved_event_ids_in(db, &listed).await?;
+        (ids, format!("events of the {} listed", listed.len()))
+    } else {
+        let from = str_arg(args, "from").and_then(parse_opt_datetime);
+        let to = str_arg(args, "to").and_then(parse_opt_datetime);
+        let stream_id = uuid_arg(args, "stream_id")?;
+        let risk_level = str_arg(args, "risk_level");
+        let ids = db::unresolved_event_ids(db, from, to, stream_id, risk_level, max + 1).await?;
+        if ids.len() as i64 > max {
+            return Err(AppError::BadRequest(format!(
+                "more than {max} unresolved events match, and one assistant action may resolve at most {max}; \
+                 narrow it down by time range, stream or risk level"
+            )));
+        }
+        let mut which = match risk_level {
+            Some(level) => format!("{level} risk events"),
+            None => "events".to_string(),
+        };
+        if let Some(id) = stream_id {
+            which.push_str(&format!(" on stream {}", db::get_stream(db, id).await?.name));
+        }
+        if let Some(from) = from {
+            which.push_str(&format!(" from {}", from.format("%Y-%m-%d %H:%M")));
+        }
+        if let Some(to) = to {
+            which.push_str(&format!(" until {}", to.format("%Y-%m-%d %H:%M")));
+        }
+        (ids, which)
+    };
+    if event_ids.is_empty() {
+        return Ok(format!("No unresolved {which}; nothing to resolve.").into());
+    }
+    let summary = format!("Resolve {} unresolved {which}", event_ids.len());
+    propose(ctx, Change::ResolveEvents { event_ids }, summary).await
+}
+
+/// The stream's VLM on its latest frame, with the rules in force now, as in
+/// the VLM profile test. Nothing is stored or alerted.
+async fn describe_snapshot(ctx: &ToolContext, args: &Map<String, Value>) -> Result<String> {
+    let db = &ctx.state.db;
+    let stream = db::get_stream(db, required_uuid(args, "stream_id")?).await?;
+    let frame = ctx
+        .state
+        .frame_store
+        .get_latest(stream.id)
+        .await
+        .ok_or_else(|| AppError::NotFound(format!("No frame captured yet for stream {}", stream.name)))?;
+    let active_rules = rules::active_at(rules::effective_rules(db, stream.id).await?, Utc::now());
+    let exclusions = db::approved_feedback_clauses(db, stream.id).await?;
+
+    let (image, _, mapping) = preprocess::prepare_frame(db, stream.id, &frame).await?;
+    let rules = references::vlm_rules(db, &active_rules, &mapping).await?;
+    let vlm = ctx.state.vlm.for_stream(db, stream.id).await?;
+    let result = vlm.analyze(&image, &stream.name, &rules, &exclusions).await?.result;
+
+    let mut out = format!("Latest frame from {}:", stream.name);
+    if let Some(title) = &result.title {
+        out.push_str(&format!(" {title}."));
+    }
+    out.push_str(&format!("\n{}\nRisk level: {}", result.description, result.risk_level.as_str()));
+    if let Some(rule) = result.triggered_rule.filter(|_| !rules.is_empty()) {
+        out.push_str(&format!(" (rule: {rule})"));
+    }
+    Ok(out)
+}
+
+async fn stats(db: &PgPool, args: &Map<String, Value>) -> Result<String> {
+    let to = str_arg(args, "to").and_then(parse_opt_datetime).unwrap_or_else(Utc::now);
+    let from = str_arg(args, "from").and_then(parse_opt_datetime).unwrap_or(to - Duration::days(DEFAULT_STATS_DAYS));
+    let stream_id = uuid_arg(args, "stream_id")?;
+    let blueprint_id = uuid_arg(args, "blueprint_id")?;
+    let risk_level = str_arg(args, "risk_level").map(str::to_string);
+
+    let counts = event_stats::counts(
+        db,
+        &EventCountsQuery {
+            bucket: Some("day".into()),
+            group_by: Some(str_arg(args, "group_by").unwrap_or("risk_level").to_string()),
+            stream_id,
+            blueprint_id,
+            risk_level: risk_level.clone(),
+            from: Some(from),
+            to: Some(to),
+            utc_offset_minutes: 0,
+        },
+    )
+    .await?;
+    let resolution = event_stats::resolution(
+        db,
+        &StatsQuery { stream_id, blueprint_id, risk_level, from: Some(from), to: Some(to), utc_offset_minutes: 0 },
+    )
+    .await?;
+
+    let mut out = format!(
+        "Events from {} to {} by {}:\n",
+        from.format("%Y-%m-%d %H:%M"),
+        to.format("%Y-%m-%d %H:%M"),
+        counts.group_by
+    );
+    if counts.totals.is_empty() {
+        out.push_str("No events.\n");
+    }
+    for t in &counts.totals {
+        out.push_str(&format!("- {}: {}\n", render::total_label(t), t.count));
+    }
+    if let Some(all) = resolution.iter().find(|r| r.risk_level.is_none()) {
+        out.push_str(&format!(
+            "Resolved {} of {}; median time to resolve {}.",
+            all.resolved,
+            all.events,
+            all.median_seconds.map(render::duration).unwrap_or_else(|| "n/a".into())
+        ));
+    }
+    Ok(out)
+}
+
+fn text(result: Result<String>) -> String {
+    result.unwrap_or_else(|e| format!("Error: {e}"))
+}
+
+fn proposal(result: Result<ToolOutput>) -> ToolOutput {
+    result.unwrap_or_else(|e| format!("Error: {e}").into())
+}
+
+async fn propose(ctx: &ToolContext, change: Change, summary: String) -> Result<ToolOutput> {
+    let action = actions::propose(ctx, change, summary).await?;
+    let text = format!(
+        "Proposed action {}: {}. Nothing has changed yet: the user has to confirm it in the app before {}. \
+         Tell them what it will do.",
+        action.id,
+        action.summary,
+        action.expires_at.format("%H:%M UTC")
+    );
+    Ok(ToolOutput { text, proposed: Some(action) })
+}
+
+/// A non-empty string argument.
+fn str_arg<'a>(args: &'a Map<String, Value>, key: &str) -> Option<&'a str> {
+    args.get(key).and_then(Value::as_str).map(str::trim).filter(|s| !s.is_empty())
+}
+
+fn uuid_arg(args: &Map<String, Value>, key: &str) -> Result<Option<Uuid>> {
+    str_arg(args, key)
+        .map(|s| Uuid::parse_str(s).map_err(|_| AppError::BadRequest(format!("{key} must be a UUID, got '{s}'"))))
+        .transpose()
+}
+
+fn required_uuid(args: &Map<String, Value>, key: &str) -> Result<Uuid> {
+    uuid_arg(args, key)?.ok_or_else(|| AppError::BadRequest(format!("{key} is required")))
+}
+
+fn threat_level_arg(args: &Map<String, Value>) -> Result<Option<RiskLevel>> {
+    str_arg(args, "threat_level")
+        .map(|s| s.to_lowercase().parse::<RiskLevel>().map_err(AppError::BadRequest))
+        .transpose()
+}
+
+fn parse_opt_datetime(s: &str) -> Option<DateTime<Utc>> {
+    let s = s.trim();
+    if s.is_empty() { return None; }
+    DateTime::parse_from_rfc3339(s).ok().map(|d| d.with_timezone(&Utc))
+        .or_else(|| chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").ok().map(|n| n.and_utc()))
+        .or_else(|| chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d").ok().map(|d| d.and_hms_opt(0,0,0).unwrap().and_utc()))
+}
@@ -0,0 +1,58 @@
+//! Command-line subcommands, run instead of the HTTP server when given.
+//!
+//! `cipher-shield-backend eval <dataset_id> [--profile <id>] [--model <name>] [--base-url <url>]`
+//!   Runs a labeled dataset through the configured VLM backend, or a VLM
+//!   profile's, and prints the report as JSON. `--base-url` points the backend
+//!   at another server, e.g. a mock VLM.
+
+use anyhow::{bail, Context, Result};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    analysis::{eval, vlm::registry::VlmRegistry},
+    config::AppConfig,
+};
+
+const USAGE: &str =
+    "usage: cipher-shield-backend eval <dataset_id> [--profile <id>] [--model <name>] [--base-url <url>]";
+
+pub async fn run(cmd: &str, args: &[String], cfg: &AppConfig, db: &PgPool) -> Result<()> {
+    match cmd {
+        "eval" => run_eval(args, cfg, db).await,
+        other => bail!("Unknown command '{other}'\n{USAGE}"),
+    }
+}
+
+async fn run_eval(args: &[String], cfg: &AppConfig, db: &PgPool) -> Result<()> {
+    let mut dataset_id = None;
+    let mut profile_id = None;
+    let mut model = None;
+    let mut base_url = None;
+
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--profile" => {
+                let id = it.next().context(USAGE)?;
+                profile_id = Some(Uuid::parse_str(id).context("--profile must be a UUID")?);
+            }
+            "--model" => model = Some(it.next().context(USAGE)?.as_str()),
+            "--base-url" => base_url = Some(it.next().context(USAGE)?.as_str()),
+            id if dataset_id.is_none() => {
+                dataset_id = Some(Uuid::parse_str(id).context("dataset_id must be a UUID")?);
+            }
+            other => bail!("Unexpected argument '{other}'\n{USAGE}"),
+        }
+    }
+    let dataset_id = dataset_id.context(USAGE)?;
+
+    let registry = VlmRegistry::new(cfg);
+    let mut backend = eval::run_backend(db, &registry, profile_id, model).await?;
+    if let Some(url) = base_url {
+        backend = backend.with_base_url(url);
+    }
+    let report = eval::run_dataset(db, &registry, &backend, dataset_id).await?;
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
+}
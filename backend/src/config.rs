@@ -1,6 +1,10 @@
 use anyhow::{Context, Result};
 use std::env;
 
+/// Upper bound on `VLM_BATCH_SIZE`: beyond a 4×4 grid tiles get too small
+/// for the model to see much.
+const MAX_BATCH_SIZE: usize = 16;
+
 #[derive(Debug, Clone)]
 pub struct ServerConfig {
     pub host: String,
@@ -11,20 +15,166 @@ pub struct ServerConfig {
 pub enum VlmBackend {
     Ollama(OllamaConfig),
     OpenAiCompat(OpenAiCompatConfig),
+    /// llama.cpp `llama-server` started with a multimodal projector (`--mmproj`).
+    LlamaCpp(OpenAiCompatConfig),
+    /// vLLM's OpenAI-compatible server.
+    Vllm(OpenAiCompatConfig),
+}
+
+impl VlmBackend {
+    /// Short backend name as stored on events, e.g. "ollama".
+    pub fn name(&self) -> &'static str {
+        match self {
+            VlmBackend::Ollama(_) => "ollama",
+            VlmBackend::OpenAiCompat(_) => "openai_compat",
+            VlmBackend::LlamaCpp(_) => "llama_cpp",
+            VlmBackend::Vllm(_) => "vllm",
+        }
+    }
+
+    pub fn model(&self) -> &str {
+        match self {
+            VlmBackend::Ollama(c) => &c.model,
+            VlmBackend::OpenAiCompat(c) | VlmBackend::LlamaCpp(c) | VlmBackend::Vllm(c) => &c.model,
+        }
+    }
+
+    pub fn base_url(&self) -> &str {
+        match self {
+            VlmBackend::Ollama(c) => &c.base_url,
+            VlmBackend::OpenAiCompat(c) | VlmBackend::LlamaCpp(c) | VlmBackend::Vllm(c) => &c.base_url,
+        }
+    }
+
+    /// (connect, request) timeouts in seconds.
+    pub fn timeouts(&self) -> (Option<u64>, Option<u64>) {
+        match self {
+            VlmBackend::Ollama(c) => (c.connect_timeout_sec, c.request_timeout_sec),
+            VlmBackend::OpenAiCompat(c) | VlmBackend::LlamaCpp(c) | VlmBackend::Vllm(c) => {
+                (c.connect_timeout_sec, c.request_timeout_sec)
+            }
+        }
+    }
+
+    /// The same backend and endpoint, talking to a different model.
+    pub fn with_model(&self, model: &str) -> VlmBackend {
+        let mut backend = self.clone();
+        match &mut backend {
+            VlmBackend::Ollama(c) => c.model = model.to_string(),
+            VlmBackend::OpenAiCompat(c) | VlmBackend::LlamaCpp(c) | VlmBackend::Vllm(c) => {
+                c.model = model.to_string()
+            }
+        }
+        backend
+    }
+
+    /// The same backend type and model at a different endpoint (e.g. a mock server).
+    pub fn with_base_url(&self, base_url: &str) -> VlmBackend {
+        let mut backend = self.clone();
+        match &mut backend {
+            VlmBackend::Ollama(c) => c.base_url = base_url.to_string(),
+            VlmBackend::OpenAiCompat(c) | VlmBackend::LlamaCpp(c) | VlmBackend::Vllm(c) => {
+                c.base_url = base_url.to_string()
+            }
+        }
+        backend
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct OllamaConfig {
     pub base_url: String,
     pub model: String,
+    pub connect_timeout_sec: Option<u64>,
+    pub request_timeout_sec: Option<u64>,
+    /// Passed as `num_predict`; None = the model's default.
+    pub max_tokens: Option<u32>,
+    /// Send rule reference images along with the frame.
+    pub multi_image: bool,
 }
 
 /// Works with HuggingFace TGI, OpenAI GPT-4o, or any /v1/chat/completions provider.
+/// Also the settings for the llama.cpp and vLLM backends (`api_key` may be empty).
 #[derive(Debug, Clone)]
 pub struct OpenAiCompatConfig {
     pub base_url: String,
     pub api_key: String,
     pub model: String,
+    pub connect_timeout_sec: Option<u64>,
+    pub request_timeout_sec: Option<u64>,
+    /// None = 512.
+    pub max_tokens: Option<u32>,
+    /// Send rule reference images along with the frame.
+    pub multi_image: bool,
+}
+
+/// Retry / failover policy for the primary VLM (and any fallback).
+#[derive(Debug, Clone)]
+pub struct VlmResilienceConfig {
+    /// Extra attempts per backend on transient errors (timeouts, 429, 5xx).
+    pub max_retries: u32,
+    /// Delay before the first retry; doubles on each further retry.
+    pub retry_backoff_ms: u64,
+    /// Consecutive failures after which a backend's circuit opens.
+    pub breaker_threshold: u32,
+    /// How long an open circuit skips its backend before trying it again.
+    pub breaker_cooldown_sec: u64,
+}
+
+/// A candidate VLM run alongside the primary one on a sample of frames.
+/// Its results are stored for comparison and never alert.
+#[derive(Debug, Clone)]
+pub struct ShadowConfig {
+    pub vlm: VlmBackend,
+    /// Fraction of analyzed frames also sent to the shadow backend (0.0 – 1.0).
+    pub sample_rate: f64,
+}
+
+/// Tiled batching: frames from several streams that arrive within one window
+/// are composed into a grid and analyzed in a single VLM call.
+#[derive(Debug, Clone)]
+pub struct BatchConfig {
+    /// Max frames per call; 1 turns batching off.
+    pub size: usize,
+    /// How long a worker waits for more frames after the first one.
+    pub window_ms: u64,
+    /// Width of each tile in the grid (tiles are 16:9).
+    pub tile_width: u32,
+}
+
+/// Ed25519 key that signs evidence exports.
+#[derive(Debug, Clone)]
+pub struct EvidenceConfig {
+    /// Base64 32-byte seed from `EVIDENCE_SIGNING_KEY`; takes precedence over the file.
+    pub signing_key: Option<String>,
+    /// Holds the seed when no key is given; created on first start.
+    pub key_file: String,
+    /// How often each stream's evidence chain head is signed; 0 = only on request.
+    pub checkpoint_interval_sec: u64,
+}
+
+/// Scheduled digest reports.
+#[derive(Debug, Clone)]
+pub struct ReportsConfig {
+    /// How often due report schedules are looked for; 0 = schedules never run.
+    pub check_interval_sec: u64,
+    /// Where this server is reachable from a phone, for the report link in
+    /// SMS digests; no link when unset.
+    pub public_url: Option<String>,
+}
+
+/// The assistant's chat model, and limits on the destructive actions it may propose.
+#[derive(Debug, Clone)]
+pub struct AssistantConfig {
+    /// Chat model with tool calling, also used for report summaries. Any
+    /// backend kind works; llama.cpp and vLLM are spoken to through their
+    /// OpenAI-compatible API.
+    pub chat: VlmBackend,
+    /// Most rows (e.g. events to resolve) one action may change; larger ones
+    /// are refused and the model is asked to narrow them down.
+    pub max_action_rows: i64,
+    /// How long a proposed action can be confirmed.
+    pub action_ttl_sec: u64,
 }
 
 #[derive(Debug, Clone)]
@@ -32,8 +182,21 @@ pub struct AppConfig {
     pub server: ServerConfig,
     pub database_url: String,
     pub vlm: VlmBackend,
+    /// Backend tried when the primary fails or its circuit is open.
+    pub vlm_fallback: Option<VlmBackend>,
+    pub vlm_resilience: VlmResilienceConfig,
+    pub shadow: Option<ShadowConfig>,
     pub analysis_workers: usize,
     pub frame_queue_size: usize,
+    /// Max concurrent requests per VLM endpoint (base URL), shared by every
+    /// stream and profile using it.
+    pub vlm_max_in_flight: usize,
+    /// Frames older than this when a worker picks them up are dropped; 0 = never.
+    pub max_frame_age_sec: u64,
+    pub batch: BatchConfig,
+    pub evidence: EvidenceConfig,
+    pub reports: ReportsConfig,
+    pub assistant: AssistantConfig,
 }
 
 impl AppConfig {
@@ -51,21 +214,62 @@ impl AppConfig {
         let database_url = env::var("DATABASE_URL").context("DATABASE_URL is required")?;
 
         let vlm_backend = env::var("VLM_BACKEND").unwrap_or_else(|_| "ollama".into());
-        let vlm = match vlm_backend.as_str() {
-            "ollama" => VlmBackend::Ollama(OllamaConfig {
-                base_url: env::var("OLLAMA_BASE_URL")
-                    .unwrap_or_else(|_| "http://localhost:11434".into()),
-                model: env::var("OLLAMA_MODEL").unwrap_or_else(|_| "moondream".into()),
-            }),
-            "openai_compat" => VlmBackend::OpenAiCompat(OpenAiCompatConfig {
-                base_url: env::var("OPENAI_COMPAT_BASE_URL")
-                    .context("OPENAI_COMPAT_BASE_URL is required for openai_compat backend")?,
-                api_key: env::var("OPENAI_COMPAT_API_KEY")
-                    .context("OPENAI_COMPAT_API_KEY is required for openai_compat backend")?,
-                model: env::var("OPENAI_COMPAT_MODEL")
-                    .unwrap_or_else(|_| "Qwen/Qwen2-VL-7B-Instruct".into()),
-            }),
-            other => anyhow::bail!("Unknown VLM_BACKEND: '{}'. Use 'ollama' or 'openai_compat'.", other),
+        let vlm = vlm_backend_from_env(&vlm_backend)?;
+
+        // Fallback backend, configured like shadow mode below.
+        let vlm_fallback = match env::var("VLM_FALLBACK_BACKEND") {
+            Ok(kind) if !kind.trim().is_empty() => {
+                let mut fallback = vlm_backend_from_env(kind.trim())?;
+                if let Ok(model) = env::var("VLM_FALLBACK_MODEL") {
+                    fallback = fallback.with_model(&model);
+                }
+                if let Ok(url) = env::var("VLM_FALLBACK_BASE_URL") {
+                    fallback = fallback.with_base_url(&url);
+                }
+                Some(fallback)
+            }
+            _ => None,
+        };
+
+        let vlm_resilience = VlmResilienceConfig {
+            max_retries: env::var("VLM_MAX_RETRIES")
+                .unwrap_or_else(|_| "2".into())
+                .parse()
+                .context("VLM_MAX_RETRIES must be a non-negative integer")?,
+            retry_backoff_ms: env::var("VLM_RETRY_BACKOFF_MS")
+                .unwrap_or_else(|_| "500".into())
+                .parse()
+                .context("VLM_RETRY_BACKOFF_MS must be a non-negative integer")?,
+            breaker_threshold: env::var("VLM_BREAKER_THRESHOLD")
+                .unwrap_or_else(|_| "5".into())
+                .parse::<u32>()
+                .context("VLM_BREAKER_THRESHOLD must be a positive integer")?
+                .max(1),
+            breaker_cooldown_sec: env::var("VLM_BREAKER_COOLDOWN_SEC")
+                .unwrap_or_else(|_| "30".into())
+                .parse()
+                .context("VLM_BREAKER_COOLDOWN_SEC must be a non-negative integer")?,
+        };
+
+        // Shadow mode: a candidate backend run on a sample of frames. Its
+        // settings default to the same env vars as the primary backend of that
+        // type, with SHADOW_VLM_MODEL / SHADOW_VLM_BASE_URL overriding.
+        let shadow = match env::var("SHADOW_VLM_BACKEND") {
+            Ok(kind) if !kind.trim().is_empty() => {
+                let mut shadow_vlm = vlm_backend_from_env(kind.trim())?;
+                if let Ok(model) = env::var("SHADOW_VLM_MODEL") {
+                    shadow_vlm = shadow_vlm.with_model(&model);
+                }
+                if let Ok(url) = env::var("SHADOW_VLM_BASE_URL") {
+                    shadow_vlm = shadow_vlm.with_base_url(&url);
+                }
+                let sample_rate: f64 = env::var("SHADOW_SAMPLE_RATE")
+                    .unwrap_or_else(|_| "0.1".into())
+                    .parse()
+                    .context("SHADOW_SAMPLE_RATE must be a number between 0 and 1")?;
+                Some(ShadowConfig { vlm: shadow_vlm, sample_rate: sample_rate.clamp(0.0, 1.0) })
+            }
+            _ => None,
         };
 
         let analysis_workers = env::var("ANALYSIS_WORKERS")
@@ -78,12 +282,181 @@ impl AppConfig {
             .parse()
             .context("FRAME_QUEUE_SIZE must be a positive integer")?;
 
+        let vlm_max_in_flight = env::var("VLM_MAX_IN_FLIGHT")
+            .unwrap_or_else(|_| "2".into())
+            .parse::<usize>()
+            .context("VLM_MAX_IN_FLIGHT must be a positive integer")?
+            .max(1);
+
+        let max_frame_age_sec = env::var("MAX_FRAME_AGE_SEC")
+            .unwrap_or_else(|_| "30".into())
+            .parse()
+            .context("MAX_FRAME_AGE_SEC must be a non-negative integer")?;
+
+        let batch = BatchConfig {
+            size: env::var("VLM_BATCH_SIZE")
+                .unwrap_or_else(|_| "1".into())
+                .parse::<usize>()
+                .context("VLM_BATCH_SIZE must be a positive integer")?
+                .clamp(1, MAX_BATCH_SIZE),
+            window_ms: env::var("VLM_BATCH_WINDOW_MS")
+                .unwrap_or_else(|_| "1000".into())
+                .parse()
+                .context("VLM_BATCH_WINDOW_MS must be a non-negative integer")?,
+            tile_width: env::var("VLM_BATCH_TILE_WIDTH")
+                .unwrap_or_else(|_| "640".into())
+                .parse::<u32>()
+                .context("VLM_BATCH_TILE_WIDTH must be a positive integer")?
+                .clamp(64, 1920),
+        };
+
+        let evidence = EvidenceConfig {
+            signing_key: env::var("EVIDENCE_SIGNING_KEY").ok().filter(|k| !k.trim().is_empty()),
+            key_file: env::var("EVIDENCE_KEY_FILE").unwrap_or_else(|_| "evidence_signing.key".into()),
+            checkpoint_interval_sec: env::var("EVIDENCE_CHECKPOINT_INTERVAL_SEC")
+                .unwrap_or_else(|_| "3600".into())
+                .parse()
+                .context("EVIDENCE_CHECKPOINT_INTERVAL_SEC must be a non-negative integer")?,
+        };
+
+        let reports = ReportsConfig {
+            check_interval_sec: env::var("REPORT_CHECK_INTERVAL_SEC")
+                .unwrap_or_else(|_| "60".into())
+                .parse()
+                .context("REPORT_CHECK_INTERVAL_SEC must be a non-negative integer")?,
+            public_url: env::var("PUBLIC_BASE_URL")
+                .ok()
+                .map(|u| u.trim().trim_end_matches('/').to_string())
+                .filter(|u| !u.is_empty()),
+        };
+
+        // The assistant's chat model: a backend of ASSISTANT_BACKEND's kind
+        // (default: VLM_BACKEND's), set up by the same env vars, with
+        // ASSISTANT_MODEL / ASSISTANT_BASE_URL overriding. Vision models
+        // rarely call tools, so with Ollama the model defaults to
+        // OLLAMA_ASSISTANT_MODEL (ASSISTANT_MODEL's older name), then qwen3,
+        // rather than OLLAMA_MODEL.
+        let chat_backend = env::var("ASSISTANT_BACKEND")
+            .ok()
+            .map(|kind| kind.trim().to_string())
+            .filter(|kind| !kind.is_empty())
+            .unwrap_or_else(|| vlm_backend.clone());
+        let mut chat = vlm_backend_from_env(&chat_backend)?;
+        let chat_model = match &chat {
+            VlmBackend::Ollama(_) => Some(
+                env::var("ASSISTANT_MODEL")
+                    .or_else(|_| env::var("OLLAMA_ASSISTANT_MODEL"))
+                    .unwrap_or_else(|_| "qwen3".into()),
+            ),
+            _ => env::var("ASSISTANT_MODEL").ok(),
+        };
+        if let Some(model) = chat_model {
+            chat = chat.with_model(&model);
+        }
+        if let Ok(url) = env::var("ASSISTANT_BASE_URL") {
+            chat = chat.with_base_url(&url);
+        }
+
+        let assistant = AssistantConfig {
+            chat,
+            max_action_rows: env::var("ASSISTANT_MAX_ACTION_ROWS")
+                .unwrap_or_else(|_| "100".into())
+                .parse::<i64>()
+                .context("ASSISTANT_MAX_ACTION_ROWS must be a positive integer")?
+                .max(1),
+            action_ttl_sec: env::var("ASSISTANT_ACTION_TTL_SEC")
+                .unwrap_or_else(|_| "900".into())
+                .parse()
+                .context("ASSISTANT_ACTION_TTL_SEC must be a non-negative integer")?,
+        };
+
         Ok(AppConfig {
             server,
             database_url,
             vlm,
+            vlm_fallback,
+            vlm_resilience,
+            shadow,
             analysis_workers,
             frame_queue_size,
+            vlm_max_in_flight,
+            max_frame_age_sec,
+            batch,
+            evidence,
+            reports,
+            assistant,
         })
     }
 }
+
+/// Builds a backend of the given kind ("ollama" | "openai_compat" | "llama_cpp" |
+/// "vllm") from its env vars.
+/// VLM_CONNECT_TIMEOUT_SEC / VLM_REQUEST_TIMEOUT_SEC / VLM_MULTI_IMAGE apply to
+/// every backend.
+fn vlm_backend_from_env(kind: &str) -> Result<VlmBackend> {
+    let connect_timeout_sec = Some(timeout_secs("VLM_CONNECT_TIMEOUT_SEC", "10")?);
+    let request_timeout_sec = Some(timeout_secs("VLM_REQUEST_TIMEOUT_SEC", "120")?);
+    let multi_image = env::var("VLM_MULTI_IMAGE")
+        .unwrap_or_else(|_| "true".into())
+        .parse()
+        .context("VLM_MULTI_IMAGE must be true or false")?;
+
+    Ok(match kind {
+        "ollama" => VlmBackend::Ollama(OllamaConfig {
+            base_url: env::var("OLLAMA_BASE_URL")
+                .unwrap_or_else(|_| "http://localhost:11434".into()),
+            model: env::var("OLLAMA_MODEL").unwrap_or_else(|_| "moondream".into()),
+            connect_timeout_sec,
+            request_timeout_sec,
+            max_tokens: None,
+            multi_image,
+        }),
+        "openai_compat" => VlmBackend::OpenAiCompat(OpenAiCompatConfig {
+            base_url: env::var("OPENAI_COMPAT_BASE_URL")
+                .context("OPENAI_COMPAT_BASE_URL is required for openai_compat backend")?,
+            api_key: env::var("OPENAI_COMPAT_API_KEY")
+                .context("OPENAI_COMPAT_API_KEY is required for openai_compat backend")?,
+            model: env::var("OPENAI_COMPAT_MODEL")
+                .unwrap_or_else(|_| "Qwen/Qwen2-VL-7B-Instruct".into()),
+            connect_timeout_sec,
+            request_timeout_sec,
+            max_tokens: None,
+            multi_image,
+        }),
+        "llama_cpp" => VlmBackend::LlamaCpp(OpenAiCompatConfig {
+            base_url: env::var("LLAMACPP_BASE_URL").unwrap_or_else(|_| "http://localhost:8080/v1".into()),
+            api_key: env::var("LLAMACPP_API_KEY").unwrap_or_default(),
+            // llama-server serves whatever model it was started with; this is
+            // only recorded on events.
+            model: env::var("LLAMACPP_MODEL").unwrap_or_else(|_| "llama-server".into()),
+            connect_timeout_sec,
+            request_timeout_sec,
+            max_tokens: None,
+            multi_image,
+        }),
+        "vllm" => VlmBackend::Vllm(OpenAiCompatConfig {
+            base_url: env::var("VLLM_BASE_URL").unwrap_or_else(|_| "http://localhost:8000/v1".into()),
+            api_key: env::var("VLLM_API_KEY").unwrap_or_default(),
+            model: env::var("VLLM_MODEL").unwrap_or_else(|_| "Qwen/Qwen2-VL-7B-Instruct".into()),
+            connect_timeout_sec,
+            request_timeout_sec,
+            max_tokens: None,
+            multi_image,
+        }),
+        other => anyhow::bail!(
+            "Unknown VLM backend: '{}'. Use 'ollama', 'openai_compat', 'llama_cpp' or 'vllm'.",
+            other
+        ),
+    })
+}
+
+/// A timeout in seconds from env var `var`. Zero is rejected: every request
+/// would time out at once.
+fn timeout_secs(var: &str, default: &str) -> Result<u64> {
+    env::var(var)
+        .unwrap_or_else(|_| default.into())
+        .parse::<u64>()
+        .ok()
+        .filter(|&secs| secs > 0)
+        .with_context(|| format!("{var} must be a positive integer"))
+}
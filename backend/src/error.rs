@@ -13,8 +13,10 @@ pub enum AppError {
     #[error("VLM error: {0}")]
     Vlm(String),
 
-    #[error("Stream error: {0}")]
-    Stream(String),
+    /// The VLM could not be reached or is overloaded (connect error, timeout,
+    /// HTTP 429/5xx). Worth retrying, unlike `Vlm`.
+    #[error("VLM unavailable: {0}")]
+    VlmUnavailable(String),
 
     #[error("Not found: {0}")]
     NotFound(String),
@@ -22,6 +24,10 @@ pub enum AppError {
     #[error("Bad request: {0}")]
     BadRequest(String),
 
+    /// The request clashes with work already in progress.
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -31,9 +37,10 @@ impl IntoResponse for AppError {
         let (status, message) = match &self {
             AppError::Database(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
             AppError::Vlm(_) => (StatusCode::BAD_GATEWAY, self.to_string()),
-            AppError::Stream(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
+            AppError::VlmUnavailable(_) => (StatusCode::SERVICE_UNAVAILABLE, self.to_string()),
             AppError::NotFound(_) => (StatusCode::NOT_FOUND, self.to_string()),
             AppError::BadRequest(_) => (StatusCode::BAD_REQUEST, self.to_string()),
+            AppError::Conflict(_) => (StatusCode::CONFLICT, self.to_string()),
             AppError::Other(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
         };
 
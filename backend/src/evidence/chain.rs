@@ -0,0 +1,146 @@
+//! Per-stream hash chain over analysis events. The database links each event
+//! to the previous one as it is stored (see `event_content_hash` in the
+//! migrations); this module signs chain heads as checkpoints and verifies
+//! chains, reporting every altered frame, altered event, broken link, missing
+//! event and bad checkpoint.
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{
+    error::Result,
+    evidence::signing::EvidenceSigner,
+    storage::{
+        db,
+        models::{ChainBreak, ChainVerification, EvidenceCheckpoint, StreamChainStatus},
+    },
+};
+
+/// Breaks listed per stream; the rest are only counted.
+const MAX_BREAKS_LISTED: usize = 100;
+
+/// Signs every chain head that moved since its last checkpoint.
+pub async fn checkpoint(db: &PgPool, signer: &EvidenceSigner) -> Result<Vec<EvidenceCheckpoint>> {
+    let key_id = signer.key_id();
+    let mut created = Vec::new();
+    for head in db::unsigned_chain_heads(db).await? {
+        // Postgres keeps microseconds; sign the time as it will be read back.
+        let now = DateTime::from_timestamp_micros(Utc::now().timestamp_micros()).unwrap_or_else(Utc::now);
+        let signature = signer.sign(checkpoint_message(head.stream_id, head.seq, &head.head_hash, now).as_bytes());
+        created.push(db::insert_checkpoint(db, &head, &key_id, &signature, now).await?);
+    }
+    Ok(created)
+}
+
+/// Signs chain heads every `interval_sec` seconds.
+pub async fn run_checkpoints(db: PgPool, signer: Arc<EvidenceSigner>, interval_sec: u64) {
+    let mut interval = tokio::time::interval(Duration::from_secs(interval_sec));
+    loop {
+        interval.tick().await;
+        match checkpoint(&db, &signer).await {
+            Ok(created) if !created.is_empty() => info!(streams = created.len(), "Signed evidence checkpoints"),
+            Ok(_) => {}
+            Err(e) => warn!("Evidence checkpoint failed: {e}"),
+        }
+    }
+}
+
+/// Recomputes every hash in the chains of `stream_id` (or of all streams) and
+/// checks each checkpoint against its signature and the event it signed.
+pub async fn verify(db: &PgPool, signer: &EvidenceSigner, stream_id: Option<Uuid>) -> Result<ChainVerification> {
+    if let Some(id) = stream_id {
+        db::get_stream(db, id).await?;
+    }
+    let verified_at = Utc::now();
+
+    let mut breaks: HashMap<Uuid, Vec<ChainBreak>> = HashMap::new();
+    for row in db::chain_breaks(db, stream_id).await? {
+        breaks.entry(row.stream_id).or_default().push(ChainBreak {
+            kind: row.kind,
+            chain_seq: row.chain_seq,
+            event_id: row.event_id,
+            detail: row.detail,
+        });
+    }
+
+    let key_id = signer.key_id();
+    let mut checkpoints: HashMap<Uuid, (i64, Option<DateTime<Utc>>)> = HashMap::new();
+    for cp in db::checkpoints_with_events(db, stream_id).await? {
+        let (count, last) = checkpoints.entry(cp.stream_id).or_default();
+        *count += 1;
+        *last = Some(cp.created_at);
+
+        let broken = |kind: &str, detail: String| ChainBreak {
+            kind: kind.to_string(),
+            chain_seq: Some(cp.chain_seq),
+            event_id: cp.event_id,
+            detail,
+        };
+        let message = checkpoint_message(cp.stream_id, cp.chain_seq, &cp.content_hash, cp.created_at);
+        let found = if cp.key_id != key_id {
+            Some(broken(
+                "checkpoint_unknown_key",
+                format!("signed with key {} rather than the current key {key_id}; verify it offline", cp.key_id),
+            ))
+        } else if !signer.verify(message.as_bytes(), &cp.signature) {
+            Some(broken("checkpoint_signature_invalid", "signature does not match the checkpoint".into()))
+        } else {
+            match &cp.event_hash {
+                None => Some(broken("missing_events", "the checkpointed event is missing".into())),
+                Some(hash) if *hash != cp.content_hash => Some(broken(
+                    "checkpoint_mismatch",
+                    format!("event hash changed since the checkpoint of {}", cp.created_at.to_rfc3339()),
+                )),
+                Some(_) => None,
+            }
+        };
+        if let Some(b) = found {
+            breaks.entry(cp.stream_id).or_default().push(b);
+        }
+    }
+
+    let mut streams = Vec::new();
+    for status in db::chain_status(db, stream_id).await? {
+        let mut stream_breaks = breaks.remove(&status.stream_id).unwrap_or_default();
+        let max_seq = status.max_seq.unwrap_or(0);
+        if max_seq < status.head_seq {
+            let detail = if max_seq + 1 == status.head_seq {
+                format!("event {} at the end of the chain is missing", status.head_seq)
+            } else {
+                format!("events {} to {} at the end of the chain are missing", max_seq + 1, status.head_seq)
+            };
+            stream_breaks.push(ChainBreak {
+                kind: "missing_events".into(),
+                chain_seq: Some(max_seq + 1),
+                event_id: None,
+                detail,
+            });
+        }
+        stream_breaks.sort_by_key(|b| b.chain_seq);
+
+        let (checkpoint_count, last_checkpoint_at) = checkpoints.remove(&status.stream_id).unwrap_or_default();
+        let break_count = stream_breaks.len();
+        stream_breaks.truncate(MAX_BREAKS_LISTED);
+        streams.push(StreamChainStatus {
+            stream_id: status.stream_id,
+            stream_name: status.stream_name,
+            events: status.events,
+            head_seq: status.head_seq,
+            checkpoints: checkpoint_count,
+            last_checkpoint_at,
+            break_count,
+            breaks: stream_breaks,
+        });
+    }
+
+    Ok(ChainVerification { ok: streams.iter().all(|s| s.break_count == 0), verified_at, streams })
+}
+
+/// The bytes a checkpoint signature covers.
+fn checkpoint_message(stream_id: Uuid, seq: i64, content_hash: &str, created_at: DateTime<Utc>) -> String {
+    format!("{stream_id}:{seq}:{content_hash}:{}", created_at.timestamp_micros())
+}
@@ -0,0 +1,435 @@
+//! Event exports for insurance claims and police reports: CSV, JSON Lines, or
+//! a ZIP evidence bundle with the frames, a manifest of SHA-256 hashes and a
+//! signed summary. Events are read a page at a time and the output streams as
+//! it is written, so memory use doesn't grow with the size of the export.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{self, Write},
+    mem,
+    sync::Arc,
+};
+
+use axum::body::Body;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use tokio::{runtime::Handle, sync::mpsc};
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::warn;
+use uuid::Uuid;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use crate::{
+    error::{AppError, Result},
+    evidence::signing::{EvidenceSigner, ALGORITHM},
+    storage::{
+        db,
+        models::{AnalysisEvent, EventQuery},
+    },
+};
+
+/// Events read per query; bundle pages carry frames, so they are smaller.
+const PAGE_SIZE: i64 = 500;
+const BUNDLE_PAGE_SIZE: i64 = 50;
+/// Bundle bytes collected before a chunk is handed to the response.
+const CHUNK_SIZE: usize = 256 * 1024;
+
+type Chunks = mpsc::Sender<io::Result<Bytes>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Ndjson,
+    Zip,
+}
+
+impl Format {
+    pub fn parse(format: Option<&str>) -> Result<Self> {
+        match format.map(str::to_ascii_lowercase).as_deref() {
+            None | Some("csv") => Ok(Self::Csv),
+            Some("ndjson" | "jsonl") => Ok(Self::Ndjson),
+            Some("zip") => Ok(Self::Zip),
+            Some(other) => Err(AppError::BadRequest(format!(
+                "unknown format '{other}', expected csv, ndjson or zip"
+            ))),
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::Ndjson => "application/x-ndjson",
+            Self::Zip => "application/zip",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Ndjson => "ndjson",
+            Self::Zip => "zip",
+        }
+    }
+}
+
+/// Starts an export of the events matching `query`'s filters (its paging
+/// fields are ignored) and returns the body it streams into. The first page is
+/// read before returning, so bad filters are a 400 rather than a broken download.
+/// `raw_query` is recorded in the bundle so the export can be reproduced.
+pub async fn export(
+    db: PgPool,
+    signer: Arc<EvidenceSigner>,
+    query: EventQuery,
+    format: Format,
+    raw_query: String,
+) -> Result<Body> {
+    let query = EventQuery {
+        limit: if format == Format::Zip { BUNDLE_PAGE_SIZE } else { PAGE_SIZE },
+        offset: 0,
+        cursor: None,
+        exclude_frame: format != Format::Zip,
+        ..query
+    };
+    let streams: HashMap<Uuid, String> =
+        db::list_streams(&db, None).await?.into_iter().map(|s| (s.id, s.name)).collect();
+    let pages = Pages::start(db, query).await?;
+
+    let (tx, rx) = mpsc::channel(4);
+    match format {
+        Format::Csv | Format::Ndjson => {
+            tokio::spawn(async move {
+                let result = write_lines(pages, &streams, format, &tx).await;
+                report(result, &tx).await;
+            });
+        }
+        Format::Zip => {
+            let rt = Handle::current();
+            tokio::task::spawn_blocking(move || {
+                let result = write_bundle(&rt, pages, &streams, &signer, &raw_query, &tx);
+                rt.block_on(report(result, &tx));
+            });
+        }
+    }
+    Ok(Body::from_stream(ReceiverStream::new(rx)))
+}
+
+/// A failed export ends in an error chunk, which aborts the response so the
+/// client sees a broken download instead of a short file.
+async fn report(result: Result<()>, tx: &Chunks) {
+    if let Err(e) = result {
+        if tx.is_closed() {
+            return;
+        }
+        warn!("Event export failed: {e}");
+        let _ = tx.send(Err(io::Error::other(e.to_string()))).await;
+    }
+}
+
+/// Matching events newest first, one page at a time.
+struct Pages {
+    db: PgPool,
+    query: EventQuery,
+    first: Option<Vec<AnalysisEvent>>,
+}
+
+impl Pages {
+    async fn start(db: PgPool, mut query: EventQuery) -> Result<Self> {
+        let (first, next) = db::list_events_page(&db, &query).await?;
+        query.cursor = next;
+        Ok(Self { db, query, first: Some(first) })
+    }
+
+    async fn next(&mut self) -> Result<Option<Vec<AnalysisEvent>>> {
+        if let Some(page) = self.first.take() {
+            return Ok(Some(page));
+        }
+        if self.query.cursor.is_none() {
+            return Ok(None);
+        }
+        let (page, next) = db::list_events_page(&self.db, &self.query).await?;
+        self.query.cursor = next;
+        Ok(Some(page))
+    }
+}
+
+// ─── CSV / JSON Lines ─────────────────────────────────────────────────────────
+
+const CSV_HEADER: [&str; 14] = [
+    "id",
+    "captured_at",
+    "stream_id",
+    "stream_name",
+    "risk_level",
+    "status",
+    "title",
+    "description",
+    "event_types",
+    "triggered_rule",
+    "rule_id",
+    "disposition",
+    "disposition_reason",
+    "model",
+];
+
+async fn write_lines(mut pages: Pages, streams: &HashMap<Uuid, String>, format: Format, tx: &Chunks) -> Result<()> {
+    let mut header = format == Format::Csv;
+    while let Some(page) = pages.next().await? {
+        let mut out = Vec::new();
+        match format {
+            Format::Csv => {
+                let mut csv = csv::Writer::from_writer(&mut out);
+                if mem::take(&mut header) {
+                    csv.write_record(CSV_HEADER).map_err(|e| AppError::Other(e.into()))?;
+                }
+                for event in &page {
+                    csv_record(&mut csv, event, streams).map_err(|e| AppError::Other(e.into()))?;
+                }
+                csv.flush().map_err(|e| AppError::Other(e.into()))?;
+            }
+            _ => {
+                for event in &page {
+                    serde_json::to_writer(&mut out, event).map_err(|e| AppError::Other(e.into()))?;
+                    out.push(b'\n');
+                }
+            }
+        }
+        if tx.send(Ok(Bytes::from(out))).await.is_err() {
+            // Client went away.
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+fn csv_record<W: Write>(
+    csv: &mut csv::Writer<W>,
+    e: &AnalysisEvent,
+    streams: &HashMap<Uuid, String>,
+) -> csv::Result<()> {
+    let opt_id = |id: Option<Uuid>| id.map(|id| id.to_string()).unwrap_or_default();
+    csv.write_record([
+        e.id.to_string().as_str(),
+        &e.captured_at.to_rfc3339(),
+        &e.stream_id.to_string(),
+        streams.get(&e.stream_id).map(String::as_str).unwrap_or_default(),
+        &e.risk_level,
+        &e.status,
+        e.title.as_deref().unwrap_or_default(),
+        &e.description,
+        &event_types(e).join(","),
+        e.triggered_rule.as_deref().unwrap_or_default(),
+        &opt_id(e.rule_id),
+        e.disposition.as_deref().unwrap_or_default(),
+        e.disposition_reason.as_deref().unwrap_or_default(),
+        e.model.as_deref().unwrap_or_default(),
+    ])
+}
+
+fn event_types(e: &AnalysisEvent) -> Vec<&str> {
+    e.events
+        .as_array()
+        .map(|events| events.iter().filter_map(|ev| ev.get("event_type")?.as_str()).collect())
+        .unwrap_or_default()
+}
+
+// ─── ZIP evidence bundle ──────────────────────────────────────────────────────
+
+#[derive(Serialize)]
+struct BundleEvent<'a> {
+    #[serde(flatten)]
+    event: &'a AnalysisEvent,
+    stream_name: Option<&'a str>,
+    /// Path of the frame in the bundle.
+    frame_file: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ManifestFile {
+    path: String,
+    sha256: String,
+    size: usize,
+}
+
+#[derive(Serialize)]
+struct Manifest<'a> {
+    export_id: Uuid,
+    generated_at: DateTime<Utc>,
+    /// Query string of the export request.
+    query: &'a str,
+    event_count: u64,
+    files: &'a [ManifestFile],
+}
+
+/// The signed part of the bundle; binds every file through `manifest_sha256`.
+#[derive(Serialize)]
+struct Summary<'a> {
+    export_id: Uuid,
+    generated_at: DateTime<Utc>,
+    query: &'a str,
+    event_count: u64,
+    frame_count: u64,
+    first_captured_at: Option<DateTime<Utc>>,
+    last_captured_at: Option<DateTime<Utc>>,
+    risk_levels: BTreeMap<String, u64>,
+    manifest_sha256: String,
+    signature_algorithm: &'static str,
+    key_id: String,
+    public_key: String,
+}
+
+/// Writes the bundle from a blocking task, reading pages through `rt`.
+/// Layout: `README.txt`, `events/<id>.json`, `frames/<id>.jpg`,
+/// `manifest.json`, `SHA256SUMS`, `summary.json` and `summary.json.sig`.
+fn write_bundle(
+    rt: &Handle,
+    mut pages: Pages,
+    streams: &HashMap<Uuid, String>,
+    signer: &EvidenceSigner,
+    raw_query: &str,
+    tx: &Chunks,
+) -> Result<()> {
+    let export_id = Uuid::new_v4();
+    let generated_at = Utc::now();
+    let now = zip_time(generated_at);
+    let mut zip = ZipWriter::new_stream(ChunkWriter { tx: tx.clone(), buf: Vec::with_capacity(CHUNK_SIZE) });
+    let mut files = Vec::new();
+
+    let readme = format!(
+        "Evidence export {export_id}, generated {}.\n\n{}",
+        generated_at.to_rfc3339(),
+        README
+    );
+    add_file(&mut zip, &mut files, "README.txt".into(), readme.as_bytes(), CompressionMethod::Deflated, now)?;
+
+    let mut event_count = 0;
+    let mut frame_count = 0;
+    let mut first_captured_at = None;
+    let mut last_captured_at = None;
+    let mut risk_levels = BTreeMap::new();
+    while let Some(page) = rt.block_on(pages.next())? {
+        for mut event in page {
+            let time = zip_time(event.captured_at);
+            let frame_file = match event.frame.take() {
+                Some(jpeg) => {
+                    let path = format!("frames/{}.jpg", event.id);
+                    add_file(&mut zip, &mut files, path.clone(), &jpeg, CompressionMethod::Stored, time)?;
+                    frame_count += 1;
+                    Some(path)
+                }
+                None => None,
+            };
+            let record = BundleEvent {
+                event: &event,
+                stream_name: streams.get(&event.stream_id).map(String::as_str),
+                frame_file,
+            };
+            let json = serde_json::to_vec_pretty(&record).map_err(|e| AppError::Other(e.into()))?;
+            let path = format!("events/{}.json", event.id);
+            add_file(&mut zip, &mut files, path, &json, CompressionMethod::Deflated, time)?;
+
+            event_count += 1;
+            *risk_levels.entry(event.risk_level.clone()).or_insert(0) += 1;
+            // Newest first: the first event seen is the last captured.
+            last_captured_at.get_or_insert(event.captured_at);
+            first_captured_at = Some(event.captured_at);
+        }
+    }
+
+    let manifest = Manifest { export_id, generated_at, query: raw_query, event_count, files: &files };
+    let manifest = serde_json::to_vec_pretty(&manifest).map_err(|e| AppError::Other(e.into()))?;
+    let manifest_sha256 = hex::encode(Sha256::digest(&manifest));
+    add_file(&mut zip, &mut files, "manifest.json".into(), &manifest, CompressionMethod::Deflated, now)?;
+
+    let sums: String = files.iter().map(|f| format!("{}  {}\n", f.sha256, f.path)).collect();
+    add_file(&mut zip, &mut files, "SHA256SUMS".into(), sums.as_bytes(), CompressionMethod::Deflated, now)?;
+
+    let key = signer.public_key();
+    let summary = Summary {
+        export_id,
+        generated_at,
+        query: raw_query,
+        event_count,
+        frame_count,
+        first_captured_at,
+        last_captured_at,
+        risk_levels,
+        manifest_sha256,
+        signature_algorithm: ALGORITHM,
+        key_id: key.key_id,
+        public_key: key.public_key,
+    };
+    let summary = serde_json::to_vec_pretty(&summary).map_err(|e| AppError::Other(e.into()))?;
+    let signature = signer.sign(&summary);
+    add_file(&mut zip, &mut files, "summary.json".into(), &summary, CompressionMethod::Deflated, now)?;
+    add_file(&mut zip, &mut files, "summary.json.sig".into(), signature.as_bytes(), CompressionMethod::Stored, now)?;
+
+    let mut out = zip.finish().map_err(zip_error)?.into_inner();
+    out.flush().map_err(|e| AppError::Other(e.into()))?;
+    Ok(())
+}
+
+const README: &str = "\
+events/<id>.json   event as recorded, with the stream name and frame path
+frames/<id>.jpg    the analyzed frame, byte for byte as stored
+manifest.json      every file above with its SHA-256 and size
+SHA256SUMS         the same hashes plus manifest.json's, for `sha256sum -c SHA256SUMS`
+summary.json       counts, the export query and manifest.json's SHA-256
+summary.json.sig   base64 Ed25519 signature of summary.json
+
+To verify the bundle, run `sha256sum -c SHA256SUMS`, check that the
+manifest_sha256 in summary.json matches, then verify summary.json.sig over
+summary.json with the public key it names. That key should match the one the
+system publishes at GET /api/evidence/public-key.
+";
+
+fn add_file(
+    zip: &mut ZipWriter<zip::write::StreamWriter<ChunkWriter>>,
+    files: &mut Vec<ManifestFile>,
+    path: String,
+    data: &[u8],
+    method: CompressionMethod,
+    time: zip::DateTime,
+) -> Result<()> {
+    let options = SimpleFileOptions::default().compression_method(method).last_modified_time(time);
+    zip.start_file(path.as_str(), options).map_err(zip_error)?;
+    zip.write_all(data).map_err(|e| AppError::Other(e.into()))?;
+    files.push(ManifestFile { path, sha256: hex::encode(Sha256::digest(data)), size: data.len() });
+    Ok(())
+}
+
+fn zip_time(t: DateTime<Utc>) -> zip::DateTime {
+    zip::DateTime::try_from(t.naive_utc()).unwrap_or_default()
+}
+
+fn zip_error(e: zip::result::ZipError) -> AppError {
+    AppError::Other(e.into())
+}
+
+/// Collects bundle bytes into chunks for the response body.
+struct ChunkWriter {
+    tx: Chunks,
+    buf: Vec<u8>,
+}
+
+impl Write for ChunkWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        if self.buf.len() >= CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let chunk = Bytes::from(mem::replace(&mut self.buf, Vec::with_capacity(CHUNK_SIZE)));
+        self.tx
+            .blocking_send(Ok(chunk))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "export download closed"))
+    }
+}
@@ -0,0 +1,7 @@
+//! Evidence handed to insurers and police: the per-stream hash chain that
+//! makes stored events tamper-evident, and signed exports of events and
+//! their frames.
+
+pub mod chain;
+pub mod export;
+pub mod signing;
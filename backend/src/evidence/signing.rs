@@ -0,0 +1,90 @@
+//! The Ed25519 key evidence is signed with. Anyone holding the public key
+//! (`GET /api/evidence/public-key`) can check a signature offline; the private
+//! key never enters the database.
+
+use std::{fs, io::Write, path::Path};
+
+use anyhow::{bail, Context};
+use base64::{engine::general_purpose::STANDARD as B64, Engine};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, SECRET_KEY_LENGTH};
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+use crate::{config::EvidenceConfig, storage::models::EvidencePublicKey};
+
+pub const ALGORITHM: &str = "Ed25519";
+
+pub struct EvidenceSigner {
+    key: SigningKey,
+}
+
+impl EvidenceSigner {
+    /// Uses `EVIDENCE_SIGNING_KEY` if set, else the key file, creating it with
+    /// a new key when it doesn't exist yet.
+    pub fn load(cfg: &EvidenceConfig) -> anyhow::Result<Self> {
+        if let Some(seed) = &cfg.signing_key {
+            let key = decode_seed(seed).context("EVIDENCE_SIGNING_KEY")?;
+            return Ok(Self { key });
+        }
+
+        let path = Path::new(&cfg.key_file);
+        if path.exists() {
+            let seed = fs::read_to_string(path).with_context(|| format!("reading {}", cfg.key_file))?;
+            let key = decode_seed(&seed).with_context(|| format!("key file {}", cfg.key_file))?;
+            return Ok(Self { key });
+        }
+
+        let key = SigningKey::generate(&mut rand::rngs::OsRng);
+        write_key_file(path, &B64.encode(key.to_bytes()))
+            .with_context(|| format!("writing evidence key file {}", cfg.key_file))?;
+        let signer = Self { key };
+        info!(file = %cfg.key_file, key_id = %signer.key_id(), "Created evidence signing key");
+        Ok(signer)
+    }
+
+    /// Base64 signature of `message`.
+    pub fn sign(&self, message: &[u8]) -> String {
+        B64.encode(self.key.sign(message).to_bytes())
+    }
+
+    /// Whether `signature` (base64) is this key's signature of `message`.
+    pub fn verify(&self, message: &[u8], signature: &str) -> bool {
+        let Ok(bytes) = B64.decode(signature.trim()) else {
+            return false;
+        };
+        let Ok(signature) = Signature::from_slice(&bytes) else {
+            return false;
+        };
+        self.key.verifying_key().verify(message, &signature).is_ok()
+    }
+
+    pub fn public_key(&self) -> EvidencePublicKey {
+        EvidencePublicKey {
+            algorithm: ALGORITHM.to_string(),
+            key_id: self.key_id(),
+            public_key: B64.encode(self.key.verifying_key().as_bytes()),
+        }
+    }
+
+    /// First 16 hex digits of the public key's SHA-256, to tell keys apart.
+    pub fn key_id(&self) -> String {
+        hex::encode(&Sha256::digest(self.key.verifying_key().as_bytes())[..8])
+    }
+}
+
+fn decode_seed(seed: &str) -> anyhow::Result<SigningKey> {
+    let bytes = B64.decode(seed.trim()).context("not valid base64")?;
+    let Ok(seed) = <[u8; SECRET_KEY_LENGTH]>::try_from(bytes.as_slice()) else {
+        bail!("expected a {SECRET_KEY_LENGTH}-byte seed, got {} bytes", bytes.len());
+    };
+    Ok(SigningKey::from_bytes(&seed))
+}
+
+fn write_key_file(path: &Path, contents: &str) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    writeln!(file, "{contents}")
+}
@@ -1,9 +1,12 @@
 mod analysis;
 mod api;
 mod assistant;
+mod cli;
 mod config;
 mod error;
+mod evidence;
 mod notifications;
+mod reports;
 mod state;
 mod storage;
 mod streams;
@@ -13,14 +16,16 @@ use std::sync::Arc;
 use anyhow::Context;
 use sqlx::postgres::PgPoolOptions;
 use tokio::sync::{broadcast, mpsc};
-use tracing::info;
+use tracing::{info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
 use crate::{
-    analysis::{vlm::build_vlm_client, worker::AnalysisWorkerPool},
+    analysis::{shadow::ShadowAnalyzer, vlm::registry::VlmRegistry, worker::AnalysisWorkerPool},
+    assistant::chat::build_chat_client,
     config::AppConfig,
+    evidence::signing::EvidenceSigner,
     state::AppState,
-    storage::models::AnalysisEvent,
+    storage::{db, models::AnalysisEvent},
     streams::{frame_store::FrameStore, manager::StreamManager},
 };
 
@@ -50,10 +55,44 @@ async fn main() -> anyhow::Result<()> {
 
     info!("Database connected and migrations applied");
 
+    // ── CLI subcommands (e.g. `eval <dataset_id>`) ────────────────────────────
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(cmd) = args.first() {
+        return cli::run(cmd, &args[1..], &cfg, &db).await;
+    }
+
+    // Re-analysis jobs and eval runs run in this process, so any still `running` were cut short.
+    let interrupted = db::fail_interrupted_reanalysis_jobs(&db).await?;
+    if interrupted > 0 {
+        warn!(jobs = interrupted, "Marked re-analysis jobs interrupted by a restart as failed");
+    }
+    let interrupted = db::fail_interrupted_eval_runs(&db).await?;
+    if interrupted > 0 {
+        warn!(runs = interrupted, "Marked evaluation runs interrupted by a restart as failed");
+    }
+
     // ── VLM client ────────────────────────────────────────────────────────────
-    let vlm = build_vlm_client(&cfg.vlm);
+    // Streams with a VLM profile get their own client; the rest share this one.
+    if let Some(f) = &cfg.vlm_fallback {
+        info!(backend = f.name(), model = f.model(), "Fallback VLM enabled");
+    }
+    let vlm = VlmRegistry::new(&cfg);
     info!("VLM client ready");
 
+    // ── Assistant chat model ──────────────────────────────────────────────────
+    let chat = build_chat_client(&cfg.assistant.chat);
+    info!(backend = cfg.assistant.chat.name(), model = cfg.assistant.chat.model(), "Assistant chat model ready");
+
+    let shadow = cfg.shadow.as_ref().map(|s| {
+        info!(
+            backend = s.vlm.name(),
+            model = s.vlm.model(),
+            sample_rate = s.sample_rate,
+            "Shadow VLM enabled"
+        );
+        ShadowAnalyzer::new(s, &vlm)
+    });
+
     // ── Channels ──────────────────────────────────────────────────────────────
     // Frame queue: capturers → analysis workers
     let (frame_tx, frame_rx) = mpsc::channel(cfg.frame_queue_size);
@@ -64,15 +103,41 @@ async fn main() -> anyhow::Result<()> {
     // ── Frame store ───────────────────────────────────────────────────────────
     let frame_store = FrameStore::new();
 
+    // ── Evidence signing key ──────────────────────────────────────────────────
+    let signer = Arc::new(EvidenceSigner::load(&cfg.evidence).context("Failed to load evidence signing key")?);
+    if cfg.evidence.checkpoint_interval_sec > 0 {
+        tokio::spawn(evidence::chain::run_checkpoints(
+            db.clone(),
+            Arc::clone(&signer),
+            cfg.evidence.checkpoint_interval_sec,
+        ));
+    }
+
+    // ── Scheduled reports ─────────────────────────────────────────────────────
+    if cfg.reports.check_interval_sec > 0 {
+        tokio::spawn(reports::schedule::run_schedules(db.clone(), Arc::clone(&chat), cfg.reports.clone()));
+    }
+
     // ── App state ─────────────────────────────────────────────────────────────
-    let state = AppState::new(db.clone(), Arc::clone(&vlm), event_tx.clone(), Arc::clone(&frame_store));
+    let state = AppState::new(
+        &cfg,
+        db.clone(),
+        Arc::clone(&vlm),
+        chat,
+        event_tx.clone(),
+        Arc::clone(&frame_store),
+        signer,
+    );
 
     // ── Analysis worker pool ──────────────────────────────────────────────────
     let worker_pool = AnalysisWorkerPool::new(
         cfg.analysis_workers,
+        cfg.max_frame_age_sec,
+        cfg.batch.clone(),
         Arc::clone(&vlm),
         db.clone(),
         event_tx,
+        shadow,
     );
     tokio::spawn(async move { worker_pool.run(frame_rx).await });
 
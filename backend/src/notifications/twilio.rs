@@ -1,4 +1,5 @@
-//! Send SMS via Twilio when threats are detected (low, medium, or high risk).
+//! Send SMS via Twilio when threats are detected (low, medium, or high risk),
+//! when the system itself needs attention, or when a digest report is ready.
 //! Reads TWILIO_ACCOUNT_SID, TWILIO_AUTH_TOKEN, TWILIO_PHONE_NUMBER, ALERT_PHONE_NUMBER from env.
 
 use std::env;
@@ -8,25 +9,65 @@ use tracing::{error, info, warn};
 /// `to_number`: if Some and non-empty, use it; else use ALERT_PHONE_NUMBER from env.
 /// `risk_level` should be "low", "medium", or "high".
 pub async fn send_alert(to_number: Option<&str>, stream_name: &str, risk_level: &str, description: &str) {
+    let body = format!(
+        "Cipher-Shield: {} risk on stream \"{}\". {}",
+        risk_level,
+        stream_name,
+        description.chars().take(100).collect::<String>()
+    );
+    if send_sms(to_number, &body).await {
+        info!("Twilio alert sent for stream {} ({} risk)", stream_name, risk_level);
+    }
+}
+
+/// Sends an SMS about the system itself (e.g. every VLM backend is down),
+/// with the same recipients and no-op rules as `send_alert`.
+pub async fn send_system_alert(to_number: Option<&str>, message: &str) {
+    let body = format!(
+        "Cipher-Shield system alert: {}",
+        message.chars().take(140).collect::<String>()
+    );
+    if send_sms(to_number, &body).await {
+        info!("Twilio system alert sent");
+    }
+}
+
+/// Sends a digest report's headline figures, with the same recipients and
+/// no-op rules as `send_alert`. Returns true if Twilio accepted it.
+pub async fn send_report(to_number: Option<&str>, title: &str, digest: &str) -> bool {
+    let body = format!(
+        "Cipher-Shield {}: {}",
+        title,
+        digest.chars().take(300).collect::<String>()
+    );
+    let sent = send_sms(to_number, &body).await;
+    if sent {
+        info!("Twilio report digest sent ({})", title);
+    }
+    sent
+}
+
+/// Returns true if Twilio accepted the message.
+async fn send_sms(to_number: Option<&str>, body: &str) -> bool {
     let account_sid = match env::var("TWILIO_ACCOUNT_SID") {
         Ok(s) if !s.is_empty() => s,
         _ => {
             warn!("TWILIO_ACCOUNT_SID not set, skipping Twilio alert");
-            return;
+            return false;
         }
     };
     let auth_token = match env::var("TWILIO_AUTH_TOKEN") {
         Ok(s) if !s.is_empty() => s,
         _ => {
             warn!("TWILIO_AUTH_TOKEN not set, skipping Twilio alert");
-            return;
+            return false;
         }
     };
     let from_number = match env::var("TWILIO_PHONE_NUMBER") {
         Ok(s) if !s.is_empty() => s,
         _ => {
             warn!("TWILIO_PHONE_NUMBER not set, skipping Twilio alert");
-            return;
+            return false;
         }
     };
     let to_number = to_number
@@ -38,16 +79,10 @@ pub async fn send_alert(to_number: Option<&str>, stream_name: &str, risk_level:
         Some(s) => s,
         None => {
             warn!("No alert phone number (set via API or ALERT_PHONE_NUMBER env), skipping Twilio alert");
-            return;
+            return false;
         }
     };
 
-    let body = format!(
-        "Cipher-Shield: {} risk on stream \"{}\". {}",
-        risk_level,
-        stream_name,
-        description.chars().take(100).collect::<String>()
-    );
     let url = format!("https://api.twilio.com/2010-04-01/Accounts/{}/Messages.json", account_sid);
 
     let client = reqwest::Client::new();
@@ -57,15 +92,13 @@ pub async fn send_alert(to_number: Option<&str>, stream_name: &str, risk_level:
         .form(&[
             ("To", to_number.as_str()),
             ("From", from_number.as_str()),
-            ("Body", body.as_str()),
+            ("Body", body),
         ])
         .send()
         .await;
 
     match res {
-        Ok(resp) if resp.status().is_success() => {
-            info!("Twilio alert sent for stream {} ({} risk)", stream_name, risk_level);
-        }
+        Ok(resp) if resp.status().is_success() => true,
         Ok(resp) => {
             let status = resp.status();
             let text = resp.text().await.unwrap_or_default();
@@ -74,9 +107,11 @@ pub async fn send_alert(to_number: Option<&str>, stream_name: &str, risk_level:
                 status,
                 text.chars().take(200).collect::<String>()
             );
+            false
         }
         Err(e) => {
             error!("Twilio request error: {}", e);
+            false
         }
     }
 }
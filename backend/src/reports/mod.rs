@@ -0,0 +1,286 @@
+//! Security digest reports: event totals per risk level, stream and rule, time
+//! to resolve and the most severe incidents of a period, optionally with a
+//! summary written by the assistant model. Reports are rendered to HTML and
+//! PDF and stored for download; scheduled ones can also be texted.
+
+pub mod pdf;
+pub mod render;
+pub mod schedule;
+
+use std::io::Cursor;
+
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+use image::codecs::jpeg::JpegEncoder;
+use sqlx::PgPool;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{
+    analysis::event_stats,
+    assistant::chat::ChatClient,
+    error::{AppError, Result},
+    notifications::twilio,
+    storage::{
+        db,
+        models::{
+            CreateReportRequest, EventCountTotal, EventCountsQuery, NewReport, Report, ReportIncident, ReportSummary,
+            StatsFilter, StatsQuery,
+        },
+    },
+};
+
+/// Incidents shown with a thumbnail.
+const MAX_INCIDENTS: i64 = 6;
+/// Longest side of an incident thumbnail, in pixels.
+const THUMBNAIL_SIZE: u32 = 320;
+const THUMBNAIL_QUALITY: u8 = 75;
+/// Longest period one report may cover.
+pub const MAX_PERIOD_HOURS: i32 = 366 * 24;
+
+const SUMMARY_SYSTEM_PROMPT: &str = "You write the summary section of a security camera digest report for \
+managers. Use only the figures and incidents given. At most two short paragraphs of plain text: no headings, \
+no lists, no markdown.";
+
+/// What to aggregate into one report.
+pub struct ReportSpec {
+    pub schedule_id: Option<Uuid>,
+    pub title: String,
+    pub stream_id: Option<Uuid>,
+    pub blueprint_id: Option<Uuid>,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub utc_offset_minutes: i32,
+    pub llm_summary: bool,
+}
+
+/// Everything a report is rendered from.
+pub struct ReportContent {
+    pub title: String,
+    /// "All streams", or the stream or blueprint reported on.
+    pub scope: String,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub offset: FixedOffset,
+    pub event_count: i64,
+    pub summary: ReportSummary,
+    pub llm_summary: Option<String>,
+    /// One per incident, in the same order; None when the frame is missing or unreadable.
+    pub thumbnails: Vec<Option<Thumbnail>>,
+}
+
+pub struct Thumbnail {
+    pub jpeg: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ReportContent {
+    /// `t` in the report's local time.
+    pub fn local(&self, t: DateTime<Utc>) -> String {
+        t.with_timezone(&self.offset).format("%Y-%m-%d %H:%M").to_string()
+    }
+
+    pub fn period(&self) -> String {
+        format!("{} to {} (UTC{})", self.local(self.from), self.local(self.to), self.offset)
+    }
+
+    /// One line of headline figures, e.g. "42 events (2 high, 5 medium, 9 low)".
+    pub fn headline(&self) -> String {
+        format!(
+            "{} event{} ({} high, {} medium, {} low)",
+            self.event_count,
+            if self.event_count == 1 { "" } else { "s" },
+            risk_count(&self.summary, "high"),
+            risk_count(&self.summary, "medium"),
+            risk_count(&self.summary, "low"),
+        )
+    }
+}
+
+/// Aggregates the period, renders the report and stores it.
+pub async fn generate(db: &PgPool, chat: &dyn ChatClient, spec: &ReportSpec) -> Result<Report> {
+    if spec.title.trim().is_empty() {
+        return Err(AppError::BadRequest("title must not be empty".into()));
+    }
+    if spec.to - spec.from > Duration::hours(MAX_PERIOD_HOURS as i64) {
+        return Err(AppError::BadRequest(format!("a report covers at most {MAX_PERIOD_HOURS} hours")));
+    }
+    let offset = utc_offset(spec.utc_offset_minutes)?;
+
+    // Totals come from the dashboard statistics, which also validate the filters.
+    let totals = |group_by: &str| EventCountsQuery {
+        bucket: Some("week".into()),
+        group_by: Some(group_by.into()),
+        stream_id: spec.stream_id,
+        blueprint_id: spec.blueprint_id,
+        risk_level: None,
+        from: Some(spec.from),
+        to: Some(spec.to),
+        utc_offset_minutes: spec.utc_offset_minutes,
+    };
+    let by_risk = event_stats::counts(db, &totals("risk_level")).await?.totals;
+    let by_stream = event_stats::counts(db, &totals("stream")).await?.totals;
+    let by_rule: Vec<EventCountTotal> =
+        event_stats::counts(db, &totals("triggered_rule")).await?.totals.into_iter().filter(|t| t.key.is_some()).collect();
+    let resolution = event_stats::resolution(
+        db,
+        &StatsQuery {
+            stream_id: spec.stream_id,
+            blueprint_id: spec.blueprint_id,
+            risk_level: None,
+            from: Some(spec.from),
+            to: Some(spec.to),
+            utc_offset_minutes: spec.utc_offset_minutes,
+        },
+    )
+    .await?;
+
+    let filter = StatsFilter {
+        from: spec.from,
+        to: spec.to,
+        stream_id: spec.stream_id,
+        blueprint_id: spec.blueprint_id,
+        risk_levels: None,
+        utc_offset_minutes: spec.utc_offset_minutes,
+    };
+    let rows = db::report_incidents(db, &filter, MAX_INCIDENTS).await?;
+    let frames: Vec<Option<Vec<u8>>> = rows.iter().map(|r| r.frame.clone()).collect();
+    let incidents: Vec<ReportIncident> = rows
+        .into_iter()
+        .map(|r| ReportIncident {
+            event_id: r.event_id,
+            stream_id: r.stream_id,
+            stream_name: r.stream_name,
+            captured_at: r.captured_at,
+            risk_level: r.risk_level,
+            title: r.title,
+            description: r.description,
+            status: r.status,
+        })
+        .collect();
+
+    let scope = match (spec.stream_id, spec.blueprint_id) {
+        (Some(id), _) => format!("Stream {}", db::get_stream(db, id).await?.name),
+        (None, Some(id)) => format!("Blueprint {}", db::get_blueprint(db, id).await?.name),
+        (None, None) => "All streams".to_string(),
+    };
+
+    let mut content = ReportContent {
+        title: spec.title.trim().to_string(),
+        scope,
+        from: spec.from,
+        to: spec.to,
+        offset,
+        event_count: by_risk.iter().map(|t| t.count).sum(),
+        summary: ReportSummary { by_risk, by_stream, by_rule, resolution, incidents },
+        llm_summary: None,
+        thumbnails: Vec::new(),
+    };
+
+    if spec.llm_summary {
+        match chat.complete(SUMMARY_SYSTEM_PROMPT, &render::text(&content)).await {
+            Ok(text) if !text.is_empty() => content.llm_summary = Some(text),
+            Ok(_) => warn!(title = %content.title, "Assistant model returned an empty report summary"),
+            Err(e) => warn!(title = %content.title, "Report summary failed, leaving it out: {e}"),
+        }
+    }
+
+    // Decoding frames and laying out the PDF is CPU-bound.
+    let (content, html, pdf) = tokio::task::spawn_blocking(move || {
+        content.thumbnails = frames.iter().map(|f| f.as_deref().and_then(thumbnail)).collect();
+        let html = render::html(&content);
+        let pdf = pdf::render(&content);
+        (content, html, pdf)
+    })
+    .await
+    .map_err(|e| AppError::Other(e.into()))?;
+
+    let summary = serde_json::to_value(&content.summary).map_err(|e| AppError::Other(e.into()))?;
+    db::insert_report(
+        db,
+        &NewReport {
+            schedule_id: spec.schedule_id,
+            title: &content.title,
+            stream_id: spec.stream_id,
+            blueprint_id: spec.blueprint_id,
+            period_start: spec.from,
+            period_end: spec.to,
+            event_count: content.event_count,
+            summary,
+            llm_summary: content.llm_summary.as_deref(),
+            html: &html,
+            pdf: &pdf,
+        },
+    )
+    .await
+}
+
+/// A report generated on request, not tied to a schedule.
+pub async fn generate_now(db: &PgPool, chat: &dyn ChatClient, req: &CreateReportRequest) -> Result<Report> {
+    let to = req.to.unwrap_or_else(Utc::now);
+    let from = req.from.unwrap_or(to - Duration::hours(24));
+    generate(
+        db,
+        chat,
+        &ReportSpec {
+            schedule_id: None,
+            title: req.title.clone().unwrap_or_else(|| "Security digest".into()),
+            stream_id: req.stream_id,
+            blueprint_id: req.blueprint_id,
+            from,
+            to,
+            utc_offset_minutes: req.utc_offset_minutes.unwrap_or(0),
+            llm_summary: req.llm_summary.unwrap_or(false),
+        },
+    )
+    .await
+}
+
+/// Texts the report's headline figures and a link to it. Returns whether
+/// Twilio accepted the message.
+pub async fn notify(db: &PgPool, report: &Report, phone: Option<&str>, public_url: Option<&str>) -> Result<bool> {
+    let summary: ReportSummary = serde_json::from_value(report.summary.clone()).map_err(|e| AppError::Other(e.into()))?;
+    let mut digest = format!(
+        "{} events, {} high, {} medium risk.",
+        report.event_count,
+        risk_count(&summary, "high"),
+        risk_count(&summary, "medium")
+    );
+    if let Some(top) = summary.incidents.first() {
+        digest.push_str(&format!(" Top: {} ({}).", top.title.as_deref().unwrap_or(&top.description), top.stream_name));
+    }
+    if let Some(url) = public_url {
+        digest.push_str(&format!(" {url}/api/reports/{}/html", report.id));
+    }
+
+    let sent = twilio::send_report(phone, &report.title, &digest).await;
+    if sent {
+        db::set_report_notified(db, report.id).await?;
+    }
+    Ok(sent)
+}
+
+pub fn utc_offset(minutes: i32) -> Result<FixedOffset> {
+    FixedOffset::east_opt(minutes * 60)
+        .filter(|_| (-14 * 60..=14 * 60).contains(&minutes))
+        .ok_or_else(|| AppError::BadRequest("utc_offset_minutes must be between -840 and 840".into()))
+}
+
+fn risk_count(summary: &ReportSummary, risk_level: &str) -> i64 {
+    summary.by_risk.iter().filter(|t| t.key.as_deref() == Some(risk_level)).map(|t| t.count).sum()
+}
+
+/// A JPEG of at most `THUMBNAIL_SIZE` pixels on its longest side.
+fn thumbnail(frame: &[u8]) -> Option<Thumbnail> {
+    let img = image::load_from_memory(frame).ok()?;
+    let img = if img.width() > THUMBNAIL_SIZE || img.height() > THUMBNAIL_SIZE {
+        img.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+    } else {
+        img
+    };
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut Cursor::new(&mut jpeg), THUMBNAIL_QUALITY)
+        .encode_image(&img.to_rgb8())
+        .ok()?;
+    Some(Thumbnail { jpeg, width: img.width(), height: img.height() })
+}
@@ -0,0 +1,302 @@
+//! Reports as A4 PDF: Helvetica text laid out top to bottom over as many pages
+//! as needed, with incident thumbnails embedded as JPEG (DCTDecode) images.
+//! Text is WinAnsi-encoded; characters outside it print as '?'.
+
+use std::mem;
+
+use pdf_writer::{Content, Filter, Finish, Name, Pdf, Rect, Ref, Str, TextStr};
+
+use crate::reports::{render, ReportContent, Thumbnail};
+
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 50.0;
+const THUMBNAIL_WIDTH: f32 = 150.0;
+const LINE_SPACING: f32 = 1.35;
+const GREY: (f32, f32, f32) = (0.4, 0.4, 0.4);
+
+const REGULAR: Name = Name(b"F1");
+const BOLD: Name = Name(b"F2");
+
+pub fn render(c: &ReportContent) -> Vec<u8> {
+    let mut page = Layout::new();
+    let text_width = PAGE_WIDTH - 2.0 * MARGIN;
+
+    page.paragraph(&c.title, BOLD, 20.0, MARGIN, text_width, (0.0, 0.0, 0.0));
+    page.paragraph(&format!("{} · {}", c.scope, c.period()), REGULAR, 10.0, MARGIN, text_width, GREY);
+    page.gap(6.0);
+    page.paragraph(&c.headline(), BOLD, 12.0, MARGIN, text_width, (0.0, 0.0, 0.0));
+
+    if let Some(summary) = &c.llm_summary {
+        page.heading("Summary");
+        for paragraph in summary.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+            page.paragraph(paragraph, REGULAR, 10.0, MARGIN, text_width, (0.0, 0.0, 0.0));
+            page.gap(4.0);
+        }
+    }
+
+    page.heading("Top incidents");
+    if c.summary.incidents.is_empty() {
+        page.paragraph("No medium or high risk events in this period.", REGULAR, 10.0, MARGIN, text_width, GREY);
+    }
+    for (i, (incident, thumb)) in c.summary.incidents.iter().zip(&c.thumbnails).enumerate() {
+        let thumb_height = thumb.as_ref().map(|t| THUMBNAIL_WIDTH * t.height as f32 / t.width.max(1) as f32);
+        let (x, width) = match thumb_height {
+            Some(_) => (MARGIN + THUMBNAIL_WIDTH + 12.0, text_width - THUMBNAIL_WIDTH - 12.0),
+            None => (MARGIN, text_width),
+        };
+        let meta = format!(
+            "{} · {} · {} · {}",
+            incident.risk_level.to_uppercase(),
+            incident.stream_name,
+            c.local(incident.captured_at),
+            incident.status
+        );
+        let title = incident.title.as_deref().unwrap_or("Untitled event");
+        let lines = [
+            (wrap(&meta, 9.0, width), REGULAR, 9.0, risk_color(&incident.risk_level)),
+            (wrap(title, 11.0, width), BOLD, 11.0, (0.0, 0.0, 0.0)),
+            (wrap(&incident.description, 9.5, width), REGULAR, 9.5, (0.0, 0.0, 0.0)),
+        ];
+        let text_height: f32 = lines.iter().map(|(l, _, size, _)| l.len() as f32 * size * LINE_SPACING).sum();
+        page.ensure(text_height.max(thumb_height.unwrap_or(0.0)));
+
+        let top = page.y;
+        if let Some(h) = thumb_height {
+            page.image(i, MARGIN, top - h, THUMBNAIL_WIDTH, h);
+        }
+        for (wrapped, font, size, color) in &lines {
+            for line in wrapped {
+                page.line(line, *font, *size, x, *color);
+            }
+        }
+        page.y = page.y.min(top - thumb_height.unwrap_or(0.0)) - 12.0;
+    }
+
+    for (heading, totals) in [
+        ("Events by risk level", &c.summary.by_risk),
+        ("Events by stream", &c.summary.by_stream),
+        ("Events by rule", &c.summary.by_rule),
+    ] {
+        if totals.is_empty() {
+            continue;
+        }
+        page.heading(heading);
+        for t in totals.iter() {
+            page.row(render::total_label(t), &[t.count.to_string()]);
+        }
+    }
+
+    page.heading("Time to resolve");
+    page.row("Risk level", &["Events".into(), "Resolved".into(), "Median".into(), "Mean".into()]);
+    for r in &c.summary.resolution {
+        let time = |s: Option<f64>| s.map(render::duration).unwrap_or_else(|| "-".into());
+        page.row(
+            r.risk_level.as_deref().unwrap_or("All"),
+            &[r.events.to_string(), r.resolved.to_string(), time(r.median_seconds), time(r.mean_seconds)],
+        );
+    }
+
+    assemble(c, page.finish())
+}
+
+/// Content streams of the pages, filled top to bottom.
+struct Layout {
+    pages: Vec<Vec<u8>>,
+    content: Content,
+    /// Top of the next line.
+    y: f32,
+}
+
+impl Layout {
+    fn new() -> Self {
+        Self { pages: Vec::new(), content: Content::new(), y: PAGE_HEIGHT - MARGIN }
+    }
+
+    /// Starts a new page unless `height` still fits on this one.
+    fn ensure(&mut self, height: f32) {
+        if self.y - height < MARGIN {
+            let content = mem::replace(&mut self.content, Content::new());
+            self.pages.push(content.finish());
+            self.y = PAGE_HEIGHT - MARGIN;
+        }
+    }
+
+    fn gap(&mut self, height: f32) {
+        self.y -= height;
+    }
+
+    fn heading(&mut self, text: &str) {
+        self.gap(12.0);
+        // Keep a heading with at least a couple of lines after it.
+        self.ensure(14.0 * LINE_SPACING + 30.0);
+        self.line(text, BOLD, 14.0, MARGIN, (0.0, 0.0, 0.0));
+        self.gap(2.0);
+    }
+
+    fn paragraph(&mut self, text: &str, font: Name, size: f32, x: f32, width: f32, color: (f32, f32, f32)) {
+        for line in wrap(text, size, width) {
+            self.line(&line, font, size, x, color);
+        }
+    }
+
+    fn line(&mut self, text: &str, font: Name, size: f32, x: f32, (r, g, b): (f32, f32, f32)) {
+        self.ensure(size * LINE_SPACING);
+        self.content
+            .set_fill_rgb(r, g, b)
+            .begin_text()
+            .set_font(font, size)
+            .next_line(x, self.y - size)
+            .show(Str(&win_ansi(text)))
+            .end_text();
+        self.y -= size * LINE_SPACING;
+    }
+
+    /// A label and right-aligned columns of 70pt each.
+    fn row(&mut self, label: &str, columns: &[String]) {
+        const SIZE: f32 = 10.0;
+        let label_width = PAGE_WIDTH - 2.0 * MARGIN - 70.0 * columns.len() as f32;
+        let label = wrap(label, SIZE, label_width).into_iter().next().unwrap_or_default();
+        self.ensure(SIZE * LINE_SPACING);
+        let y = self.y;
+        self.line(&label, REGULAR, SIZE, MARGIN, (0.0, 0.0, 0.0));
+        let after = self.y;
+        for (i, column) in columns.iter().enumerate() {
+            let right = PAGE_WIDTH - MARGIN - 70.0 * (columns.len() - 1 - i) as f32;
+            self.y = y;
+            self.line(column, REGULAR, SIZE, right - text_width(column, SIZE), (0.0, 0.0, 0.0));
+        }
+        self.y = after;
+    }
+
+    /// Draws thumbnail `index` with its bottom-left corner at (x, y).
+    fn image(&mut self, index: usize, x: f32, y: f32, width: f32, height: f32) {
+        let name = format!("Im{index}");
+        self.content
+            .save_state()
+            .transform([width, 0.0, 0.0, height, x, y])
+            .x_object(Name(name.as_bytes()))
+            .restore_state();
+    }
+
+    fn finish(mut self) -> Vec<Vec<u8>> {
+        self.pages.push(self.content.finish());
+        self.pages
+    }
+}
+
+/// Writes the page streams, fonts and thumbnails into a PDF file.
+fn assemble(c: &ReportContent, pages: Vec<Vec<u8>>) -> Vec<u8> {
+    let mut next = Ref::new(1);
+    let catalog_id = next.bump();
+    let tree_id = next.bump();
+    let info_id = next.bump();
+    let regular_id = next.bump();
+    let bold_id = next.bump();
+    let page_ids: Vec<(Ref, Ref)> = pages.iter().map(|_| (next.bump(), next.bump())).collect();
+    let images: Vec<(String, Ref, &Thumbnail)> = c
+        .thumbnails
+        .iter()
+        .enumerate()
+        .filter_map(|(i, t)| t.as_ref().map(|t| (format!("Im{i}"), next.bump(), t)))
+        .collect();
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(catalog_id).pages(tree_id);
+    pdf.document_info(info_id).title(TextStr(&c.title)).producer(TextStr("Cipher-Shield"));
+    pdf.pages(tree_id).kids(page_ids.iter().map(|(page, _)| *page)).count(page_ids.len() as i32);
+
+    for ((page_id, content_id), content) in page_ids.iter().zip(&pages) {
+        let mut page = pdf.page(*page_id);
+        page.media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT)).parent(tree_id).contents(*content_id);
+        let mut resources = page.resources();
+        resources.fonts().pair(REGULAR, regular_id).pair(BOLD, bold_id);
+        let mut x_objects = resources.x_objects();
+        for (name, id, _) in &images {
+            x_objects.pair(Name(name.as_bytes()), *id);
+        }
+        x_objects.finish();
+        resources.finish();
+        page.finish();
+        pdf.stream(*content_id, content);
+    }
+
+    pdf.type1_font(regular_id).base_font(Name(b"Helvetica")).encoding_predefined(Name(b"WinAnsiEncoding"));
+    pdf.type1_font(bold_id).base_font(Name(b"Helvetica-Bold")).encoding_predefined(Name(b"WinAnsiEncoding"));
+
+    for (_, id, thumb) in &images {
+        let mut image = pdf.image_xobject(*id, &thumb.jpeg);
+        image.filter(Filter::DctDecode);
+        image.width(thumb.width as i32);
+        image.height(thumb.height as i32);
+        image.color_space().device_rgb();
+        image.bits_per_component(8);
+    }
+
+    pdf.finish()
+}
+
+fn risk_color(risk_level: &str) -> (f32, f32, f32) {
+    match risk_level {
+        "high" => (0.78, 0.16, 0.16),
+        "medium" => (0.94, 0.42, 0.0),
+        "low" => (0.71, 0.58, 0.0),
+        _ => (0.18, 0.49, 0.2),
+    }
+}
+
+/// Approximate Helvetica width: about half the font size per character.
+fn text_width(text: &str, size: f32) -> f32 {
+    text.chars().count() as f32 * size * 0.5
+}
+
+/// Splits `text` into lines of about `width` points, breaking at spaces and
+/// splitting words longer than a line.
+fn wrap(text: &str, size: f32, width: f32) -> Vec<String> {
+    let max = ((width / (size * 0.5)) as usize).max(8);
+    let mut lines = Vec::new();
+    for source_line in text.lines() {
+        let mut line = String::new();
+        for word in source_line.split_whitespace() {
+            let mut word: Vec<char> = word.chars().collect();
+            while word.len() > max {
+                if !line.is_empty() {
+                    lines.push(mem::take(&mut line));
+                }
+                lines.push(word.drain(..max).collect());
+            }
+            let word: String = word.into_iter().collect();
+            if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > max {
+                lines.push(mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&word);
+        }
+        if !line.is_empty() {
+            lines.push(line);
+        }
+    }
+    lines
+}
+
+/// WinAnsiEncoding bytes of `text`. It matches Latin-1 except for 0x80 – 0x9F.
+fn win_ansi(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|ch| match ch {
+            ' '..='~' | '\u{a0}'..='\u{ff}' => ch as u8,
+            '€' => 0x80,
+            '‚' => 0x82,
+            '…' => 0x85,
+            '‘' => 0x91,
+            '’' => 0x92,
+            '“' => 0x93,
+            '”' => 0x94,
+            '•' => 0x95,
+            '–' => 0x96,
+            '—' => 0x97,
+            _ => b'?',
+        })
+        .collect()
+}
@@ -0,0 +1,165 @@
+//! Reports as a self-contained HTML page (thumbnails inlined as data URIs)
+//! and as plain text for the assistant model to summarize.
+
+use base64::{engine::general_purpose::STANDARD as B64, Engine};
+
+use crate::{reports::ReportContent, storage::models::EventCountTotal};
+
+const STYLE: &str = "body{font-family:Helvetica,Arial,sans-serif;color:#222;max-width:900px;margin:24px auto;\
+padding:0 16px}h1{margin-bottom:4px}h2{margin-top:28px;border-bottom:1px solid #ddd;padding-bottom:4px}\
+.muted{color:#666}table{border-collapse:collapse;width:100%}td,th{text-align:left;padding:4px 8px;\
+border-bottom:1px solid #eee}td.n{text-align:right}.risk{font-weight:bold;text-transform:uppercase}\
+.incident{display:flex;gap:16px;margin:12px 0;padding-bottom:12px;border-bottom:1px solid #eee}\
+.incident img{width:240px;height:auto;border-radius:4px}";
+
+pub fn html(c: &ReportContent) -> String {
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{title}</title><style>{STYLE}</style></head>\
+         <body>\n<h1>{title}</h1>\n<p class=\"muted\">{scope} · {period}</p>\n<p><strong>{headline}</strong></p>\n",
+        title = escape(&c.title),
+        scope = escape(&c.scope),
+        period = escape(&c.period()),
+        headline = escape(&c.headline()),
+    );
+
+    if let Some(summary) = &c.llm_summary {
+        out.push_str("<h2>Summary</h2>\n");
+        for paragraph in summary.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+            out.push_str(&format!("<p>{}</p>\n", escape(paragraph)));
+        }
+    }
+
+    out.push_str("<h2>Top incidents</h2>\n");
+    if c.summary.incidents.is_empty() {
+        out.push_str("<p class=\"muted\">No medium or high risk events in this period.</p>\n");
+    }
+    for (incident, thumb) in c.summary.incidents.iter().zip(&c.thumbnails) {
+        out.push_str("<div class=\"incident\">");
+        if let Some(t) = thumb {
+            out.push_str(&format!("<img src=\"data:image/jpeg;base64,{}\" alt=\"\">", B64.encode(&t.jpeg)));
+        }
+        out.push_str(&format!(
+            "<div><span class=\"risk\" style=\"color:{}\">{}</span> · {} · {}<br><strong>{}</strong>\
+             <p>{}</p><span class=\"muted\">{}</span></div></div>\n",
+            risk_color(&incident.risk_level),
+            escape(&incident.risk_level),
+            escape(&incident.stream_name),
+            escape(&c.local(incident.captured_at)),
+            escape(incident.title.as_deref().unwrap_or("Untitled event")),
+            escape(&incident.description),
+            escape(&incident.status),
+        ));
+    }
+
+    out.push_str(&totals_table("Events by risk level", "Risk level", &c.summary.by_risk));
+    out.push_str(&totals_table("Events by stream", "Stream", &c.summary.by_stream));
+    out.push_str(&totals_table("Events by rule", "Rule", &c.summary.by_rule));
+
+    out.push_str("<h2>Time to resolve</h2>\n<table><tr><th>Risk level</th><th>Events</th><th>Resolved</th>\
+                  <th>Unresolved</th><th>Median</th><th>Mean</th></tr>\n");
+    for r in &c.summary.resolution {
+        out.push_str(&format!(
+            "<tr><td>{}</td><td class=\"n\">{}</td><td class=\"n\">{}</td><td class=\"n\">{}</td>\
+             <td class=\"n\">{}</td><td class=\"n\">{}</td></tr>\n",
+            escape(r.risk_level.as_deref().unwrap_or("All")),
+            r.events,
+            r.resolved,
+            r.unresolved,
+            r.median_seconds.map(duration).unwrap_or_else(|| "–".into()),
+            r.mean_seconds.map(duration).unwrap_or_else(|| "–".into()),
+        ));
+    }
+    out.push_str("</table>\n</body></html>\n");
+    out
+}
+
+/// The report's figures and incidents as plain text.
+pub fn text(c: &ReportContent) -> String {
+    let mut out = format!("{}\n{} · {}\n{}\n", c.title, c.scope, c.period(), c.headline());
+    let mut section = |heading: &str, totals: &[EventCountTotal]| {
+        if !totals.is_empty() {
+            out.push_str(&format!("\n{heading}:\n"));
+            for t in totals {
+                out.push_str(&format!("- {}: {}\n", total_label(t), t.count));
+            }
+        }
+    };
+    section("Events by stream", &c.summary.by_stream);
+    section("Events by rule", &c.summary.by_rule);
+
+    if let Some(all) = c.summary.resolution.iter().find(|r| r.risk_level.is_none()) {
+        out.push_str(&format!(
+            "\nResolved {} of {} events; median time to resolve {}.\n",
+            all.resolved,
+            all.events,
+            all.median_seconds.map(duration).unwrap_or_else(|| "n/a".into())
+        ));
+    }
+    if !c.summary.incidents.is_empty() {
+        out.push_str("\nTop incidents:\n");
+        for i in &c.summary.incidents {
+            out.push_str(&format!(
+                "- {} risk, {}, {} ({}): {} {}\n",
+                i.risk_level,
+                i.stream_name,
+                c.local(i.captured_at),
+                i.status,
+                i.title.as_deref().unwrap_or(""),
+                i.description
+            ));
+        }
+    }
+    out
+}
+
+/// Stream or blueprint name if known, else the key.
+pub fn total_label(t: &EventCountTotal) -> &str {
+    t.label.as_deref().or(t.key.as_deref()).unwrap_or("–")
+}
+
+/// Seconds as "45s", "12m", "3h 05m" or "2d 4h".
+pub fn duration(seconds: f64) -> String {
+    let s = seconds.max(0.0).round() as i64;
+    match s {
+        0..=59 => format!("{s}s"),
+        60..=3599 => format!("{}m", s / 60),
+        3600..=86399 => format!("{}h {:02}m", s / 3600, s % 3600 / 60),
+        _ => format!("{}d {}h", s / 86400, s % 86400 / 3600),
+    }
+}
+
+fn totals_table(heading: &str, column: &str, totals: &[EventCountTotal]) -> String {
+    if totals.is_empty() {
+        return String::new();
+    }
+    let mut out = format!("<h2>{heading}</h2>\n<table><tr><th>{column}</th><th>Events</th></tr>\n");
+    for t in totals {
+        out.push_str(&format!("<tr><td>{}</td><td class=\"n\">{}</td></tr>\n", escape(total_label(t)), t.count));
+    }
+    out.push_str("</table>\n");
+    out
+}
+
+fn risk_color(risk_level: &str) -> &'static str {
+    match risk_level {
+        "high" => "#c62828",
+        "medium" => "#ef6c00",
+        "low" => "#b59500",
+        _ => "#2e7d32",
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for ch in s.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
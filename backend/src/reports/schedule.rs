@@ -0,0 +1,163 @@
+//! Report schedules: cron patterns evaluated in local time at the schedule's
+//! UTC offset, and the loop that generates due reports.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use croner::Cron;
+use sqlx::PgPool;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{
+    assistant::chat::{ChatClient, DynChatClient},
+    config::ReportsConfig,
+    error::{AppError, Result},
+    reports::{self, ReportSpec, MAX_PERIOD_HOURS},
+    storage::{
+        db,
+        models::{CreateReportScheduleRequest, Report, ReportSchedule, UpdateReportScheduleRequest},
+    },
+};
+
+pub async fn create(db: &PgPool, req: &CreateReportScheduleRequest) -> Result<ReportSchedule> {
+    let next_run_at = validate(
+        db,
+        &req.name,
+        &req.cron,
+        req.utc_offset_minutes.unwrap_or(0),
+        req.period_hours.unwrap_or(24),
+        req.stream_id,
+        req.blueprint_id,
+    )
+    .await?;
+    db::create_report_schedule(db, req, next_run_at).await
+}
+
+/// The next run is recomputed from now, so a changed pattern takes effect at once.
+pub async fn update(db: &PgPool, id: Uuid, req: &UpdateReportScheduleRequest) -> Result<ReportSchedule> {
+    let current = db::get_report_schedule(db, id).await?;
+    let next_run_at = validate(
+        db,
+        req.name.as_deref().unwrap_or(&current.name),
+        req.cron.as_deref().unwrap_or(&current.cron),
+        req.utc_offset_minutes.unwrap_or(current.utc_offset_minutes),
+        req.period_hours.unwrap_or(current.period_hours),
+        req.stream_id.unwrap_or(current.stream_id),
+        req.blueprint_id.unwrap_or(current.blueprint_id),
+    )
+    .await?;
+    db::update_report_schedule(db, &current, req, next_run_at).await
+}
+
+/// Generates the schedule's report for the period ending now and texts it if
+/// the schedule asks for it. Doesn't move the schedule's next run.
+pub async fn run(
+    db: &PgPool,
+    chat: &dyn ChatClient,
+    schedule: &ReportSchedule,
+    cfg: &ReportsConfig,
+) -> Result<Report> {
+    let to = Utc::now();
+    let mut report = reports::generate(
+        db,
+        chat,
+        &ReportSpec {
+            schedule_id: Some(schedule.id),
+            title: schedule.name.clone(),
+            stream_id: schedule.stream_id,
+            blueprint_id: schedule.blueprint_id,
+            from: to - chrono::Duration::hours(schedule.period_hours as i64),
+            to,
+            utc_offset_minutes: schedule.utc_offset_minutes,
+            llm_summary: schedule.llm_summary,
+        },
+    )
+    .await?;
+    if schedule.notify_sms {
+        report.notified =
+            reports::notify(db, &report, schedule.notify_phone.as_deref(), cfg.public_url.as_deref()).await?;
+    }
+    Ok(report)
+}
+
+/// Every `check_interval_sec`, generates the reports of the schedules that are due.
+pub async fn run_schedules(db: PgPool, chat: DynChatClient, cfg: ReportsConfig) {
+    let mut interval = tokio::time::interval(Duration::from_secs(cfg.check_interval_sec));
+    loop {
+        interval.tick().await;
+        let now = Utc::now();
+        let due = match db::due_report_schedules(&db, now).await {
+            Ok(due) => due,
+            Err(e) => {
+                warn!("Loading due report schedules failed: {e}");
+                continue;
+            }
+        };
+        for schedule in due {
+            // A run missed while the server was down is made once, not once per missed slot.
+            let next_run_at = match next_run(&schedule.cron, schedule.utc_offset_minutes, now) {
+                Ok(next) => next,
+                Err(e) => {
+                    warn!(schedule = %schedule.name, "Report schedule has no next run: {e}");
+                    continue;
+                }
+            };
+            match db::claim_report_schedule(&db, &schedule, next_run_at).await {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(e) => {
+                    warn!(schedule = %schedule.name, "Claiming report schedule failed: {e}");
+                    continue;
+                }
+            }
+            match run(&db, chat.as_ref(), &schedule, &cfg).await {
+                Ok(report) => info!(
+                    schedule = %schedule.name,
+                    report_id = %report.id,
+                    events = report.event_count,
+                    notified = report.notified,
+                    "Generated scheduled report"
+                ),
+                Err(e) => warn!(schedule = %schedule.name, "Scheduled report failed: {e}"),
+            }
+        }
+    }
+}
+
+/// First time strictly after `after` matching `cron` in local time at the offset.
+pub fn next_run(cron: &str, utc_offset_minutes: i32, after: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    let offset = reports::utc_offset(utc_offset_minutes)?;
+    let pattern = Cron::new(cron.trim())
+        .parse()
+        .map_err(|e| AppError::BadRequest(format!("invalid cron pattern '{cron}': {e}")))?;
+    let next = pattern
+        .find_next_occurrence(&after.with_timezone(&offset), false)
+        .map_err(|e| AppError::BadRequest(format!("cron pattern '{cron}' never matches: {e}")))?;
+    Ok(next.with_timezone(&Utc))
+}
+
+/// Checks a schedule's fields and returns its next run.
+async fn validate(
+    db: &PgPool,
+    name: &str,
+    cron: &str,
+    utc_offset_minutes: i32,
+    period_hours: i32,
+    stream_id: Option<Uuid>,
+    blueprint_id: Option<Uuid>,
+) -> Result<DateTime<Utc>> {
+    if name.trim().is_empty() {
+        return Err(AppError::BadRequest("name must not be empty".into()));
+    }
+    if !(1..=MAX_PERIOD_HOURS).contains(&period_hours) {
+        return Err(AppError::BadRequest(format!("period_hours must be between 1 and {MAX_PERIOD_HOURS}")));
+    }
+    if let Some(id) = stream_id {
+        db::get_stream(db, id).await?;
+    }
+    if let Some(id) = blueprint_id {
+        db::get_blueprint(db, id).await?;
+    }
+    next_run(cron, utc_offset_minutes, Utc::now())
+}
@@ -4,7 +4,10 @@ use sqlx::PgPool;
 use tokio::sync::broadcast;
 
 use crate::{
-    analysis::vlm::DynVlmClient,
+    analysis::vlm::registry::VlmRegistry,
+    assistant::{chat::DynChatClient, AssistantRuns},
+    config::{AppConfig, AssistantConfig, ReportsConfig},
+    evidence::signing::EvidenceSigner,
     storage::models::AnalysisEvent,
     streams::frame_store::FrameStore,
 };
@@ -13,20 +16,44 @@ use crate::{
 #[derive(Clone)]
 pub struct AppState {
     pub db: PgPool,
-    pub vlm: DynVlmClient,
+    /// Global VLM client plus per-profile clients for streams that have one.
+    pub vlm: Arc<VlmRegistry>,
+    /// Chat model of the assistant and report summaries.
+    pub chat: DynChatClient,
     /// Broadcast channel – analysis workers publish; WS handlers subscribe.
     pub event_tx: broadcast::Sender<AnalysisEvent>,
     /// Latest frame per stream + per-stream live MJPEG channels.
     pub frame_store: Arc<FrameStore>,
+    /// Signs evidence exports.
+    pub signer: Arc<EvidenceSigner>,
+    /// Link and schedule settings for digest reports.
+    pub reports: ReportsConfig,
+    /// Limits on the actions the assistant proposes.
+    pub assistant: AssistantConfig,
+    /// Assistant replies in progress, for cancelling.
+    pub assistant_runs: Arc<AssistantRuns>,
 }
 
 impl AppState {
     pub fn new(
+        cfg: &AppConfig,
         db: PgPool,
-        vlm: DynVlmClient,
+        vlm: Arc<VlmRegistry>,
+        chat: DynChatClient,
         event_tx: broadcast::Sender<AnalysisEvent>,
         frame_store: Arc<FrameStore>,
+        signer: Arc<EvidenceSigner>,
     ) -> Arc<Self> {
-        Arc::new(Self { db, vlm, event_tx, frame_store })
+        Arc::new(Self {
+            db,
+            vlm,
+            chat,
+            event_tx,
+            frame_store,
+            signer,
+            reports: cfg.reports.clone(),
+            assistant: cfg.assistant.clone(),
+            assistant_runs: Arc::default(),
+        })
     }
 }
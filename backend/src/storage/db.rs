@@ -1,3 +1,4 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use chrono::{DateTime, Utc};
 use serde_json::Value;
 use sqlx::PgPool;
@@ -7,8 +8,16 @@ use crate::{
     error::{AppError, Result},
     storage::models::{
         AnalysisEvent, Blueprint, BlueprintSummary, CreateRuleRequest,
-        CreateStreamRequest, EventQuery, Stream, StreamRule, UpdateRuleRequest,
-        UpdateStreamRequest,
+        CreateStreamRequest, CreateVlmProfileRequest, EvalDataset, EvalRun, EvalSample, EventQuery, EventReanalysis, NewAnalysisEvent,
+        NewEventReanalysis, NewShadowResult, ReanalysisJob, RiskTransition, RuleOverride, RuleScope,
+        RuleHitRow, DailyRuleHits, FalsePositiveRow, FeedbackClause, NewFeedbackClause, RuleTemplate, RuleTemplateDoc,
+        ReferenceImageRow, RuleReferenceImage, EventCountBucket, HeatmapCell, ResolutionStats, StatsFilter,
+        ChainBreakRow, ChainHead, ChainStatusRow, CheckpointCheckRow, EvidenceCheckpoint,
+        AssistantAction, AssistantActionQuery, AssistantConversation, AssistantMessage,
+        CreateReportScheduleRequest, NewReport, Report, ReportIncidentRow, ReportQuery, ReportSchedule,
+        UpdateReportScheduleRequest,
+        SetRuleOverrideRequest, ShadowAgreementRow, ShadowReportQuery, ShadowResult, Stream, StreamRule,
+        UpdateRuleRequest, UpdateStreamRequest, UpdateVlmProfileRequest, VlmProfile,
     },
 };
 
@@ -18,7 +27,7 @@ pub async fn list_streams(db: &PgPool, blueprint_id: Option<Uuid>) -> Result<Vec
     let mut qb = sqlx::QueryBuilder::new(
         "SELECT id, name, source_type, source_url, capture_interval_sec, \
                 enabled, position_x, position_y, rotation, \
-                blueprint_id, created_at, updated_at \
+                blueprint_id, created_at, updated_at, vlm_profile_id, preprocessing \
          FROM streams WHERE 1=1",
     );
     if let Some(bid) = blueprint_id {
@@ -35,7 +44,7 @@ pub async fn get_stream(db: &PgPool, id: Uuid) -> Result<Stream> {
         Stream,
         r#"SELECT id, name, source_type, source_url, capture_interval_sec,
                   enabled, position_x, position_y, rotation,
-                  blueprint_id, created_at, updated_at
+                  blueprint_id, created_at, updated_at, vlm_profile_id, preprocessing
            FROM streams WHERE id = $1"#,
         id
     )
@@ -45,19 +54,23 @@ pub async fn get_stream(db: &PgPool, id: Uuid) -> Result<Stream> {
 }
 
 pub async fn create_stream(db: &PgPool, req: &CreateStreamRequest) -> Result<Stream> {
+    let preprocessing = req.preprocessing.as_ref().map(|c| serde_json::to_value(c).unwrap_or_default());
     let row = sqlx::query_as!(
         Stream,
-        r#"INSERT INTO streams (name, source_type, source_url, capture_interval_sec, enabled, blueprint_id)
-           VALUES ($1, $2, $3, $4, $5, $6)
+        r#"INSERT INTO streams (name, source_type, source_url, capture_interval_sec, enabled, blueprint_id,
+                                vlm_profile_id, preprocessing)
+           VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
            RETURNING id, name, source_type, source_url, capture_interval_sec,
                      enabled, position_x, position_y, rotation,
-                     blueprint_id, created_at, updated_at"#,
+                     blueprint_id, created_at, updated_at, vlm_profile_id, preprocessing"#,
         req.name,
         req.source_type,
         req.source_url,
         req.capture_interval_sec,
         req.enabled,
         req.blueprint_id,
+        req.vlm_profile_id,
+        preprocessing,
     )
     .fetch_one(db)
     .await?;
@@ -70,6 +83,11 @@ pub async fn update_stream(db: &PgPool, id: Uuid, req: &UpdateStreamRequest) ->
         None => current.blueprint_id,
         Some(opt) => opt,
     };
+    let vlm_profile_id = req.vlm_profile_id.unwrap_or(current.vlm_profile_id);
+    let preprocessing = match &req.preprocessing {
+        None => current.preprocessing,
+        Some(cfg) => cfg.as_ref().map(|c| serde_json::to_value(c).unwrap_or_default()),
+    };
 
     let row = sqlx::query_as!(
         Stream,
@@ -83,11 +101,13 @@ pub async fn update_stream(db: &PgPool, id: Uuid, req: &UpdateStreamRequest) ->
                position_y           = $8,
                rotation             = $9,
                blueprint_id         = $10,
+               vlm_profile_id       = $11,
+               preprocessing        = $12,
                updated_at           = NOW()
            WHERE id = $1
            RETURNING id, name, source_type, source_url, capture_interval_sec,
                      enabled, position_x, position_y, rotation,
-                     blueprint_id, created_at, updated_at"#,
+                     blueprint_id, created_at, updated_at, vlm_profile_id, preprocessing"#,
         id,
         req.name.as_deref().unwrap_or(&current.name),
         req.source_type.as_deref().unwrap_or(&current.source_type),
@@ -98,6 +118,8 @@ pub async fn update_stream(db: &PgPool, id: Uuid, req: &UpdateStreamRequest) ->
         req.position_y.unwrap_or(current.position_y),
         req.rotation.unwrap_or(current.rotation),
         blueprint_id,
+        vlm_profile_id,
+        preprocessing,
     )
     .fetch_one(db)
     .await?;
@@ -124,7 +146,7 @@ pub async fn set_stream_enabled(db: &PgPool, id: Uuid, enabled: bool) -> Result<
            WHERE id = $1
            RETURNING id, name, source_type, source_url, capture_interval_sec,
                      enabled, position_x, position_y, rotation,
-                     blueprint_id, created_at, updated_at"#,
+                     blueprint_id, created_at, updated_at, vlm_profile_id, preprocessing"#,
         id,
         enabled,
     )
@@ -135,6 +157,14 @@ pub async fn set_stream_enabled(db: &PgPool, id: Uuid, enabled: bool) -> Result<
     Ok(row)
 }
 
+/// The stream's `PreprocessConfig` JSON, if it has one.
+pub async fn get_stream_preprocessing(db: &PgPool, stream_id: Uuid) -> Result<Option<Value>> {
+    let row = sqlx::query_scalar!("SELECT preprocessing FROM streams WHERE id = $1", stream_id)
+        .fetch_optional(db)
+        .await?;
+    Ok(row.flatten())
+}
+
 // ─── App settings (global alert phone used when high risk) ────────────────────
 
 const ALERT_PHONE_KEY: &str = "alert_phone_number";
@@ -164,36 +194,46 @@ pub async fn set_alert_phone_number(db: &PgPool, value: Option<&str>) -> Result<
 
 // ─── Analysis Events ──────────────────────────────────────────────────────────
 
-pub async fn insert_event(
-    db: &PgPool,
-    id: Uuid,
-    stream_id: Uuid,
-    captured_at: DateTime<Utc>,
-    description: &str,
-    events: Value,
-    risk_level: &str,
-    triggered_rule: Option<&str>,
-    title: Option<&str>,
-    frame: Option<&[u8]>,
-    status: &str,
-) -> Result<AnalysisEvent> {
+pub async fn insert_event(db: &PgPool, ev: &NewAnalysisEvent<'_>) -> Result<AnalysisEvent> {
     let row = sqlx::query_as!(
         AnalysisEvent,
         r#"INSERT INTO analysis_events
-               (id, stream_id, captured_at, description, events, risk_level, triggered_rule, title, frame, status)
-           VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+               (id, stream_id, captured_at, description, events, risk_level, triggered_rule, title, frame, status,
+                raw_response, system_prompt, rules_snapshot, model, vlm_backend,
+                latency_ms, prompt_tokens, completion_tokens, preprocess_stats, batch_id, batch_tile, rule_check,
+                rule_id)
+           VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21,
+                   $22, $23)
            RETURNING id, stream_id, captured_at, description,
-                     events, risk_level, triggered_rule, raw_response, title, frame, status, created_at"#,
-        id,
-        stream_id,
-        captured_at,
-        description,
-        events,
-        risk_level,
-        triggered_rule,
-        title,
-        frame,
-        status,
+                     events, risk_level, triggered_rule, raw_response, title, frame, status, created_at,
+                     system_prompt, rules_snapshot, model, vlm_backend,
+                     latency_ms, prompt_tokens, completion_tokens, preprocess_stats,
+                     batch_id, batch_tile, rule_check, rule_id,
+                     disposition, disposition_reason, disposition_at,
+                     chain_seq, prev_hash, content_hash, frame_sha256"#,
+        ev.id,
+        ev.stream_id,
+        ev.captured_at,
+        ev.description,
+        ev.events,
+        ev.risk_level,
+        ev.triggered_rule,
+        ev.title,
+        ev.frame,
+        ev.status,
+        ev.raw_response,
+        ev.system_prompt,
+        ev.rules_snapshot,
+        ev.model,
+        ev.vlm_backend,
+        ev.latency_ms,
+        ev.prompt_tokens,
+        ev.completion_tokens,
+        ev.preprocess_stats,
+        ev.batch_id,
+        ev.batch_tile,
+        ev.rule_check,
+        ev.rule_id,
     )
     .fetch_one(db)
     .await?;
@@ -202,18 +242,98 @@ pub async fn insert_event(
 }
 
 pub async fn list_events(db: &PgPool, query: &EventQuery) -> Result<Vec<AnalysisEvent>> {
+    Ok(list_events_page(db, query).await?.0)
+}
+
+/// One page of events matching `query`, newest first, and the cursor of the
+/// next page if there is one.
+pub async fn list_events_page(db: &PgPool, query: &EventQuery) -> Result<(Vec<AnalysisEvent>, Option<String>)> {
     // Build the query dynamically based on which filters are set.
     // sqlx doesn't support fully dynamic queries with query_as!, so we use
     // QueryBuilder for optional filters.
-    let mut qb = sqlx::QueryBuilder::new(
-        "SELECT id, stream_id, captured_at, description, events, risk_level, triggered_rule, raw_response, title, frame, status, created_at FROM analysis_events WHERE 1=1",
-    );
+    let frame = if query.exclude_frame { "NULL::BYTEA AS frame" } else { "frame" };
+    let mut qb = sqlx::QueryBuilder::new(format!(
+        "SELECT id, stream_id, captured_at, description, events, risk_level, triggered_rule, raw_response, title, {frame}, status, created_at, \
+                system_prompt, rules_snapshot, model, vlm_backend, latency_ms, prompt_tokens, completion_tokens, \
+                preprocess_stats, batch_id, batch_tile, rule_check, rule_id, \
+                disposition, disposition_reason, disposition_at, \
+                chain_seq, prev_hash, content_hash, frame_sha256 \
+         FROM analysis_events WHERE 1=1"
+    ));
+    push_event_filters(&mut qb, query)?;
+
+    // One row more than asked for tells whether there is a next page.
+    let limit = query.limit.max(1);
+    qb.push(" ORDER BY captured_at DESC, id DESC")
+        .push(" LIMIT ")
+        .push_bind(limit + 1)
+        .push(" OFFSET ")
+        .push_bind(query.offset);
+
+    let mut rows = qb
+        .build_query_as::<AnalysisEvent>()
+        .fetch_all(db)
+        .await?;
+
+    let next_cursor = if rows.len() as i64 > limit {
+        rows.truncate(limit as usize);
+        rows.last().map(|e| encode_event_cursor(e.captured_at, e.id))
+    } else {
+        None
+    };
+    Ok((rows, next_cursor))
+}
+
+/// IDs of the events matching `query`, newest first, without loading frames.
+pub async fn list_event_ids(db: &PgPool, query: &EventQuery) -> Result<Vec<Uuid>> {
+    let mut qb = sqlx::QueryBuilder::new("SELECT id FROM analysis_events WHERE 1=1");
+    push_event_filters(&mut qb, query)?;
+    qb.push(" ORDER BY captured_at DESC, id DESC")
+        .push(" LIMIT ")
+        .push_bind(query.limit)
+        .push(" OFFSET ")
+        .push_bind(query.offset);
+
+    let rows = qb.build_query_scalar::<Uuid>().fetch_all(db).await?;
+    Ok(rows)
+}
 
+/// Appends the optional `EventQuery` filters as `AND ...` clauses.
+fn push_event_filters<'a>(qb: &mut sqlx::QueryBuilder<'a, sqlx::Postgres>, query: &'a EventQuery) -> Result<()> {
     if let Some(sid) = query.stream_id {
         qb.push(" AND stream_id = ").push_bind(sid);
     }
-    if let Some(ref rl) = query.risk_level {
-        qb.push(" AND risk_level = ").push_bind(rl.as_str());
+    if let Some(bid) = query.blueprint_id {
+        qb.push(" AND stream_id IN (SELECT id FROM streams WHERE blueprint_id = ")
+            .push_bind(bid)
+            .push(")");
+    }
+    if let Some(levels) = split_list(query.risk_level.as_deref()) {
+        qb.push(" AND risk_level = ANY(").push_bind(levels).push(")");
+    }
+    if let Some(statuses) = split_list(query.status.as_deref()) {
+        qb.push(" AND status = ANY(").push_bind(statuses).push(")");
+    }
+    if let Some(types) = split_list(query.event_type.as_deref()) {
+        // Containment, so the GIN index on `events` applies.
+        qb.push(" AND (");
+        for (i, event_type) in types.into_iter().enumerate() {
+            if i > 0 {
+                qb.push(" OR ");
+            }
+            qb.push("events @> ")
+                .push_bind(serde_json::json!([{ "event_type": event_type }]));
+        }
+        qb.push(")");
+    }
+    if let Some(rule) = query.triggered_rule.as_deref().map(str::trim).filter(|r| !r.is_empty()) {
+        qb.push(" AND lower(triggered_rule) = lower(").push_bind(rule).push(")");
+    }
+    if let Some(text) = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+        // Same expression as idx_events_fts.
+        qb.push(" AND to_tsvector('english', coalesce(title, '') || ' ' || description) @@ websearch_to_tsquery('english', ")
+            .push_bind(text)
+            .push(")");
     }
     if let Some(from) = query.from {
         qb.push(" AND captured_at >= ").push_bind(from);
@@ -221,26 +341,48 @@ pub async fn list_events(db: &PgPool, query: &EventQuery) -> Result<Vec<Analysis
     if let Some(to) = query.to {
         qb.push(" AND captured_at <= ").push_bind(to);
     }
+    if let Some(cursor) = query.cursor.as_deref().filter(|c| !c.is_empty()) {
+        let (captured_at, id) = decode_event_cursor(cursor)?;
+        qb.push(" AND (captured_at, id) < (")
+            .push_bind(captured_at)
+            .push(", ")
+            .push_bind(id)
+            .push(")");
+    }
+    Ok(())
+}
 
-    qb.push(" ORDER BY captured_at DESC")
-        .push(" LIMIT ")
-        .push_bind(query.limit)
-        .push(" OFFSET ")
-        .push_bind(query.offset);
+/// Comma-separated values, trimmed; None if there are none.
+fn split_list(s: Option<&str>) -> Option<Vec<String>> {
+    let values: Vec<String> =
+        s?.split(',').map(str::trim).filter(|v| !v.is_empty()).map(String::from).collect();
+    (!values.is_empty()).then_some(values)
+}
 
-    let rows = qb
-        .build_query_as::<AnalysisEvent>()
-        .fetch_all(db)
-        .await?;
+/// Opaque keyset cursor: the last event's `captured_at` (microseconds) and id.
+fn encode_event_cursor(captured_at: DateTime<Utc>, id: Uuid) -> String {
+    URL_SAFE_NO_PAD.encode(format!("{}:{id}", captured_at.timestamp_micros()))
+}
 
-    Ok(rows)
+fn decode_event_cursor(cursor: &str) -> Result<(DateTime<Utc>, Uuid)> {
+    let invalid = || AppError::BadRequest("invalid cursor".into());
+    let raw = URL_SAFE_NO_PAD.decode(cursor).map_err(|_| invalid())?;
+    let raw = String::from_utf8(raw).map_err(|_| invalid())?;
+    let (micros, id) = raw.split_once(':').ok_or_else(invalid)?;
+    let captured_at = micros.parse().ok().and_then(DateTime::from_timestamp_micros).ok_or_else(invalid)?;
+    Ok((captured_at, id.parse().map_err(|_| invalid())?))
 }
 
 pub async fn get_event(db: &PgPool, id: Uuid) -> Result<AnalysisEvent> {
     sqlx::query_as!(
         AnalysisEvent,
         r#"SELECT id, stream_id, captured_at, description,
-                  events, risk_level, triggered_rule, raw_response, title, frame, status, created_at
+                  events, risk_level, triggered_rule, raw_response, title, frame, status, created_at,
+                  system_prompt, rules_snapshot, model, vlm_backend,
+                  latency_ms, prompt_tokens, completion_tokens, preprocess_stats,
+                  batch_id, batch_tile, rule_check, rule_id,
+                  disposition, disposition_reason, disposition_at,
+                  chain_seq, prev_hash, content_hash, frame_sha256
            FROM analysis_events WHERE id = $1"#,
         id
     )
@@ -254,7 +396,12 @@ pub async fn update_event_status(db: &PgPool, id: Uuid, status: &str) -> Result<
         AnalysisEvent,
         r#"UPDATE analysis_events SET status = $1 WHERE id = $2
            RETURNING id, stream_id, captured_at, description, events, risk_level,
-                     triggered_rule, raw_response, title, frame, status, created_at"#,
+                     triggered_rule, raw_response, title, frame, status, created_at,
+                     system_prompt, rules_snapshot, model, vlm_backend,
+                     latency_ms, prompt_tokens, completion_tokens, preprocess_stats,
+                     batch_id, batch_tile, rule_check, rule_id,
+                     disposition, disposition_reason, disposition_at,
+                     chain_seq, prev_hash, content_hash, frame_sha256"#,
         status,
         id
     )
@@ -263,19 +410,62 @@ pub async fn update_event_status(db: &PgPool, id: Uuid, status: &str) -> Result<
     row    .ok_or_else(|| AppError::NotFound(format!("Event {id} not found")))
 }
 
-pub async fn resolve_events_by_filter(
+/// Sets or clears (`disposition` None) the operator's verdict on an event.
+pub async fn set_event_disposition(
+    db: &PgPool,
+    id: Uuid,
+    disposition: Option<&str>,
+    reason: Option<&str>,
+) -> Result<AnalysisEvent> {
+    let row = sqlx::query_as!(
+        AnalysisEvent,
+        r#"UPDATE analysis_events
+           SET disposition        = $2::VARCHAR,
+               disposition_reason = CASE WHEN $2::VARCHAR IS NULL THEN NULL ELSE $3 END,
+               disposition_at     = CASE WHEN $2::VARCHAR IS NULL THEN NULL ELSE NOW() END
+           WHERE id = $1
+           RETURNING id, stream_id, captured_at, description, events, risk_level,
+                     triggered_rule, raw_response, title, frame, status, created_at,
+                     system_prompt, rules_snapshot, model, vlm_backend,
+                     latency_ms, prompt_tokens, completion_tokens, preprocess_stats,
+                     batch_id, batch_tile, rule_check, rule_id,
+                     disposition, disposition_reason, disposition_at,
+                     chain_seq, prev_hash, content_hash, frame_sha256"#,
+        id,
+        disposition,
+        reason,
+    )
+    .fetch_optional(db)
+    .await?;
+    row.ok_or_else(|| AppError::NotFound(format!("Event {id} not found")))
+}
+
+pub async fn unresolved_event_ids(
     db: &PgPool,
     from: Option<DateTime<Utc>>,
     to: Option<DateTime<Utc>>,
     stream_id: Option<Uuid>,
     risk_level: Option<&str>,
-) -> Result<u64> {
-    let mut qb = sqlx::QueryBuilder::new("UPDATE analysis_events SET status = 'resolved' WHERE status = 'unresolved'");
+    limit: i64,
+) -> Result<Vec<Uuid>> {
+    let mut qb = sqlx::QueryBuilder::new("SELECT id FROM analysis_events WHERE status = 'unresolved'");
     if let Some(f) = from { qb.push(" AND captured_at >= ").push_bind(f); }
     if let Some(t) = to { qb.push(" AND captured_at <= ").push_bind(t); }
     if let Some(sid) = stream_id { qb.push(" AND stream_id = ").push_bind(sid); }
     if let Some(rl) = risk_level { qb.push(" AND risk_level = ").push_bind(rl); }
-    Ok(qb.build().execute(db).await?.rows_affected())
+    qb.push(" ORDER BY captured_at LIMIT ").push_bind(limit);
+    Ok(qb.build_query_scalar().fetch_all(db).await?)
+}
+
+/// Which of `ids` are events that are still unresolved.
+pub async fn unresolved_event_ids_in(db: &PgPool, ids: &[Uuid]) -> Result<Vec<Uuid>> {
+    let rows = sqlx::query_scalar!(
+        r#"SELECT id FROM analysis_events WHERE status = 'unresolved' AND id = ANY($1) ORDER BY captured_at"#,
+        ids,
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
 }
 
 /// Resolve specific events by ID (e.g. after user asks to resolve only certain descriptions).
@@ -292,90 +482,1897 @@ pub async fn resolve_events_by_ids(db: &PgPool, ids: &[Uuid]) -> Result<u64> {
     Ok(qb.build().execute(db).await?.rows_affected())
 }
 
-// ─── Stream Rules ─────────────────────────────────────────────────────────────
+// ─── Re-analysis ──────────────────────────────────────────────────────────────
+
+pub async fn create_reanalysis_job(
+    db: &PgPool,
+    filter: Value,
+    model: Option<&str>,
+    total: i32,
+) -> Result<ReanalysisJob> {
+    let row = sqlx::query_as!(
+        ReanalysisJob,
+        r#"INSERT INTO reanalysis_jobs (filter, model, total)
+           VALUES ($1, $2, $3)
+           RETURNING id, status, filter, model, total, processed, failed, error, created_at, finished_at"#,
+        filter,
+        model,
+        total,
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(row)
+}
+
+pub async fn get_reanalysis_job(db: &PgPool, id: Uuid) -> Result<ReanalysisJob> {
+    sqlx::query_as!(
+        ReanalysisJob,
+        r#"SELECT id, status, filter, model, total, processed, failed, error, created_at, finished_at
+           FROM reanalysis_jobs WHERE id = $1"#,
+        id
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("Re-analysis job {id} not found")))
+}
+
+/// Count one more event as processed (or failed) for a running job.
+pub async fn record_reanalysis_progress(db: &PgPool, id: Uuid, failed: bool) -> Result<()> {
+    sqlx::query!(
+        r#"UPDATE reanalysis_jobs
+           SET processed = processed + 1,
+               failed    = failed + CASE WHEN $2 THEN 1 ELSE 0 END
+           WHERE id = $1"#,
+        id,
+        failed,
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+pub async fn finish_reanalysis_job(
+    db: &PgPool,
+    id: Uuid,
+    status: &str,
+    error: Option<&str>,
+) -> Result<()> {
+    sqlx::query!(
+        r#"UPDATE reanalysis_jobs SET status = $2, error = $3, finished_at = NOW() WHERE id = $1"#,
+        id,
+        status,
+        error,
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Fails the jobs a restart cut short, which would otherwise stay `running`.
+/// Returns how many there were.
+pub async fn fail_interrupted_reanalysis_jobs(db: &PgPool) -> Result<u64> {
+    let result = sqlx::query!(
+        r#"UPDATE reanalysis_jobs
+           SET status = 'failed', error = 'interrupted by a server restart', finished_at = NOW()
+           WHERE status = 'running'"#
+    )
+    .execute(db)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+pub async fn insert_event_reanalysis(db: &PgPool, r: &NewEventReanalysis<'_>) -> Result<()> {
+    sqlx::query!(
+        r#"INSERT INTO event_reanalyses
+               (job_id, event_id, original_risk_level, risk_level, triggered_rule, title, description, events,
+                raw_response, system_prompt, rules_snapshot, model, vlm_backend,
+                latency_ms, prompt_tokens, completion_tokens)
+           VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)"#,
+        r.job_id,
+        r.event_id,
+        r.original_risk_level,
+        r.risk_level,
+        r.triggered_rule,
+        r.title,
+        r.description,
+        r.events,
+        r.raw_response,
+        r.system_prompt,
+        r.rules_snapshot,
+        r.model,
+        r.vlm_backend,
+        r.latency_ms,
+        r.prompt_tokens,
+        r.completion_tokens,
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
 
-pub async fn list_rules(db: &PgPool, stream_id: Uuid) -> Result<Vec<StreamRule>> {
+pub async fn list_event_reanalyses(db: &PgPool, job_id: Uuid) -> Result<Vec<EventReanalysis>> {
     let rows = sqlx::query_as!(
-        StreamRule,
-        r#"SELECT id, stream_id, description, threat_level, position, created_at, updated_at
-           FROM stream_rules
-           WHERE stream_id = $1
-           ORDER BY position ASC, created_at ASC"#,
-        stream_id
+        EventReanalysis,
+        r#"SELECT id, job_id, event_id, original_risk_level, risk_level, triggered_rule, title, description,
+                  events, raw_response, system_prompt, rules_snapshot, model, vlm_backend,
+                  latency_ms, prompt_tokens, completion_tokens, created_at
+           FROM event_reanalyses
+           WHERE job_id = $1
+           ORDER BY created_at ASC"#,
+        job_id
     )
     .fetch_all(db)
     .await?;
     Ok(rows)
 }
 
-pub async fn create_rule(
+/// Counts of (original risk level → new risk level) for a job's results.
+pub async fn reanalysis_transitions(db: &PgPool, job_id: Uuid) -> Result<Vec<RiskTransition>> {
+    let rows = sqlx::query_as!(
+        RiskTransition,
+        r#"SELECT original_risk_level AS "from", risk_level AS "to", COUNT(*) AS "count!"
+           FROM event_reanalyses
+           WHERE job_id = $1
+           GROUP BY original_risk_level, risk_level
+           ORDER BY original_risk_level, risk_level"#,
+        job_id
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+// ─── Shadow mode ──────────────────────────────────────────────────────────────
+
+pub async fn insert_shadow_result(db: &PgPool, r: &NewShadowResult<'_>) -> Result<()> {
+    sqlx::query!(
+        r#"INSERT INTO shadow_results
+               (event_id, risk_level, triggered_rule, title, description, events, raw_response,
+                model, vlm_backend, latency_ms, prompt_tokens, completion_tokens)
+           VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)"#,
+        r.event_id,
+        r.risk_level,
+        r.triggered_rule,
+        r.title,
+        r.description,
+        r.events,
+        r.raw_response,
+        r.model,
+        r.vlm_backend,
+        r.latency_ms,
+        r.prompt_tokens,
+        r.completion_tokens,
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+pub async fn list_shadow_results(db: &PgPool, event_id: Uuid) -> Result<Vec<ShadowResult>> {
+    let rows = sqlx::query_as!(
+        ShadowResult,
+        r#"SELECT id, event_id, risk_level, triggered_rule, title, description, events, raw_response,
+                  model, vlm_backend, latency_ms, prompt_tokens, completion_tokens, created_at
+           FROM shadow_results WHERE event_id = $1
+           ORDER BY created_at ASC"#,
+        event_id
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+/// Appends the `ShadowReportQuery` filters; expects `s` = shadow_results, `e` = analysis_events.
+fn push_shadow_filters<'a>(qb: &mut sqlx::QueryBuilder<'a, sqlx::Postgres>, query: &'a ShadowReportQuery) {
+    if let Some(sid) = query.stream_id {
+        qb.push(" AND e.stream_id = ").push_bind(sid);
+    }
+    if let Some(ref model) = query.model {
+        qb.push(" AND s.model = ").push_bind(model.as_str());
+    }
+    if let Some(from) = query.from {
+        qb.push(" AND e.captured_at >= ").push_bind(from);
+    }
+    if let Some(to) = query.to {
+        qb.push(" AND e.captured_at <= ").push_bind(to);
+    }
+}
+
+/// Counts of (primary risk level → shadow risk level).
+pub async fn shadow_transitions(db: &PgPool, query: &ShadowReportQuery) -> Result<Vec<RiskTransition>> {
+    let mut qb = sqlx::QueryBuilder::new(
+        r#"SELECT e.risk_level AS "from", s.risk_level AS "to", COUNT(*) AS "count"
+           FROM shadow_results s JOIN analysis_events e ON e.id = s.event_id
+           WHERE 1=1"#,
+    );
+    push_shadow_filters(&mut qb, query);
+    qb.push(" GROUP BY e.risk_level, s.risk_level ORDER BY e.risk_level, s.risk_level");
+    Ok(qb.build_query_as::<RiskTransition>().fetch_all(db).await?)
+}
+
+pub async fn shadow_agreement(db: &PgPool, query: &ShadowReportQuery) -> Result<ShadowAgreementRow> {
+    let mut qb = sqlx::QueryBuilder::new(
+        r#"SELECT COUNT(*) AS compared,
+                  COUNT(*) FILTER (
+                      WHERE COALESCE(LOWER(TRIM(e.triggered_rule)), '') = COALESCE(LOWER(TRIM(s.triggered_rule)), '')
+                  ) AS rule_matches,
+                  AVG(e.latency_ms)::FLOAT8 AS mean_primary_latency_ms,
+                  AVG(s.latency_ms)::FLOAT8 AS mean_shadow_latency_ms
+           FROM shadow_results s JOIN analysis_events e ON e.id = s.event_id
+           WHERE 1=1"#,
+    );
+    push_shadow_filters(&mut qb, query);
+    Ok(qb.build_query_as::<ShadowAgreementRow>().fetch_one(db).await?)
+}
+
+pub async fn shadow_models(db: &PgPool, query: &ShadowReportQuery) -> Result<Vec<String>> {
+    let mut qb = sqlx::QueryBuilder::new(
+        "SELECT DISTINCT s.model FROM shadow_results s JOIN analysis_events e ON e.id = s.event_id WHERE 1=1",
+    );
+    push_shadow_filters(&mut qb, query);
+    qb.push(" ORDER BY s.model");
+    Ok(qb.build_query_scalar::<String>().fetch_all(db).await?)
+}
+
+// ─── Evaluation datasets ──────────────────────────────────────────────────────
+
+pub async fn list_eval_datasets(db: &PgPool) -> Result<Vec<EvalDataset>> {
+    let rows = sqlx::query_as!(
+        EvalDataset,
+        r#"SELECT id, name, description, rules, created_at, updated_at
+           FROM eval_datasets ORDER BY created_at ASC"#
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+pub async fn get_eval_dataset(db: &PgPool, id: Uuid) -> Result<EvalDataset> {
+    sqlx::query_as!(
+        EvalDataset,
+        r#"SELECT id, name, description, rules, created_at, updated_at
+           FROM eval_datasets WHERE id = $1"#,
+        id
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("Dataset {id} not found")))
+}
+
+pub async fn create_eval_dataset(
     db: &PgPool,
-    stream_id: Uuid,
-    req: &CreateRuleRequest,
-) -> Result<StreamRule> {
+    name: &str,
+    description: Option<&str>,
+    rules: Option<&Value>,
+) -> Result<EvalDataset> {
     let row = sqlx::query_as!(
-        StreamRule,
-        r#"INSERT INTO stream_rules (stream_id, description, threat_level, position)
-           VALUES ($1, $2, $3, $4)
-           RETURNING id, stream_id, description, threat_level, position, created_at, updated_at"#,
-        stream_id,
-        req.description,
-        req.threat_level,
-        req.position,
+        EvalDataset,
+        r#"INSERT INTO eval_datasets (name, description, rules) VALUES ($1, $2, $3)
+           RETURNING id, name, description, rules, created_at, updated_at"#,
+        name,
+        description,
+        rules,
     )
     .fetch_one(db)
     .await?;
     Ok(row)
 }
 
-pub async fn update_rule(
+pub async fn delete_eval_dataset(db: &PgPool, id: Uuid) -> Result<()> {
+    let result = sqlx::query!("DELETE FROM eval_datasets WHERE id = $1", id)
+        .execute(db)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!("Dataset {id} not found")));
+    }
+    Ok(())
+}
+
+pub async fn create_eval_sample(
     db: &PgPool,
-    rule_id: Uuid,
-    stream_id: Uuid,
-    req: &UpdateRuleRequest,
-) -> Result<StreamRule> {
-    let current = sqlx::query_as!(
-        StreamRule,
-        r#"SELECT id, stream_id, description, threat_level, position, created_at, updated_at
-           FROM stream_rules WHERE id = $1 AND stream_id = $2"#,
-        rule_id,
+    dataset_id: Uuid,
+    stream_id: Option<Uuid>,
+    event_id: Option<Uuid>,
+    image: &[u8],
+    expected_risk_level: &str,
+    expected_triggered_rule: Option<&str>,
+) -> Result<EvalSample> {
+    let row = sqlx::query_as!(
+        EvalSample,
+        r#"INSERT INTO eval_samples
+               (dataset_id, stream_id, event_id, image, expected_risk_level, expected_triggered_rule)
+           VALUES ($1, $2, $3, $4, $5, $6)
+           RETURNING id, dataset_id, stream_id, event_id, expected_risk_level,
+                     expected_triggered_rule, created_at"#,
+        dataset_id,
         stream_id,
+        event_id,
+        image,
+        expected_risk_level,
+        expected_triggered_rule,
     )
-    .fetch_optional(db)
-    .await?
-    .ok_or_else(|| AppError::NotFound(format!("Rule {rule_id} not found")))?;
+    .fetch_one(db)
+    .await?;
+    Ok(row)
+}
+
+pub async fn list_eval_samples(db: &PgPool, dataset_id: Uuid) -> Result<Vec<EvalSample>> {
+    let rows = sqlx::query_as!(
+        EvalSample,
+        r#"SELECT id, dataset_id, stream_id, event_id, expected_risk_level,
+                  expected_triggered_rule, created_at
+           FROM eval_samples WHERE dataset_id = $1
+           ORDER BY created_at ASC"#,
+        dataset_id
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+pub async fn get_eval_sample_image(db: &PgPool, id: Uuid) -> Result<Vec<u8>> {
+    sqlx::query_scalar!("SELECT image FROM eval_samples WHERE id = $1", id)
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Sample {id} not found")))
+}
+
+pub async fn delete_eval_sample(db: &PgPool, id: Uuid, dataset_id: Uuid) -> Result<()> {
+    let result = sqlx::query!(
+        "DELETE FROM eval_samples WHERE id = $1 AND dataset_id = $2",
+        id,
+        dataset_id,
+    )
+    .execute(db)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!("Sample {id} not found")));
+    }
+    Ok(())
+}
 
+pub async fn create_eval_run(db: &PgPool, dataset_id: Uuid, model: Option<&str>) -> Result<EvalRun> {
     let row = sqlx::query_as!(
-        StreamRule,
-        r#"UPDATE stream_rules
-           SET description  = $3,
-               threat_level = $4,
-               position     = $5,
-               updated_at   = NOW()
-           WHERE id = $1 AND stream_id = $2
-           RETURNING id, stream_id, description, threat_level, position, created_at, updated_at"#,
-        rule_id,
-        stream_id,
-        req.description.as_deref().unwrap_or(&current.description),
-        req.threat_level.as_deref().unwrap_or(&current.threat_level),
-        req.position.unwrap_or(current.position),
+        EvalRun,
+        r#"INSERT INTO eval_runs (dataset_id, model) VALUES ($1, $2)
+           RETURNING id, dataset_id, status, model, report, error, created_at, finished_at"#,
+        dataset_id,
+        model,
     )
     .fetch_one(db)
     .await?;
     Ok(row)
 }
 
-pub async fn delete_rule(db: &PgPool, rule_id: Uuid, stream_id: Uuid) -> Result<()> {
+/// Fails the runs a restart cut short, which would otherwise stay `running`.
+/// Returns how many there were.
+pub async fn fail_interrupted_eval_runs(db: &PgPool) -> Result<u64> {
     let result = sqlx::query!(
-        "DELETE FROM stream_rules WHERE id = $1 AND stream_id = $2",
-        rule_id,
-        stream_id,
+        r#"UPDATE eval_runs
+           SET status = 'failed', error = 'interrupted by a server restart', finished_at = NOW()
+           WHERE status = 'running'"#
     )
     .execute(db)
     .await?;
+    Ok(result.rows_affected())
+}
 
-    if result.rows_affected() == 0 {
-        return Err(AppError::NotFound(format!("Rule {rule_id} not found")));
+pub async fn finish_eval_run(
+    db: &PgPool,
+    id: Uuid,
+    status: &str,
+    report: Option<&Value>,
+    error: Option<&str>,
+) -> Result<()> {
+    sqlx::query!(
+        r#"UPDATE eval_runs SET status = $2, report = $3, error = $4, finished_at = NOW() WHERE id = $1"#,
+        id,
+        status,
+        report,
+        error,
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_eval_run(db: &PgPool, id: Uuid) -> Result<EvalRun> {
+    sqlx::query_as!(
+        EvalRun,
+        r#"SELECT id, dataset_id, status, model, report, error, created_at, finished_at
+           FROM eval_runs WHERE id = $1"#,
+        id
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("Eval run {id} not found")))
+}
+
+pub async fn list_eval_runs(db: &PgPool, dataset_id: Uuid) -> Result<Vec<EvalRun>> {
+    let rows = sqlx::query_as!(
+        EvalRun,
+        r#"SELECT id, dataset_id, status, model, report, error, created_at, finished_at
+           FROM eval_runs WHERE dataset_id = $1
+           ORDER BY created_at DESC"#,
+        dataset_id
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+// ─── VLM profiles ─────────────────────────────────────────────────────────────
+
+pub async fn list_vlm_profiles(db: &PgPool) -> Result<Vec<VlmProfile>> {
+    let rows = sqlx::query_as!(
+        VlmProfile,
+        r#"SELECT id, name, backend, model, base_url, api_key, connect_timeout_sec,
+                  request_timeout_sec, max_tokens, multi_image, created_at, updated_at
+           FROM vlm_profiles ORDER BY name ASC"#
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+pub async fn get_vlm_profile(db: &PgPool, id: Uuid) -> Result<VlmProfile> {
+    sqlx::query_as!(
+        VlmProfile,
+        r#"SELECT id, name, backend, model, base_url, api_key, connect_timeout_sec,
+                  request_timeout_sec, max_tokens, multi_image, created_at, updated_at
+           FROM vlm_profiles WHERE id = $1"#,
+        id
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("VLM profile {id} not found")))
+}
+
+/// The profile assigned to a stream, or None if it uses the global VLM.
+pub async fn get_stream_vlm_profile(db: &PgPool, stream_id: Uuid) -> Result<Option<VlmProfile>> {
+    let row = sqlx::query_as!(
+        VlmProfile,
+        r#"SELECT p.id, p.name, p.backend, p.model, p.base_url, p.api_key, p.connect_timeout_sec,
+                  p.request_timeout_sec, p.max_tokens, p.multi_image, p.created_at, p.updated_at
+           FROM vlm_profiles p
+           JOIN streams s ON s.vlm_profile_id = p.id
+           WHERE s.id = $1"#,
+        stream_id
+    )
+    .fetch_optional(db)
+    .await?;
+    Ok(row)
+}
+
+pub async fn create_vlm_profile(db: &PgPool, req: &CreateVlmProfileRequest) -> Result<VlmProfile> {
+    let row = sqlx::query_as!(
+        VlmProfile,
+        r#"INSERT INTO vlm_profiles (name, backend, model, base_url, api_key, connect_timeout_sec,
+                                     request_timeout_sec, max_tokens, multi_image)
+           VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+           RETURNING id, name, backend, model, base_url, api_key, connect_timeout_sec,
+                     request_timeout_sec, max_tokens, multi_image, created_at, updated_at"#,
+        req.name,
+        req.backend,
+        req.model,
+        req.base_url,
+        req.api_key.as_deref().filter(|k| !k.trim().is_empty()),
+        req.connect_timeout_sec,
+        req.request_timeout_sec,
+        req.max_tokens,
+        req.multi_image.unwrap_or(true),
+    )
+    .fetch_one(db)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(d) if d.is_unique_violation() => {
+            AppError::BadRequest(format!("a VLM profile named '{}' already exists", req.name))
+        }
+        e => e.into(),
+    })?;
+    Ok(row)
+}
+
+pub async fn update_vlm_profile(db: &PgPool, id: Uuid, req: &UpdateVlmProfileRequest) -> Result<VlmProfile> {
+    let current = get_vlm_profile(db, id).await?;
+    let api_key = match req.api_key.as_deref() {
+        None => current.api_key.as_deref(),
+        Some(k) if k.trim().is_empty() => None,
+        Some(k) => Some(k),
+    };
+
+    let name = req.name.as_deref().unwrap_or(&current.name);
+    let row = sqlx::query_as!(
+        VlmProfile,
+        r#"UPDATE vlm_profiles
+           SET name                = $2,
+               backend             = $3,
+               model               = $4,
+               base_url            = $5,
+               api_key             = $6,
+               connect_timeout_sec = $7,
+               request_timeout_sec = $8,
+               max_tokens          = $9,
+               multi_image         = $10,
+               updated_at          = NOW()
+           WHERE id = $1
+           RETURNING id, name, backend, model, base_url, api_key, connect_timeout_sec,
+                     request_timeout_sec, max_tokens, multi_image, created_at, updated_at"#,
+        id,
+        name,
+        req.backend.as_deref().unwrap_or(&current.backend),
+        req.model.as_deref().unwrap_or(&current.model),
+        req.base_url.as_deref().unwrap_or(&current.base_url),
+        api_key,
+        req.connect_timeout_sec.or(current.connect_timeout_sec),
+        req.request_timeout_sec.or(current.request_timeout_sec),
+        req.max_tokens.or(current.max_tokens),
+        req.multi_image.unwrap_or(current.multi_image),
+    )
+    .fetch_one(db)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(d) if d.is_unique_violation() => {
+            AppError::BadRequest(format!("a VLM profile named '{}' already exists", name))
+        }
+        e => e.into(),
+    })?;
+    Ok(row)
+}
+
+/// Streams using the profile fall back to the global VLM.
+pub async fn delete_vlm_profile(db: &PgPool, id: Uuid) -> Result<()> {
+    let result = sqlx::query!("DELETE FROM vlm_profiles WHERE id = $1", id)
+        .execute(db)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!("VLM profile {id} not found")));
+    }
+    Ok(())
+}
+
+// ─── Stream Rules ─────────────────────────────────────────────────────────────
+
+/// Rules defined at `scope` itself (not inherited ones).
+pub async fn list_rules(db: &PgPool, scope: RuleScope) -> Result<Vec<StreamRule>> {
+    let rows = sqlx::query_as!(
+        StreamRule,
+        r#"SELECT id, scope, stream_id, blueprint_id, description, threat_level, position, created_at, updated_at,
+                  schedule, zone, min_count, min_dwell_sec, event_types
+           FROM stream_rules
+           WHERE scope = $1
+             AND blueprint_id IS NOT DISTINCT FROM $2
+             AND stream_id IS NOT DISTINCT FROM $3
+           ORDER BY position ASC, created_at ASC"#,
+        scope.as_str(),
+        scope.blueprint_id(),
+        scope.stream_id(),
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+/// Global rules, the rules of the stream's blueprint and the stream's own
+/// rules, in that order. Overrides are not applied.
+pub async fn list_rules_for_stream(db: &PgPool, stream_id: Uuid) -> Result<Vec<StreamRule>> {
+    let rows = sqlx::query_as!(
+        StreamRule,
+        r#"SELECT r.id, r.scope, r.stream_id, r.blueprint_id, r.description, r.threat_level, r.position,
+                  r.created_at, r.updated_at, r.schedule, r.zone, r.min_count, r.min_dwell_sec, r.event_types
+           FROM stream_rules r
+           JOIN streams s ON s.id = $1
+           WHERE r.scope = 'global'
+              OR (r.scope = 'blueprint' AND r.blueprint_id = s.blueprint_id)
+              OR (r.scope = 'stream' AND r.stream_id = s.id)
+           ORDER BY CASE r.scope WHEN 'global' THEN 0 WHEN 'blueprint' THEN 1 ELSE 2 END,
+                    r.position ASC, r.created_at ASC"#,
+        stream_id
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+pub async fn create_rule(
+    db: &PgPool,
+    scope: RuleScope,
+    req: &CreateRuleRequest,
+) -> Result<StreamRule> {
+    let row = sqlx::query_as!(
+        StreamRule,
+        r#"INSERT INTO stream_rules
+               (scope, blueprint_id, stream_id, description, threat_level, position,
+                schedule, zone, min_count, min_dwell_sec, event_types)
+           VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+           RETURNING id, scope, stream_id, blueprint_id, description, threat_level, position, created_at, updated_at,
+                     schedule, zone, min_count, min_dwell_sec, event_types"#,
+        scope.as_str(),
+        scope.blueprint_id(),
+        scope.stream_id(),
+        req.description,
+        req.threat_level,
+        req.position,
+        req.schedule.as_ref().map(|s| serde_json::to_value(s).unwrap_or_default()),
+        req.zone.as_ref().map(|z| serde_json::to_value(z).unwrap_or_default()),
+        req.min_count,
+        req.min_dwell_sec,
+        &req.event_types,
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(row)
+}
+
+/// A rule by id, only if it belongs to `scope`.
+pub async fn get_rule(db: &PgPool, rule_id: Uuid, scope: RuleScope) -> Result<StreamRule> {
+    sqlx::query_as!(
+        StreamRule,
+        r#"SELECT id, scope, stream_id, blueprint_id, description, threat_level, position, created_at, updated_at,
+                  schedule, zone, min_count, min_dwell_sec, event_types
+           FROM stream_rules
+           WHERE id = $1
+             AND scope = $2
+             AND blueprint_id IS NOT DISTINCT FROM $3
+             AND stream_id IS NOT DISTINCT FROM $4"#,
+        rule_id,
+        scope.as_str(),
+        scope.blueprint_id(),
+        scope.stream_id(),
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("Rule {rule_id} not found")))
+}
+
+pub async fn update_rule(
+    db: &PgPool,
+    rule_id: Uuid,
+    scope: RuleScope,
+    req: &UpdateRuleRequest,
+) -> Result<StreamRule> {
+    let current = get_rule(db, rule_id, scope).await?;
+
+    let schedule = match &req.schedule {
+        Some(s) => s.as_ref().map(|s| serde_json::to_value(s).unwrap_or_default()),
+        None => current.schedule,
+    };
+    let zone = match &req.zone {
+        Some(z) => z.as_ref().map(|z| serde_json::to_value(z).unwrap_or_default()),
+        None => current.zone,
+    };
+
+    let row = sqlx::query_as!(
+        StreamRule,
+        r#"UPDATE stream_rules
+           SET description   = $2,
+               threat_level  = $3,
+               position      = $4,
+               schedule      = $5,
+               zone          = $6,
+               min_count     = $7,
+               min_dwell_sec = $8,
+               event_types   = $9,
+               updated_at    = NOW()
+           WHERE id = $1
+           RETURNING id, scope, stream_id, blueprint_id, description, threat_level, position, created_at, updated_at,
+                     schedule, zone, min_count, min_dwell_sec, event_types"#,
+        rule_id,
+        req.description.as_deref().unwrap_or(&current.description),
+        req.threat_level.as_deref().unwrap_or(&current.threat_level),
+        req.position.unwrap_or(current.position),
+        schedule,
+        zone,
+        req.min_count.unwrap_or(current.min_count),
+        req.min_dwell_sec.unwrap_or(current.min_dwell_sec),
+        req.event_types.as_deref().unwrap_or(&current.event_types),
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(row)
+}
+
+pub async fn delete_rule(db: &PgPool, rule_id: Uuid, scope: RuleScope) -> Result<()> {
+    let result = sqlx::query!(
+        r#"DELETE FROM stream_rules
+           WHERE id = $1
+             AND scope = $2
+             AND blueprint_id IS NOT DISTINCT FROM $3
+             AND stream_id IS NOT DISTINCT FROM $4"#,
+        rule_id,
+        scope.as_str(),
+        scope.blueprint_id(),
+        scope.stream_id(),
+    )
+    .execute(db)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!("Rule {rule_id} not found")));
+    }
+    Ok(())
+}
+
+pub async fn list_rule_overrides(db: &PgPool, stream_id: Uuid) -> Result<Vec<RuleOverride>> {
+    let rows = sqlx::query_as!(
+        RuleOverride,
+        r#"SELECT stream_id, rule_id, disabled, threat_level, updated_at
+           FROM stream_rule_overrides WHERE stream_id = $1"#,
+        stream_id
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+pub async fn set_rule_override(
+    db: &PgPool,
+    stream_id: Uuid,
+    rule_id: Uuid,
+    req: &SetRuleOverrideRequest,
+) -> Result<RuleOverride> {
+    let row = sqlx::query_as!(
+        RuleOverride,
+        r#"INSERT INTO stream_rule_overrides (stream_id, rule_id, disabled, threat_level)
+           VALUES ($1, $2, $3, $4)
+           ON CONFLICT (stream_id, rule_id)
+           DO UPDATE SET disabled = EXCLUDED.disabled, threat_level = EXCLUDED.threat_level, updated_at = NOW()
+           RETURNING stream_id, rule_id, disabled, threat_level, updated_at"#,
+        stream_id,
+        rule_id,
+        req.disabled,
+        req.threat_level,
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(row)
+}
+
+pub async fn delete_rule_override(db: &PgPool, stream_id: Uuid, rule_id: Uuid) -> Result<()> {
+    let result = sqlx::query!(
+        "DELETE FROM stream_rule_overrides WHERE stream_id = $1 AND rule_id = $2",
+        stream_id,
+        rule_id
+    )
+    .execute(db)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!("No override of rule {rule_id} on stream {stream_id}")));
+    }
+    Ok(())
+}
+
+// ─── False-positive feedback ──────────────────────────────────────────────────
+
+/// The stream's false positives since `since` that no approved or rejected
+/// clause was written from, newest first.
+pub async fn unreviewed_false_positives(
+    db: &PgPool,
+    stream_id: Uuid,
+    since: DateTime<Utc>,
+) -> Result<Vec<FalsePositiveRow>> {
+    let rows = sqlx::query_as!(
+        FalsePositiveRow,
+        r#"SELECT e.id, e.rule_id, r.description AS "rule_description?", e.title, e.description,
+                  e.disposition_reason
+           FROM analysis_events e
+           LEFT JOIN stream_rules r ON r.id = e.rule_id
+           WHERE e.stream_id = $1
+             AND e.disposition = 'false_positive'
+             AND e.disposition_at >= $2
+             AND NOT EXISTS (
+                 SELECT 1 FROM feedback_clauses c
+                 WHERE c.stream_id = e.stream_id AND c.status <> 'pending' AND e.id = ANY(c.source_event_ids)
+             )
+           ORDER BY e.disposition_at DESC"#,
+        stream_id,
+        since,
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+/// Replaces the stream's pending clauses with `clauses`.
+pub async fn replace_pending_feedback_clauses(
+    db: &PgPool,
+    stream_id: Uuid,
+    clauses: &[NewFeedbackClause],
+) -> Result<Vec<FeedbackClause>> {
+    sqlx::query!("DELETE FROM feedback_clauses WHERE stream_id = $1 AND status = 'pending'", stream_id)
+        .execute(db)
+        .await?;
+    let mut rows = Vec::with_capacity(clauses.len());
+    for c in clauses {
+        let row = sqlx::query_as!(
+            FeedbackClause,
+            r#"INSERT INTO feedback_clauses (stream_id, rule_id, clause, source_event_ids)
+               VALUES ($1, $2, $3, $4)
+               RETURNING id, stream_id, rule_id, clause, source_event_ids, status, created_at, reviewed_at"#,
+            stream_id,
+            c.rule_id,
+            c.clause,
+            &c.source_event_ids,
+        )
+        .fetch_one(db)
+        .await?;
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+pub async fn list_feedback_clauses(
+    db: &PgPool,
+    stream_id: Uuid,
+    status: Option<&str>,
+) -> Result<Vec<FeedbackClause>> {
+    let rows = sqlx::query_as!(
+        FeedbackClause,
+        r#"SELECT id, stream_id, rule_id, clause, source_event_ids, status, created_at, reviewed_at
+           FROM feedback_clauses
+           WHERE stream_id = $1 AND ($2::TEXT IS NULL OR status = $2)
+           ORDER BY created_at"#,
+        stream_id,
+        status,
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+/// Text of the stream's approved clauses, for the prompt.
+pub async fn approved_feedback_clauses(db: &PgPool, stream_id: Uuid) -> Result<Vec<String>> {
+    let rows = sqlx::query_scalar!(
+        "SELECT clause FROM feedback_clauses WHERE stream_id = $1 AND status = 'approved' ORDER BY created_at",
+        stream_id
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+pub async fn review_feedback_clause(
+    db: &PgPool,
+    id: Uuid,
+    status: &str,
+    clause: Option<&str>,
+) -> Result<FeedbackClause> {
+    sqlx::query_as!(
+        FeedbackClause,
+        r#"UPDATE feedback_clauses
+           SET status = $2, clause = COALESCE($3, clause), reviewed_at = NOW()
+           WHERE id = $1
+           RETURNING id, stream_id, rule_id, clause, source_event_ids, status, created_at, reviewed_at"#,
+        id,
+        status,
+        clause,
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("Feedback clause {id} not found")))
+}
+
+pub async fn delete_feedback_clause(db: &PgPool, id: Uuid) -> Result<()> {
+    let result = sqlx::query!("DELETE FROM feedback_clauses WHERE id = $1", id)
+        .execute(db)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!("Feedback clause {id} not found")));
+    }
+    Ok(())
+}
+
+// ─── Rule reference images ────────────────────────────────────────────────────
+
+/// Any rule by id, whatever its scope.
+pub async fn get_rule_by_id(db: &PgPool, rule_id: Uuid) -> Result<StreamRule> {
+    sqlx::query_as!(
+        StreamRule,
+        r#"SELECT id, scope, stream_id, blueprint_id, description, threat_level, position, created_at, updated_at,
+                  schedule, zone, min_count, min_dwell_sec, event_types
+           FROM stream_rules WHERE id = $1"#,
+        rule_id,
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("Rule {rule_id} not found")))
+}
+
+pub async fn list_reference_images(db: &PgPool, rule_id: Uuid) -> Result<Vec<RuleReferenceImage>> {
+    let rows = sqlx::query_as!(
+        RuleReferenceImage,
+        r#"SELECT id, rule_id, kind, caption, event_id, created_at
+           FROM rule_reference_images WHERE rule_id = $1
+           ORDER BY created_at ASC"#,
+        rule_id
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+pub async fn count_reference_images(db: &PgPool, rule_id: Uuid) -> Result<i64> {
+    let count = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!" FROM rule_reference_images WHERE rule_id = $1"#,
+        rule_id
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(count)
+}
+
+/// The images of every rule in `rule_ids`, with their bytes, oldest first.
+pub async fn reference_images_for_rules(db: &PgPool, rule_ids: &[Uuid]) -> Result<Vec<ReferenceImageRow>> {
+    let rows = sqlx::query_as!(
+        ReferenceImageRow,
+        r#"SELECT rule_id, kind, caption, image
+           FROM rule_reference_images WHERE rule_id = ANY($1)
+           ORDER BY created_at ASC"#,
+        rule_ids
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+pub async fn create_reference_image(
+    db: &PgPool,
+    rule_id: Uuid,
+    kind: &str,
+    caption: Option<&str>,
+    image: &[u8],
+    event_id: Option<Uuid>,
+) -> Result<RuleReferenceImage> {
+    let row = sqlx::query_as!(
+        RuleReferenceImage,
+        r#"INSERT INTO rule_reference_images (rule_id, kind, caption, image, event_id)
+           VALUES ($1, $2, $3, $4, $5)
+           RETURNING id, rule_id, kind, caption, event_id, created_at"#,
+        rule_id,
+        kind,
+        caption,
+        image,
+        event_id,
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(row)
+}
+
+pub async fn get_reference_image_data(db: &PgPool, id: Uuid, rule_id: Uuid) -> Result<Vec<u8>> {
+    sqlx::query_scalar!(
+        "SELECT image FROM rule_reference_images WHERE id = $1 AND rule_id = $2",
+        id,
+        rule_id
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("Reference image {id} not found")))
+}
+
+pub async fn delete_reference_image(db: &PgPool, id: Uuid, rule_id: Uuid) -> Result<()> {
+    let result = sqlx::query!(
+        "DELETE FROM rule_reference_images WHERE id = $1 AND rule_id = $2",
+        id,
+        rule_id,
+    )
+    .execute(db)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!("Reference image {id} not found")));
+    }
+    Ok(())
+}
+
+// ─── Rule analytics ───────────────────────────────────────────────────────────
+
+/// Hit counts for every rule between `from` and `to`. A rule's `analyzed`
+/// count covers the streams it currently applies to, from its creation on.
+pub async fn rule_hit_stats(
+    db: &PgPool,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    stream_id: Option<Uuid>,
+) -> Result<Vec<RuleHitRow>> {
+    let rows = sqlx::query_as!(
+        RuleHitRow,
+        r#"SELECT r.id AS "rule_id!",
+                  (SELECT COUNT(*)
+                   FROM analysis_events e JOIN streams s ON s.id = e.stream_id
+                   WHERE e.status <> 'analysis_failed'
+                     AND e.captured_at >= GREATEST($1, r.created_at) AND e.captured_at < $2
+                     AND ($3::UUID IS NULL OR e.stream_id = $3)
+                     AND (r.scope = 'global'
+                          OR (r.scope = 'blueprint' AND s.blueprint_id = r.blueprint_id)
+                          OR (r.scope = 'stream' AND e.stream_id = r.stream_id))
+                  ) AS "analyzed!",
+                  h.hits AS "hits!",
+                  h.false_positives AS "false_positives!",
+                  h.mean_confidence,
+                  (SELECT MAX(e.captured_at) FROM analysis_events e
+                   WHERE e.rule_id = r.id AND ($3::UUID IS NULL OR e.stream_id = $3)) AS last_triggered
+           FROM stream_rules r
+           CROSS JOIN LATERAL (
+               SELECT COUNT(*) AS hits,
+                      COUNT(*) FILTER (WHERE e.disposition = 'false_positive') AS false_positives,
+                      AVG((SELECT AVG((d->>'confidence')::FLOAT8)
+                           FROM jsonb_array_elements(
+                                    CASE WHEN jsonb_typeof(e.events) = 'array' THEN e.events ELSE '[]' END) d
+                           WHERE d->>'event_type' IS DISTINCT FROM 'empty_scene'
+                             AND jsonb_typeof(d->'confidence') = 'number')) AS mean_confidence
+               FROM analysis_events e
+               WHERE e.rule_id = r.id
+                 AND e.captured_at >= $1 AND e.captured_at < $2
+                 AND ($3::UUID IS NULL OR e.stream_id = $3)
+           ) h"#,
+        from,
+        to,
+        stream_id,
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+pub async fn rule_daily_hits(
+    db: &PgPool,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    stream_id: Option<Uuid>,
+) -> Result<Vec<DailyRuleHits>> {
+    let rows = sqlx::query_as!(
+        DailyRuleHits,
+        r#"SELECT rule_id AS "rule_id!",
+                  (captured_at AT TIME ZONE 'UTC')::DATE AS "day!",
+                  COUNT(*) AS "hits!"
+           FROM analysis_events
+           WHERE rule_id IS NOT NULL
+             AND captured_at >= $1 AND captured_at < $2
+             AND ($3::UUID IS NULL OR stream_id = $3)
+           GROUP BY 1, 2
+           ORDER BY 2"#,
+        from,
+        to,
+        stream_id,
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+/// Every rule at every scope.
+pub async fn list_all_rules(db: &PgPool) -> Result<Vec<StreamRule>> {
+    let rows = sqlx::query_as!(
+        StreamRule,
+        r#"SELECT id, scope, stream_id, blueprint_id, description, threat_level, position, created_at, updated_at,
+                  schedule, zone, min_count, min_dwell_sec, event_types
+           FROM stream_rules
+           ORDER BY CASE scope WHEN 'global' THEN 0 WHEN 'blueprint' THEN 1 ELSE 2 END, position, created_at"#
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+// ─── Event statistics ─────────────────────────────────────────────────────────
+
+/// Event counts per time bucket ("hour" | "day" | "week") and `group_by` value.
+/// Buckets are aligned to local midnight at the filter's UTC offset.
+pub async fn event_counts(
+    db: &PgPool,
+    filter: &StatsFilter,
+    bucket: &str,
+    group_by: &str,
+) -> Result<Vec<EventCountBucket>> {
+    let (key, label, join, count) = match group_by {
+        "risk_level" => ("e.risk_level", "NULL::TEXT", "", "COUNT(*)"),
+        "stream" => ("e.stream_id::TEXT", "s.name", " JOIN streams s ON s.id = e.stream_id", "COUNT(*)"),
+        "blueprint" => (
+            "s.blueprint_id::TEXT",
+            "b.name",
+            " JOIN streams s ON s.id = e.stream_id LEFT JOIN blueprints b ON b.id = s.blueprint_id",
+            "COUNT(*)",
+        ),
+        // One row per detection; an event counts once per type it contains.
+        "event_type" => (
+            "t.value->>'event_type'",
+            "NULL::TEXT",
+            " CROSS JOIN LATERAL jsonb_array_elements(\
+               CASE WHEN jsonb_typeof(e.events) = 'array' THEN e.events ELSE '[]'::JSONB END) t",
+            "COUNT(DISTINCT e.id)",
+        ),
+        "triggered_rule" => ("e.triggered_rule", "NULL::TEXT", "", "COUNT(*)"),
+        other => return Err(AppError::BadRequest(format!("unknown group_by '{other}'"))),
+    };
+
+    let mut qb = sqlx::QueryBuilder::new("SELECT (date_trunc(");
+    qb.push_bind(bucket)
+        .push(", (e.captured_at AT TIME ZONE 'UTC') + make_interval(mins => ")
+        .push_bind(filter.utc_offset_minutes)
+        .push(")) - make_interval(mins => ")
+        .push_bind(filter.utc_offset_minutes)
+        .push(format!(
+            ")) AT TIME ZONE 'UTC' AS bucket_start, {key} AS key, {label} AS label, {count} AS count \
+             FROM analysis_events e{join} WHERE 1=1"
+        ));
+    push_stats_filters(&mut qb, filter);
+    qb.push(" GROUP BY 1, 2, 3 ORDER BY 1, 2");
+
+    let rows = qb.build_query_as::<EventCountBucket>().fetch_all(db).await?;
+    Ok(rows)
+}
+
+/// Appends `StatsFilter` as `AND ...` clauses on `analysis_events e`.
+fn push_stats_filters<'a>(qb: &mut sqlx::QueryBuilder<'a, sqlx::Postgres>, filter: &'a StatsFilter) {
+    qb.push(" AND e.status <> 'analysis_failed'");
+    qb.push(" AND e.captured_at >= ").push_bind(filter.from);
+    qb.push(" AND e.captured_at < ").push_bind(filter.to);
+    if let Some(sid) = filter.stream_id {
+        qb.push(" AND e.stream_id = ").push_bind(sid);
+    }
+    if let Some(bid) = filter.blueprint_id {
+        qb.push(" AND e.stream_id IN (SELECT id FROM streams WHERE blueprint_id = ")
+            .push_bind(bid)
+            .push(")");
+    }
+    if let Some(levels) = &filter.risk_levels {
+        qb.push(" AND e.risk_level = ANY(").push_bind(levels).push(")");
+    }
+}
+
+/// Time to resolve per risk level, plus one row (risk_level None) for all.
+pub async fn resolution_stats(db: &PgPool, filter: &StatsFilter) -> Result<Vec<ResolutionStats>> {
+    let rows = sqlx::query_as!(
+        ResolutionStats,
+        r#"SELECT risk_level AS "risk_level?",
+                  COUNT(*) AS "events!",
+                  COUNT(resolved_at) AS "resolved!",
+                  COUNT(*) FILTER (WHERE status <> 'resolved') AS "unresolved!",
+                  AVG(EXTRACT(EPOCH FROM resolved_at - captured_at))::FLOAT8 AS mean_seconds,
+                  (PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY EXTRACT(EPOCH FROM resolved_at - captured_at)))::FLOAT8
+                      AS median_seconds
+           FROM analysis_events
+           WHERE status <> 'analysis_failed'
+             AND captured_at >= $1 AND captured_at < $2
+             AND ($3::UUID IS NULL OR stream_id = $3)
+             AND ($4::UUID IS NULL OR stream_id IN (SELECT id FROM streams WHERE blueprint_id = $4))
+             AND ($5::TEXT[] IS NULL OR risk_level = ANY($5))
+           GROUP BY GROUPING SETS ((risk_level), ())
+           ORDER BY GROUPING(risk_level) DESC,
+                    CASE risk_level WHEN 'high' THEN 0 WHEN 'medium' THEN 1 WHEN 'low' THEN 2 ELSE 3 END"#,
+        filter.from,
+        filter.to,
+        filter.stream_id,
+        filter.blueprint_id,
+        filter.risk_levels.as_deref(),
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+/// Events per hour of the week in local time; all 168 cells, Monday 00:00 first.
+pub async fn event_heatmap(db: &PgPool, filter: &StatsFilter) -> Result<Vec<HeatmapCell>> {
+    let rows = sqlx::query_as!(
+        HeatmapCell,
+        r#"WITH local AS (
+               SELECT (captured_at AT TIME ZONE 'UTC') + make_interval(mins => $6) AS t
+               FROM analysis_events
+               WHERE status <> 'analysis_failed'
+                 AND captured_at >= $1 AND captured_at < $2
+                 AND ($3::UUID IS NULL OR stream_id = $3)
+                 AND ($4::UUID IS NULL OR stream_id IN (SELECT id FROM streams WHERE blueprint_id = $4))
+                 AND ($5::TEXT[] IS NULL OR risk_level = ANY($5))
+           ),
+           counts AS (
+               SELECT (EXTRACT(ISODOW FROM t) - 1)::INT AS weekday, EXTRACT(HOUR FROM t)::INT AS hour, COUNT(*) AS n
+               FROM local GROUP BY 1, 2
+           )
+           SELECT d AS "weekday!", h AS "hour!", COALESCE(c.n, 0) AS "count!"
+           FROM generate_series(0, 6) d
+           CROSS JOIN generate_series(0, 23) h
+           LEFT JOIN counts c ON c.weekday = d AND c.hour = h
+           ORDER BY d, h"#,
+        filter.from,
+        filter.to,
+        filter.stream_id,
+        filter.blueprint_id,
+        filter.risk_levels.as_deref(),
+        filter.utc_offset_minutes,
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+// ─── Evidence chain ───────────────────────────────────────────────────────────
+
+/// Chain heads that moved since their stream's last checkpoint.
+pub async fn unsigned_chain_heads(db: &PgPool) -> Result<Vec<ChainHead>> {
+    let rows = sqlx::query_as!(
+        ChainHead,
+        r#"SELECT h.stream_id, h.seq, h.head_hash AS "head_hash!"
+           FROM evidence_chain_heads h
+           WHERE h.head_hash IS NOT NULL
+             AND h.seq > COALESCE((SELECT MAX(c.chain_seq) FROM evidence_checkpoints c WHERE c.stream_id = h.stream_id), 0)
+           ORDER BY h.stream_id"#
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+pub async fn insert_checkpoint(
+    db: &PgPool,
+    head: &ChainHead,
+    key_id: &str,
+    signature: &str,
+    created_at: DateTime<Utc>,
+) -> Result<EvidenceCheckpoint> {
+    let row = sqlx::query_as!(
+        EvidenceCheckpoint,
+        r#"INSERT INTO evidence_checkpoints (stream_id, chain_seq, content_hash, key_id, signature, created_at)
+           VALUES ($1, $2, $3, $4, $5, $6)
+           RETURNING id, stream_id, chain_seq, content_hash, key_id, signature, created_at"#,
+        head.stream_id,
+        head.seq,
+        head.head_hash,
+        key_id,
+        signature,
+        created_at,
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(row)
+}
+
+pub async fn list_checkpoints(db: &PgPool, stream_id: Option<Uuid>, limit: i64) -> Result<Vec<EvidenceCheckpoint>> {
+    let rows = sqlx::query_as!(
+        EvidenceCheckpoint,
+        r#"SELECT id, stream_id, chain_seq, content_hash, key_id, signature, created_at
+           FROM evidence_checkpoints
+           WHERE ($1::UUID IS NULL OR stream_id = $1)
+           ORDER BY created_at DESC, chain_seq DESC
+           LIMIT $2"#,
+        stream_id,
+        limit,
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+pub async fn chain_status(db: &PgPool, stream_id: Option<Uuid>) -> Result<Vec<ChainStatusRow>> {
+    let rows = sqlx::query_as!(
+        ChainStatusRow,
+        r#"SELECT s.id AS stream_id, s.name AS stream_name,
+                  COUNT(e.id) AS "events!", COALESCE(h.seq, 0) AS "head_seq!", MAX(e.chain_seq) AS max_seq
+           FROM streams s
+           LEFT JOIN evidence_chain_heads h ON h.stream_id = s.id
+           LEFT JOIN analysis_events e ON e.stream_id = s.id
+           WHERE ($1::UUID IS NULL OR s.id = $1)
+           GROUP BY s.id, s.name, h.seq
+           ORDER BY s.name"#,
+        stream_id,
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+/// Recomputes every frame and content hash and follows the links between
+/// consecutive events. Reads every stored frame.
+pub async fn chain_breaks(db: &PgPool, stream_id: Option<Uuid>) -> Result<Vec<ChainBreakRow>> {
+    let rows = sqlx::query_as!(
+        ChainBreakRow,
+        r#"WITH c AS (
+               SELECT e.id, e.stream_id, e.chain_seq, e.prev_hash,
+                      e.frame_sha256 IS DISTINCT FROM encode(sha256(e.frame), 'hex') AS frame_altered,
+                      e.content_hash IS DISTINCT FROM event_content_hash(e) AS content_altered,
+                      COALESCE(LAG(e.chain_seq) OVER w, 0) AS prev_seq,
+                      LAG(e.content_hash) OVER w AS prev_content
+               FROM analysis_events e
+               WHERE ($1::UUID IS NULL OR e.stream_id = $1) AND e.chain_seq IS NOT NULL
+               WINDOW w AS (PARTITION BY e.stream_id ORDER BY e.chain_seq)
+           )
+           SELECT c.stream_id AS "stream_id!", c.chain_seq, c.id AS "event_id?", b.kind AS "kind!", b.detail AS "detail!"
+           FROM c CROSS JOIN LATERAL (VALUES
+               (c.frame_altered, 'frame_altered', 'frame does not match its recorded SHA-256'),
+               (c.content_altered, 'content_altered', 'metadata does not match content_hash'),
+               (c.chain_seq <> c.prev_seq + 1, 'missing_events',
+                CASE WHEN c.chain_seq = c.prev_seq + 2 THEN format('event %s of the chain is missing', c.prev_seq + 1)
+                     ELSE format('events %s to %s of the chain are missing', c.prev_seq + 1, c.chain_seq - 1) END),
+               (c.chain_seq = c.prev_seq + 1 AND c.prev_hash IS DISTINCT FROM c.prev_content, 'link_broken',
+                'prev_hash does not match the previous event''s content_hash')
+           ) AS b(broken, kind, detail)
+           WHERE b.broken
+           UNION ALL
+           SELECT e.stream_id, NULL, e.id, 'unchained', 'event was stored without a place in the chain'
+           FROM analysis_events e
+           WHERE ($1::UUID IS NULL OR e.stream_id = $1) AND e.chain_seq IS NULL
+           ORDER BY 1, 2 NULLS LAST"#,
+        stream_id,
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+/// Checkpoints oldest first, each with the current hash of the event it signed.
+pub async fn checkpoints_with_events(db: &PgPool, stream_id: Option<Uuid>) -> Result<Vec<CheckpointCheckRow>> {
+    let rows = sqlx::query_as!(
+        CheckpointCheckRow,
+        r#"SELECT cp.stream_id, cp.chain_seq, cp.content_hash, cp.key_id, cp.signature, cp.created_at,
+                  e.id AS "event_id?", e.content_hash AS event_hash
+           FROM evidence_checkpoints cp
+           LEFT JOIN analysis_events e ON e.stream_id = cp.stream_id AND e.chain_seq = cp.chain_seq
+           WHERE ($1::UUID IS NULL OR cp.stream_id = $1)
+           ORDER BY cp.stream_id, cp.chain_seq"#,
+        stream_id,
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+// ─── Assistant conversations ──────────────────────────────────────────────────
+
+/// Most recently active first.
+pub async fn list_conversations(db: &PgPool) -> Result<Vec<AssistantConversation>> {
+    let rows = sqlx::query_as!(
+        AssistantConversation,
+        r#"SELECT id, title, created_at, updated_at
+           FROM assistant_conversations ORDER BY updated_at DESC"#
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+pub async fn get_conversation(db: &PgPool, id: Uuid) -> Result<AssistantConversation> {
+    sqlx::query_as!(
+        AssistantConversation,
+        r#"SELECT id, title, created_at, updated_at FROM assistant_conversations WHERE id = $1"#,
+        id
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("Conversation {id} not found")))
+}
+
+pub async fn create_conversation(db: &PgPool, title: &str) -> Result<AssistantConversation> {
+    let row = sqlx::query_as!(
+        AssistantConversation,
+        r#"INSERT INTO assistant_conversations (title) VALUES ($1)
+           RETURNING id, title, created_at, updated_at"#,
+        title
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(row)
+}
+
+pub async fn delete_conversation(db: &PgPool, id: Uuid) -> Result<()> {
+    let result = sqlx::query!("DELETE FROM assistant_conversations WHERE id = $1", id)
+        .execute(db)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!("Conversation {id} not found")));
+    }
+    Ok(())
+}
+
+/// The last `limit` messages of a conversation, oldest first.
+pub async fn conversation_messages(db: &PgPool, conversation_id: Uuid, limit: i64) -> Result<Vec<AssistantMessage>> {
+    let rows = sqlx::query_as!(
+        AssistantMessage,
+        r#"SELECT id, role, content, tool_calls, tool_name, cancelled, created_at
+           FROM (
+               SELECT * FROM assistant_messages WHERE conversation_id = $1
+               ORDER BY seq DESC LIMIT $2
+           ) m
+           ORDER BY seq"#,
+        conversation_id,
+        limit,
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+/// Appends a message and marks the conversation active. An untitled
+/// conversation takes its title from the first user message.
+pub async fn insert_conversation_message(
+    db: &PgPool,
+    conversation_id: Uuid,
+    role: &str,
+    content: &str,
+    tool_calls: Option<&Value>,
+    tool_name: Option<&str>,
+    cancelled: bool,
+) -> Result<AssistantMessage> {
+    let row = sqlx::query_as!(
+        AssistantMessage,
+        r#"INSERT INTO assistant_messages (conversation_id, role, content, tool_calls, tool_name, cancelled)
+           VALUES ($1, $2, $3, $4, $5, $6)
+           RETURNING id, role, content, tool_calls, tool_name, cancelled, created_at"#,
+        conversation_id,
+        role,
+        content,
+        tool_calls,
+        tool_name,
+        cancelled,
+    )
+    .fetch_one(db)
+    .await?;
+    sqlx::query!(
+        r#"UPDATE assistant_conversations
+           SET updated_at = NOW(),
+               title = CASE WHEN title = '' AND $2 = 'user' THEN left($3, 80) ELSE title END
+           WHERE id = $1"#,
+        conversation_id,
+        role,
+        content,
+    )
+    .execute(db)
+    .await?;
+    Ok(row)
+}
+
+pub async fn insert_assistant_action(
+    db: &PgPool,
+    conversation_id: Option<Uuid>,
+    tool: &str,
+    change: &Value,
+    summary: &str,
+    affected: i64,
+    expires_at: DateTime<Utc>,
+) -> Result<AssistantAction> {
+    let row = sqlx::query_as!(
+        AssistantAction,
+        r#"INSERT INTO assistant_actions (conversation_id, tool, change, summary, affected, expires_at)
+           VALUES ($1, $2, $3, $4, $5, $6)
+           RETURNING id, conversation_id, tool, change, summary, affected, status, result,
+                     created_at, expires_at, decided_at"#,
+        conversation_id,
+        tool,
+        change,
+        summary,
+        affected,
+        expires_at,
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(row)
+}
+
+/// Newest first.
+pub async fn list_assistant_actions(db: &PgPool, q: &AssistantActionQuery) -> Result<Vec<AssistantAction>> {
+    let rows = sqlx::query_as!(
+        AssistantAction,
+        r#"SELECT id, conversation_id, tool, change, summary, affected, status, result,
+                  created_at, expires_at, decided_at
+           FROM assistant_actions
+           WHERE ($1::UUID IS NULL OR conversation_id = $1)
+             AND ($2::TEXT IS NULL OR status = $2)
+           ORDER BY created_at DESC
+           LIMIT 200"#,
+        q.conversation_id,
+        q.status,
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+pub async fn get_assistant_action(db: &PgPool, id: Uuid) -> Result<AssistantAction> {
+    sqlx::query_as!(
+        AssistantAction,
+        r#"SELECT id, conversation_id, tool, change, summary, affected, status, result,
+                  created_at, expires_at, decided_at
+           FROM assistant_actions WHERE id = $1"#,
+        id,
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("Assistant action {id} not found")))
+}
+
+/// Moves a pending, unexpired action to `status`. None if it was already
+/// decided or has expired, so an action is only ever executed once.
+pub async fn decide_assistant_action(db: &PgPool, id: Uuid, status: &str) -> Result<Option<AssistantAction>> {
+    let row = sqlx::query_as!(
+        AssistantAction,
+        r#"UPDATE assistant_actions
+           SET status = $2, decided_at = NOW()
+           WHERE id = $1 AND status = 'pending' AND expires_at > NOW()
+           RETURNING id, conversation_id, tool, change, summary, affected, status, result,
+                     created_at, expires_at, decided_at"#,
+        id,
+        status,
+    )
+    .fetch_optional(db)
+    .await?;
+    Ok(row)
+}
+
+pub async fn set_assistant_action_result(
+    db: &PgPool,
+    id: Uuid,
+    status: &str,
+    result: &str,
+) -> Result<AssistantAction> {
+    let row = sqlx::query_as!(
+        AssistantAction,
+        r#"UPDATE assistant_actions SET status = $2, result = $3
+           WHERE id = $1
+           RETURNING id, conversation_id, tool, change, summary, affected, status, result,
+                     created_at, expires_at, decided_at"#,
+        id,
+        status,
+        result,
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(row)
+}
+
+// ─── Reports ──────────────────────────────────────────────────────────────────
+
+pub async fn list_report_schedules(db: &PgPool) -> Result<Vec<ReportSchedule>> {
+    let rows = sqlx::query_as!(
+        ReportSchedule,
+        r#"SELECT id, name, cron, utc_offset_minutes, period_hours, stream_id, blueprint_id, llm_summary,
+                  notify_sms, notify_phone, enabled, next_run_at, last_run_at, created_at, updated_at
+           FROM report_schedules ORDER BY name ASC"#
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+pub async fn get_report_schedule(db: &PgPool, id: Uuid) -> Result<ReportSchedule> {
+    sqlx::query_as!(
+        ReportSchedule,
+        r#"SELECT id, name, cron, utc_offset_minutes, period_hours, stream_id, blueprint_id, llm_summary,
+                  notify_sms, notify_phone, enabled, next_run_at, last_run_at, created_at, updated_at
+           FROM report_schedules WHERE id = $1"#,
+        id
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("Report schedule {id} not found")))
+}
+
+pub async fn create_report_schedule(
+    db: &PgPool,
+    req: &CreateReportScheduleRequest,
+    next_run_at: DateTime<Utc>,
+) -> Result<ReportSchedule> {
+    let row = sqlx::query_as!(
+        ReportSchedule,
+        r#"INSERT INTO report_schedules (name, cron, utc_offset_minutes, period_hours, stream_id, blueprint_id,
+                                         llm_summary, notify_sms, notify_phone, enabled, next_run_at)
+           VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+           RETURNING id, name, cron, utc_offset_minutes, period_hours, stream_id, blueprint_id, llm_summary,
+                     notify_sms, notify_phone, enabled, next_run_at, last_run_at, created_at, updated_at"#,
+        req.name.trim(),
+        req.cron.trim(),
+        req.utc_offset_minutes.unwrap_or(0),
+        req.period_hours.unwrap_or(24),
+        req.stream_id,
+        req.blueprint_id,
+        req.llm_summary.unwrap_or(false),
+        req.notify_sms.unwrap_or(false),
+        req.notify_phone.as_deref().map(str::trim).filter(|p| !p.is_empty()),
+        req.enabled.unwrap_or(true),
+        next_run_at,
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(row)
+}
+
+/// `next_run_at` is recomputed by the caller from the updated cron pattern and offset.
+pub async fn update_report_schedule(
+    db: &PgPool,
+    current: &ReportSchedule,
+    req: &UpdateReportScheduleRequest,
+    next_run_at: DateTime<Utc>,
+) -> Result<ReportSchedule> {
+    let notify_phone = match req.notify_phone.as_deref().map(str::trim) {
+        None => current.notify_phone.as_deref(),
+        Some("") => None,
+        Some(p) => Some(p),
+    };
+
+    let row = sqlx::query_as!(
+        ReportSchedule,
+        r#"UPDATE report_schedules
+           SET name               = $2,
+               cron               = $3,
+               utc_offset_minutes = $4,
+               period_hours       = $5,
+               stream_id          = $6,
+               blueprint_id       = $7,
+               llm_summary        = $8,
+               notify_sms         = $9,
+               notify_phone       = $10,
+               enabled            = $11,
+               next_run_at        = $12,
+               updated_at         = NOW()
+           WHERE id = $1
+           RETURNING id, name, cron, utc_offset_minutes, period_hours, stream_id, blueprint_id, llm_summary,
+                     notify_sms, notify_phone, enabled, next_run_at, last_run_at, created_at, updated_at"#,
+        current.id,
+        req.name.as_deref().map(str::trim).unwrap_or(&current.name),
+        req.cron.as_deref().map(str::trim).unwrap_or(&current.cron),
+        req.utc_offset_minutes.unwrap_or(current.utc_offset_minutes),
+        req.period_hours.unwrap_or(current.period_hours),
+        req.stream_id.unwrap_or(current.stream_id),
+        req.blueprint_id.unwrap_or(current.blueprint_id),
+        req.llm_summary.unwrap_or(current.llm_summary),
+        req.notify_sms.unwrap_or(current.notify_sms),
+        notify_phone,
+        req.enabled.unwrap_or(current.enabled),
+        next_run_at,
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(row)
+}
+
+/// Reports already generated by the schedule are kept.
+pub async fn delete_report_schedule(db: &PgPool, id: Uuid) -> Result<()> {
+    let result = sqlx::query!("DELETE FROM report_schedules WHERE id = $1", id)
+        .execute(db)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!("Report schedule {id} not found")));
+    }
+    Ok(())
+}
+
+/// Enabled schedules whose next run is due.
+pub async fn due_report_schedules(db: &PgPool, now: DateTime<Utc>) -> Result<Vec<ReportSchedule>> {
+    let rows = sqlx::query_as!(
+        ReportSchedule,
+        r#"SELECT id, name, cron, utc_offset_minutes, period_hours, stream_id, blueprint_id, llm_summary,
+                  notify_sms, notify_phone, enabled, next_run_at, last_run_at, created_at, updated_at
+           FROM report_schedules
+           WHERE enabled AND next_run_at <= $1
+           ORDER BY next_run_at"#,
+        now,
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+/// Moves a due schedule on to its next run. False if another instance (or an
+/// update) moved it first, so each run is generated once.
+pub async fn claim_report_schedule(
+    db: &PgPool,
+    schedule: &ReportSchedule,
+    next_run_at: DateTime<Utc>,
+) -> Result<bool> {
+    let result = sqlx::query!(
+        r#"UPDATE report_schedules SET next_run_at = $3, last_run_at = NOW()
+           WHERE id = $1 AND next_run_at = $2"#,
+        schedule.id,
+        schedule.next_run_at,
+        next_run_at,
+    )
+    .execute(db)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// The most severe events of the period (high, then medium, newest first), with frames.
+pub async fn report_incidents(db: &PgPool, filter: &StatsFilter, limit: i64) -> Result<Vec<ReportIncidentRow>> {
+    let rows = sqlx::query_as!(
+        ReportIncidentRow,
+        r#"SELECT e.id AS event_id, e.stream_id, s.name AS stream_name, e.captured_at, e.risk_level,
+                  e.title, e.description, e.status, e.frame
+           FROM analysis_events e
+           JOIN streams s ON s.id = e.stream_id
+           WHERE e.captured_at >= $1 AND e.captured_at < $2
+             AND ($3::UUID IS NULL OR e.stream_id = $3)
+             AND ($4::UUID IS NULL OR s.blueprint_id = $4)
+             AND e.risk_level IN ('high', 'medium')
+           ORDER BY CASE e.risk_level WHEN 'high' THEN 0 ELSE 1 END, e.captured_at DESC
+           LIMIT $5"#,
+        filter.from,
+        filter.to,
+        filter.stream_id,
+        filter.blueprint_id,
+        limit,
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+pub async fn insert_report(db: &PgPool, report: &NewReport<'_>) -> Result<Report> {
+    let row = sqlx::query_as!(
+        Report,
+        r#"INSERT INTO reports (schedule_id, title, stream_id, blueprint_id, period_start, period_end,
+                                event_count, summary, llm_summary, html, pdf)
+           VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+           RETURNING id, schedule_id, title, stream_id, blueprint_id, period_start, period_end,
+                     event_count, summary, llm_summary, notified, created_at"#,
+        report.schedule_id,
+        report.title,
+        report.stream_id,
+        report.blueprint_id,
+        report.period_start,
+        report.period_end,
+        report.event_count,
+        report.summary,
+        report.llm_summary,
+        report.html,
+        report.pdf,
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(row)
+}
+
+pub async fn set_report_notified(db: &PgPool, id: Uuid) -> Result<()> {
+    sqlx::query!("UPDATE reports SET notified = TRUE WHERE id = $1", id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+/// Newest first.
+pub async fn list_reports(db: &PgPool, query: &ReportQuery) -> Result<Vec<Report>> {
+    let rows = sqlx::query_as!(
+        Report,
+        r#"SELECT id, schedule_id, title, stream_id, blueprint_id, period_start, period_end,
+                  event_count, summary, llm_summary, notified, created_at
+           FROM reports
+           WHERE ($1::UUID IS NULL OR schedule_id = $1)
+           ORDER BY created_at DESC
+           LIMIT $2"#,
+        query.schedule_id,
+        query.limit,
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+pub async fn get_report(db: &PgPool, id: Uuid) -> Result<Report> {
+    sqlx::query_as!(
+        Report,
+        r#"SELECT id, schedule_id, title, stream_id, blueprint_id, period_start, period_end,
+                  event_count, summary, llm_summary, notified, created_at
+           FROM reports WHERE id = $1"#,
+        id
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("Report {id} not found")))
+}
+
+pub async fn get_report_html(db: &PgPool, id: Uuid) -> Result<String> {
+    sqlx::query_scalar!("SELECT html FROM reports WHERE id = $1", id)
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Report {id} not found")))
+}
+
+pub async fn get_report_pdf(db: &PgPool, id: Uuid) -> Result<Vec<u8>> {
+    sqlx::query_scalar!("SELECT pdf FROM reports WHERE id = $1", id)
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Report {id} not found")))
+}
+
+pub async fn delete_report(db: &PgPool, id: Uuid) -> Result<()> {
+    let result = sqlx::query!("DELETE FROM reports WHERE id = $1", id)
+        .execute(db)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!("Report {id} not found")));
+    }
+    Ok(())
+}
+
+// ─── Rule Templates ───────────────────────────────────────────────────────────
+
+pub async fn list_rule_templates(db: &PgPool) -> Result<Vec<RuleTemplate>> {
+    let rows = sqlx::query_as!(
+        RuleTemplate,
+        "SELECT id, name, description, parameters, rules, created_at, updated_at
+         FROM rule_templates ORDER BY name"
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+pub async fn get_rule_template(db: &PgPool, id: Uuid) -> Result<RuleTemplate> {
+    sqlx::query_as!(
+        RuleTemplate,
+        "SELECT id, name, description, parameters, rules, created_at, updated_at
+         FROM rule_templates WHERE id = $1",
+        id
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("Rule template {id} not found")))
+}
+
+pub async fn create_rule_template(db: &PgPool, doc: &RuleTemplateDoc) -> Result<RuleTemplate> {
+    sqlx::query_as!(
+        RuleTemplate,
+        r#"INSERT INTO rule_templates (name, description, parameters, rules)
+           VALUES ($1, $2, $3, $4)
+           ON CONFLICT (name) DO NOTHING
+           RETURNING id, name, description, parameters, rules, created_at, updated_at"#,
+        doc.name,
+        doc.description,
+        serde_json::to_value(&doc.parameters).unwrap_or_default(),
+        serde_json::to_value(&doc.rules).unwrap_or_default(),
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::BadRequest(format!("a rule template named '{}' already exists", doc.name)))
+}
+
+/// Creates the template, or replaces the one with the same name (import).
+pub async fn upsert_rule_template(db: &PgPool, doc: &RuleTemplateDoc) -> Result<RuleTemplate> {
+    let row = sqlx::query_as!(
+        RuleTemplate,
+        r#"INSERT INTO rule_templates (name, description, parameters, rules)
+           VALUES ($1, $2, $3, $4)
+           ON CONFLICT (name) DO UPDATE SET
+               description = EXCLUDED.description,
+               parameters  = EXCLUDED.parameters,
+               rules       = EXCLUDED.rules,
+               updated_at  = NOW()
+           RETURNING id, name, description, parameters, rules, created_at, updated_at"#,
+        doc.name,
+        doc.description,
+        serde_json::to_value(&doc.parameters).unwrap_or_default(),
+        serde_json::to_value(&doc.rules).unwrap_or_default(),
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(row)
+}
+
+pub async fn update_rule_template(db: &PgPool, id: Uuid, doc: &RuleTemplateDoc) -> Result<RuleTemplate> {
+    let row = sqlx::query_as!(
+        RuleTemplate,
+        r#"UPDATE rule_templates
+           SET name = $2, description = $3, parameters = $4, rules = $5, updated_at = NOW()
+           WHERE id = $1
+           RETURNING id, name, description, parameters, rules, created_at, updated_at"#,
+        id,
+        doc.name,
+        doc.description,
+        serde_json::to_value(&doc.parameters).unwrap_or_default(),
+        serde_json::to_value(&doc.rules).unwrap_or_default(),
+    )
+    .fetch_optional(db)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(d) if d.is_unique_violation() => {
+            AppError::BadRequest(format!("a rule template named '{}' already exists", doc.name))
+        }
+        e => e.into(),
+    })?;
+    row.ok_or_else(|| AppError::NotFound(format!("Rule template {id} not found")))
+}
+
+pub async fn delete_rule_template(db: &PgPool, id: Uuid) -> Result<()> {
+    let result = sqlx::query!("DELETE FROM rule_templates WHERE id = $1", id)
+        .execute(db)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!("Rule template {id} not found")));
     }
     Ok(())
 }
@@ -1,4 +1,4 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use utoipa::{IntoParams, ToSchema};
@@ -23,6 +23,18 @@ where
     }
 }
 
+/// Deserializes an optional-nullable field.
+/// - Field absent → `None` (leave unchanged)
+/// - Field `null` → `Some(None)` (clear)
+/// - Field value  → `Some(Some(value))`
+fn deser_nullable<'de, D, T>(d: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(d).map(Some)
+}
+
 /// Mirrors the `streams` table. Stream = camera; belongs to at most one blueprint (blueprint_id).
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
 pub struct Stream {
@@ -39,6 +51,10 @@ pub struct Stream {
     pub blueprint_id: Option<Uuid>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// VLM profile used to analyze this stream; None = the global VLM.
+    pub vlm_profile_id: Option<Uuid>,
+    /// `PreprocessConfig` applied to frames before analysis; None = send as captured.
+    pub preprocessing: Option<Value>,
 }
 
 /// Payload for creating a new stream via the REST API.
@@ -53,6 +69,10 @@ pub struct CreateStreamRequest {
     pub enabled: bool,
     /// Optional blueprint to bind this stream to.
     pub blueprint_id: Option<Uuid>,
+    /// Optional VLM profile; defaults to the global VLM.
+    pub vlm_profile_id: Option<Uuid>,
+    /// Optional preprocessing applied before frames go to the VLM.
+    pub preprocessing: Option<PreprocessConfig>,
 }
 
 fn default_interval() -> i32 { 5 }
@@ -78,6 +98,61 @@ pub struct UpdateStreamRequest {
     /// Set to null or "" in JSON to unbind from blueprint; omit to leave unchanged.
     #[serde(default, deserialize_with = "deser_nullable_uuid")]
     pub blueprint_id: Option<Option<Uuid>>,
+    /// Set to null or "" to fall back to the global VLM; omit to leave unchanged.
+    #[serde(default, deserialize_with = "deser_nullable_uuid")]
+    pub vlm_profile_id: Option<Option<Uuid>>,
+    /// Set to null to send frames as captured; omit to leave unchanged.
+    #[serde(default, deserialize_with = "deser_nullable")]
+    pub preprocessing: Option<Option<PreprocessConfig>>,
+}
+
+// ─── Frame preprocessing ──────────────────────────────────────────────────────
+
+/// Per-stream image adjustments applied, in field order, before a frame is sent
+/// to the VLM. The stored event keeps the frame as captured.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct PreprocessConfig {
+    /// Clockwise rotation in degrees: 0, 90, 180 or 270.
+    #[serde(default)]
+    pub rotate: u16,
+    #[serde(default)]
+    pub flip_horizontal: bool,
+    #[serde(default)]
+    pub flip_vertical: bool,
+    pub fisheye: Option<FisheyeCorrection>,
+    /// Region of interest, in fractions (0.0 – 1.0) of the rotated frame.
+    pub roi: Option<Roi>,
+    /// Longest side in pixels after resizing; smaller frames are left as is.
+    pub max_dimension: Option<u32>,
+    /// JPEG quality (1 – 100) of the re-encoded frame; default 85.
+    pub jpeg_quality: Option<u8>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum FisheyeCorrection {
+    /// Keep the central `ratio` (0.1 – 1.0) of each side, where distortion is lowest.
+    CenterCrop { ratio: f64 },
+    /// Radial barrel-distortion correction; `strength` 0.0 (none) – 0.9.
+    Dewarp { strength: f64 },
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema)]
+pub struct Roi {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// What preprocessing produced for one frame; stored on the event.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PreprocessStats {
+    pub input_bytes: usize,
+    pub output_bytes: usize,
+    pub width: u32,
+    pub height: u32,
+    pub elapsed_ms: u32,
 }
 
 /// Mirrors the `analysis_events` table.
@@ -97,35 +172,483 @@ pub struct AnalysisEvent {
     pub frame: Option<Vec<u8>>,
     pub status: String,
     pub created_at: DateTime<Utc>,
+    /// Full system prompt (base prompt + rules addendum) sent to the VLM.
+    pub system_prompt: Option<String>,
+    /// The rules in force when the frame was analyzed: `[{description, threat_level}]`.
+    pub rules_snapshot: Option<Value>,
+    pub model: Option<String>,
+    /// "ollama" | "openai_compat" | "llama_cpp" | "vllm"
+    pub vlm_backend: Option<String>,
+    pub latency_ms: Option<i32>,
+    pub prompt_tokens: Option<i32>,
+    pub completion_tokens: Option<i32>,
+    /// `PreprocessStats` of the image sent to the VLM, if the stream preprocesses frames.
+    pub preprocess_stats: Option<Value>,
+    /// Shared by the events analyzed together in one tiled VLM call.
+    pub batch_id: Option<Uuid>,
+    /// This frame's 1-based tile number in the batch's grid image.
+    pub batch_tile: Option<i32>,
+    /// `RuleCheck` of the triggered rule, if it has structured conditions.
+    pub rule_check: Option<Value>,
+    /// The rule `triggered_rule` was matched to.
+    pub rule_id: Option<Uuid>,
+    /// Operator verdict: "false_positive" | "true_positive".
+    pub disposition: Option<String>,
+    pub disposition_reason: Option<String>,
+    pub disposition_at: Option<DateTime<Utc>>,
+    /// Position in the stream's evidence chain, from 1.
+    pub chain_seq: Option<i64>,
+    /// `content_hash` of the previous event in the chain; None for the first.
+    pub prev_hash: Option<String>,
+    /// Hex SHA-256 over the frame's hash, the immutable metadata and `prev_hash`.
+    pub content_hash: Option<String>,
+    pub frame_sha256: Option<String>,
 }
 
-/// Payload for updating an event (e.g. resolve threat).
+/// Everything the analysis worker persists for one analyzed frame.
+pub struct NewAnalysisEvent<'a> {
+    pub id: Uuid,
+    pub stream_id: Uuid,
+    pub captured_at: DateTime<Utc>,
+    pub description: &'a str,
+    pub events: Value,
+    pub risk_level: &'a str,
+    pub triggered_rule: Option<&'a str>,
+    pub title: Option<&'a str>,
+    pub frame: Option<&'a [u8]>,
+    pub status: &'a str,
+    pub raw_response: Option<&'a str>,
+    pub system_prompt: Option<&'a str>,
+    pub rules_snapshot: Option<Value>,
+    pub model: Option<&'a str>,
+    pub vlm_backend: Option<&'a str>,
+    pub latency_ms: Option<i32>,
+    pub prompt_tokens: Option<i32>,
+    pub completion_tokens: Option<i32>,
+    pub preprocess_stats: Option<Value>,
+    pub batch_id: Option<Uuid>,
+    pub batch_tile: Option<i32>,
+    pub rule_check: Option<Value>,
+    pub rule_id: Option<Uuid>,
+}
+
+/// Payload for updating an event (e.g. resolve threat, or mark it a false positive).
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct UpdateEventRequest {
-    pub status: String,
+    /// Defaults to "resolved" when a disposition is set.
+    pub status: Option<String>,
+    /// "false_positive" | "true_positive"; null clears it.
+    #[serde(default, deserialize_with = "deser_nullable")]
+    pub disposition: Option<Option<String>>,
+    /// Why, e.g. "staff restocking the counter". Used to word feedback clauses.
+    pub disposition_reason: Option<String>,
 }
 
 /// Payload for assistant chat.
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct AssistantChatRequest {
     pub message: String,
+    /// Continue this conversation and store the turn in it; omit for a
+    /// one-off question that isn't stored.
+    pub conversation_id: Option<Uuid>,
 }
 
-/// Query filters for listing analysis events.
-#[derive(Debug, Deserialize, IntoParams)]
+/// Query filters for listing analysis events. List filters take one value or
+/// several separated by commas, and match any of them.
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
 pub struct EventQuery {
     pub stream_id: Option<Uuid>,
+    /// Events of the streams placed on this blueprint.
+    pub blueprint_id: Option<Uuid>,
+    /// e.g. "high" or "medium,high"
     pub risk_level: Option<String>,
+    /// e.g. "unresolved" or "unresolved,analysis_failed"
+    pub status: Option<String>,
+    /// Detected event types, e.g. "person_detected,vehicle_detected".
+    pub event_type: Option<String>,
+    /// Exact rule description (case-insensitive).
+    pub triggered_rule: Option<String>,
+    /// Full-text search over title and description (web search syntax:
+    /// quoted phrases, `or`, `-word`).
+    pub q: Option<String>,
     pub from: Option<DateTime<Utc>>,
     pub to: Option<DateTime<Utc>>,
     #[serde(default = "default_limit")]
     pub limit: i64,
     #[serde(default)]
     pub offset: i64,
+    /// `X-Next-Cursor` of the previous page; use instead of `offset`.
+    pub cursor: Option<String>,
+    /// Leave `frame` out of each event (null), for light list views.
+    #[serde(default)]
+    pub exclude_frame: bool,
 }
 
 fn default_limit() -> i64 { 50 }
 
+// ─── Re-analysis ──────────────────────────────────────────────────────────────
+
+/// Payload for re-running stored frames through the VLM.
+/// Filters mirror `EventQuery`; `model` overrides the configured model name.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ReanalyzeRequest {
+    pub stream_id: Option<Uuid>,
+    pub blueprint_id: Option<Uuid>,
+    pub risk_level: Option<String>,
+    pub status: Option<String>,
+    pub event_type: Option<String>,
+    pub triggered_rule: Option<String>,
+    pub q: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    /// Max events to re-run (default 50, max 1000).
+    #[serde(default = "default_limit")]
+    pub limit: i64,
+    /// Model to use instead of each stream's own, on the stream's backend.
+    pub model: Option<String>,
+}
+
+/// Mirrors the `reanalysis_jobs` table.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct ReanalysisJob {
+    pub id: Uuid,
+    /// "running" | "completed" | "failed"
+    pub status: String,
+    pub filter: Value,
+    pub model: Option<String>,
+    pub total: i32,
+    pub processed: i32,
+    pub failed: i32,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// Mirrors the `event_reanalyses` table: a new result stored next to the original event.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct EventReanalysis {
+    pub id: Uuid,
+    pub job_id: Uuid,
+    pub event_id: Uuid,
+    pub original_risk_level: String,
+    pub risk_level: String,
+    pub triggered_rule: Option<String>,
+    pub title: Option<String>,
+    pub description: String,
+    pub events: Value,
+    pub raw_response: Option<String>,
+    pub system_prompt: Option<String>,
+    pub rules_snapshot: Option<Value>,
+    pub model: Option<String>,
+    pub vlm_backend: Option<String>,
+    pub latency_ms: Option<i32>,
+    pub prompt_tokens: Option<i32>,
+    pub completion_tokens: Option<i32>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Everything the re-analysis job persists for one re-run event.
+pub struct NewEventReanalysis<'a> {
+    pub job_id: Uuid,
+    pub event_id: Uuid,
+    pub original_risk_level: &'a str,
+    pub risk_level: &'a str,
+    pub triggered_rule: Option<&'a str>,
+    pub title: Option<&'a str>,
+    pub description: &'a str,
+    pub events: Value,
+    pub raw_response: Option<&'a str>,
+    pub system_prompt: Option<&'a str>,
+    pub rules_snapshot: Option<Value>,
+    pub model: Option<&'a str>,
+    pub vlm_backend: Option<&'a str>,
+    pub latency_ms: Option<i32>,
+    pub prompt_tokens: Option<i32>,
+    pub completion_tokens: Option<i32>,
+}
+
+/// How many re-analyzed events moved from one risk level to another.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct RiskTransition {
+    pub from: String,
+    pub to: String,
+    pub count: i64,
+}
+
+/// Risk-level diff of new results (re-analysis or shadow) against the originals.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RiskDiffSummary {
+    pub compared: i64,
+    pub unchanged: i64,
+    /// New risk level is higher than the original.
+    pub escalated: i64,
+    /// New risk level is lower than the original.
+    pub downgraded: i64,
+    pub transitions: Vec<RiskTransition>,
+}
+
+/// Job progress plus the risk-level diff so far.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ReanalysisJobStatus {
+    pub job: ReanalysisJob,
+    pub summary: RiskDiffSummary,
+}
+
+// ─── Shadow mode ──────────────────────────────────────────────────────────────
+
+/// Mirrors the `shadow_results` table: a candidate model's answer for an event.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct ShadowResult {
+    pub id: Uuid,
+    pub event_id: Uuid,
+    pub risk_level: String,
+    pub triggered_rule: Option<String>,
+    pub title: Option<String>,
+    pub description: String,
+    pub events: Value,
+    pub raw_response: Option<String>,
+    pub model: String,
+    pub vlm_backend: String,
+    pub latency_ms: Option<i32>,
+    pub prompt_tokens: Option<i32>,
+    pub completion_tokens: Option<i32>,
+    pub created_at: DateTime<Utc>,
+}
+
+pub struct NewShadowResult<'a> {
+    pub event_id: Uuid,
+    pub risk_level: &'a str,
+    pub triggered_rule: Option<&'a str>,
+    pub title: Option<&'a str>,
+    pub description: &'a str,
+    pub events: Value,
+    pub raw_response: Option<&'a str>,
+    pub model: &'a str,
+    pub vlm_backend: &'a str,
+    pub latency_ms: Option<i32>,
+    pub prompt_tokens: Option<i32>,
+    pub completion_tokens: Option<i32>,
+}
+
+/// Filters for the shadow agreement report.
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ShadowReportQuery {
+    pub stream_id: Option<Uuid>,
+    /// Only include shadow results for this model.
+    pub model: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// Aggregates computed in SQL for the shadow report.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ShadowAgreementRow {
+    pub compared: i64,
+    pub rule_matches: i64,
+    pub mean_primary_latency_ms: Option<f64>,
+    pub mean_shadow_latency_ms: Option<f64>,
+}
+
+/// How often the shadow model agreed with the primary one.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ShadowReport {
+    /// Shadow models included in the comparison.
+    pub shadow_models: Vec<String>,
+    pub compared: i64,
+    /// Share of frames where both models chose the same risk level.
+    pub risk_agreement: Option<f64>,
+    /// Share of frames where both models reported the same triggered_rule.
+    pub triggered_rule_agreement: Option<f64>,
+    pub mean_primary_latency_ms: Option<f64>,
+    pub mean_shadow_latency_ms: Option<f64>,
+    /// Shadow risk level relative to the primary one.
+    pub diff: RiskDiffSummary,
+}
+
+// ─── Evaluation datasets ──────────────────────────────────────────────────────
+
+/// Mirrors the `eval_datasets` table.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct EvalDataset {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    /// Fixed rule set `[{description, threat_level}]`, or null to use each
+    /// sample's stream rules.
+    pub rules: Option<Value>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateEvalDatasetRequest {
+    pub name: String,
+    pub description: Option<String>,
+    /// Optional fixed rule set `[{description, threat_level}]`.
+    pub rules: Option<Value>,
+}
+
+/// A labeled frame (image bytes omitted).
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct EvalSample {
+    pub id: Uuid,
+    pub dataset_id: Uuid,
+    pub stream_id: Option<Uuid>,
+    pub event_id: Option<Uuid>,
+    pub expected_risk_level: String,
+    pub expected_triggered_rule: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Add a labeled frame: either upload `image_base64` or copy the frame of `event_id`.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateEvalSampleRequest {
+    pub image_base64: Option<String>,
+    pub event_id: Option<Uuid>,
+    /// Stream whose rules apply; defaults to the event's stream.
+    pub stream_id: Option<Uuid>,
+    /// "none" | "low" | "medium" | "high"
+    pub expected_risk_level: String,
+    /// Exact rule description the VLM should report, or null if none should match.
+    pub expected_triggered_rule: Option<String>,
+}
+
+/// Mirrors the `eval_runs` table. `report` holds an `EvalReport` once completed.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct EvalRun {
+    pub id: Uuid,
+    pub dataset_id: Uuid,
+    /// "running" | "completed" | "failed"
+    pub status: String,
+    pub model: Option<String>,
+    pub report: Option<Value>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct RunEvalRequest {
+    /// VLM profile to evaluate instead of the globally configured backend.
+    pub profile_id: Option<Uuid>,
+    /// Model to use instead of the backend's own, on the same backend.
+    pub model: Option<String>,
+}
+
+/// Precision / recall for one risk level, treating it as the positive class.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct LevelMetrics {
+    pub risk_level: String,
+    /// Samples labeled with this level.
+    pub support: i64,
+    pub precision: Option<f64>,
+    pub recall: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct LatencyStats {
+    pub mean_ms: f64,
+    pub p50_ms: i32,
+    pub p95_ms: i32,
+    pub max_ms: i32,
+}
+
+/// Result of running a dataset through a VLM.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct EvalReport {
+    pub model: String,
+    pub backend: String,
+    pub samples: i64,
+    /// Samples whose VLM call failed (excluded from the metrics below).
+    pub errors: i64,
+    /// Share of samples whose risk level matched exactly.
+    pub risk_accuracy: Option<f64>,
+    pub per_level: Vec<LevelMetrics>,
+    /// Row/column labels for `confusion_matrix`: none, low, medium, high.
+    pub labels: Vec<String>,
+    /// `confusion_matrix[expected][predicted]` counts.
+    pub confusion_matrix: Vec<Vec<i64>>,
+    /// Share of samples whose triggered_rule matched the label (null = null).
+    pub triggered_rule_accuracy: Option<f64>,
+    pub latency: Option<LatencyStats>,
+}
+
+// ─── VLM profiles ─────────────────────────────────────────────────────────────
+
+/// A named VLM configuration streams can use instead of the global one.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct VlmProfile {
+    pub id: Uuid,
+    pub name: String,
+    /// "ollama" | "openai_compat" | "llama_cpp" | "vllm"
+    pub backend: String,
+    pub model: String,
+    pub base_url: String,
+    /// Never returned by the API.
+    #[serde(skip_serializing)]
+    pub api_key: Option<String>,
+    pub connect_timeout_sec: Option<i32>,
+    pub request_timeout_sec: Option<i32>,
+    pub max_tokens: Option<i32>,
+    /// Whether the model accepts several images per request. When false, rule
+    /// reference images are left out and only the frame is sent.
+    pub multi_image: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateVlmProfileRequest {
+    pub name: String,
+    pub backend: String,
+    pub model: String,
+    pub base_url: String,
+    pub api_key: Option<String>,
+    pub connect_timeout_sec: Option<i32>,
+    pub request_timeout_sec: Option<i32>,
+    pub max_tokens: Option<i32>,
+    /// Defaults to true.
+    pub multi_image: Option<bool>,
+}
+
+/// Omitted fields are left unchanged. An empty `api_key` clears it.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateVlmProfileRequest {
+    pub name: Option<String>,
+    pub backend: Option<String>,
+    pub model: Option<String>,
+    pub base_url: Option<String>,
+    pub api_key: Option<String>,
+    pub connect_timeout_sec: Option<i32>,
+    pub request_timeout_sec: Option<i32>,
+    pub max_tokens: Option<i32>,
+    pub multi_image: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TestVlmProfileRequest {
+    /// Stream whose latest captured frame and rules are used.
+    pub stream_id: Uuid,
+}
+
+/// What a profile answered for a stream's latest frame. Nothing is stored.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct VlmProfileTestResult {
+    pub profile_id: Uuid,
+    pub stream_id: Uuid,
+    pub model: String,
+    pub backend: String,
+    pub latency_ms: i32,
+    pub prompt_tokens: Option<i32>,
+    pub completion_tokens: Option<i32>,
+    pub risk_level: String,
+    pub triggered_rule: Option<String>,
+    pub title: Option<String>,
+    pub description: String,
+    pub events: Value,
+    pub raw_response: String,
+}
+
 /// Global alert settings (single phone number used when high risk is identified).
 #[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct AlertSettings {
@@ -140,11 +663,18 @@ pub struct UpdateAlertSettings {
 
 // ─── Stream Rules ─────────────────────────────────────────────────────────────
 
-/// A per-stream rule the VLM uses to assign threat levels.
+/// A rule the VLM uses to assign threat levels. The structured fields are
+/// checked by the worker: `schedule` decides whether the rule is in the prompt
+/// at all, the others whether a VLM match is confirmed.
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
 pub struct StreamRule {
     pub id: Uuid,
-    pub stream_id: Uuid,
+    /// "global" | "blueprint" | "stream"
+    pub scope: String,
+    /// Set for stream rules.
+    pub stream_id: Option<Uuid>,
+    /// Set for blueprint rules, which apply to every stream on the blueprint.
+    pub blueprint_id: Option<Uuid>,
     /// Human-readable description, e.g. "Person climbing the fence".
     pub description: String,
     /// "none" | "low" | "medium" | "high"
@@ -153,6 +683,18 @@ pub struct StreamRule {
     pub position: i32,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// `RuleSchedule`; None = always active.
+    #[schema(value_type = Option<RuleSchedule>)]
+    pub schedule: Option<Value>,
+    /// Polygon (`Vec<ZonePoint>`) the detection must fall in; None = anywhere.
+    #[schema(value_type = Option<Vec<ZonePoint>>)]
+    pub zone: Option<Value>,
+    /// Minimum number of matching people / vehicles / objects.
+    pub min_count: Option<i32>,
+    /// Seconds the rule must keep matching on consecutive frames.
+    pub min_dwell_sec: Option<i32>,
+    /// Event types that must all be detected; empty = no requirement.
+    pub event_types: Vec<String>,
 }
 
 #[derive(Debug, Deserialize, ToSchema)]
@@ -162,13 +704,849 @@ pub struct CreateRuleRequest {
     pub threat_level: String,
     #[serde(default)]
     pub position: i32,
+    pub schedule: Option<RuleSchedule>,
+    pub zone: Option<Vec<ZonePoint>>,
+    pub min_count: Option<i32>,
+    pub min_dwell_sec: Option<i32>,
+    #[serde(default)]
+    pub event_types: Vec<String>,
 }
 
+/// Omitted fields are left unchanged; set a structured field to null to remove it.
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct UpdateRuleRequest {
     pub description: Option<String>,
     pub threat_level: Option<String>,
     pub position: Option<i32>,
+    #[serde(default, deserialize_with = "deser_nullable")]
+    pub schedule: Option<Option<RuleSchedule>>,
+    #[serde(default, deserialize_with = "deser_nullable")]
+    pub zone: Option<Option<Vec<ZonePoint>>>,
+    #[serde(default, deserialize_with = "deser_nullable")]
+    pub min_count: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "deser_nullable")]
+    pub min_dwell_sec: Option<Option<i32>>,
+    pub event_types: Option<Vec<String>>,
+}
+
+/// Where a rule lives; streams inherit global rules and their blueprint's rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleScope {
+    Global,
+    Blueprint(Uuid),
+    Stream(Uuid),
+}
+
+impl RuleScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RuleScope::Global => "global",
+            RuleScope::Blueprint(_) => "blueprint",
+            RuleScope::Stream(_) => "stream",
+        }
+    }
+
+    pub fn blueprint_id(&self) -> Option<Uuid> {
+        match self {
+            RuleScope::Blueprint(id) => Some(*id),
+            _ => None,
+        }
+    }
+
+    pub fn stream_id(&self) -> Option<Uuid> {
+        match self {
+            RuleScope::Stream(id) => Some(*id),
+            _ => None,
+        }
+    }
+}
+
+impl StreamRule {
+    pub fn rule_scope(&self) -> RuleScope {
+        match (self.stream_id, self.blueprint_id) {
+            (Some(id), _) => RuleScope::Stream(id),
+            (None, Some(id)) => RuleScope::Blueprint(id),
+            (None, None) => RuleScope::Global,
+        }
+    }
+}
+
+/// A stream's change to an inherited (global or blueprint) rule.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct RuleOverride {
+    pub stream_id: Uuid,
+    pub rule_id: Uuid,
+    /// The rule does not apply to this stream.
+    pub disabled: bool,
+    /// Threat level used on this stream instead of the rule's own.
+    pub threat_level: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SetRuleOverrideRequest {
+    #[serde(default)]
+    pub disabled: bool,
+    /// "none" | "low" | "medium" | "high"; omit to keep the rule's level.
+    pub threat_level: Option<String>,
+}
+
+/// A rule as it applies to one stream, after inheritance and overrides.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct EffectiveRule {
+    /// `threat_level` is the level in force on this stream.
+    #[serde(flatten)]
+    pub rule: StreamRule,
+    /// True for global and blueprint rules.
+    pub inherited: bool,
+    /// The rule's own threat level, when this stream overrides it.
+    pub overridden_threat_level: Option<String>,
+}
+
+/// When a rule is in force: any of `windows`, in local time at `utc_offset_minutes`.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RuleSchedule {
+    pub windows: Vec<ScheduleWindow>,
+    /// Offset of the local time the windows are written in, e.g. 60 for UTC+1.
+    #[serde(default)]
+    pub utc_offset_minutes: i32,
+}
+
+/// A daily time window, from `start` up to (not including) `end`. One whose
+/// `end` is before its `start` runs past midnight and belongs to the day it
+/// starts on. `start` and `end` must differ.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ScheduleWindow {
+    /// "mon" … "sun"; empty = every day.
+    #[serde(default)]
+    pub days: Vec<String>,
+    /// "HH:MM"
+    pub start: String,
+    /// "HH:MM"
+    pub end: String,
+}
+
+/// A polygon vertex in fractions (0.0 – 1.0) of the frame width / height.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema)]
+pub struct ZonePoint {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// The worker's verdict on the rule the VLM triggered, stored on the event.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RuleCheck {
+    pub rule_id: Uuid,
+    /// False if a structured condition failed; the event then keeps no
+    /// triggered rule and at most a low risk level.
+    pub passed: bool,
+    /// The conditions that failed (or could not be verified).
+    pub notes: Vec<String>,
+}
+
+// ─── False-positive feedback ──────────────────────────────────────────────────
+
+/// A "do not flag" instruction for one stream's prompt, summarized from
+/// confirmed false positives. Only approved clauses are sent to the VLM.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct FeedbackClause {
+    pub id: Uuid,
+    pub stream_id: Uuid,
+    /// The rule the false positives were attributed to; None = the model's own judgment.
+    pub rule_id: Option<Uuid>,
+    pub clause: String,
+    /// The false-positive events the clause was written from.
+    pub source_event_ids: Vec<Uuid>,
+    /// "pending" | "approved" | "rejected"
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+}
+
+pub struct NewFeedbackClause {
+    pub rule_id: Option<Uuid>,
+    pub clause: String,
+    pub source_event_ids: Vec<Uuid>,
+}
+
+/// A confirmed false positive not yet covered by a reviewed clause.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct FalsePositiveRow {
+    pub id: Uuid,
+    pub rule_id: Option<Uuid>,
+    pub rule_description: Option<String>,
+    pub title: Option<String>,
+    pub description: String,
+    pub disposition_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ReviewFeedbackClauseRequest {
+    /// "approved" | "rejected"
+    pub status: String,
+    /// Replaces the suggested wording.
+    pub clause: Option<String>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct FeedbackClauseQuery {
+    /// "pending" | "approved" | "rejected"
+    pub status: Option<String>,
+}
+
+// ─── Rule reference images ────────────────────────────────────────────────────
+
+/// An example image attached to a rule (image bytes omitted). Sent to
+/// multi-image VLMs before the frame being analyzed.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct RuleReferenceImage {
+    pub id: Uuid,
+    pub rule_id: Uuid,
+    /// "positive" (the rule applies to this scene) | "negative" (it does not)
+    pub kind: String,
+    pub caption: Option<String>,
+    /// Event the image was copied from, if any.
+    pub event_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Add a reference image: either upload `image_base64` or copy the frame of `event_id`.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateReferenceImageRequest {
+    /// "positive" | "negative"
+    pub kind: String,
+    /// What the example shows, e.g. "staff in blue uniform restocking".
+    pub caption: Option<String>,
+    pub image_base64: Option<String>,
+    pub event_id: Option<Uuid>,
+}
+
+/// A reference image with its bytes, as loaded for a prompt.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ReferenceImageRow {
+    pub rule_id: Uuid,
+    pub kind: String,
+    pub caption: Option<String>,
+    pub image: Vec<u8>,
+}
+
+// ─── Rule analytics ───────────────────────────────────────────────────────────
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct RuleStatsQuery {
+    /// Only the rules in force on this stream, counting only its events.
+    pub stream_id: Option<Uuid>,
+    /// Defaults to 30 days before `to`.
+    pub from: Option<DateTime<Utc>>,
+    /// Defaults to now.
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// Per-rule aggregates computed in SQL.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct RuleHitRow {
+    pub rule_id: Uuid,
+    pub analyzed: i64,
+    pub hits: i64,
+    pub false_positives: i64,
+    pub mean_confidence: Option<f64>,
+    pub last_triggered: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct DailyRuleHits {
+    #[serde(skip)]
+    pub rule_id: Uuid,
+    /// UTC day.
+    pub day: NaiveDate,
+    pub hits: i64,
+}
+
+/// How a rule has been firing over the requested period.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RuleStats {
+    pub rule_id: Uuid,
+    /// "global" | "blueprint" | "stream"
+    pub scope: String,
+    pub stream_id: Option<Uuid>,
+    pub blueprint_id: Option<Uuid>,
+    pub description: String,
+    pub threat_level: String,
+    /// Frames analyzed on streams the rule applies to, since it was created.
+    pub analyzed: i64,
+    /// Events linked to the rule.
+    pub hits: i64,
+    pub hit_rate: Option<f64>,
+    /// Hits whose event was marked as a false positive.
+    pub false_positives: i64,
+    pub false_positive_rate: Option<f64>,
+    /// Most recent hit, regardless of the period.
+    pub last_triggered: Option<DateTime<Utc>>,
+    /// Mean VLM confidence of the detections on the rule's events.
+    pub mean_confidence: Option<f64>,
+    /// Days without hits are left out.
+    pub hits_per_day: Vec<DailyRuleHits>,
+    /// "never_fires" | "always_fires", once enough frames have been analyzed.
+    pub flag: Option<String>,
+}
+
+// ─── Event statistics ─────────────────────────────────────────────────────────
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct EventCountsQuery {
+    /// "hour" | "day" | "week" (weeks start on Monday); default "day".
+    pub bucket: Option<String>,
+    /// "risk_level" | "stream" | "blueprint" | "event_type" | "triggered_rule";
+    /// default "risk_level".
+    pub group_by: Option<String>,
+    pub stream_id: Option<Uuid>,
+    /// Events of the streams placed on this blueprint.
+    pub blueprint_id: Option<Uuid>,
+    /// One level or several separated by commas.
+    pub risk_level: Option<String>,
+    /// Defaults to 2 days (hour), 30 days (day) or 26 weeks (week) before `to`.
+    pub from: Option<DateTime<Utc>>,
+    /// Defaults to now.
+    pub to: Option<DateTime<Utc>>,
+    /// Days and weeks start at midnight at this offset from UTC; default 0.
+    #[serde(default)]
+    pub utc_offset_minutes: i32,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct StatsQuery {
+    pub stream_id: Option<Uuid>,
+    /// Events of the streams placed on this blueprint.
+    pub blueprint_id: Option<Uuid>,
+    /// One level or several separated by commas.
+    pub risk_level: Option<String>,
+    /// Defaults to 28 days before `to`.
+    pub from: Option<DateTime<Utc>>,
+    /// Defaults to now.
+    pub to: Option<DateTime<Utc>>,
+    /// Local time used for hours of the week; default 0 (UTC).
+    #[serde(default)]
+    pub utc_offset_minutes: i32,
+}
+
+/// Validated filters shared by the statistics queries. Frames the VLM could
+/// not analyze (`analysis_failed` events) are never counted.
+#[derive(Debug, Clone)]
+pub struct StatsFilter {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub stream_id: Option<Uuid>,
+    pub blueprint_id: Option<Uuid>,
+    pub risk_levels: Option<Vec<String>>,
+    pub utc_offset_minutes: i32,
+}
+
+/// Events in one time bucket with one value of the grouping.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct EventCountBucket {
+    pub bucket_start: DateTime<Utc>,
+    /// Risk level, stream / blueprint id, event type or rule description;
+    /// None = no blueprint / no triggered rule.
+    pub key: Option<String>,
+    /// Stream or blueprint name.
+    pub label: Option<String>,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct EventCountTotal {
+    pub key: Option<String>,
+    pub label: Option<String>,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct EventCounts {
+    pub bucket: String,
+    pub group_by: String,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    /// Ordered by bucket, then key. Buckets without events are left out.
+    pub buckets: Vec<EventCountBucket>,
+    /// Per key over the whole period, largest first.
+    pub totals: Vec<EventCountTotal>,
+}
+
+/// How long events captured in the period took to be resolved.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct ResolutionStats {
+    /// None = all risk levels.
+    pub risk_level: Option<String>,
+    pub events: i64,
+    /// Events with a recorded resolve time.
+    pub resolved: i64,
+    pub unresolved: i64,
+    /// Mean seconds from capture to resolution.
+    pub mean_seconds: Option<f64>,
+    pub median_seconds: Option<f64>,
+}
+
+/// Events in one hour of the week, in local time at `utc_offset_minutes`.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct HeatmapCell {
+    /// 0 = Monday … 6 = Sunday.
+    pub weekday: i32,
+    /// 0 – 23.
+    pub hour: i32,
+    pub count: i64,
+}
+
+// ─── Evidence export ──────────────────────────────────────────────────────────
+
+/// Export format; the event filters are the `EventQuery` ones.
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ExportQuery {
+    /// "csv" | "ndjson" | "zip" (events, frames, manifest with SHA-256 hashes
+    /// and a signed summary); default "csv".
+    pub format: Option<String>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ChainQuery {
+    /// Only this stream's chain.
+    pub stream_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct CheckpointQuery {
+    pub stream_id: Option<Uuid>,
+    #[serde(default = "default_limit")]
+    pub limit: i64,
+}
+
+/// A stream's chain head signed with the evidence key.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow, ToSchema)]
+pub struct EvidenceCheckpoint {
+    pub id: Uuid,
+    pub stream_id: Uuid,
+    pub chain_seq: i64,
+    pub content_hash: String,
+    pub key_id: String,
+    /// Base64 Ed25519 signature of "<stream_id>:<chain_seq>:<content_hash>:<created_at µs>".
+    pub signature: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Result of recomputing every hash and signature of the evidence chains.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ChainVerification {
+    /// No breaks in any chain.
+    pub ok: bool,
+    pub verified_at: DateTime<Utc>,
+    pub streams: Vec<StreamChainStatus>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct StreamChainStatus {
+    pub stream_id: Uuid,
+    pub stream_name: String,
+    pub events: i64,
+    /// Last sequence number handed out; events missing from the end show up here.
+    pub head_seq: i64,
+    pub checkpoints: i64,
+    pub last_checkpoint_at: Option<DateTime<Utc>>,
+    /// All breaks found; `breaks` lists at most the first 100.
+    pub break_count: usize,
+    pub breaks: Vec<ChainBreak>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ChainBreak {
+    /// "frame_altered" | "content_altered" | "link_broken" | "missing_events" |
+    /// "checkpoint_mismatch" | "checkpoint_signature_invalid" | "checkpoint_unknown_key"
+    pub kind: String,
+    pub chain_seq: Option<i64>,
+    pub event_id: Option<Uuid>,
+    pub detail: String,
+}
+
+/// Last link of a stream's chain.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ChainHead {
+    pub stream_id: Uuid,
+    pub seq: i64,
+    pub head_hash: String,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ChainStatusRow {
+    pub stream_id: Uuid,
+    pub stream_name: String,
+    pub events: i64,
+    pub head_seq: i64,
+    pub max_seq: Option<i64>,
+}
+
+/// A break found in SQL, before checkpoints are checked.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ChainBreakRow {
+    pub stream_id: Uuid,
+    pub chain_seq: Option<i64>,
+    pub event_id: Option<Uuid>,
+    pub kind: String,
+    pub detail: String,
+}
+
+/// A checkpoint with the current hash of the event it signed.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct CheckpointCheckRow {
+    pub stream_id: Uuid,
+    pub chain_seq: i64,
+    pub content_hash: String,
+    pub key_id: String,
+    pub signature: String,
+    pub created_at: DateTime<Utc>,
+    pub event_id: Option<Uuid>,
+    pub event_hash: Option<String>,
+}
+
+/// Key that evidence signatures verify against.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct EvidencePublicKey {
+    /// "Ed25519"
+    pub algorithm: String,
+    /// First 16 hex digits of the public key's SHA-256.
+    pub key_id: String,
+    /// Base64 of the 32-byte public key.
+    pub public_key: String,
+}
+
+// ─── Assistant conversations ──────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow, ToSchema)]
+pub struct AssistantConversation {
+    pub id: Uuid,
+    /// The first question, unless given when the conversation was created.
+    pub title: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateConversationRequest {
+    pub title: Option<String>,
+}
+
+/// A conversation with every stored message, oldest first.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AssistantConversationDetail {
+    #[serde(flatten)]
+    pub conversation: AssistantConversation,
+    /// A reply is being generated right now.
+    pub running: bool,
+    pub messages: Vec<AssistantMessage>,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow, ToSchema)]
+pub struct AssistantMessage {
+    pub id: Uuid,
+    /// "user" | "assistant" | "tool"
+    pub role: String,
+    pub content: String,
+    /// Assistant messages: the tool calls `[{function: {name, arguments}}]` the model made.
+    pub tool_calls: Option<Value>,
+    /// Tool messages: the tool whose result `content` is.
+    pub tool_name: Option<String>,
+    /// The reply was cancelled; `content` is what had streamed so far.
+    pub cancelled: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Body of `POST /api/assistant/conversations/{id}/messages`.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SendMessageRequest {
+    pub message: String,
+}
+
+/// One server-sent event of a streamed assistant reply, sent as JSON tagged by `type`.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AssistantEvent {
+    /// Part of the reply text, in order.
+    Token { text: String },
+    /// The model called a tool; its result follows.
+    ToolCall { name: String, arguments: Value },
+    ToolResult { name: String, result: String },
+    /// The full reply; the last event of a turn.
+    Done { content: String },
+    /// A tool proposed a destructive action; it waits for the user to confirm it.
+    ActionProposed { action: AssistantAction },
+    /// The turn was cancelled; `content` is the reply so far.
+    Cancelled { content: String },
+    Error { message: String },
+}
+
+/// A destructive assistant tool call, held until the user confirms or rejects it.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow, ToSchema)]
+pub struct AssistantAction {
+    /// Also the confirmation token.
+    pub id: Uuid,
+    pub conversation_id: Option<Uuid>,
+    /// "resolve_events" | "update_rule" | "delete_rule" | "disable_stream"
+    pub tool: String,
+    /// Exactly what confirming does, fixed when proposed (e.g. the event ids to resolve).
+    pub change: Value,
+    pub summary: String,
+    /// Rows (events, rules or streams) the change touches, as previewed.
+    pub affected: i64,
+    /// "pending" | "executed" | "failed" | "rejected"
+    pub status: String,
+    /// What executing the action did, or why it failed.
+    pub result: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// A pending action can't be confirmed after this.
+    pub expires_at: DateTime<Utc>,
+    pub decided_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct AssistantActionQuery {
+    pub conversation_id: Option<Uuid>,
+    /// "pending" | "executed" | "failed" | "rejected"
+    pub status: Option<String>,
+}
+
+// ─── Reports ──────────────────────────────────────────────────────────────────
+
+/// Generates a digest report of the last `period_hours` on a cron schedule.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct ReportSchedule {
+    pub id: Uuid,
+    pub name: String,
+    /// Five-field cron pattern ("0 7 * * 1-5") or a nickname ("@daily", "@weekly").
+    pub cron: String,
+    /// The cron pattern and report times are in local time at this offset from UTC.
+    pub utc_offset_minutes: i32,
+    pub period_hours: i32,
+    /// Only this stream's events; None = all streams.
+    pub stream_id: Option<Uuid>,
+    /// Only events of the streams placed on this blueprint.
+    pub blueprint_id: Option<Uuid>,
+    /// Ask the assistant model for a written summary.
+    pub llm_summary: bool,
+    /// Text a short digest with a link to the report.
+    pub notify_sms: bool,
+    /// SMS recipient; None = the global alert phone number.
+    pub notify_phone: Option<String>,
+    pub enabled: bool,
+    pub next_run_at: DateTime<Utc>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateReportScheduleRequest {
+    pub name: String,
+    pub cron: String,
+    /// Default 0.
+    pub utc_offset_minutes: Option<i32>,
+    /// Default 24.
+    pub period_hours: Option<i32>,
+    pub stream_id: Option<Uuid>,
+    pub blueprint_id: Option<Uuid>,
+    pub llm_summary: Option<bool>,
+    pub notify_sms: Option<bool>,
+    pub notify_phone: Option<String>,
+    /// Default true.
+    pub enabled: Option<bool>,
+}
+
+/// Omitted fields are left unchanged. `stream_id` / `blueprint_id` null
+/// clears the filter; an empty `notify_phone` clears it.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateReportScheduleRequest {
+    pub name: Option<String>,
+    pub cron: Option<String>,
+    pub utc_offset_minutes: Option<i32>,
+    pub period_hours: Option<i32>,
+    #[serde(default, deserialize_with = "deser_nullable_uuid")]
+    pub stream_id: Option<Option<Uuid>>,
+    #[serde(default, deserialize_with = "deser_nullable_uuid")]
+    pub blueprint_id: Option<Option<Uuid>>,
+    pub llm_summary: Option<bool>,
+    pub notify_sms: Option<bool>,
+    pub notify_phone: Option<String>,
+    pub enabled: Option<bool>,
+}
+
+/// A report generated now, outside any schedule.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateReportRequest {
+    /// Defaults to "Security digest".
+    pub title: Option<String>,
+    pub stream_id: Option<Uuid>,
+    pub blueprint_id: Option<Uuid>,
+    /// Defaults to 24 hours before `to`.
+    pub from: Option<DateTime<Utc>>,
+    /// Defaults to now.
+    pub to: Option<DateTime<Utc>>,
+    /// Times in the report are shown at this offset from UTC; default 0.
+    pub utc_offset_minutes: Option<i32>,
+    pub llm_summary: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ReportQuery {
+    pub schedule_id: Option<Uuid>,
+    #[serde(default = "default_limit")]
+    pub limit: i64,
+}
+
+/// A generated report; the rendered documents are at `/api/reports/{id}/html`
+/// and `/api/reports/{id}/pdf`.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow, ToSchema)]
+pub struct Report {
+    pub id: Uuid,
+    pub schedule_id: Option<Uuid>,
+    pub title: String,
+    pub stream_id: Option<Uuid>,
+    pub blueprint_id: Option<Uuid>,
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    pub event_count: i64,
+    /// `ReportSummary`.
+    pub summary: Value,
+    /// Written by the assistant model, if the report asked for it and the model answered.
+    pub llm_summary: Option<String>,
+    /// An SMS digest was accepted by Twilio.
+    pub notified: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// What a report aggregates over its period.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ReportSummary {
+    /// Event counts per risk level, stream and triggered rule, largest first.
+    pub by_risk: Vec<EventCountTotal>,
+    pub by_stream: Vec<EventCountTotal>,
+    pub by_rule: Vec<EventCountTotal>,
+    pub resolution: Vec<ResolutionStats>,
+    /// Highest-risk events of the period, most severe first.
+    pub incidents: Vec<ReportIncident>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ReportIncident {
+    pub event_id: Uuid,
+    pub stream_id: Uuid,
+    pub stream_name: String,
+    pub captured_at: DateTime<Utc>,
+    pub risk_level: String,
+    pub title: Option<String>,
+    pub description: String,
+    pub status: String,
+}
+
+/// A top incident with its frame, for the thumbnails.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ReportIncidentRow {
+    pub event_id: Uuid,
+    pub stream_id: Uuid,
+    pub stream_name: String,
+    pub captured_at: DateTime<Utc>,
+    pub risk_level: String,
+    pub title: Option<String>,
+    pub description: String,
+    pub status: String,
+    pub frame: Option<Vec<u8>>,
+}
+
+/// Everything stored for one generated report.
+pub struct NewReport<'a> {
+    pub schedule_id: Option<Uuid>,
+    pub title: &'a str,
+    pub stream_id: Option<Uuid>,
+    pub blueprint_id: Option<Uuid>,
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    pub event_count: i64,
+    pub summary: Value,
+    pub llm_summary: Option<&'a str>,
+    pub html: &'a str,
+    pub pdf: &'a [u8],
+}
+
+// ─── Rule Templates ───────────────────────────────────────────────────────────
+
+/// A reusable set of rules. Strings in `rules` may contain `{{parameter}}`
+/// placeholders, filled in when the template is applied.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct RuleTemplate {
+    pub id: Uuid,
+    pub name: String,
+    pub description: String,
+    #[schema(value_type = Vec<TemplateParameter>)]
+    pub parameters: Value,
+    /// Rule definitions in the `CreateRuleRequest` shape, with placeholders.
+    #[schema(value_type = Vec<Object>)]
+    pub rules: Value,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TemplateParameter {
+    /// Used as `{{name}}`; letters, digits and underscores.
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Value used when applying without one; None = required.
+    pub default: Option<String>,
+}
+
+/// A template without its database fields: the create payload and the
+/// import / export format.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RuleTemplateDoc {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub parameters: Vec<TemplateParameter>,
+    /// A placeholder that makes up the whole value of `position`, `min_count`,
+    /// `min_dwell_sec` or `utc_offset_minutes` is filled in as a number.
+    #[schema(value_type = Vec<Object>)]
+    pub rules: Vec<Value>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateRuleTemplateRequest {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub parameters: Option<Vec<TemplateParameter>>,
+    #[schema(value_type = Option<Vec<Object>>)]
+    pub rules: Option<Vec<Value>>,
+}
+
+/// Where to add a template's rules: any number of streams and/or a blueprint.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ApplyTemplateRequest {
+    #[serde(default)]
+    pub stream_ids: Vec<Uuid>,
+    /// The rules become blueprint rules, inherited by the blueprint's streams.
+    pub blueprint_id: Option<Uuid>,
+    /// Parameter values by name; omitted parameters take their default.
+    #[serde(default)]
+    pub params: std::collections::HashMap<String, String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApplyTemplateResult {
+    pub created: Vec<StreamRule>,
+    /// Rules the target already had (same description), not added again.
+    pub skipped: usize,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct TemplateFormatQuery {
+    /// "json" | "yaml"; on import, defaults to the request's Content-Type.
+    pub format: Option<String>,
 }
 
 // ─── Blueprints (floor plan image + cameras) ─────────────────────────────────
@@ -110,13 +110,12 @@ impl StreamManager {
         }
     }
 
-    /// Restart a stream (e.g. after an update).
+    /// Restart a stream (e.g. after an update). A disabled stream is only
+    /// stopped, so editing it doesn't start capturing its frames.
     pub async fn restart_stream(&self, stream: StreamRecord) {
         self.stop_stream(stream.id).await;
-        self.start_stream(stream).await;
-    }
-
-    pub fn frame_sender(&self) -> mpsc::Sender<CapturedFrame> {
-        self.frame_tx.clone()
+        if stream.enabled {
+            self.start_stream(stream).await;
+        }
     }
 }
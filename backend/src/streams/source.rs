@@ -27,6 +27,7 @@ pub enum SourceType {
     /// Mock / test source. Two sub-modes selected by `source_url`:
     ///   - Local file path → played on repeat with `-stream_loop -1`.
     ///   - YouTube / web URL → resolved via `yt-dlp`, played once then restarted.
+    ///
     /// Requires `ffmpeg` on PATH; YouTube mode additionally requires `yt-dlp`.
     Mock,
 }